@@ -0,0 +1,89 @@
+// benches/backends.rs
+
+//! Every enabled backend on a loop-heavy chunk.
+//! Run with `cargo bench --features fastexec,jit`.
+
+use std::time::{Duration, Instant};
+use c4_rust_AlRafaah::bytecode::{Chunk, OpCode};
+use c4_rust_AlRafaah::vm::{VmConfig, VM};
+
+const N: i64 = 2_000_000;
+const ITERATIONS: usize = 5;
+
+/// `i = N; while (i) { sum += i; i -= 1; } return sum;` in bytecode; the
+/// compiler can't emit loops yet.
+fn sum_loop(n: i64) -> Chunk {
+    let mut c = Chunk::default();
+    c.push_int(OpCode::ENT, 2);
+    c.push_int(OpCode::LEA, 0);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::IMM, n);
+    c.push(OpCode::SI);
+    c.push_int(OpCode::LEA, 0);
+    c.push(OpCode::LI);
+    c.push_jump(OpCode::BZ, 26);
+    c.push_int(OpCode::LEA, 1);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::LEA, 1);
+    c.push(OpCode::LI);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::LEA, 0);
+    c.push(OpCode::LI);
+    c.push(OpCode::ADD);
+    c.push(OpCode::SI);
+    c.push_int(OpCode::LEA, 0);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::LEA, 0);
+    c.push(OpCode::LI);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::IMM, 1);
+    c.push(OpCode::SUB);
+    c.push(OpCode::SI);
+    c.push_jump(OpCode::JMP, 5);
+    c.push_int(OpCode::LEA, 1);
+    c.push(OpCode::LI);
+    c.push(OpCode::EXIT);
+    c
+}
+
+/// Fastest of `ITERATIONS` timed runs.
+fn best(mut run: impl FnMut() -> i64) -> Duration {
+    (0..ITERATIONS)
+        .map(|_| {
+            let start = Instant::now();
+            assert_eq!(run(), N * (N + 1) / 2);
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, time: Duration, baseline: Duration) {
+    println!(
+        "{:<10} {:>10.3} ms  {:>6.2}x",
+        name,
+        time.as_secs_f64() * 1000.0,
+        baseline.as_secs_f64() / time.as_secs_f64()
+    );
+}
+
+fn main() {
+    let chunk = sum_loop(N);
+    let config = VmConfig { stack_size: 1024, ..VmConfig::default() };
+    let vm = best(|| VM::with_config(config.clone()).run(&chunk).unwrap());
+    report("vm", vm, vm);
+
+    #[cfg(feature = "fastexec")]
+    {
+        let program = c4_rust_AlRafaah::fastexec::ClosureProgram::compile(&chunk);
+        report("closures", best(|| program.run(&config).unwrap().exit_code), vm);
+    }
+
+    #[cfg(feature = "jit")]
+    {
+        let start = Instant::now();
+        let program = c4_rust_AlRafaah::jit::JitProgram::compile(&chunk).unwrap();
+        println!("(jit compile {:.3} ms)", start.elapsed().as_secs_f64() * 1000.0);
+        report("jit", best(|| program.run(&config).unwrap().exit_code), vm);
+    }
+}
@@ -0,0 +1,51 @@
+// benches/reuse.rs
+
+//! Many short runs: a fresh VM for each against one VM with the program
+//! loaded, as a server running small scripts would. Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+use c4_rust_AlRafaah::compile_source;
+use c4_rust_AlRafaah::vm::VM;
+
+const RUNS: usize = 2_000;
+const ITERATIONS: usize = 5;
+
+/// Fastest of `ITERATIONS` timed batches of `RUNS` runs.
+fn best(mut batch: impl FnMut()) -> Duration {
+    (0..ITERATIONS)
+        .map(|_| {
+            let start = Instant::now();
+            batch();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let src = "int main() { return (1 + 2) * (3 + 4) - 5 % 3; }";
+    let chunk = compile_source(src).unwrap();
+
+    let fresh = best(|| {
+        for _ in 0..RUNS {
+            assert_eq!(VM::new().run_collecting(&chunk).unwrap().exit_code, 19);
+        }
+    });
+
+    let mut vm = VM::new();
+    let id = vm.load(compile_source(src).unwrap()).unwrap();
+    let reused = best(|| {
+        for _ in 0..RUNS {
+            assert_eq!(vm.run_program(id, &[]).unwrap().exit_code, 19);
+        }
+    });
+
+    for (name, time) in [("fresh vm", fresh), ("reused vm", reused)] {
+        println!(
+            "{:<10} {:>8.3} us/run  {:>6.2}x",
+            name,
+            time.as_secs_f64() * 1e6 / RUNS as f64,
+            fresh.as_secs_f64() / time.as_secs_f64()
+        );
+    }
+}
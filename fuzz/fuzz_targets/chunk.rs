@@ -0,0 +1,14 @@
+#![no_main]
+
+use c4_rust_AlRafaah::bytecode::Chunk;
+use c4_rust_AlRafaah::vm::{VmConfig, VM};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Saved chunks are read from disk, so any bytes must fail cleanly, and
+    // whatever does load must run without panicking.
+    if let Ok(chunk) = Chunk::deserialize(data) {
+        let config = VmConfig { stack_size: 4096, max_steps: Some(10_000), ..VmConfig::default() };
+        let _ = VM::with_config(config).run(&chunk);
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use c4_rust_AlRafaah::lexer::Lexer;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(src) = std::str::from_utf8(data) {
+        let _ = Lexer::new(src).tokenize_all();
+    }
+});
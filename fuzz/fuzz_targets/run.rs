@@ -0,0 +1,14 @@
+#![no_main]
+
+use c4_rust_AlRafaah::run_source_with;
+use c4_rust_AlRafaah::vm::VmConfig;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(src) = std::str::from_utf8(data) {
+        // A tiny stack bounds memory and runaway recursion per input, and
+        // the fuel bounds time, so `while (1);` can't hang the fuzzer.
+        let config = VmConfig { stack_size: 4096, max_steps: Some(10_000), ..VmConfig::default() };
+        let _ = run_source_with(src, config);
+    }
+});
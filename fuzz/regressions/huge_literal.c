@@ -0,0 +1 @@
+int main() { return 99999999999999999999; }
\ No newline at end of file
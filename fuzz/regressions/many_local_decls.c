@@ -0,0 +1 @@
+int main() { 1; int v1; int v2; int v3; int v4; int v5; int v6; int v7; int v8; int v9; int v10; int v11; int v12; int v13; int v14; int v15; int v16; int v17; int v18; int v19; int v20; int v21; int v22; int v23; int v24; int v25; int v26; int v27; int v28; int v29; int v30; int v31; int v32; int v33; int v34; int v35; int v36; int v37; int v38; int v39; int v40; int v41; int v42; int v43; int v44; int v45; int v46; int v47; int v48; int v49; int v50; int v51; int v52; int v53; int v54; int v55; int v56; int v57; int v58; int v59; int v60; int v61; int v62; int v63; int v64; int v65; int v66; int v67; int v68; int v69; int v70; int v71; int v72; int v73; int v74; int v75; int v76; int v77; int v78; int v79; int v80; int v81; int v82; int v83; int v84; int v85; int v86; int v87; int v88; int v89; int v90; int v91; int v92; int v93; int v94; int v95; int v96; int v97; int v98; int v99; int v100; int v101; int v102; int v103; int v104; int v105; int v106; int v107; int v108; int v109; int v110; int v111; int v112; int v113; int v114; int v115; int v116; int v117; int v118; int v119; int v120; int v121; int v122; int v123; int v124; int v125; int v126; int v127; int v128; int v129; int v130; int v131; int v132; int v133; int v134; int v135; int v136; int v137; int v138; int v139; int v140; int v141; int v142; int v143; int v144; int v145; int v146; int v147; int v148; int v149; int v150; int v151; int v152; int v153; int v154; int v155; int v156; int v157; int v158; int v159; int v160; int v161; int v162; int v163; int v164; int v165; int v166; int v167; int v168; int v169; int v170; int v171; int v172; int v173; int v174; int v175; int v176; int v177; int v178; int v179; int v180; int v181; int v182; int v183; int v184; int v185; int v186; int v187; int v188; int v189; int v190; int v191; int v192; int v193; int v194; int v195; int v196; int v197; int v198; int v199; int v200; int v201; int v202; int v203; int v204; int v205; int v206; int v207; int v208; int v209; int v210; int v211; int v212; int v213; int v214; int v215; int v216; int v217; int v218; int v219; int v220; int v221; int v222; int v223; int v224; int v225; int v226; int v227; int v228; int v229; int v230; int v231; int v232; int v233; int v234; int v235; int v236; int v237; int v238; int v239; int v240; int v241; int v242; int v243; int v244; int v245; int v246; int v247; int v248; int v249; int v250; int v251; int v252; int v253; int v254; int v255; int v256; int v257; int v258; int v259; int v260; int v261; int v262; int v263; int v264; int v265; int v266; int v267; int v268; int v269; int v270; int v271; int v272; int v273; int v274; int v275; int v276; int v277; int v278; int v279; int v280; int v281; int v282; int v283; int v284; int v285; int v286; int v287; int v288; int v289; int v290; int v291; int v292; int v293; int v294; int v295; int v296; int v297; int v298; int v299; int v300; int v301; int v302; int v303; int v304; int v305; int v306; int v307; int v308; int v309; int v310; int v311; int v312; int v313; int v314; int v315; int v316; int v317; int v318; int v319; int v320; int v321; int v322; int v323; int v324; int v325; int v326; int v327; int v328; int v329; int v330; int v331; int v332; int v333; int v334; int v335; int v336; int v337; int v338; int v339; int v340; int v341; int v342; int v343; int v344; int v345; int v346; int v347; int v348; int v349; int v350; int v351; int v352; int v353; int v354; int v355; int v356; int v357; int v358; int v359; int v360; int v361; int v362; int v363; int v364; int v365; int v366; int v367; int v368; int v369; int v370; int v371; int v372; int v373; int v374; int v375; int v376; int v377; int v378; int v379; int v380; int v381; int v382; int v383; int v384; int v385; int v386; int v387; int v388; int v389; int v390; int v391; int v392; int v393; int v394; int v395; int v396; int v397; int v398; int v399; int v400; int v401; int v402; int v403; int v404; int v405; int v406; int v407; int v408; int v409; int v410; int v411; int v412; int v413; int v414; int v415; int v416; int v417; int v418; int v419; int v420; int v421; int v422; int v423; int v424; int v425; int v426; int v427; int v428; int v429; int v430; int v431; int v432; int v433; int v434; int v435; int v436; int v437; int v438; int v439; int v440; int v441; int v442; int v443; int v444; int v445; int v446; int v447; int v448; int v449; int v450; int v451; int v452; int v453; int v454; int v455; int v456; int v457; int v458; int v459; int v460; int v461; int v462; int v463; int v464; int v465; int v466; int v467; int v468; int v469; int v470; int v471; int v472; int v473; int v474; int v475; int v476; int v477; int v478; int v479; int v480; int v481; int v482; int v483; int v484; int v485; int v486; int v487; int v488; int v489; int v490; int v491; int v492; int v493; int v494; int v495; int v496; int v497; int v498; int v499; int v500; int v501; int v502; int v503; int v504; int v505; int v506; int v507; int v508; int v509; int v510; int v511; int v512; int v513; int v514; int v515; int v516; int v517; int v518; int v519; int v520; int v521; int v522; int v523; int v524; int v525; int v526; int v527; int v528; int v529; int v530; int v531; int v532; int v533; int v534; int v535; int v536; int v537; int v538; int v539; int v540; int v541; int v542; int v543; int v544; int v545; int v546; int v547; int v548; int v549; int v550; int v551; int v552; int v553; int v554; int v555; int v556; int v557; int v558; int v559; int v560; int v561; int v562; int v563; int v564; int v565; int v566; int v567; int v568; int v569; int v570; int v571; int v572; int v573; int v574; int v575; int v576; int v577; int v578; int v579; int v580; int v581; int v582; int v583; int v584; int v585; int v586; int v587; int v588; int v589; int v590; int v591; int v592; int v593; int v594; int v595; int v596; int v597; int v598; int v599; int v600; int v601; int v602; int v603; int v604; int v605; int v606; int v607; int v608; int v609; int v610; int v611; int v612; int v613; int v614; int v615; int v616; int v617; int v618; int v619; int v620; int v621; int v622; int v623; int v624; int v625; int v626; int v627; int v628; int v629; int v630; int v631; int v632; int v633; int v634; int v635; int v636; int v637; int v638; int v639; int v640; int v641; int v642; int v643; int v644; int v645; int v646; int v647; int v648; int v649; int v650; int v651; int v652; int v653; int v654; int v655; int v656; int v657; int v658; int v659; int v660; int v661; int v662; int v663; int v664; int v665; int v666; int v667; int v668; int v669; int v670; int v671; int v672; int v673; int v674; int v675; int v676; int v677; int v678; int v679; int v680; int v681; int v682; int v683; int v684; int v685; int v686; int v687; int v688; int v689; int v690; int v691; int v692; int v693; int v694; int v695; int v696; int v697; int v698; int v699; int v700; int v701; int v702; int v703; int v704; int v705; int v706; int v707; int v708; int v709; int v710; int v711; int v712; int v713; int v714; int v715; int v716; int v717; int v718; int v719; int v720; int v721; int v722; int v723; int v724; int v725; int v726; int v727; int v728; int v729; int v730; int v731; int v732; int v733; int v734; int v735; int v736; int v737; int v738; int v739; int v740; int v741; int v742; int v743; int v744; int v745; int v746; int v747; int v748; int v749; int v750; int v751; int v752; int v753; int v754; int v755; int v756; int v757; int v758; int v759; int v760; int v761; int v762; int v763; int v764; int v765; int v766; int v767; int v768; int v769; int v770; int v771; int v772; int v773; int v774; int v775; int v776; int v777; int v778; int v779; int v780; int v781; int v782; int v783; int v784; int v785; int v786; int v787; int v788; int v789; int v790; int v791; int v792; int v793; int v794; int v795; int v796; int v797; int v798; int v799; int v800; int v801; int v802; int v803; int v804; int v805; int v806; int v807; int v808; int v809; int v810; int v811; int v812; int v813; int v814; int v815; int v816; int v817; int v818; int v819; int v820; int v821; int v822; int v823; int v824; int v825; int v826; int v827; int v828; int v829; int v830; int v831; int v832; int v833; int v834; int v835; int v836; int v837; int v838; int v839; int v840; int v841; int v842; int v843; int v844; int v845; int v846; int v847; int v848; int v849; int v850; int v851; int v852; int v853; int v854; int v855; int v856; int v857; int v858; int v859; int v860; int v861; int v862; int v863; int v864; int v865; int v866; int v867; int v868; int v869; int v870; int v871; int v872; int v873; int v874; int v875; int v876; int v877; int v878; int v879; int v880; int v881; int v882; int v883; int v884; int v885; int v886; int v887; int v888; int v889; int v890; int v891; int v892; int v893; int v894; int v895; int v896; int v897; int v898; int v899; int v900; int v901; int v902; int v903; int v904; int v905; int v906; int v907; int v908; int v909; int v910; int v911; int v912; int v913; int v914; int v915; int v916; int v917; int v918; int v919; int v920; int v921; int v922; int v923; int v924; int v925; int v926; int v927; int v928; int v929; int v930; int v931; int v932; int v933; int v934; int v935; int v936; int v937; int v938; int v939; int v940; int v941; int v942; int v943; int v944; int v945; int v946; int v947; int v948; int v949; int v950; int v951; int v952; int v953; int v954; int v955; int v956; int v957; int v958; int v959; int v960; int v961; int v962; int v963; int v964; int v965; int v966; int v967; int v968; int v969; int v970; int v971; int v972; int v973; int v974; int v975; int v976; int v977; int v978; int v979; int v980; int v981; int v982; int v983; int v984; int v985; int v986; int v987; int v988; int v989; int v990; int v991; int v992; int v993; int v994; int v995; int v996; int v997; int v998; int v999; int v1000; return 0; }
\ No newline at end of file
@@ -0,0 +1 @@
+int main() { return 4611686018427387904 * 4; }
\ No newline at end of file
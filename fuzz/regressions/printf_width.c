@@ -0,0 +1 @@
+int main() { printf("%99999999999999999999d", 1); printf("%999999999999d", 1); printf("%.99999999999999999999s", "x"); return 0; }
\ No newline at end of file
@@ -0,0 +1 @@
+int main() { return 1 << 99; }
\ No newline at end of file
@@ -0,0 +1 @@
+int main() { return main(); }
\ No newline at end of file
@@ -0,0 +1 @@
+int main() { return 'é
\ No newline at end of file
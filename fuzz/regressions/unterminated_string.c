@@ -0,0 +1 @@
+int main() { return "never closed
\ No newline at end of file
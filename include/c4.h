@@ -0,0 +1,58 @@
+/* include/c4.h -- C API for c4_rust_AlRafaah (build with the `capi` feature). */
+
+#ifndef C4_H
+#define C4_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status codes returned by every entry point. */
+#define C4_OK                   0
+#define C4_ERR_LEX              1
+#define C4_ERR_PARSE            2
+#define C4_ERR_COMPILE          3
+#define C4_ERR_RUNTIME          4
+#define C4_ERR_INVALID_ARGUMENT 5
+#define C4_ERR_PANIC            6
+
+/* A compiled program; opaque. */
+typedef struct C4Chunk C4Chunk;
+
+/* Error details. Zero-initialize before first use (C4Error err = {0};) and
+ * release with c4_error_free. */
+typedef struct C4Error {
+    int32_t code;
+    char *message;
+} C4Error;
+
+/* Compile NUL-terminated UTF-8 source into *out. err may be NULL. */
+int32_t c4_compile(const char *src, C4Chunk **out, C4Error *err);
+
+/* Run chunk on a fresh VM, storing main's return value in *exit_code. */
+int32_t c4_run(C4Chunk *chunk, int64_t *exit_code, C4Error *err);
+
+/* Stack size, in words, for later c4_run calls. */
+int32_t c4_set_stack_size(C4Chunk *chunk, size_t words);
+
+/* Instruction limit for later c4_run calls, 0 for none; a run that reaches
+ * it fails with C4_ERR_RUNTIME. */
+int32_t c4_set_max_steps(C4Chunk *chunk, uint64_t steps);
+
+/* Free a chunk; NULL is ignored. */
+void c4_chunk_free(C4Chunk *chunk);
+
+/* The message in err, or "" if none. Valid until err is reused or freed. */
+const char *c4_error_message(const C4Error *err);
+
+/* Free err's message and reset it to C4_OK. */
+void c4_error_free(C4Error *err);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* C4_H */
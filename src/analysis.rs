@@ -0,0 +1,277 @@
+// src/analysis.rs
+
+//! Diagnostics for editor integration: [`analyze`] checks a source file
+//! without running it and reports every problem it can find, plus an outline
+//! of the top-level symbols it managed to parse.
+//!
+//! The parser stops at the first error, so recovery happens one level up:
+//! the token stream is cut into top-level items (ending at a `;` or closing
+//! `}` at brace depth zero) and each item is parsed on its own. A broken
+//! function therefore costs only its own outline entry.
+
+use alloc::collections::BTreeSet;
+
+use crate::ast::*;
+use crate::bytecode::Chunk;
+use crate::error::Error;
+use crate::lexer::{Lexer, Token};
+use crate::parser::Parser;
+use crate::prelude::*;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One problem found in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier: `E0001` lex, `E0002` parse, `E0003` compile,
+    /// `W0001` unused local, `W0002` unreachable statement.
+    pub code: &'static str,
+    pub message: String,
+    /// Byte offset the diagnostic points at. Errors point at the offending
+    /// token where the phase reports one; warnings point at the start of
+    /// the enclosing function.
+    pub offset: Option<usize>,
+    /// A fix to offer, when there is an obvious one.
+    pub suggestion: Option<String>,
+}
+
+/// What a top-level symbol is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Global,
+    EnumConstant,
+}
+
+/// A top-level symbol for outline views.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Byte offset of the declaration that introduced it.
+    pub offset: usize,
+}
+
+/// Everything [`analyze`] learned about a file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    pub symbols: Vec<Symbol>,
+}
+
+impl Analysis {
+    /// True if any diagnostic is an error.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// Check `src` without running it. Never panics; broken input yields
+/// diagnostics and whatever symbols could still be recovered.
+pub fn analyze(src: &str) -> Analysis {
+    let mut analysis = Analysis::default();
+    for start in item_starts(src) {
+        analysis.item(src, start);
+    }
+    analysis
+}
+
+impl Analysis {
+    /// Parse, compile and lint the item beginning at `start`.
+    fn item(&mut self, src: &str, start: usize) {
+        let parsed = Parser::new(&src[start..]).and_then(|mut p| p.parse_item());
+        let items = match parsed {
+            Ok(items) => items,
+            Err(e) => return self.error(&e, start),
+        };
+        for item in &items {
+            self.symbols(item, start);
+            if let Err(e) = item.compile(&mut Chunk::default()) {
+                self.error(&Error::Compile(e), start);
+            }
+            if let Item::Function(f) = item {
+                self.lint(f, start);
+            }
+        }
+    }
+
+    fn error(&mut self, e: &Error, base: usize) {
+        let (code, offset, suggestion) = match e {
+            Error::Lex(_) => ("E0001", None, None),
+            Error::Parse(p) => {
+                let suggestion = p.message.starts_with("expected Semicolon").then(|| "insert `;`".to_string());
+                ("E0002", Some(base + p.offset), suggestion)
+            }
+            Error::Compile(_) => ("E0003", Some(base), None),
+            // Nothing is run and no files are touched.
+            _ => ("E0000", None, None),
+        };
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: e.to_string(),
+            offset,
+            suggestion,
+        });
+    }
+
+    fn warning(&mut self, code: &'static str, message: String, offset: usize, suggestion: Option<String>) {
+        self.diagnostics.push(Diagnostic { severity: Severity::Warning, code, message, offset: Some(offset), suggestion });
+    }
+
+    fn symbols(&mut self, item: &Item, offset: usize) {
+        let mut add = |name: &str, kind| self.symbols.push(Symbol { name: name.to_string(), kind, offset });
+        match item {
+            Item::Function(f) => add(&f.name, SymbolKind::Function),
+            Item::Global(g) => add(&g.name, SymbolKind::Global),
+            Item::Enum(e) => {
+                for (name, _) in &e.variants {
+                    add(name, SymbolKind::EnumConstant);
+                }
+            }
+        }
+    }
+
+    fn lint(&mut self, f: &FuncDef, offset: usize) {
+        let mut used = BTreeSet::new();
+        for stmt in &f.body.stmts {
+            stmt_vars(stmt, &mut used);
+        }
+        for (name, _) in &f.locals {
+            if !used.contains(name.as_str()) {
+                self.warning(
+                    "W0001",
+                    format!("unused local variable `{}` in `{}`", name, f.name),
+                    offset,
+                    Some(format!("remove `{}`", name)),
+                );
+            }
+        }
+        self.unreachable(&f.body, &f.name, offset);
+    }
+
+    /// Flag the first statement after a `return` in each block.
+    fn unreachable(&mut self, block: &Block, func: &str, offset: usize) {
+        let mut returned = false;
+        for stmt in &block.stmts {
+            if returned && stmt.kind != StmtKind::Empty {
+                self.warning("W0002", format!("unreachable statement in `{}`", func), offset, None);
+                break;
+            }
+            match &stmt.kind {
+                StmtKind::Return(_) => returned = true,
+                StmtKind::Block(b) => self.unreachable(b, func, offset),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Byte offsets where each top-level item starts. A lex error ends the scan;
+/// the rest of the file becomes one item, which reports the error when parsed.
+fn item_starts(src: &str) -> Vec<usize> {
+    let mut lex = Lexer::new(src);
+    let mut starts = Vec::new();
+    let mut depth = 0usize;
+    let mut at_item_start = true;
+    loop {
+        let tok = match lex.next_token() {
+            Ok(Token::Eof) => break,
+            Ok(tok) => tok,
+            Err(_) => {
+                if at_item_start {
+                    starts.push(lex.token_start());
+                }
+                break;
+            }
+        };
+        if at_item_start {
+            // `enum { ... };` ends with `}` then `;`: keep the `;` with it.
+            if tok == Token::Semicolon && !starts.is_empty() && depth == 0 {
+                continue;
+            }
+            starts.push(lex.token_start());
+            at_item_start = false;
+        }
+        match tok {
+            Token::LBrace => depth += 1,
+            Token::RBrace => {
+                depth = depth.saturating_sub(1);
+                at_item_start = depth == 0;
+            }
+            Token::Semicolon if depth == 0 => at_item_start = true,
+            _ => {}
+        }
+    }
+    starts
+}
+
+fn stmt_vars<'a>(stmt: &'a Stmt, used: &mut BTreeSet<&'a str>) {
+    match &stmt.kind {
+        StmtKind::If { cond, then_branch, else_branch } => {
+            expr_vars(cond, used);
+            stmt_vars(then_branch, used);
+            if let Some(e) = else_branch {
+                stmt_vars(e, used);
+            }
+        }
+        StmtKind::While { cond, body } | StmtKind::DoWhile { body, cond } => {
+            expr_vars(cond, used);
+            stmt_vars(body, used);
+        }
+        StmtKind::For { init, cond, step, body } => {
+            for e in [init, cond, step].into_iter().flatten() {
+                expr_vars(e, used);
+            }
+            stmt_vars(body, used);
+        }
+        StmtKind::Switch { scrutinee, cases } => {
+            expr_vars(scrutinee, used);
+            for s in cases.iter().flat_map(|(_, stmts)| stmts) {
+                stmt_vars(s, used);
+            }
+        }
+        StmtKind::Return(Some(e)) | StmtKind::Expr(e) => expr_vars(e, used),
+        StmtKind::Block(b) => {
+            for s in &b.stmts {
+                stmt_vars(s, used);
+            }
+        }
+        StmtKind::Break | StmtKind::Continue | StmtKind::Return(None) | StmtKind::Empty => {}
+    }
+}
+
+fn expr_vars<'a>(expr: &'a Expr, used: &mut BTreeSet<&'a str>) {
+    match &expr.kind {
+        ExprKind::Var(name) => {
+            used.insert(name);
+        }
+        ExprKind::Unary { expr, .. } | ExprKind::Cast { expr, .. } => expr_vars(expr, used),
+        ExprKind::Binary { left, right, .. } | ExprKind::CompoundAssign { target: left, value: right, .. } => {
+            expr_vars(left, used);
+            expr_vars(right, used);
+        }
+        ExprKind::Call { callee, args } => {
+            expr_vars(callee, used);
+            for a in args {
+                expr_vars(a, used);
+            }
+        }
+        ExprKind::Conditional { cond, then_expr, else_expr } => {
+            expr_vars(cond, used);
+            expr_vars(then_expr, used);
+            expr_vars(else_expr, used);
+        }
+        ExprKind::Index { array, index } => {
+            expr_vars(array, used);
+            expr_vars(index, used);
+        }
+        ExprKind::Num(_) | ExprKind::Str(_) | ExprKind::SizeOf(_) => {}
+    }
+}
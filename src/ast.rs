@@ -1,15 +1,31 @@
 // src/ast.rs
 
 //! Abstract Syntax Tree (AST) for the C4 compiler subset in Rust.
+//!
+//! With the `json` feature every node is serde-serializable; see
+//! the `json` module for the format.
+//!
+//! Statements and expressions remember where in the source they came
+//! from, when they came from the parser. A span covers the node's first
+//! token through its last, so `a + b * c` spans all of it and `b * c`
+//! only that part. Spans are only for reporting: they aren't compared
+//! by `==` and aren't part of the JSON, so a tree built by hand or read
+//! back from JSON equals the parsed one.
+
+use crate::prelude::*;
+
+pub use crate::lexer::Span;
 
 /// A full C4 program: a list of top-level items.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
     pub items: Vec<Item>,
 }
 
 /// Top-level items: global variables, functions, or enum declarations.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub enum Item {
     Global(GlobalDecl),
     Function(FuncDef),
@@ -17,7 +33,8 @@ pub enum Item {
 }
 
 /// A global variable declaration: e.g., `int x;` or `char *p;`
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalDecl {
     /// Name and type of a single declarator.  
     /// (Comma‐separated lists of globals are emitted as multiple `GlobalDecl` items.)
@@ -26,14 +43,16 @@ pub struct GlobalDecl {
 }
 
 /// An anonymous enum declaration: e.g., `enum { A = 0, B, C = 5 };`
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumDecl {
     /// List of (name, optional initializer)
     pub variants: Vec<(String, Option<i64>)>,
 }
 
 /// A function definition: `int f(int a, char b) { ... }`
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub struct FuncDef {
     pub ret: Type,
     pub name: String,
@@ -44,14 +63,27 @@ pub struct FuncDef {
 
 
 /// A block `{ ... }`: a sequence of statements.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
+    /// Variables declared in the block, visible throughout it. A function
+    /// body's own declarations are the function's `locals` instead.
+    #[cfg_attr(feature = "json", serde(default))]
+    pub locals: Vec<(String, Type)>,
     pub stmts: Vec<Stmt>,
 }
 
+/// A statement and the source it was parsed from.
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Option<Span>,
+}
+
 /// Statements in C4.
-#[derive(Debug, PartialEq)]
-pub enum Stmt {
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub enum StmtKind {
     If {
         cond: Expr,
         then_branch: Box<Stmt>,
@@ -61,17 +93,50 @@ pub enum Stmt {
         cond: Expr,
         body: Box<Stmt>,
     },
+    /// `do body while (cond);`: the body runs before the first test.
+    DoWhile {
+        body: Box<Stmt>,
+        cond: Expr,
+    },
+    /// `for (init; cond; step) body`; a missing `cond` is always true.
+    For {
+        init: Option<Expr>,
+        cond: Option<Expr>,
+        step: Option<Expr>,
+        body: Box<Stmt>,
+    },
+    /// `switch (scrutinee) { case 1: ... default: ... }`. Each case is its
+    /// label, `None` for `default`, and the statements up to the next
+    /// label; control falls through from one case into the next.
+    Switch {
+        scrutinee: Expr,
+        cases: Vec<(Option<i64>, Vec<Stmt>)>,
+    },
+    /// Leave the innermost loop or `switch`.
+    Break,
+    /// Skip to the next test of the innermost loop's condition (its step,
+    /// in a `for`).
+    Continue,
     Return(Option<Expr>),
     Expr(Expr),   // expression statement `expr;`
     Block(Block), // nested block
     Empty,        // empty statement `;`
 }
 
+/// An expression and the source it was parsed from.
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Option<Span>,
+}
+
 /// Expressions in C4.
-#[derive(Debug, PartialEq)]
-pub enum Expr {
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExprKind {
     Num(i64),
-    Str(String),
+    /// A string literal's bytes, without the NUL.
+    Str(Vec<u8>),
     Var(String),
     Unary {
         op: UnOp,
@@ -101,10 +166,69 @@ pub enum Expr {
         array: Box<Expr>,
         index: Box<Expr>,
     },
+    /// `target op= value`, such as `x += 2`: `target = target op value`
+    /// with `target` evaluated once.
+    CompoundAssign {
+        op: BinOp,
+        target: Box<Expr>,
+        value: Box<Expr>,
+    },
+}
+
+/// Spans, and the impls that ignore them, for both node types.
+macro_rules! spanned {
+    ($node:ident, $kind:ident) => {
+        impl $node {
+            /// `kind` spanning `span` of the source.
+            pub fn at(kind: $kind, span: Span) -> Self {
+                $node { kind, span: Some(span) }
+            }
+        }
+
+        /// A node with no span, as built by hand.
+        impl From<$kind> for $node {
+            fn from(kind: $kind) -> Self {
+                $node { kind, span: None }
+            }
+        }
+
+        impl PartialEq for $node {
+            fn eq(&self, other: &Self) -> bool {
+                self.kind == other.kind
+            }
+        }
+
+        #[cfg(feature = "json")]
+        impl serde::Serialize for $node {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                self.kind.serialize(s)
+            }
+        }
+
+        #[cfg(feature = "json")]
+        impl<'de> serde::Deserialize<'de> for $node {
+            fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                $kind::deserialize(d).map($node::from)
+            }
+        }
+    };
+}
+
+spanned!(Stmt, StmtKind);
+spanned!(Expr, ExprKind);
+
+impl Expr {
+    /// Whether the expression has the form of storage: a variable, a
+    /// dereference or an index. A variable may still turn out to be an
+    /// enum constant, which only name resolution can tell.
+    pub fn is_lvalue(&self) -> bool {
+        matches!(self.kind, ExprKind::Var(_) | ExprKind::Unary { op: UnOp::Deref, .. } | ExprKind::Index { .. })
+    }
 }
 
 /// Binary operators in C4.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinOp {
     Assign,        // =
     Add, Sub, Mul, Div, Mod,
@@ -118,7 +242,8 @@ pub enum BinOp {
 }
 
 /// Unary operators, including prefix/postfix.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnOp {
     PreInc,   // ++x
     PreDec,   // --x
@@ -134,9 +259,22 @@ pub enum UnOp {
 
 /// Types in C4: void, int, char, or pointer to.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     Void,
     Int,
     Char,
     Ptr(Box<Type>),
 }
+
+impl Type {
+    /// What `sizeof` gives, in bytes, as in c4: 1 for a `char`, 8 for an
+    /// `int`, `void` or any pointer. Pointer arithmetic steps by the
+    /// pointee's size.
+    pub fn size_of(&self) -> usize {
+        match self {
+            Type::Char => 1,
+            Type::Int | Type::Void | Type::Ptr(_) => 8,
+        }
+    }
+}
@@ -0,0 +1,460 @@
+// src/ast_display.rs
+
+//! `Display` for the AST: C source that parses back to an equal tree.
+//!
+//! Expressions get parentheses only where the parser's precedence needs
+//! them, which is this parser's rather than C's: `?:` binds tighter than
+//! `&&`, but its arms extend as far as an assignment would, so a
+//! conditional anywhere but the top of an expression or an assignment's
+//! right-hand side is parenthesized. Statements are indented four spaces
+//! per level.
+//!
+//! A function's locals are declared at the top of its body, since the
+//! parser collects them from wherever they appeared, and the empty
+//! statement the parser adds to the end of every function body isn't
+//! printed.
+
+use core::fmt::{self, Display, Formatter, Write as _};
+
+use crate::ast::*;
+use crate::prelude::*;
+
+const INDENT: &str = "    ";
+
+impl BinOp {
+    /// The operator as written in C.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Assign => "=",
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::BitAnd => "&",
+            BinOp::BitOr => "|",
+            BinOp::Xor => "^",
+            BinOp::Shl => "<<",
+            BinOp::Shr => ">>",
+            BinOp::LogAnd => "&&",
+            BinOp::LogOr => "||",
+        }
+    }
+
+    /// How tightly the parser binds it; operands of a looser kind need
+    /// parentheses.
+    fn precedence(&self) -> u8 {
+        match self {
+            BinOp::Assign => ASSIGN,
+            BinOp::LogOr => 2,
+            BinOp::LogAnd => 3,
+            BinOp::BitOr => 4,
+            BinOp::Xor => 5,
+            BinOp::BitAnd => 6,
+            BinOp::Eq | BinOp::Ne => 7,
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 8,
+            BinOp::Shl | BinOp::Shr => 9,
+            BinOp::Add | BinOp::Sub => 10,
+            BinOp::Mul | BinOp::Div | BinOp::Mod => 11,
+        }
+    }
+}
+
+impl UnOp {
+    /// The operator as written in C, before or after its operand.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnOp::PreInc | UnOp::PostInc => "++",
+            UnOp::PreDec | UnOp::PostDec => "--",
+            UnOp::Plus => "+",
+            UnOp::Neg => "-",
+            UnOp::Not => "!",
+            UnOp::BitNot => "~",
+            UnOp::Deref => "*",
+            UnOp::Addr => "&",
+        }
+    }
+
+    fn is_postfix(&self) -> bool {
+        matches!(self, UnOp::PostInc | UnOp::PostDec)
+    }
+}
+
+/// Assignments and conditionals: what `parse_assignment` accepts.
+const ASSIGN: u8 = 1;
+/// Prefix operators and casts.
+const PREFIX: u8 = 12;
+/// Calls, indexing and `x++`.
+const POSTFIX: u8 = 13;
+const PRIMARY: u8 = 14;
+
+impl Expr {
+    fn precedence(&self) -> u8 {
+        match &self.kind {
+            ExprKind::Binary { op, .. } => op.precedence(),
+            ExprKind::CompoundAssign { .. } | ExprKind::Conditional { .. } => ASSIGN,
+            ExprKind::Unary { op, .. } if op.is_postfix() => POSTFIX,
+            ExprKind::Unary { .. } | ExprKind::Cast { .. } => PREFIX,
+            ExprKind::Num(n) if *n < 0 => PREFIX,
+            ExprKind::Call { .. } | ExprKind::Index { .. } => POSTFIX,
+            ExprKind::Num(_) | ExprKind::Str(_) | ExprKind::Var(_) | ExprKind::SizeOf(_) => PRIMARY,
+        }
+    }
+
+    /// Whether it starts with a prefix operator, which another prefix
+    /// operator must be kept apart from: `- -x`, not `--x`.
+    fn starts_with_prefix(&self) -> bool {
+        match &self.kind {
+            ExprKind::Unary { op, .. } => !op.is_postfix(),
+            ExprKind::Num(n) => *n < 0,
+            _ => false,
+        }
+    }
+
+    /// Write it where the parser expects an expression of at least
+    /// precedence `min`, in parentheses if it binds more loosely.
+    fn write(&self, f: &mut Formatter<'_>, min: u8) -> fmt::Result {
+        if self.precedence() < min {
+            f.write_char('(')?;
+            self.write(f, 0)?;
+            return f.write_char(')');
+        }
+        match &self.kind {
+            ExprKind::Num(n) => write!(f, "{}", n),
+            ExprKind::Str(s) => write_string(f, s),
+            ExprKind::Var(name) => f.write_str(name),
+            ExprKind::Unary { op, expr } if op.is_postfix() => {
+                expr.write(f, POSTFIX)?;
+                f.write_str(op.symbol())
+            }
+            ExprKind::Unary { op, expr } => {
+                f.write_str(op.symbol())?;
+                if expr.starts_with_prefix() {
+                    f.write_char(' ')?;
+                }
+                expr.write(f, PREFIX)
+            }
+            ExprKind::Binary { op: op @ BinOp::Assign, left, right } => {
+                left.write(f, ASSIGN + 1)?;
+                write!(f, " {} ", op.symbol())?;
+                right.write(f, ASSIGN)
+            }
+            ExprKind::Binary { op, left, right } => {
+                left.write(f, op.precedence())?;
+                write!(f, " {} ", op.symbol())?;
+                right.write(f, op.precedence() + 1)
+            }
+            ExprKind::CompoundAssign { op, target, value } => {
+                target.write(f, ASSIGN + 1)?;
+                write!(f, " {}= ", op.symbol())?;
+                value.write(f, ASSIGN)
+            }
+            ExprKind::Call { callee, args } => {
+                callee.write(f, POSTFIX)?;
+                f.write_char('(')?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    arg.write(f, ASSIGN)?;
+                }
+                f.write_char(')')
+            }
+            ExprKind::Cast { ty, expr } => {
+                write!(f, "({})", ty)?;
+                expr.write(f, PREFIX)
+            }
+            ExprKind::SizeOf(ty) => write!(f, "sizeof({})", ty),
+            ExprKind::Conditional { cond, then_expr, else_expr } => {
+                // The condition is parsed as a `|`-expression.
+                cond.write(f, BinOp::BitOr.precedence())?;
+                f.write_str(" ? ")?;
+                then_expr.write(f, ASSIGN)?;
+                f.write_str(" : ")?;
+                else_expr.write(f, ASSIGN)
+            }
+            ExprKind::Index { array, index } => {
+                array.write(f, POSTFIX)?;
+                f.write_char('[')?;
+                index.write(f, ASSIGN)?;
+                f.write_char(']')
+            }
+        }
+    }
+}
+
+/// `s` as a string literal that lexes back to `s`: other control
+/// characters become three-digit octal escapes, so a digit after one
+/// can't extend it.
+fn write_string(f: &mut Formatter<'_>, s: &[u8]) -> fmt::Result {
+    f.write_char('"')?;
+    // UTF-8 text is written as it is; other bytes as octal escapes.
+    for chunk in s.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\n' => f.write_str("\\n")?,
+                '\t' => f.write_str("\\t")?,
+                '\r' => f.write_str("\\r")?,
+                '"' | '\\' => write!(f, "\\{}", c)?,
+                c if c.is_ascii_control() => write!(f, "\\{:03o}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        for b in chunk.invalid() {
+            write!(f, "\\{:03o}", b)?;
+        }
+    }
+    f.write_char('"')
+}
+
+/// `int *p`: the type, then the name, with the stars against the name.
+fn write_decl(f: &mut Formatter<'_>, ty: &Type, name: &str) -> fmt::Result {
+    match ty {
+        Type::Ptr(_) => write!(f, "{}{}", ty, name),
+        _ => write!(f, "{} {}", ty, name),
+    }
+}
+
+fn pad(f: &mut Formatter<'_>, level: usize) -> fmt::Result {
+    for _ in 0..level {
+        f.write_str(INDENT)?;
+    }
+    Ok(())
+}
+
+/// `{`, the declarations and statements a level deeper, then `}` at
+/// `level`. The opening brace is written where the caller left off.
+fn write_block(f: &mut Formatter<'_>, locals: &[(String, Type)], stmts: &[Stmt], level: usize) -> fmt::Result {
+    f.write_str("{\n")?;
+    for (name, ty) in locals {
+        pad(f, level + 1)?;
+        write_decl(f, ty, name)?;
+        f.write_str(";\n")?;
+    }
+    for stmt in stmts {
+        stmt.write(f, level + 1)?;
+        f.write_char('\n')?;
+    }
+    pad(f, level)?;
+    f.write_char('}')
+}
+
+impl Stmt {
+    /// Whether it ends in an `if` without an `else`, which would take an
+    /// `else` written after it.
+    fn ends_in_open_if(&self) -> bool {
+        match &self.kind {
+            StmtKind::If { else_branch: None, .. } => true,
+            StmtKind::If { else_branch: Some(s), .. } | StmtKind::While { body: s, .. } | StmtKind::For { body: s, .. } => {
+                s.ends_in_open_if()
+            }
+            _ => false,
+        }
+    }
+
+    /// Write it as the body of an `if`, loop or `else`, after the header
+    /// at `level`: a block on the same line, anything else on the next.
+    fn write_body(&self, f: &mut Formatter<'_>, level: usize) -> fmt::Result {
+        if let StmtKind::Block(b) = &self.kind {
+            f.write_char(' ')?;
+            write_block(f, &b.locals, &b.stmts, level)
+        } else {
+            f.write_char('\n')?;
+            self.write(f, level + 1)
+        }
+    }
+
+    /// Write it indented to `level`, without a trailing newline.
+    fn write(&self, f: &mut Formatter<'_>, level: usize) -> fmt::Result {
+        pad(f, level)?;
+        self.write_unpadded(f, level)
+    }
+
+    fn write_unpadded(&self, f: &mut Formatter<'_>, level: usize) -> fmt::Result {
+        match &self.kind {
+            StmtKind::If { cond, then_branch, else_branch } => {
+                write!(f, "if ({})", cond)?;
+                let Some(else_branch) = else_branch else {
+                    return then_branch.write_body(f, level);
+                };
+                if then_branch.ends_in_open_if() {
+                    // Braces keep the `else` with this `if`.
+                    f.write_str(" {\n")?;
+                    then_branch.write(f, level + 1)?;
+                    f.write_char('\n')?;
+                    pad(f, level)?;
+                    f.write_str("} else")?;
+                } else if let StmtKind::Block(_) = then_branch.kind {
+                    then_branch.write_body(f, level)?;
+                    f.write_str(" else")?;
+                } else {
+                    then_branch.write_body(f, level)?;
+                    f.write_char('\n')?;
+                    pad(f, level)?;
+                    f.write_str("else")?;
+                }
+                if let StmtKind::If { .. } = else_branch.kind {
+                    f.write_char(' ')?;
+                    else_branch.write_unpadded(f, level)
+                } else {
+                    else_branch.write_body(f, level)
+                }
+            }
+            StmtKind::While { cond, body } => {
+                write!(f, "while ({})", cond)?;
+                body.write_body(f, level)
+            }
+            StmtKind::DoWhile { body, cond } => {
+                f.write_str("do")?;
+                body.write_body(f, level)?;
+                if let StmtKind::Block(_) = body.kind {
+                    f.write_char(' ')?;
+                } else {
+                    f.write_char('\n')?;
+                    pad(f, level)?;
+                }
+                write!(f, "while ({});", cond)
+            }
+            StmtKind::For { init, cond, step, body } => {
+                f.write_str("for (")?;
+                if let Some(init) = init {
+                    write!(f, "{}", init)?;
+                }
+                f.write_char(';')?;
+                if let Some(cond) = cond {
+                    write!(f, " {}", cond)?;
+                }
+                f.write_char(';')?;
+                if let Some(step) = step {
+                    write!(f, " {}", step)?;
+                }
+                f.write_char(')')?;
+                body.write_body(f, level)
+            }
+            StmtKind::Switch { scrutinee, cases } => {
+                writeln!(f, "switch ({}) {{", scrutinee)?;
+                for (label, stmts) in cases {
+                    pad(f, level)?;
+                    match label {
+                        Some(n) => writeln!(f, "case {}:", n)?,
+                        None => f.write_str("default:\n")?,
+                    }
+                    for stmt in stmts {
+                        stmt.write(f, level + 1)?;
+                        f.write_char('\n')?;
+                    }
+                }
+                pad(f, level)?;
+                f.write_char('}')
+            }
+            StmtKind::Break => f.write_str("break;"),
+            StmtKind::Continue => f.write_str("continue;"),
+            StmtKind::Return(None) => f.write_str("return;"),
+            StmtKind::Return(Some(e)) => write!(f, "return {};", e),
+            StmtKind::Expr(e) => write!(f, "{};", e),
+            StmtKind::Block(b) => write_block(f, &b.locals, &b.stmts, level),
+            StmtKind::Empty => f.write_char(';'),
+        }
+    }
+}
+
+impl Display for Type {
+    /// `int`, or `char **` for pointers.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Void => f.write_str("void"),
+            Type::Int => f.write_str("int"),
+            Type::Char => f.write_str("char"),
+            Type::Ptr(inner) => match **inner {
+                Type::Ptr(_) => write!(f, "{}*", inner),
+                _ => write!(f, "{} *", inner),
+            },
+        }
+    }
+}
+
+impl Display for BinOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+impl Display for UnOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+impl Display for Stmt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+impl Display for FuncDef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_decl(f, &self.ret, &self.name)?;
+        f.write_char('(')?;
+        for (i, (name, ty)) in self.params.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write_decl(f, ty, name)?;
+        }
+        f.write_str(") ")?;
+        let stmts = match self.body.stmts.split_last() {
+            Some((last, rest)) if last.kind == StmtKind::Empty => rest,
+            _ => &self.body.stmts,
+        };
+        write_block(f, &self.locals, stmts, 0)
+    }
+}
+
+impl Display for Item {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Item::Global(g) => {
+                write_decl(f, &g.ty, &g.name)?;
+                f.write_char(';')
+            }
+            Item::Function(func) => write!(f, "{}", func),
+            Item::Enum(e) => {
+                f.write_str("enum {")?;
+                for (i, (name, value)) in e.variants.iter().enumerate() {
+                    f.write_str(if i > 0 { ", " } else { " " })?;
+                    f.write_str(name)?;
+                    if let Some(n) = value {
+                        write!(f, " = {}", n)?;
+                    }
+                }
+                f.write_str(" };")
+            }
+        }
+    }
+}
+
+impl Display for Program {
+    /// Each item on its own line, with functions set off by blank lines.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, item) in self.items.iter().enumerate() {
+            let function = matches!(item, Item::Function(_));
+            if i > 0 && (function || matches!(self.items[i - 1], Item::Function(_))) {
+                f.write_char('\n')?;
+            }
+            writeln!(f, "{}", item)?;
+        }
+        Ok(())
+    }
+}
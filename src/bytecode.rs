@@ -1,3 +1,10 @@
+use core::fmt;
+use core::ops::Range;
+
+use crate::ast::Type;
+use crate::prelude::*;
+use crate::symtable::SymbolTable;
+
 /// Defines the bytecode instruction set, along with helper data structures
 /// that represent compiled bytecode chunks in the Rust version of the C4 compiler.
 
@@ -48,6 +55,33 @@ pub enum OpCode {
     MSET,
     MCMP,
     EXIT,
+
+    // Extensions
+    /// Fail with `VmError::AssertionFailed` if `a` is zero.
+    ASRT,
+    /// `a = rand()`, from the run's `vm::Rng`.
+    RAND,
+    /// `srand(a)`: reseed the run's `vm::Rng`.
+    SRND,
+    /// `a = clock()`: milliseconds since the run's clock was started.
+    CLCK,
+    /// `a = time(a)`: seconds since the Unix epoch; the argument is ignored.
+    TIME,
+    /// `GLO n`: `a` = the address of word `n` of the data segment.
+    GLO,
+}
+
+impl OpCode {
+    /// Every opcode, in declaration order, so `ALL[op as usize] == op`.
+    pub const ALL: [OpCode; 45] = [
+        OpCode::LEA, OpCode::IMM, OpCode::JMP, OpCode::JSR, OpCode::BZ, OpCode::BNZ, OpCode::ENT,
+        OpCode::ADJ, OpCode::LEV, OpCode::LI, OpCode::LC, OpCode::SI, OpCode::SC, OpCode::PSH,
+        OpCode::OR, OpCode::XOR, OpCode::AND, OpCode::EQ, OpCode::NE, OpCode::LT, OpCode::GT,
+        OpCode::LE, OpCode::GE, OpCode::SHL, OpCode::SHR, OpCode::ADD, OpCode::SUB, OpCode::MUL,
+        OpCode::DIV, OpCode::MOD, OpCode::OPEN, OpCode::READ, OpCode::CLOS, OpCode::PRTF,
+        OpCode::MALC, OpCode::FREE, OpCode::MSET, OpCode::MCMP, OpCode::EXIT, OpCode::ASRT,
+        OpCode::RAND, OpCode::SRND, OpCode::CLCK, OpCode::TIME, OpCode::GLO,
+    ];
 }
 
 /// A single instruction can be an OpCode with optional operands
@@ -63,13 +97,165 @@ pub enum Instruction {
     Call(OpCode, usize),
 }
 
+/// Version of the instruction set. Bump it whenever the compiler starts
+/// emitting different code for the same source, or the serialized form of
+/// a chunk changes, so chunks saved by an older build are not reused.
+pub const FORMAT_VERSION: u32 = 9;
+
+/// Bytes in a word, the unit the stack, heap and data segment are made of
+/// and the size of an `int` or a pointer. Addresses count bytes; a word's
+/// bytes are stored little-endian.
+pub const WORD_SIZE: usize = 8;
+
+/// `bytes` packed into words, little-endian, the last one zero-padded.
+pub fn pack_bytes(bytes: &[u8]) -> Vec<i64> {
+    bytes
+        .chunks(WORD_SIZE)
+        .map(|chunk| {
+            let mut word = [0; WORD_SIZE];
+            word[..chunk.len()].copy_from_slice(chunk);
+            i64::from_le_bytes(word)
+        })
+        .collect()
+}
+
+/// A compiled function: its name, parameter names and the address of its
+/// `ENT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuncSym {
+    pub name: String,
+    pub params: Vec<String>,
+    pub entry: usize,
+}
+
+/// A global variable: its name, type and the offset of its word in the
+/// data segment, in bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalSym {
+    pub name: String,
+    pub ty: Type,
+    pub offset: usize,
+}
+
+/// Where a chunk's code came from: `(pc, offset)` pairs in pc order, each
+/// saying the instructions from `pc` on were compiled from the source at
+/// byte `offset`, or from no source at all. Like the AST's spans it is only
+/// for reporting, so it isn't compared by `==`.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap(pub Vec<(usize, Option<usize>)>);
+
+impl PartialEq for SourceMap {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
 /// Represents a compiled chunk of instructions
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
 pub struct Chunk {
     pub code: Vec<Instruction>,
+    /// Every compiled function, in definition order.
+    pub functions: Vec<FuncSym>,
+    /// Calls compiled before their callee. Compiling a whole program
+    /// patches them all once every function has an entry.
+    pub symbols: SymbolTable,
+    /// The data segment as a run starts: one word per global, zeroed,
+    /// and each string literal and its NUL packed into as many words as
+    /// they take.
+    pub data: Vec<i64>,
+    /// Every global variable, in definition order.
+    pub globals: Vec<GlobalSym>,
+    /// Enum constants and their values, which compile to `IMM`.
+    pub constants: Vec<(String, i64)>,
+    /// Each distinct string literal and the offset of its first byte in
+    /// the data segment.
+    pub strings: Vec<(Vec<u8>, usize)>,
+    /// The statement each instruction was compiled from, for code
+    /// compiled from parsed source.
+    pub source_map: SourceMap,
 }
 
 impl Chunk {
+    /// The compiled function called `name`, if there is one.
+    pub fn function(&self, name: &str) -> Option<&FuncSym> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+
+    /// The global variable called `name`, if there is one.
+    pub fn global(&self, name: &str) -> Option<&GlobalSym> {
+        self.globals.iter().find(|g| g.name == name)
+    }
+
+    /// Give the global `name` the next word of the data segment, zeroed,
+    /// and return its offset there in bytes.
+    pub fn alloc_global(&mut self, name: &str, ty: Type) -> usize {
+        let offset = self.data.len() * WORD_SIZE;
+        self.globals.push(GlobalSym { name: name.to_string(), ty, offset });
+        self.data.push(0);
+        offset
+    }
+
+    /// The value of the enum constant called `name`, if there is one.
+    pub fn constant(&self, name: &str) -> Option<i64> {
+        self.constants.iter().find(|(n, _)| n == name).map(|&(_, v)| v)
+    }
+
+    /// The data segment offset of the string literal `s`, in bytes,
+    /// storing it NUL-terminated from the start of a word the first time
+    /// it is seen.
+    pub fn intern(&mut self, s: &[u8]) -> usize {
+        if let Some(&(_, offset)) = self.strings.iter().find(|(t, _)| t == s) {
+            return offset;
+        }
+        let offset = self.data.len() * WORD_SIZE;
+        let mut bytes = s.to_vec();
+        bytes.push(0);
+        self.data.extend(pack_bytes(&bytes));
+        self.strings.push((s.to_vec(), offset));
+        offset
+    }
+
+    /// The function whose code contains `pc`: the last one starting at or
+    /// before it. None for startup code ahead of the first function.
+    pub fn function_at(&self, pc: usize) -> Option<&FuncSym> {
+        self.functions.iter().filter(|f| f.entry <= pc).max_by_key(|f| f.entry)
+    }
+
+    /// Attribute the instructions compiled from now on to the source at
+    /// `offset`, or to none, returning what they were attributed to
+    /// before so it can be restored.
+    pub fn mark_source(&mut self, offset: Option<usize>) -> Option<usize> {
+        let pc = self.code.len();
+        let map = &mut self.source_map.0;
+        let before = map.last().and_then(|&(_, offset)| offset);
+        match map.last_mut() {
+            Some(last) if last.0 == pc => last.1 = offset,
+            _ if before != offset => map.push((pc, offset)),
+            _ => {}
+        }
+        before
+    }
+
+    /// Byte offset of the source the instruction at `pc` was compiled
+    /// from, if it is known.
+    pub fn source_offset(&self, pc: usize) -> Option<usize> {
+        let map = &self.source_map.0;
+        let i = map.partition_point(|&(start, _)| start <= pc);
+        map.get(i.checked_sub(1)?)?.1
+    }
+
+    /// FNV-1a hash of the instruction set version and the canonical
+    /// listing, for telling whether a saved artifact belongs to this chunk.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let listing = self.to_asm();
+        for byte in FORMAT_VERSION.to_le_bytes().iter().chain(listing.as_bytes()) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        hash
+    }
+
     /// Add a no-operand instruction
     pub fn push(&mut self, op: OpCode) {
         self.code.push(Instruction::Instr(op));
@@ -85,15 +271,372 @@ impl Chunk {
         self.code.push(Instruction::Jump(op, target));
     }
 
+    /// The address the next instruction will have.
+    pub fn current_offset(&self) -> usize {
+        self.code.len()
+    }
+
+    /// Point the jump at `at` to `target`, keeping its opcode.
+    ///
+    /// # Panics
+    ///
+    /// If the instruction at `at` isn't a jump.
+    pub fn patch_jump(&mut self, at: usize, target: usize) {
+        match &mut self.code[at] {
+            Instruction::Jump(_, t) => *t = target,
+            other => panic!("patch_jump at {}: {:?} is not a jump", at, other),
+        }
+    }
+
     /// Add a call instruction
     pub fn push_call(&mut self, op: OpCode, target: usize) {
         self.code.push(Instruction::Call(op, target));
     }
 
+    /// Canonical assembly listing, stable enough to snapshot in tests: every
+    /// jump or call target gets a label `L0`, `L1`, ... numbered in address
+    /// order, and instructions are printed one per line without addresses.
+    /// A non-empty data segment follows, its words on one line.
+    pub fn to_asm(&self) -> String {
+        self.listing(|_| "    ".to_string(), false, 0..self.code.len())
+    }
+
+    /// Like [`Chunk::to_asm`], but for reading rather than snapshotting:
+    /// each instruction starts with its address, as in `0003: BZ L1`, and
+    /// each function starts after a blank line with its name, which calls
+    /// use in place of a label.
+    pub fn disassemble(&self) -> String {
+        self.listing(|addr| format!("{:04}: ", addr), true, 0..self.code.len())
+    }
+
+    /// The part of [`Chunk::disassemble`] for the function called `name`:
+    /// its instructions up to the next function's entry, labelled as in
+    /// the whole listing. None if there's no such function.
+    pub fn disassemble_function(&self, name: &str) -> Option<String> {
+        let entry = self.function(name)?.entry;
+        let end = self.functions.iter().map(|f| f.entry).filter(|&e| e > entry).min().unwrap_or(self.code.len());
+        Some(self.listing(|addr| format!("{:04}: ", addr), true, entry..end))
+    }
+
+    /// The symbols the compiler resolved, for reading: each function with
+    /// its entry, arity and parameters, each global with its data segment
+    /// offset, type and initial value, and each enum constant's value.
+    pub fn symbol_listing(&self) -> String {
+        let mut out = String::from("functions:\n");
+        for f in &self.functions {
+            out += &format!("    {:04}: {}({}), {} params\n", f.entry, f.name, f.params.join(", "), f.params.len());
+        }
+        out += "globals:\n";
+        for g in &self.globals {
+            let init = self.data.get(g.offset / WORD_SIZE).copied().unwrap_or_default();
+            out += &format!("    {:04}: {} {} = {}\n", g.offset, g.ty, g.name, init);
+        }
+        out += "enum constants:\n";
+        for (name, value) in &self.constants {
+            out += &format!("    {} = {}\n", name, value);
+        }
+        out
+    }
+
+    /// Every jump or call target, sorted, leaving out function entries if
+    /// they are `named`: label `Ln` is the `n`th.
+    fn jump_targets(&self, named: bool) -> Vec<usize> {
+        let mut targets: Vec<usize> = self
+            .code
+            .iter()
+            .filter_map(|instr| match instr {
+                Instruction::Jump(_, t) | Instruction::Call(_, t) => Some(*t),
+                _ => None,
+            })
+            .filter(|t| !named || !self.functions.iter().any(|f| f.entry == *t))
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+        targets
+    }
+
+    /// The labelled listing of the instructions in `range`, each after
+    /// `prefix(addr)`, and with function names if `named`. Labels past the
+    /// end and the data segment only come with the whole chunk.
+    fn listing(&self, prefix: impl Fn(usize) -> String, named: bool, range: Range<usize>) -> String {
+        let targets = self.jump_targets(named);
+        let function_at = |t: usize| self.functions.iter().find(|f| named && f.entry == t);
+        let label = |t: usize| match function_at(t) {
+            Some(f) => Ok(f.name.clone()),
+            None => targets.binary_search(&t).map(|i| format!("L{}", i)),
+        };
+
+        let mut out = String::new();
+        let whole = range == (0..self.code.len());
+        for (addr, instr) in self.code.iter().enumerate().take(range.end).skip(range.start) {
+            if let Some(f) = function_at(addr) {
+                out += &format!("\n{}:\n", f.name);
+            } else if let Ok(l) = label(addr) {
+                out += &format!("{}:\n", l);
+            }
+            let line = match instr {
+                Instruction::Instr(op) => format!("{:?}", op),
+                Instruction::InstrInt(op, n) => format!("{:?} {}", op, n),
+                Instruction::Jump(op, t) | Instruction::Call(op, t) => {
+                    format!("{:?} {}", op, label(*t).unwrap_or_default())
+                }
+            };
+            out += &format!("{}{}\n", prefix(addr), line);
+        }
+        if !whole {
+            return out;
+        }
+        // Targets past the last instruction (jumps to the end).
+        for &t in targets.iter().filter(|&&t| t >= self.code.len()) {
+            out += &format!("{}:\n", label(t).unwrap_or_default());
+        }
+        if !self.data.is_empty() {
+            let words: Vec<String> = self.data.iter().map(|w| w.to_string()).collect();
+            out += &format!("data:\n    {}\n", words.join(" "));
+        }
+        out
+    }
+
+    /// The chunk in a compact binary form that [`Chunk::deserialize`] reads
+    /// back. All numbers are little-endian:
+    ///
+    /// - the magic `C4RS`, then [`FORMAT_VERSION`] as 4 bytes;
+    /// - a 4-byte instruction count, then each instruction as a tag byte
+    ///   (0 `Instr`, 1 `InstrInt`, 2 `Jump`, 3 `Call`), its opcode byte
+    ///   and, unless it is an `Instr`, an 8-byte operand;
+    /// - the functions: a 4-byte count, then each one's name, a 4-byte
+    ///   parameter count and the parameters' names, and entry;
+    /// - the data segment: a 4-byte count, then 8 bytes per word;
+    /// - the globals: a 4-byte count, then each one's name, type and
+    ///   offset;
+    /// - the enum constants: a 4-byte count, then each one's name and
+    ///   8-byte value;
+    /// - the string literals: a 4-byte count, then each one's text, as a
+    ///   name, and offset;
+    /// - the source map: a 4-byte count, then each entry's pc and source
+    ///   offset, with `u64::MAX` for none.
+    ///
+    /// Names are a 4-byte length and UTF-8; counts and addresses inside
+    /// those tables take 8 bytes; a type is its number of `*`s and a byte
+    /// for `void`, `int` or `char`. Pending calls, which only a chunk
+    /// still being compiled has, aren't saved.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        put_len(&mut out, self.code.len());
+        for instr in &self.code {
+            let (tag, op, operand) = match *instr {
+                Instruction::Instr(op) => (0, op, None),
+                Instruction::InstrInt(op, n) => (1, op, Some(n as u64)),
+                Instruction::Jump(op, t) => (2, op, Some(t as u64)),
+                Instruction::Call(op, t) => (3, op, Some(t as u64)),
+            };
+            out.extend_from_slice(&[tag, op as u8]);
+            if let Some(operand) = operand {
+                out.extend_from_slice(&operand.to_le_bytes());
+            }
+        }
+        put_len(&mut out, self.functions.len());
+        for f in &self.functions {
+            put_str(&mut out, &f.name);
+            put_len(&mut out, f.params.len());
+            for param in &f.params {
+                put_str(&mut out, param);
+            }
+            out.extend_from_slice(&(f.entry as u64).to_le_bytes());
+        }
+        put_len(&mut out, self.data.len());
+        for word in &self.data {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        put_len(&mut out, self.globals.len());
+        for g in &self.globals {
+            put_str(&mut out, &g.name);
+            let (mut base, mut stars) = (&g.ty, 0u8);
+            while let Type::Ptr(inner) = base {
+                base = inner;
+                stars += 1;
+            }
+            let base = match base {
+                Type::Void => 0,
+                Type::Int => 1,
+                _ => 2,
+            };
+            out.extend_from_slice(&[stars, base]);
+            out.extend_from_slice(&(g.offset as u64).to_le_bytes());
+        }
+        put_len(&mut out, self.constants.len());
+        for (name, value) in &self.constants {
+            put_str(&mut out, name);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        put_len(&mut out, self.strings.len());
+        for (s, offset) in &self.strings {
+            put_bytes(&mut out, s);
+            out.extend_from_slice(&(*offset as u64).to_le_bytes());
+        }
+        put_len(&mut out, self.source_map.0.len());
+        for &(pc, offset) in &self.source_map.0 {
+            out.extend_from_slice(&(pc as u64).to_le_bytes());
+            out.extend_from_slice(&offset.map_or(u64::MAX, |o| o as u64).to_le_bytes());
+        }
+        out
+    }
+
+    /// Read back a chunk written by [`Chunk::serialize`]. Fails on a bad
+    /// magic number, a different [`FORMAT_VERSION`], or data that is
+    /// truncated, malformed or followed by anything else.
+    pub fn deserialize(bytes: &[u8]) -> Result<Chunk, String> {
+        let mut r = Reader { bytes };
+        if r.take(4)? != MAGIC {
+            return Err("not a serialized chunk: bad magic".into());
+        }
+        let version = r.u32()?;
+        if version != FORMAT_VERSION {
+            return Err(format!("chunk format version {} is not {}", version, FORMAT_VERSION));
+        }
+        let mut chunk = Chunk::default();
+        for _ in 0..r.u32()? {
+            let tag = r.u8()?;
+            let op = r.u8()?;
+            let op = *OpCode::ALL.get(op as usize).ok_or_else(|| format!("unknown opcode {}", op))?;
+            chunk.code.push(match tag {
+                0 => Instruction::Instr(op),
+                1 => Instruction::InstrInt(op, r.u64()? as i64),
+                2 => Instruction::Jump(op, r.usize()?),
+                3 => Instruction::Call(op, r.usize()?),
+                _ => return Err(format!("unknown instruction tag {}", tag)),
+            });
+        }
+        for _ in 0..r.u32()? {
+            let name = r.string()?;
+            let params = (0..r.u32()?).map(|_| r.string()).collect::<Result<_, _>>()?;
+            chunk.functions.push(FuncSym { name, params, entry: r.usize()? });
+        }
+        for _ in 0..r.u32()? {
+            chunk.data.push(r.u64()? as i64);
+        }
+        for _ in 0..r.u32()? {
+            let name = r.string()?;
+            let stars = r.u8()?;
+            let mut ty = match r.u8()? {
+                0 => Type::Void,
+                1 => Type::Int,
+                2 => Type::Char,
+                other => return Err(format!("unknown type {}", other)),
+            };
+            for _ in 0..stars {
+                ty = Type::Ptr(Box::new(ty));
+            }
+            chunk.globals.push(GlobalSym { name, ty, offset: r.usize()? });
+        }
+        for _ in 0..r.u32()? {
+            let name = r.string()?;
+            chunk.constants.push((name, r.u64()? as i64));
+        }
+        for _ in 0..r.u32()? {
+            let s = r.byte_string()?;
+            chunk.strings.push((s, r.usize()?));
+        }
+        for _ in 0..r.u32()? {
+            let pc = r.usize()?;
+            let offset = match r.u64()? {
+                u64::MAX => None,
+                n => Some(usize::try_from(n).map_err(|_| format!("{} is out of range", n))?),
+            };
+            chunk.source_map.0.push((pc, offset));
+        }
+        if !r.bytes.is_empty() {
+            return Err(format!("{} bytes after the chunk", r.bytes.len()));
+        }
+        Ok(chunk)
+    }
+
+    /// Write the chunk to `out` in [`Chunk::serialize`]'s format.
+    #[cfg(feature = "std")]
+    pub fn write_to(&self, out: &mut impl std::io::Write) -> std::io::Result<()> {
+        out.write_all(&self.serialize())
+    }
+
+    /// Read a chunk written by [`Chunk::write_to`] from the rest of
+    /// `input`. Anything [`Chunk::deserialize`] rejects is an
+    /// `InvalidData` error.
+    #[cfg(feature = "std")]
+    pub fn read_from(input: &mut impl std::io::Read) -> std::io::Result<Chunk> {
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+        Chunk::deserialize(&bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
     /// Debug helper to print all instructions
+    #[cfg(feature = "std")]
     pub fn dump(&self) {
-        for (i, instr) in self.code.iter().enumerate() {
-            println!("{:04}: {:?}", i, instr);
+        print!("{}", self);
+    }
+}
+
+/// The [`Chunk::disassemble`] listing.
+impl fmt::Display for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.disassemble())
+    }
+}
+
+/// The first bytes of a serialized chunk.
+const MAGIC: &[u8; 4] = b"C4RS";
+
+fn put_len(out: &mut Vec<u8>, len: usize) {
+    out.extend_from_slice(&(len as u32).to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_bytes(out, s.as_bytes());
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_len(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+/// The unread rest of a serialized chunk.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < n {
+            return Err("truncated chunk".into());
         }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().expect("4 bytes")))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().expect("8 bytes")))
+    }
+
+    fn usize(&mut self) -> Result<usize, String> {
+        let n = self.u64()?;
+        usize::try_from(n).map_err(|_| format!("{} is out of range", n))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        String::from_utf8(self.byte_string()?).map_err(|_| "name is not UTF-8".to_string())
+    }
+
+    fn byte_string(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
     }
 }
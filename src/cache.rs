@@ -0,0 +1,169 @@
+// src/cache.rs
+
+//! A compile cache for long-running hosts (REPL servers, watch loops,
+//! graders) that see the same sources over and over. Chunks are keyed on the
+//! source text, the [`Compiler`]'s options and [`FORMAT_VERSION`], shared as
+//! `Arc`s, and evicted least-recently-used once the cache is full.
+//!
+//! A cache made with [`CompileCache::with_dir`] also keeps every chunk it
+//! compiles as a file in that directory, so a later cache, even in another
+//! process, can load it instead of compiling again. Each file holds the
+//! source, to rule out hash collisions, followed by the serialized chunk.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::bytecode::{Chunk, FORMAT_VERSION};
+use crate::compiler::Compiler;
+use crate::error::Error;
+
+struct Entry {
+    source: String,
+    chunk: Arc<Chunk>,
+}
+
+/// A bounded cache of compiled chunks.
+pub struct CompileCache {
+    capacity: usize,
+    entries: HashMap<u64, Entry>,
+    /// Keys from least to most recently used.
+    order: VecDeque<u64>,
+    dir: Option<PathBuf>,
+    hits: u64,
+    misses: u64,
+}
+
+impl CompileCache {
+    /// An in-memory cache holding at most `capacity` chunks (at least one).
+    pub fn new(capacity: usize) -> Self {
+        CompileCache {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            dir: None,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// A cache holding `capacity` chunks in memory and every chunk it
+    /// compiles in `dir`, which is created if missing.
+    pub fn with_dir(capacity: usize, dir: impl AsRef<Path>) -> Result<Self, Error> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(CompileCache { dir: Some(dir.as_ref().to_path_buf()), ..CompileCache::new(capacity) })
+    }
+
+    /// The chunk `compiler` makes of `src`, compiling it only if it isn't
+    /// cached in memory or on disk. Warnings are not collected. Errors are
+    /// returned as-is and not cached.
+    pub fn get_or_compile(&mut self, compiler: &Compiler, src: &str) -> Result<Arc<Chunk>, Error> {
+        let key = cache_key(compiler, src);
+        if let Some(entry) = self.entries.get(&key) {
+            // A hash collision compiles afresh and replaces the old entry.
+            if entry.source == src {
+                let chunk = Arc::clone(&entry.chunk);
+                self.hits += 1;
+                self.touch(key);
+                return Ok(chunk);
+            }
+        }
+
+        let chunk = match self.read_file(key, src) {
+            Some(chunk) => {
+                self.hits += 1;
+                Arc::new(chunk)
+            }
+            None => {
+                self.misses += 1;
+                let chunk = compiler.compile_str(src)?.chunk;
+                self.write_file(key, src, &chunk);
+                Arc::new(chunk)
+            }
+        };
+        if self.entries.insert(key, Entry { source: src.to_string(), chunk: Arc::clone(&chunk) }).is_some() {
+            self.touch(key);
+        } else {
+            self.order.push_back(key);
+            if self.order.len() > self.capacity {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.entries.remove(&oldest);
+                }
+            }
+        }
+        Ok(chunk)
+    }
+
+    /// Mark `key` as the most recently used.
+    fn touch(&mut self, key: u64) {
+        if let Some(i) = self.order.iter().position(|&k| k == key) {
+            self.order.remove(i);
+        }
+        self.order.push_back(key);
+    }
+
+    fn path(&self, key: u64) -> Option<PathBuf> {
+        Some(self.dir.as_ref()?.join(format!("{:016x}.c4c", key)))
+    }
+
+    /// The chunk stored for `key`, if there is one and it was compiled from
+    /// `src`. Unreadable or stale files count as missing.
+    fn read_file(&self, key: u64, src: &str) -> Option<Chunk> {
+        let bytes = fs::read(self.path(key)?).ok()?;
+        let len = usize::try_from(u64::from_le_bytes(bytes.get(..8)?.try_into().ok()?)).ok()?;
+        let (source, chunk) = bytes[8..].split_at_checked(len)?;
+        if source != src.as_bytes() {
+            return None;
+        }
+        Chunk::deserialize(chunk).ok()
+    }
+
+    /// Store `chunk` for `key`. A cache that can't write still works, so
+    /// failures are ignored.
+    fn write_file(&self, key: u64, src: &str, chunk: &Chunk) {
+        let Some(path) = self.path(key) else {
+            return;
+        };
+        let mut bytes = (src.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(src.as_bytes());
+        bytes.extend(chunk.serialize());
+        let _ = fs::write(path, bytes);
+    }
+
+    /// Number of chunks currently cached in memory.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Lookups answered from memory or disk.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Lookups that had to compile.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Drop every chunk cached in memory. Chunks already handed out stay
+    /// valid, and files on disk are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+fn cache_key(compiler: &Compiler, src: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    FORMAT_VERSION.hash(&mut hasher);
+    compiler.hash(&mut hasher);
+    src.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,188 @@
+// src/capi.rs
+
+//! C-callable embedding API, enabled by the `capi` feature. The matching
+//! header is `include/c4.h`; build the shared library with
+//! `cargo rustc --release --features capi --lib --crate-type cdylib`.
+//!
+//! Handles are opaque pointers, strings are NUL-terminated UTF-8, and every
+//! entry point returns a `C4_*` status code. Panics never cross the FFI
+//! boundary; they are reported as `C4_ERR_PANIC`.
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::bytecode::Chunk;
+use crate::driver::compile_source;
+use crate::error::Error;
+use crate::vm::{VmConfig, VM};
+
+pub const C4_OK: i32 = 0;
+pub const C4_ERR_LEX: i32 = 1;
+pub const C4_ERR_PARSE: i32 = 2;
+pub const C4_ERR_COMPILE: i32 = 3;
+pub const C4_ERR_RUNTIME: i32 = 4;
+pub const C4_ERR_INVALID_ARGUMENT: i32 = 5;
+pub const C4_ERR_PANIC: i32 = 6;
+
+/// A compiled program plus the VM settings it will run with.
+pub struct C4Chunk {
+    chunk: Chunk,
+    config: VmConfig,
+}
+
+/// Error details filled in by failing calls. `message` is owned by the
+/// library; release it with [`c4_error_free`].
+#[repr(C)]
+pub struct C4Error {
+    pub code: i32,
+    pub message: *mut c_char,
+}
+
+/// Status code for a crate error.
+fn error_code(e: &Error) -> i32 {
+    match e {
+        Error::Lex(_) => C4_ERR_LEX,
+        Error::Parse(_) => C4_ERR_PARSE,
+        Error::Compile(_) => C4_ERR_COMPILE,
+        Error::Runtime(_) => C4_ERR_RUNTIME,
+        Error::Io(_) => C4_ERR_INVALID_ARGUMENT,
+    }
+}
+
+/// Record `code` and `message` in `err` (if non-null) and return `code`.
+unsafe fn fail(err: *mut C4Error, code: i32, message: &str) -> i32 {
+    if let Some(err) = err.as_mut() {
+        c4_error_free(err);
+        err.code = code;
+        // Interior NULs can't appear in a C string; drop them.
+        let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+        err.message = message.into_raw();
+    }
+    code
+}
+
+/// Run `f`, turning a panic into `C4_ERR_PANIC`.
+unsafe fn guard(err: *mut C4Error, f: impl FnOnce() -> Result<(), (i32, String)>) -> i32 {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => {
+            if let Some(err) = err.as_mut() {
+                c4_error_free(err);
+            }
+            C4_OK
+        }
+        Ok(Err((code, message))) => fail(err, code, &message),
+        Err(_) => fail(err, C4_ERR_PANIC, "internal panic"),
+    }
+}
+
+/// Compile the NUL-terminated source `src` and store a new chunk in `*out`.
+///
+/// # Safety
+/// `src` must be a valid C string, `out` a valid pointer, and `err` null or
+/// a valid `C4Error`.
+#[no_mangle]
+pub unsafe extern "C" fn c4_compile(src: *const c_char, out: *mut *mut C4Chunk, err: *mut C4Error) -> i32 {
+    if src.is_null() || out.is_null() {
+        return fail(err, C4_ERR_INVALID_ARGUMENT, "null argument");
+    }
+    guard(err, || {
+        let src = CStr::from_ptr(src)
+            .to_str()
+            .map_err(|_| (C4_ERR_INVALID_ARGUMENT, "source is not valid UTF-8".to_string()))?;
+        let chunk = compile_source(src).map_err(|e| (error_code(&e), e.to_string()))?;
+        *out = Box::into_raw(Box::new(C4Chunk { chunk, config: VmConfig::default() }));
+        Ok(())
+    })
+}
+
+/// Run `chunk` on a fresh VM and store the program's return value in
+/// `*exit_code`. Program output goes to stdout.
+///
+/// # Safety
+/// `chunk` must come from [`c4_compile`], `exit_code` must be valid, and
+/// `err` null or a valid `C4Error`.
+#[no_mangle]
+pub unsafe extern "C" fn c4_run(chunk: *mut C4Chunk, exit_code: *mut i64, err: *mut C4Error) -> i32 {
+    let (Some(chunk), false) = (chunk.as_ref(), exit_code.is_null()) else {
+        return fail(err, C4_ERR_INVALID_ARGUMENT, "null argument");
+    };
+    guard(err, || {
+        let mut vm = VM::with_config(chunk.config.clone());
+        let code = vm
+            .run(&chunk.chunk)
+            .map_err(|e| (C4_ERR_RUNTIME, Error::from(e).to_string()))?;
+        *exit_code = code;
+        Ok(())
+    })
+}
+
+/// Set the stack size, in words, used by later [`c4_run`] calls.
+///
+/// # Safety
+/// `chunk` must come from [`c4_compile`] and not yet be freed.
+#[no_mangle]
+pub unsafe extern "C" fn c4_set_stack_size(chunk: *mut C4Chunk, words: usize) -> i32 {
+    match chunk.as_mut() {
+        Some(chunk) if words > 0 => {
+            chunk.config.stack_size = words;
+            C4_OK
+        }
+        _ => C4_ERR_INVALID_ARGUMENT,
+    }
+}
+
+/// Limit later [`c4_run`] calls to `steps` instructions; 0 means no limit.
+/// A run that reaches the limit fails with `C4_ERR_RUNTIME`.
+///
+/// # Safety
+/// `chunk` must come from [`c4_compile`] and not yet be freed.
+#[no_mangle]
+pub unsafe extern "C" fn c4_set_max_steps(chunk: *mut C4Chunk, steps: u64) -> i32 {
+    match chunk.as_mut() {
+        Some(chunk) => {
+            chunk.config.max_steps = (steps > 0).then_some(steps);
+            C4_OK
+        }
+        None => C4_ERR_INVALID_ARGUMENT,
+    }
+}
+
+/// Release a chunk. Null is ignored.
+///
+/// # Safety
+/// `chunk` must come from [`c4_compile`] and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn c4_chunk_free(chunk: *mut C4Chunk) {
+    if !chunk.is_null() {
+        drop(Box::from_raw(chunk));
+    }
+}
+
+/// The message recorded in `err`, or an empty string if there is none.
+/// Valid until `err` is reused or freed.
+///
+/// # Safety
+/// `err` must be null or a valid `C4Error`.
+#[no_mangle]
+pub unsafe extern "C" fn c4_error_message(err: *const C4Error) -> *const c_char {
+    match err.as_ref() {
+        Some(err) if !err.message.is_null() => err.message,
+        _ => c"".as_ptr(),
+    }
+}
+
+/// Release the message held by `err` and reset it to `C4_OK`.
+///
+/// # Safety
+/// `err` must be null or a valid `C4Error`.
+#[no_mangle]
+pub unsafe extern "C" fn c4_error_free(err: *mut C4Error) {
+    if let Some(err) = err.as_mut() {
+        if !err.message.is_null() {
+            drop(CString::from_raw(err.message));
+        }
+        err.code = C4_OK;
+        err.message = ptr::null_mut();
+    }
+}
@@ -0,0 +1,177 @@
+// src/cfg.rs
+
+//! Control-flow graphs over compiled code.
+//!
+//! [`Cfg::build`] splits a range of a chunk's instructions into basic
+//! blocks. A block starts at the beginning of the range, at every jump or
+//! call target, and after every jump, call, `LEV` and `EXIT`. Edges leaving
+//! the range (a jump past the end, a call into another function) are not
+//! recorded.
+
+use core::fmt::Write as _;
+use core::ops::Range;
+
+use crate::bytecode::{Chunk, Instruction, OpCode};
+use crate::prelude::*;
+
+/// How control gets from one block to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Straight on to the next instruction: the untaken side of a branch,
+    /// the return site of a call, or a block that simply ends.
+    Fallthrough,
+    /// The target of a jump, branch or call.
+    Taken,
+}
+
+/// An edge to the block with index `to`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    pub to: usize,
+}
+
+/// Instructions `start..end`, entered only at `start`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    pub succs: Vec<Edge>,
+}
+
+/// The basic blocks of one instruction range, in address order. Block 0
+/// is the entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl Cfg {
+    /// Build the graph for `chunk.code[range]`, typically one function or
+    /// the whole chunk.
+    pub fn build(chunk: &Chunk, range: Range<usize>) -> Cfg {
+        let code = &chunk.code[range.clone()];
+        let inside = |pc: usize| range.contains(&pc);
+        let mut starts = vec![false; code.len() + 1];
+        starts[0] = true;
+        starts[code.len()] = true;
+        for (i, instr) in code.iter().enumerate() {
+            match instr {
+                Instruction::Jump(_, target) | Instruction::Call(_, target) => {
+                    if inside(*target) {
+                        starts[target - range.start] = true;
+                    }
+                    starts[i + 1] = true;
+                }
+                Instruction::Instr(OpCode::LEV | OpCode::EXIT) => {
+                    starts[i + 1] = true;
+                }
+                _ => {}
+            }
+        }
+
+        let mut blocks = Vec::new();
+        let mut start = 0;
+        while start < code.len() {
+            let end = (start + 1..=code.len()).find(|&i| starts[i]).unwrap_or(code.len());
+            blocks.push(BasicBlock { start: range.start + start, end: range.start + end, succs: Vec::new() });
+            start = end;
+        }
+
+        let mut cfg = Cfg { blocks };
+        for b in 0..cfg.blocks.len() {
+            let end = cfg.blocks[b].end;
+            let last = &chunk.code[end - 1];
+            let (falls_through, taken) = match last {
+                Instruction::Jump(OpCode::JMP, target) => (false, Some(*target)),
+                Instruction::Jump(_, target) | Instruction::Call(_, target) => (true, Some(*target)),
+                Instruction::Instr(OpCode::LEV | OpCode::EXIT) => (false, None),
+                _ => (true, None),
+            };
+            let mut succs = Vec::new();
+            if falls_through {
+                succs.extend(cfg.block_at(end).map(|to| Edge { kind: EdgeKind::Fallthrough, to }));
+            }
+            if let Some(target) = taken {
+                succs.extend(cfg.block_at(target).map(|to| Edge { kind: EdgeKind::Taken, to }));
+            }
+            cfg.blocks[b].succs = succs;
+        }
+        cfg
+    }
+
+    /// Index of the block starting at `pc`, if there is one.
+    pub fn block_at(&self, pc: usize) -> Option<usize> {
+        self.blocks.binary_search_by_key(&pc, |b| b.start).ok()
+    }
+
+    /// Index of the block containing `pc`, if it is in range.
+    pub fn block_containing(&self, pc: usize) -> Option<usize> {
+        let i = self.blocks.partition_point(|b| b.start <= pc).checked_sub(1)?;
+        (pc < self.blocks[i].end).then_some(i)
+    }
+
+    /// Blocks with an edge into block `b`.
+    pub fn predecessors(&self, b: usize) -> Vec<usize> {
+        (0..self.blocks.len()).filter(|&p| self.blocks[p].succs.iter().any(|e| e.to == b)).collect()
+    }
+
+    /// Blocks reachable from the entry, in reverse postorder: every block
+    /// comes before its successors, except along back edges.
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.blocks.len());
+        if self.blocks.is_empty() {
+            return order;
+        }
+        let mut visited = vec![false; self.blocks.len()];
+        // (block, next successor to visit)
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+        while let Some((b, i)) = stack.pop() {
+            match self.blocks[b].succs.get(i) {
+                Some(edge) => {
+                    stack.push((b, i + 1));
+                    if !visited[edge.to] {
+                        visited[edge.to] = true;
+                        stack.push((edge.to, 0));
+                    }
+                }
+                None => order.push(b),
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// Graphviz rendering: one box per block listing its instructions,
+    /// taken edges solid and fallthrough edges dashed.
+    pub fn to_dot(&self, chunk: &Chunk) -> String {
+        let mut out = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        for (i, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for pc in block.start..block.end {
+                let _ = write!(label, "{:04}  {}\\l", pc, render(&chunk.code[pc]));
+            }
+            let _ = writeln!(out, "    b{} [label=\"{}\"];", i, label);
+        }
+        for (i, block) in self.blocks.iter().enumerate() {
+            for edge in &block.succs {
+                let style = match edge.kind {
+                    EdgeKind::Taken => "",
+                    EdgeKind::Fallthrough => " [style=dashed]",
+                };
+                let _ = writeln!(out, "    b{} -> b{}{};", i, edge.to, style);
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn render(instr: &Instruction) -> String {
+    match instr {
+        Instruction::Instr(op) => format!("{:?}", op),
+        Instruction::InstrInt(op, n) => format!("{:?} {}", op, n),
+        Instruction::Jump(op, t) | Instruction::Call(op, t) => format!("{:?} {}", op, t),
+    }
+}
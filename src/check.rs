@@ -0,0 +1,288 @@
+// src/check.rs
+
+//! Semantic checks on a whole [`Program`], run before code generation.
+//!
+//! The parser only guarantees that a program is well formed; a tree built
+//! by hand (see the `json` module) isn't even that. [`check_program`]
+//! rejects what c4 itself rejects while compiling: names that don't
+//! resolve, duplicate definitions, calls to things that aren't functions,
+//! assignments, `++`/`--` and `&` on something that isn't an lvalue, and
+//! `*` or `[]` on something that isn't a pointer. The messages are c4's.
+//!
+//! Unlike c4, which compiles in one pass, a function may be called before
+//! its definition, and calls to user functions must pass the declared
+//! number of arguments.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+
+use crate::ast::*;
+use crate::error::CompileError;
+use crate::prelude::*;
+
+/// c4's system calls plus the builtins the code generator knows. Their
+/// argument counts are checked by the code generator, if at all.
+const BUILTINS: &[&str] = &[
+    "open", "read", "close", "printf", "malloc", "free", "memset", "memcmp", "exit", "assert", "rand",
+    "srand", "clock", "time",
+];
+
+/// What a top-level name refers to.
+enum Global {
+    Var(Type),
+    Const,
+    Function { ret: Type, params: usize },
+}
+
+/// Check `program`, returning the first problem found.
+pub fn check_program(program: &Program) -> Result<(), CompileError> {
+    let mut globals = BTreeMap::new();
+    let mut define = |name: &str, g: Global| match globals.insert(name.to_string(), g) {
+        Some(_) => Err(CompileError::new(format!("duplicate global definition: {}", name))),
+        None => Ok(()),
+    };
+    for item in &program.items {
+        match item {
+            Item::Global(g) => define(&g.name, Global::Var(g.ty.clone()))?,
+            Item::Enum(e) => {
+                for (name, _) in &e.variants {
+                    define(name, Global::Const)?;
+                }
+            }
+            Item::Function(f) => {
+                define(&f.name, Global::Function { ret: f.ret.clone(), params: f.params.len() })?
+            }
+        }
+    }
+
+    for item in &program.items {
+        if let Item::Function(f) = item {
+            let mut scope = Scope { globals: &globals, locals: BTreeMap::new() };
+            for (name, ty) in &f.params {
+                if !scope.declare(name, ty)? {
+                    return Err(CompileError::new(format!("duplicate parameter definition: {}", name)));
+                }
+            }
+            for (name, ty) in &f.locals {
+                if !scope.declare(name, ty)? {
+                    return Err(CompileError::new(format!("duplicate local definition: {}", name)));
+                }
+            }
+            scope.block(&f.body, Jumps::default()).map_err(|e| CompileError::new(format!("{} (in {})", e.message, f.name)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a `break` or `continue` has a loop or `switch` to leave.
+#[derive(Debug, Clone, Copy, Default)]
+struct Jumps {
+    can_break: bool,
+    can_continue: bool,
+}
+
+/// Names visible inside one function.
+struct Scope<'a> {
+    globals: &'a BTreeMap<String, Global>,
+    /// Parameters and locals, which shadow globals.
+    locals: BTreeMap<&'a str, Type>,
+}
+
+impl<'a> Scope<'a> {
+    /// Add a parameter or local, returning whether the name was new. It
+    /// may shadow a global variable but not an enum constant.
+    fn declare(&mut self, name: &'a str, ty: &Type) -> Result<bool, CompileError> {
+        if let Some(Global::Const) = self.globals.get(name) {
+            return Err(CompileError::new(format!("{} is already an enum constant", name)));
+        }
+        Ok(self.locals.insert(name, ty.clone()).is_none())
+    }
+
+    /// Check `block`, inside the statements `jumps` can leave.
+    fn block(&self, block: &Block, jumps: Jumps) -> Result<(), CompileError> {
+        if block.locals.is_empty() {
+            return block.stmts.iter().try_for_each(|s| self.stmt(s, jumps));
+        }
+        // The block's locals shadow the enclosing ones, but not each other.
+        let mut inner = Scope { globals: self.globals, locals: self.locals.clone() };
+        let mut declared = BTreeSet::new();
+        for (name, ty) in &block.locals {
+            if !declared.insert(name.as_str()) {
+                return Err(CompileError::new(format!("duplicate local definition: {}", name)));
+            }
+            inner.declare(name, ty)?;
+        }
+        block.stmts.iter().try_for_each(|s| inner.stmt(s, jumps))
+    }
+
+    fn stmt(&self, stmt: &Stmt, jumps: Jumps) -> Result<(), CompileError> {
+        match &stmt.kind {
+            StmtKind::If { cond, then_branch, else_branch } => {
+                self.expr(cond)?;
+                self.stmt(then_branch, jumps)?;
+                if let Some(e) = else_branch {
+                    self.stmt(e, jumps)?;
+                }
+            }
+            StmtKind::While { cond, body } | StmtKind::DoWhile { body, cond } => {
+                self.expr(cond)?;
+                self.stmt(body, Jumps { can_break: true, can_continue: true })?;
+            }
+            StmtKind::For { init, cond, step, body } => {
+                for e in [init, cond, step].into_iter().flatten() {
+                    self.expr(e)?;
+                }
+                self.stmt(body, Jumps { can_break: true, can_continue: true })?;
+            }
+            StmtKind::Switch { scrutinee, cases } => {
+                self.expr(scrutinee)?;
+                let mut labels = BTreeSet::new();
+                for (label, stmts) in cases {
+                    if !labels.insert(*label) {
+                        return Err(CompileError::new(match label {
+                            Some(n) => format!("duplicate case value: {}", n),
+                            None => "multiple default labels in one switch".to_string(),
+                        }));
+                    }
+                    for s in stmts {
+                        self.stmt(s, Jumps { can_break: true, ..jumps })?;
+                    }
+                }
+            }
+            StmtKind::Break if !jumps.can_break => return Err(CompileError::new("break outside a loop or switch")),
+            StmtKind::Continue if !jumps.can_continue => return Err(CompileError::new("continue outside a loop")),
+            StmtKind::Return(Some(e)) | StmtKind::Expr(e) => {
+                self.expr(e)?;
+            }
+            StmtKind::Block(b) => self.block(b, jumps)?,
+            StmtKind::Break | StmtKind::Continue | StmtKind::Return(None) | StmtKind::Empty => {}
+        }
+        Ok(())
+    }
+
+    /// Check `expr` and return its type.
+    fn expr(&self, expr: &Expr) -> Result<Type, CompileError> {
+        Ok(match &expr.kind {
+            ExprKind::Num(_) | ExprKind::SizeOf(_) => Type::Int,
+            ExprKind::Str(_) => Type::Ptr(Box::new(Type::Char)),
+            ExprKind::Var(name) => match self.locals.get(name.as_str()) {
+                Some(ty) => ty.clone(),
+                None => match self.globals.get(name) {
+                    Some(Global::Var(ty)) => ty.clone(),
+                    Some(Global::Const) => Type::Int,
+                    _ => return Err(CompileError::new(format!("undefined variable: {}", name))),
+                },
+            },
+            ExprKind::Unary { op, expr } => {
+                let ty = self.expr(expr)?;
+                match op {
+                    UnOp::PreInc | UnOp::PreDec | UnOp::PostInc | UnOp::PostDec => {
+                        if !self.is_lvalue(expr) {
+                            return Err(CompileError::new("bad lvalue in increment or decrement"));
+                        }
+                        ty
+                    }
+                    UnOp::Deref => pointee(ty).ok_or_else(|| CompileError::new("bad dereference"))?,
+                    UnOp::Addr => {
+                        if !self.is_lvalue(expr) {
+                            return Err(CompileError::new("bad address-of"));
+                        }
+                        Type::Ptr(Box::new(ty))
+                    }
+                    UnOp::Plus | UnOp::Neg | UnOp::Not | UnOp::BitNot => Type::Int,
+                }
+            }
+            ExprKind::Binary { op, left, right } => {
+                let lt = self.expr(left)?;
+                let rt = self.expr(right)?;
+                match op {
+                    BinOp::Assign => {
+                        if !self.is_lvalue(left) {
+                            return Err(CompileError::new("bad lvalue in assignment"));
+                        }
+                        lt
+                    }
+                    BinOp::Add if is_ptr(&lt) => lt,
+                    BinOp::Sub if is_ptr(&lt) && is_ptr(&rt) => Type::Int,
+                    BinOp::Sub if is_ptr(&lt) => lt,
+                    _ => Type::Int,
+                }
+            }
+            ExprKind::CompoundAssign { op, target, value } => {
+                let ty = self.expr(target)?;
+                self.expr(value)?;
+                if !self.is_lvalue(target) {
+                    return Err(CompileError::new("bad lvalue in assignment"));
+                }
+                let arithmetic = matches!(
+                    op,
+                    BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod
+                        | BinOp::BitAnd | BinOp::BitOr | BinOp::Xor | BinOp::Shl | BinOp::Shr
+                );
+                if !arithmetic {
+                    return Err(CompileError::new(format!("bad compound assignment operator: {:?}", op)));
+                }
+                ty
+            }
+            ExprKind::Call { callee, args } => {
+                for arg in args {
+                    self.expr(arg)?;
+                }
+                let name = match &callee.kind {
+                    ExprKind::Var(name) if !self.locals.contains_key(name.as_str()) => name,
+                    _ => return Err(CompileError::new("bad function call")),
+                };
+                match self.globals.get(name) {
+                    Some(Global::Function { ret, params }) => {
+                        if args.len() != *params {
+                            return Err(CompileError::new(format!(
+                                "{} expects {} arguments, got {}",
+                                name,
+                                params,
+                                args.len()
+                            )));
+                        }
+                        ret.clone()
+                    }
+                    None if BUILTINS.contains(&name.as_str()) => Type::Int,
+                    _ => return Err(CompileError::new(format!("bad function call: {}", name))),
+                }
+            }
+            ExprKind::Cast { ty, expr } => {
+                self.expr(expr)?;
+                ty.clone()
+            }
+            ExprKind::Conditional { cond, then_expr, else_expr } => {
+                self.expr(cond)?;
+                self.expr(then_expr)?;
+                self.expr(else_expr)?
+            }
+            ExprKind::Index { array, index } => {
+                let ty = self.expr(array)?;
+                self.expr(index)?;
+                pointee(ty).ok_or_else(|| CompileError::new("pointer type expected"))?
+            }
+        })
+    }
+
+    /// Whether `expr` names storage: a variable (not an enum constant), a
+    /// dereference or an index.
+    fn is_lvalue(&self, expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Var(name) => {
+                self.locals.contains_key(name.as_str()) || matches!(self.globals.get(name), Some(Global::Var(_)))
+            }
+            _ => expr.is_lvalue(),
+        }
+    }
+}
+
+fn is_ptr(ty: &Type) -> bool {
+    matches!(ty, Type::Ptr(_))
+}
+
+fn pointee(ty: Type) -> Option<Type> {
+    match ty {
+        Type::Ptr(inner) => Some(*inner),
+        _ => None,
+    }
+}
@@ -0,0 +1,412 @@
+// src/cli.rs
+
+//! Command-line argument parsing for the driver binary.
+//! Hand-rolled so the crate stays dependency-free.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::driver::Phase;
+
+/// Description of one command-line flag, used for parsing, `--help`,
+/// and "did you mean" suggestions.
+pub struct FlagSpec {
+    pub long: &'static str,
+    pub short: Option<&'static str>,
+    /// Placeholder for the flag's value, if it takes one.
+    pub value: Option<&'static str>,
+    pub group: &'static str,
+    pub help: &'static str,
+}
+
+/// Every flag the driver understands, in `--help` order.
+pub const FLAGS: &[FlagSpec] = &[
+    FlagSpec { long: "--time", short: None, value: None, group: "Diagnostics", help: "Print per-phase timing and VM throughput to stderr" },
+    FlagSpec { long: "--verbose", short: Some("-v"), value: None, group: "Diagnostics", help: "Print the run's resource usage to stderr" },
+    FlagSpec { long: "--coverage", short: None, value: None, group: "Diagnostics", help: "Print how often each instruction ran to stderr" },
+    FlagSpec { long: "--leak-check", short: None, value: None, group: "Diagnostics", help: "Print unfreed heap blocks to stderr; exit 1 if any leaked" },
+    FlagSpec { long: "--core-dump", short: None, value: Some("FILE"), group: "Diagnostics", help: "If the program faults, save the VM's state to FILE" },
+    FlagSpec { long: "--record", short: None, value: Some("FILE"), group: "Diagnostics", help: "Save the program's open and read calls and their results to FILE" },
+    FlagSpec { long: "--replay", short: None, value: Some("FILE"), group: "Diagnostics", help: "Answer open and read from a --record FILE instead of the disk" },
+    FlagSpec { long: "--check", short: None, value: None, group: "Execution", help: "Parse and compile only; report errors without running" },
+    FlagSpec { long: "--test", short: None, value: None, group: "Execution", help: "Run the program's test_ functions instead of main; exit 1 if any fail" },
+    FlagSpec { long: "--from-json", short: None, value: Some("FILE"), group: "Execution", help: "Compile and run the JSON AST in FILE instead of a C source file" },
+    FlagSpec { long: "--watch", short: None, value: None, group: "Execution", help: "Recompile and rerun whenever the source file changes" },
+    FlagSpec { long: "--nostdlib", short: None, value: None, group: "Execution", help: "Don't link the prelude (strlen, strcpy, strcmp, atoi, abs, min, max)" },
+    FlagSpec { long: "--stack-size", short: None, value: Some("WORDS"), group: "Execution", help: "Give the VM a stack of WORDS words (K and M suffixes allowed)" },
+    FlagSpec { long: "--heap-size", short: None, value: Some("WORDS"), group: "Execution", help: "Give the VM a heap of WORDS words; defaults to the stack size" },
+    FlagSpec { long: "--fuel", short: None, value: Some("N"), group: "Execution", help: "Stop the program with an error after N instructions" },
+    FlagSpec { long: "--output", short: None, value: Some("FILE"), group: "Output", help: "Write the program's output to FILE ('-' for stdout)" },
+    FlagSpec { long: "--append", short: None, value: None, group: "Output", help: "Append to the --output file instead of truncating it" },
+    FlagSpec { long: "--emit-c", short: None, value: Some("FILE"), group: "Output", help: "Translate the program to C99 in FILE instead of running it" },
+    FlagSpec { long: "--emit-bytecode", short: Some("-o"), value: Some("FILE"), group: "Output", help: "Save the compiled program to FILE instead of running it; name it *.c4b to run it later" },
+    FlagSpec { long: "--asm", short: Some("-s"), value: None, group: "Output", help: "Print the compiled instructions, by function, instead of running them" },
+    FlagSpec { long: "--disasm", short: None, value: Some("FUNC"), group: "Output", help: "Print only FUNC's compiled instructions instead of running; repeatable" },
+    FlagSpec { long: "--dump-symbols", short: None, value: None, group: "Output", help: "Print the compiled functions, globals and enum constants instead of running" },
+    FlagSpec { long: "--ast-json", short: None, value: None, group: "Output", help: "Print the program's AST as JSON instead of running it" },
+    FlagSpec { long: "--fmt", short: None, value: None, group: "Formatting", help: "Reformat the source file in place instead of running it" },
+    FlagSpec { long: "--fmt-check", short: None, value: None, group: "Formatting", help: "Exit 1 if the source file is not formatted; change nothing" },
+    FlagSpec { long: "--help", short: Some("-h"), value: None, group: "General", help: "Print this help and exit" },
+    FlagSpec { long: "--version", short: Some("-V"), value: None, group: "General", help: "Print the version and exit" },
+];
+
+/// Exit code used for malformed command lines.
+pub const USAGE_EXIT_CODE: i32 = 2;
+
+/// Exit code used when the program fails to compile or stops with a
+/// runtime error, running out of fuel or stack included.
+pub const ERROR_EXIT_CODE: i32 = 1;
+
+/// Settings for a normal compile-and-run invocation.
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    /// The C4 source file to compile.
+    pub source: PathBuf,
+    /// `source` holds a JSON AST rather than C (`--from-json`).
+    pub from_json: bool,
+    /// Arguments following the source file (or `--`), passed to the
+    /// program's `main` after the source path as `argv`.
+    pub program_args: Vec<String>,
+    /// Report per-phase timing on stderr.
+    pub time: bool,
+    /// Report the run's resource usage on stderr.
+    pub verbose: bool,
+    /// Report per-instruction execution counts on stderr.
+    pub coverage: bool,
+    /// Track heap allocations and report the ones never freed.
+    pub leak_check: bool,
+    /// Where to save a core dump if the program faults.
+    pub core_dump: Option<PathBuf>,
+    /// Where to save the run's syscalls (`--record`).
+    pub record: Option<PathBuf>,
+    /// Syscall log to answer the run's syscalls from (`--replay`).
+    pub replay: Option<PathBuf>,
+    /// Run the `test_*` functions instead of `main`.
+    pub unit_tests: bool,
+    /// Keep running, recompiling whenever the source changes.
+    pub watch: bool,
+    /// Leave the standard prelude out (`--nostdlib`).
+    pub nostdlib: bool,
+    /// VM stack size in words, if not the default.
+    pub stack_size: Option<usize>,
+    /// VM heap size in words, if not the default.
+    pub heap_size: Option<usize>,
+    /// Most instructions the program may execute.
+    pub fuel: Option<u64>,
+    /// Last pipeline phase to run; `--check` stops after compiling.
+    pub stop_after: Phase,
+    /// File receiving the program's output; None or `-` means stdout.
+    pub output: Option<PathBuf>,
+    /// Append to `output` rather than truncating it.
+    pub append: bool,
+    /// Format the source instead of running it.
+    pub format: Option<FormatMode>,
+    /// Write the program translated to C here instead of running it.
+    pub emit_c: Option<PathBuf>,
+    /// Print the AST as JSON instead of running.
+    pub ast_json: bool,
+    /// Print the compiled instructions instead of running, like c4's `-s`.
+    pub asm: bool,
+    /// Print only these functions' instructions instead of running
+    /// (`--disasm`, repeatable).
+    pub disasm: Vec<String>,
+    /// Print the resolved symbols instead of running; with `asm`, before
+    /// the listing.
+    pub dump_symbols: bool,
+    /// Save the compiled chunk here instead of running it.
+    pub emit_bytecode: Option<PathBuf>,
+}
+
+/// What `--fmt` / `--fmt-check` asked for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormatMode {
+    /// Rewrite the file with its formatted source.
+    Write,
+    /// Only report whether the file is already formatted.
+    Check,
+}
+
+/// Settings for the `bench` subcommand.
+#[derive(Debug, PartialEq)]
+pub struct BenchOptions {
+    pub source: PathBuf,
+    /// Measured runs.
+    pub iterations: usize,
+    /// Unmeasured runs before timing starts.
+    pub warmup: usize,
+}
+
+/// What the driver has been asked to do.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Run(Box<Options>),
+    /// `test DIR`: run the annotated conformance programs under DIR.
+    Test(PathBuf),
+    /// `bench FILE`: time repeated runs of one program.
+    Bench(BenchOptions),
+    Help,
+    Version,
+}
+
+/// A malformed command line.
+#[derive(Debug, PartialEq)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Parse the arguments following the program name.
+///
+/// Flags may appear anywhere before a `--`, after the source file too, so
+/// `prog.c -s` works as it does in c4. The first positional argument is the
+/// source file. Later positionals go to the program, and so does everything
+/// after `--`, untouched: that is how a program gets arguments that look like
+/// flags.
+pub fn parse_args<I>(args: I) -> Result<Action, UsageError>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut opts = Options::default();
+    let mut positionals = Vec::new();
+    let mut args = split_shebang_flags(args.into_iter().collect()).into_iter().peekable();
+
+    match args.peek().map(String::as_str) {
+        Some("test") => {
+            args.next();
+            return match (args.next(), args.next()) {
+                (Some(dir), None) if !dir.starts_with('-') => Ok(Action::Test(PathBuf::from(dir))),
+                _ => Err(UsageError("usage: test <DIR>".into())),
+            };
+        }
+        Some("bench") => {
+            args.next();
+            return parse_bench(args).map(Action::Bench);
+        }
+        _ => {}
+    }
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            positionals.extend(args.by_ref());
+            break;
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            positionals.push(arg);
+            continue;
+        }
+
+        // Split `--flag=value` forms.
+        let (name, inline) = match arg.split_once('=') {
+            Some((n, v)) if n.starts_with("--") => (n, Some(v.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let spec = FLAGS
+            .iter()
+            .find(|f| f.long == name || f.short == Some(name))
+            .ok_or_else(|| unknown_flag(name))?;
+        let value = match (spec.value, inline) {
+            (Some(_), Some(v)) => v,
+            (Some(placeholder), None) => args.next().ok_or_else(|| {
+                UsageError(format!("{} requires a value <{}>", spec.long, placeholder))
+            })?,
+            (None, Some(_)) => return Err(UsageError(format!("{} does not take a value", spec.long))),
+            (None, None) => String::new(),
+        };
+
+        if matches!(spec.long, "--from-json" | "--ast-json") && !cfg!(feature = "json") {
+            return Err(UsageError(format!("{} needs the json feature, which this build lacks", spec.long)));
+        }
+
+        match spec.long {
+            "--help" => return Ok(Action::Help),
+            "--version" => return Ok(Action::Version),
+            "--time" => opts.time = true,
+            "--verbose" => opts.verbose = true,
+            "--coverage" => opts.coverage = true,
+            "--leak-check" => opts.leak_check = true,
+            "--core-dump" => opts.core_dump = Some(PathBuf::from(value)),
+            "--record" => opts.record = Some(PathBuf::from(value)),
+            "--replay" => opts.replay = Some(PathBuf::from(value)),
+            "--test" => opts.unit_tests = true,
+            "--nostdlib" => opts.nostdlib = true,
+            "--from-json" => {
+                opts.source = PathBuf::from(value);
+                opts.from_json = true;
+            }
+            "--watch" => opts.watch = true,
+            "--stack-size" => opts.stack_size = Some(parse_size(spec.long, &value)?),
+            "--heap-size" => opts.heap_size = Some(parse_size(spec.long, &value)?),
+            "--fuel" => opts.fuel = Some(parse_count(spec.long, &value)?),
+            "--check" => opts.stop_after = Phase::Compile,
+            "--output" => opts.output = Some(PathBuf::from(value)),
+            "--append" => opts.append = true,
+            "--emit-c" => opts.emit_c = Some(PathBuf::from(value)),
+            "--ast-json" => opts.ast_json = true,
+            "--asm" => opts.asm = true,
+            "--disasm" => opts.disasm.push(value),
+            "--dump-symbols" => opts.dump_symbols = true,
+            "--emit-bytecode" => opts.emit_bytecode = Some(PathBuf::from(value)),
+            "--fmt" => opts.format = Some(FormatMode::Write),
+            "--fmt-check" => opts.format = Some(FormatMode::Check),
+            other => unreachable!("flag {} has no handler", other),
+        }
+    }
+
+    // With --from-json the source came with the flag, so every positional
+    // belongs to the program.
+    let mut positionals = positionals.into_iter();
+    if !opts.from_json {
+        opts.source = positionals
+            .next()
+            .map(PathBuf::from)
+            .ok_or_else(|| UsageError("no source file given".into()))?;
+    }
+    opts.program_args = positionals.collect();
+    if opts.from_json && opts.format.is_some() {
+        return Err(UsageError("--fmt and --fmt-check need C source, not --from-json".into()));
+    }
+    if opts.append && opts.output.is_none() {
+        return Err(UsageError("--append requires --output".into()));
+    }
+    if opts.record.is_some() && opts.replay.is_some() {
+        return Err(UsageError("--record and --replay can't be used together".into()));
+    }
+    Ok(Action::Run(Box::new(opts)))
+}
+
+/// A size for `flag`: a positive number of words, optionally followed by
+/// `K` (1024) or `M` (1024 * 1024).
+fn parse_size(flag: &str, value: &str) -> Result<usize, UsageError> {
+    let (digits, scale) = match value.as_bytes().last() {
+        Some(b'K' | b'k') => (&value[..value.len() - 1], 1024),
+        Some(b'M' | b'm') => (&value[..value.len() - 1], 1024 * 1024),
+        _ => (value, 1),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(scale))
+        .filter(|&n| n > 0)
+        .ok_or_else(|| UsageError(format!("{} needs a positive size like 4096, 64K or 1M, not '{}'", flag, value)))
+}
+
+/// A positive count for `flag`.
+fn parse_count(flag: &str, value: &str) -> Result<u64, UsageError> {
+    value
+        .parse::<u64>()
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| UsageError(format!("{} needs a positive number, not '{}'", flag, value)))
+}
+
+/// Arguments of `bench FILE [--iterations N] [--warmup N]`.
+fn parse_bench(mut args: impl Iterator<Item = String>) -> Result<BenchOptions, UsageError> {
+    let mut source = None;
+    let mut iterations = 10;
+    let mut warmup = 2;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--iterations" | "--warmup" => {
+                let n = args
+                    .next()
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .ok_or_else(|| UsageError(format!("{} requires a number", arg)))?;
+                if arg == "--iterations" {
+                    iterations = n;
+                } else {
+                    warmup = n;
+                }
+            }
+            _ if arg.starts_with('-') => return Err(unknown_flag(&arg)),
+            _ if source.is_none() => source = Some(PathBuf::from(arg)),
+            _ => return Err(UsageError(format!("unexpected argument '{}'", arg))),
+        }
+    }
+    if iterations == 0 {
+        return Err(UsageError("--iterations must be at least 1".into()));
+    }
+    let source = source.ok_or_else(|| UsageError("usage: bench <FILE> [--iterations N] [--warmup N]".into()))?;
+    Ok(BenchOptions { source, iterations, warmup })
+}
+
+/// A script starting `#!/path/to/c4 --time --output=log` is run by the kernel
+/// with all the flags as a single argument, so split a leading flag argument
+/// that contains whitespace back into separate flags.
+fn split_shebang_flags(mut args: Vec<String>) -> Vec<String> {
+    match args.first() {
+        Some(first) if first.starts_with('-') && first.contains(char::is_whitespace) => {
+            let first = args.remove(0);
+            let mut split: Vec<String> = first.split_whitespace().map(String::from).collect();
+            split.append(&mut args);
+            split
+        }
+        _ => args,
+    }
+}
+
+/// Grouped usage text for `--help`.
+pub fn help_text(prog: &str) -> String {
+    let mut out = format!(
+        "Usage: {0} [OPTIONS] <source.c> [--] [PROGRAM ARGS...]\n       {0} test <DIR>\n       {0} bench <source.c> [--iterations N] [--warmup N]\n",
+        prog
+    );
+    let mut groups: Vec<&str> = Vec::new();
+    for f in FLAGS {
+        if !groups.contains(&f.group) {
+            groups.push(f.group);
+        }
+    }
+    for group in groups {
+        out.push_str(&format!("\n{}:\n", group));
+        for f in FLAGS.iter().filter(|f| f.group == group) {
+            let mut left = match f.short {
+                Some(s) => format!("{}, {}", s, f.long),
+                None => format!("    {}", f.long),
+            };
+            if let Some(v) = f.value {
+                left.push_str(&format!(" <{}>", v));
+            }
+            out.push_str(&format!("  {:<26} {}\n", left, f.help));
+        }
+    }
+    out
+}
+
+/// One-line version string for `--version`.
+pub fn version_text() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+/// Build the error for an unrecognized flag, suggesting the nearest one.
+fn unknown_flag(name: &str) -> UsageError {
+    let closest = FLAGS
+        .iter()
+        .map(|f| (edit_distance(name, f.long), f.long))
+        .min();
+    match closest {
+        Some((d, flag)) if d <= 3 => {
+            UsageError(format!("unknown flag '{}' (did you mean '{}'?)", name, flag))
+        }
+        _ => UsageError(format!("unknown flag '{}'", name)),
+    }
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
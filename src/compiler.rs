@@ -0,0 +1,241 @@
+// src/compiler.rs
+
+//! [`Compiler`], the one place the source-to-chunk pipeline is put
+//! together: which input language to read, whether to collect warnings,
+//! whether to link the standard prelude, which constants to predefine,
+//! whether to fold constant expressions and keep a source map, and how
+//! several files become one program. The driver's
+//! [`compile_source`](crate::compile_source) and the command-line binary
+//! both go through it.
+
+use crate::analysis::{analyze, Diagnostic, Severity};
+use crate::ast::{BinOp, EnumDecl, Expr, ExprKind, Item, Program, UnOp};
+use crate::bytecode::{Chunk, SourceMap};
+use crate::error::Error;
+use crate::parser::Parser;
+use crate::prelude::*;
+use crate::stdlib;
+use crate::visitor::{walk_expr_mut, VisitorMut};
+
+/// A compiled chunk and the diagnostics collected on the way.
+#[derive(Debug, Default, PartialEq)]
+pub struct Compiled {
+    pub chunk: Chunk,
+    /// Warnings, if [`Compiler::warnings`] asked for them, in source
+    /// order. Errors stop compilation and come back as [`Error`] instead.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Compilation settings, set with chained calls:
+///
+/// ```
+/// # use c4_rust_AlRafaah::compiler::Compiler;
+/// let compiled = Compiler::new().warnings(true).compile_str("int main() { int x; return 0; }").unwrap();
+/// assert_eq!(compiled.diagnostics[0].code, "W0001");
+/// ```
+#[derive(Debug, Clone, Default, Hash)]
+pub struct Compiler {
+    json: bool,
+    warnings: bool,
+    nostdlib: bool,
+    optimize: u8,
+    defines: Vec<(String, i64)>,
+    no_debug_info: bool,
+}
+
+impl Compiler {
+    /// A compiler for C source that links the prelude, keeps a source map
+    /// and collects no warnings.
+    pub fn new() -> Self {
+        Compiler::default()
+    }
+
+    /// Read a JSON AST, as [`json::to_json`](crate::json::to_json) writes
+    /// it, instead of C source. JSON input gets no warnings.
+    #[cfg(feature = "json")]
+    pub fn json(mut self, on: bool) -> Self {
+        self.json = on;
+        self
+    }
+
+    /// Collect the warnings [`analyze`] finds (unused locals, unreachable
+    /// statements) alongside the chunk.
+    pub fn warnings(mut self, on: bool) -> Self {
+        self.warnings = on;
+        self
+    }
+
+    /// Link the [standard prelude](crate::stdlib) into every program, the
+    /// default, or leave calls to its functions undefined.
+    pub fn stdlib(mut self, on: bool) -> Self {
+        self.nostdlib = !on;
+        self
+    }
+
+    /// How hard to optimize: 0, the default, compiles the program as
+    /// written; 1 and up fold expressions made only of numbers and
+    /// `sizeof`, such as `2 * 3 + sizeof(int)`, into one. Divisions by a
+    /// constant zero are left to fail when they run.
+    pub fn optimize(mut self, level: u8) -> Self {
+        self.optimize = level;
+        self
+    }
+
+    /// Predefine `name` as the integer constant `value`, as if the program
+    /// began with `enum { name = value };`. A program that defines `name`
+    /// itself fails to compile.
+    pub fn define(mut self, name: &str, value: i64) -> Self {
+        self.defines.push((name.to_string(), value));
+        self
+    }
+
+    /// Record in each chunk's [`source_map`](Chunk::source_map) the
+    /// statement every instruction came from, the default, as line
+    /// coverage needs; without it the map is left empty.
+    pub fn debug_info(mut self, on: bool) -> Self {
+        self.no_debug_info = !on;
+        self
+    }
+
+    /// Parse `src` into a program, without linking or compiling it.
+    pub fn parse(&self, src: &str) -> Result<Program, Error> {
+        #[cfg(feature = "json")]
+        if self.json {
+            return crate::json::program_from_json(src);
+        }
+        Parser::new(src)?.parse_program()
+    }
+
+    /// Add the prelude functions `program` calls but doesn't define, if
+    /// the prelude is linked.
+    pub fn link(&self, program: &mut Program) {
+        if !self.nostdlib {
+            stdlib::link(program);
+        }
+    }
+
+    /// Compile a parsed and linked program into a fresh chunk, with the
+    /// defines and optimizations asked for.
+    pub fn compile_program(&self, program: &Program) -> Result<Chunk, Error> {
+        let mut chunk = Chunk::default();
+        if self.defines.is_empty() && self.optimize == 0 {
+            program.compile(&mut chunk)?;
+        } else {
+            let defines = self
+                .defines
+                .iter()
+                .map(|(name, value)| Item::Enum(EnumDecl { variants: vec![(name.clone(), Some(*value))] }));
+            let mut program = Program { items: defines.chain(program.items.iter().cloned()).collect() };
+            if self.optimize > 0 {
+                FoldConstants.visit_program(&mut program);
+            }
+            program.compile(&mut chunk)?;
+        }
+        if self.no_debug_info {
+            chunk.source_map = SourceMap::default();
+        }
+        Ok(chunk)
+    }
+
+    /// Parse, link and compile `src`.
+    pub fn compile_str(&self, src: &str) -> Result<Compiled, Error> {
+        let mut program = self.parse(src)?;
+        self.link(&mut program);
+        let chunk = self.compile_program(&program)?;
+        Ok(Compiled { chunk, diagnostics: self.lint(src) })
+    }
+
+    /// Read, parse, link and compile the file at `path`.
+    #[cfg(feature = "std")]
+    pub fn compile_file(&self, path: impl AsRef<std::path::Path>) -> Result<Compiled, Error> {
+        self.compile_files(&[path])
+    }
+
+    /// Read and parse every file in `paths` and compile them as one
+    /// program, as if they were concatenated in order, linking the prelude
+    /// once for all of them. Each diagnostic's message starts with the file
+    /// it is about, and its offset is within that file.
+    #[cfg(feature = "std")]
+    pub fn compile_files<P: AsRef<std::path::Path>>(&self, paths: &[P]) -> Result<Compiled, Error> {
+        let mut program = Program { items: Vec::new() };
+        let mut diagnostics = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let src = std::fs::read_to_string(path)?;
+            program.items.extend(self.parse(&src)?.items);
+            for mut d in self.lint(&src) {
+                d.message = format!("{}: {}", path.display(), d.message);
+                diagnostics.push(d);
+            }
+        }
+        self.link(&mut program);
+        Ok(Compiled { chunk: self.compile_program(&program)?, diagnostics })
+    }
+
+    /// The warnings in `src`, if they were asked for.
+    fn lint(&self, src: &str) -> Vec<Diagnostic> {
+        if !self.warnings || self.json {
+            return Vec::new();
+        }
+        analyze(src).diagnostics.into_iter().filter(|d| d.severity == Severity::Warning).collect()
+    }
+}
+
+/// Replaces each expression made only of numbers and `sizeof` with the
+/// number the VM would compute for it, innermost first.
+struct FoldConstants;
+
+impl VisitorMut for FoldConstants {
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+        let num = |e: &Expr| match e.kind {
+            ExprKind::Num(n) => Some(n),
+            _ => None,
+        };
+        let folded = match &expr.kind {
+            ExprKind::SizeOf(ty) => Some(ty.size_of() as i64),
+            ExprKind::Unary { op, expr: inner } => num(inner).and_then(|n| match op {
+                UnOp::Plus => Some(n),
+                UnOp::Neg => Some(n.wrapping_neg()),
+                UnOp::Not => Some((n == 0) as i64),
+                UnOp::BitNot => Some(!n),
+                _ => None,
+            }),
+            ExprKind::Binary { op, left, right } => match (num(left), num(right)) {
+                (Some(l), Some(r)) => fold_binary(op, l, r),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(n) = folded {
+            expr.kind = ExprKind::Num(n);
+        }
+    }
+}
+
+/// `l op r` as the VM computes it; None for assignment and for division by
+/// zero, which has to fail at run time.
+fn fold_binary(op: &BinOp, l: i64, r: i64) -> Option<i64> {
+    Some(match op {
+        BinOp::Add => l.wrapping_add(r),
+        BinOp::Sub => l.wrapping_sub(r),
+        BinOp::Mul => l.wrapping_mul(r),
+        BinOp::Div | BinOp::Mod if r == 0 => return None,
+        BinOp::Div => l.wrapping_div(r),
+        BinOp::Mod => l.wrapping_rem(r),
+        BinOp::Eq => (l == r) as i64,
+        BinOp::Ne => (l != r) as i64,
+        BinOp::Lt => (l < r) as i64,
+        BinOp::Le => (l <= r) as i64,
+        BinOp::Gt => (l > r) as i64,
+        BinOp::Ge => (l >= r) as i64,
+        BinOp::BitAnd => l & r,
+        BinOp::BitOr => l | r,
+        BinOp::Xor => l ^ r,
+        BinOp::Shl => l.wrapping_shl(r as u32),
+        BinOp::Shr => l.wrapping_shr(r as u32),
+        BinOp::LogAnd => (l != 0 && r != 0) as i64,
+        BinOp::LogOr => (l != 0 || r != 0) as i64,
+        BinOp::Assign => return None,
+    })
+}
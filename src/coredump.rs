@@ -0,0 +1,169 @@
+// src/coredump.rs
+
+//! Post-mortem snapshots of a VM that stopped on an error.
+//!
+//! [`VmError::dump`](crate::vm::VmError::dump) captures the registers, the
+//! saved call frames, the live part of the stack, the heap's live blocks
+//! and the chunk's fingerprint. A dump round-trips through a line-based text format
+//! ([`CoreDump::to_text`] and [`CoreDump::parse`]), which is what
+//! `--core-dump FILE` writes, and [`CoreDump::render`] lays it out for
+//! reading next to the chunk that produced it.
+//!
+//! Heap blocks are listed by address and size, not their contents; the VM
+//! has no trace buffer yet, and the accumulator `a` lives only inside the
+//! dispatch loop.
+
+use core::fmt::Write as _;
+
+use crate::bytecode::{Chunk, Instruction};
+use crate::prelude::*;
+
+/// First line of every dump, naming the format and its version.
+const HEADER: &str = "c4-core 2";
+
+/// Registers saved by a call: where to return to, and the caller's `sp`
+/// and `fp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub return_pc: usize,
+    pub sp: usize,
+    pub fp: usize,
+}
+
+/// A heap block that was still allocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapBlock {
+    /// Address of its first byte, as `malloc` returned it.
+    pub addr: i64,
+    pub words: usize,
+}
+
+/// The state of a VM at the moment it faulted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreDump {
+    /// The error, as displayed.
+    pub error: String,
+    /// pc of the faulting instruction.
+    pub fault_pc: usize,
+    pub sp: usize,
+    pub fp: usize,
+    /// Saved frames, outermost first.
+    pub frames: Vec<Frame>,
+    /// Stack words `0..sp`.
+    pub stack: Vec<i64>,
+    /// Live heap blocks, in address order.
+    pub heap: Vec<HeapBlock>,
+    /// [`Chunk::fingerprint`] of the chunk that was running.
+    pub fingerprint: u64,
+}
+
+impl CoreDump {
+    /// The dump in its file format: a header line, then one `key value`
+    /// line per register, frame, stack word and heap block.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", HEADER);
+        let _ = writeln!(out, "fingerprint {:016x}", self.fingerprint);
+        let _ = writeln!(out, "error {}", self.error);
+        let _ = writeln!(out, "fault-pc {}", self.fault_pc);
+        let _ = writeln!(out, "sp {}", self.sp);
+        let _ = writeln!(out, "fp {}", self.fp);
+        for f in &self.frames {
+            let _ = writeln!(out, "frame {} {} {}", f.return_pc, f.sp, f.fp);
+        }
+        for word in &self.stack {
+            let _ = writeln!(out, "stack {}", word);
+        }
+        for block in &self.heap {
+            let _ = writeln!(out, "heap {} {}", block.addr, block.words);
+        }
+        out
+    }
+
+    /// Read back a dump written by [`CoreDump::to_text`].
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, l)| l) != Some(HEADER) {
+            return Err(format!("not a core dump: expected '{}' on the first line", HEADER));
+        }
+        let mut dump = CoreDump {
+            error: String::new(),
+            fault_pc: 0,
+            sp: 0,
+            fp: 0,
+            frames: Vec::new(),
+            stack: Vec::new(),
+            heap: Vec::new(),
+            fingerprint: 0,
+        };
+        for (i, line) in lines {
+            let bad = || format!("line {}: malformed '{}'", i + 1, line);
+            let (key, value) = line.split_once(' ').ok_or_else(bad)?;
+            match key {
+                "fingerprint" => dump.fingerprint = u64::from_str_radix(value, 16).map_err(|_| bad())?,
+                "error" => dump.error = value.to_string(),
+                "fault-pc" => dump.fault_pc = value.parse().map_err(|_| bad())?,
+                "sp" => dump.sp = value.parse().map_err(|_| bad())?,
+                "fp" => dump.fp = value.parse().map_err(|_| bad())?,
+                "frame" => {
+                    let fields: Vec<usize> =
+                        value.split(' ').map(str::parse).collect::<Result<_, _>>().map_err(|_| bad())?;
+                    let [return_pc, sp, fp] = fields[..] else {
+                        return Err(bad());
+                    };
+                    dump.frames.push(Frame { return_pc, sp, fp });
+                }
+                "stack" => dump.stack.push(value.parse().map_err(|_| bad())?),
+                "heap" => {
+                    let (addr, words) = value.split_once(' ').ok_or_else(bad)?;
+                    let addr = addr.parse().map_err(|_| bad())?;
+                    dump.heap.push(HeapBlock { addr, words: words.parse().map_err(|_| bad())? });
+                }
+                _ => return Err(format!("line {}: unknown key '{}'", i + 1, key)),
+            }
+        }
+        Ok(dump)
+    }
+
+    /// Human-readable report: the error, the backtrace from the faulting
+    /// instruction outwards, the stack with `fp` marked, and the live heap
+    /// blocks. Addresses are
+    /// resolved against `chunk`, with a warning if it isn't the chunk the
+    /// dump came from.
+    pub fn render(&self, chunk: &Chunk) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "core dump: {}", self.error);
+        if self.fingerprint != chunk.fingerprint() {
+            out.push_str("warning: the dump was taken from a different chunk\n");
+        }
+        let _ = writeln!(out, "registers: pc {:04}  sp {}  fp {}", self.fault_pc, self.sp, self.fp);
+
+        out.push_str("\nbacktrace:\n");
+        let returns = self.frames.iter().rev().map(|f| f.return_pc);
+        for (depth, pc) in [self.fault_pc].into_iter().chain(returns).enumerate() {
+            let text = match chunk.code.get(pc) {
+                Some(Instruction::Instr(op)) => format!("{:?}", op),
+                Some(Instruction::InstrInt(op, n)) => format!("{:?} {}", op, n),
+                Some(Instruction::Jump(op, t) | Instruction::Call(op, t)) => format!("{:?} {}", op, t),
+                None => "<end>".to_string(),
+            };
+            let place = match chunk.function_at(pc) {
+                Some(f) => format!("in {}", f.name),
+                None => "in startup".to_string(),
+            };
+            let _ = writeln!(out, "  #{:<3} {:04}  {:<12} {}", depth, pc, text, place);
+        }
+
+        let _ = writeln!(out, "\nstack ({} words):", self.stack.len());
+        for (addr, word) in self.stack.iter().enumerate() {
+            let mark = if addr == self.fp { "  <- fp" } else { "" };
+            let _ = writeln!(out, "  {:04}  {}{}", addr, word, mark);
+        }
+
+        let _ = writeln!(out, "\nheap ({} blocks):", self.heap.len());
+        for block in &self.heap {
+            let _ = writeln!(out, "  {}  {} words", block.addr, block.words);
+        }
+        out
+    }
+}
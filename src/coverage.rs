@@ -0,0 +1,187 @@
+// src/coverage.rs
+
+//! Execution coverage. A [`VM`](crate::vm::VM) with coverage enabled counts
+//! how often each instruction runs, accumulating across runs, and
+//! [`VM::take_coverage`](crate::vm::VM::take_coverage) hands the counts back
+//! as a [`CoverageReport`].
+//!
+//! Counts are kept per instruction. A chunk's source map ties each one to
+//! the statement it was compiled from, so [`CoverageReport::lines`] and
+//! [`CoverageReport::render`] can give them per source line instead;
+//! [`CoverageReport::render_instructions`] works without the source.
+//! Functions are told apart by their `ENT`, which opens every compiled
+//! function.
+
+use alloc::collections::BTreeSet;
+use core::fmt::Write as _;
+
+use crate::bytecode::{Chunk, Instruction, OpCode};
+use crate::prelude::*;
+
+/// How often each instruction of a chunk ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    counts: Vec<u64>,
+}
+
+/// Coverage of one function, or of the startup code before the first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    /// pc of the function's `ENT`, or 0 for the startup code.
+    pub start: usize,
+    pub end: usize,
+    /// Instructions in `start..end` that ran at least once.
+    pub executed: usize,
+}
+
+impl FunctionCoverage {
+    /// Share of the function's instructions that ran, in percent.
+    pub fn percent(&self) -> f64 {
+        let total = self.end - self.start;
+        if total == 0 {
+            return 100.0;
+        }
+        self.executed as f64 * 100.0 / total as f64
+    }
+}
+
+impl CoverageReport {
+    /// A report from raw counts, indexed by pc.
+    pub fn new(counts: Vec<u64>) -> Self {
+        CoverageReport { counts }
+    }
+
+    /// Times the instruction at `pc` ran.
+    pub fn hits(&self, pc: usize) -> u64 {
+        self.counts.get(pc).copied().unwrap_or(0)
+    }
+
+    /// Per-function coverage of `chunk`, in address order.
+    pub fn functions(&self, chunk: &Chunk) -> Vec<FunctionCoverage> {
+        let mut starts: Vec<usize> = chunk
+            .code
+            .iter()
+            .enumerate()
+            .filter(|(_, instr)| matches!(instr, Instruction::InstrInt(OpCode::ENT, _)))
+            .map(|(pc, _)| pc)
+            .collect();
+        if starts.first() != Some(&0) {
+            starts.insert(0, 0);
+        }
+        let ends = starts.iter().skip(1).copied().chain([chunk.code.len()]);
+        starts
+            .iter()
+            .zip(ends)
+            .filter(|(start, end)| **start < *end)
+            .map(|(&start, end)| FunctionCoverage {
+                start,
+                end,
+                executed: (start..end).filter(|&pc| self.hits(pc) > 0).count(),
+            })
+            .collect()
+    }
+
+    /// How often each line of `src` ran: the most times any instruction
+    /// compiled from it ran, or None for a line no code came from. `chunk`
+    /// must have been compiled from `src` alone.
+    pub fn lines(&self, chunk: &Chunk, src: &str) -> Vec<Option<u64>> {
+        let starts = line_starts(src);
+        let mut lines = vec![None; starts.len()];
+        for pc in 0..chunk.code.len() {
+            let Some(offset) = chunk.source_offset(pc) else { continue };
+            if let Some(count) = lines.get_mut(line_of(&starts, offset)) {
+                *count = Some(self.hits(pc).max(count.unwrap_or(0)));
+            }
+        }
+        lines
+    }
+
+    /// `src` with each line's count in the margin (`#####` for never run,
+    /// `-` for no code), followed by the share of lines with code that ran,
+    /// per function and overall.
+    pub fn render(&self, chunk: &Chunk, src: &str) -> String {
+        let lines = self.lines(chunk, src);
+        let starts = line_starts(src);
+        let mut out = String::new();
+        for (i, (text, count)) in src.lines().zip(&lines).enumerate() {
+            let count = match count {
+                None => "-".to_string(),
+                Some(0) => "#####".to_string(),
+                Some(n) => n.to_string(),
+            };
+            let _ = writeln!(out, "{:>8} | {:>4}  {}", count, i + 1, text);
+        }
+        out.push('\n');
+        let summary = |out: &mut String, name: &str, with_code: &BTreeSet<usize>| {
+            let ran = with_code.iter().filter(|&&line| lines[line] > Some(0)).count();
+            let percent = if with_code.is_empty() { 100.0 } else { ran as f64 * 100.0 / with_code.len() as f64 };
+            let _ = writeln!(out, "{:<18} {:>4}/{:<4} {:>5.1}%", name, ran, with_code.len(), percent);
+        };
+        let mut all = BTreeSet::new();
+        for (i, f) in chunk.functions.iter().enumerate() {
+            let end = chunk.functions.get(i + 1).map_or(chunk.code.len(), |next| next.entry);
+            let with_code: BTreeSet<usize> = (f.entry..end)
+                .filter_map(|pc| chunk.source_offset(pc))
+                .map(|offset| line_of(&starts, offset))
+                .collect();
+            // Linked prelude functions have no source here.
+            if !with_code.is_empty() {
+                summary(&mut out, &f.name, &with_code);
+                all.extend(with_code);
+            }
+        }
+        summary(&mut out, "total", &all);
+        out
+    }
+
+    /// `chunk`'s instructions with their counts in the margin (`#####` for
+    /// never run), followed by a summary per function and overall.
+    pub fn render_instructions(&self, chunk: &Chunk) -> String {
+        let mut out = String::new();
+        for (pc, instr) in chunk.code.iter().enumerate() {
+            let count = match self.hits(pc) {
+                0 => "#####".to_string(),
+                n => n.to_string(),
+            };
+            let text = match instr {
+                Instruction::Instr(op) => format!("{:?}", op),
+                Instruction::InstrInt(op, n) => format!("{:?} {}", op, n),
+                Instruction::Jump(op, t) | Instruction::Call(op, t) => format!("{:?} {}", op, t),
+            };
+            let _ = writeln!(out, "{:>8} | {:04}  {}", count, pc, text);
+        }
+        out.push('\n');
+        let functions = self.functions(chunk);
+        for f in &functions {
+            let name = match chunk.code[f.start] {
+                Instruction::InstrInt(OpCode::ENT, _) => format!("function at {:04}", f.start),
+                _ => "startup".to_string(),
+            };
+            let _ = writeln!(out, "{:<18} {:>4}/{:<4} {:>5.1}%", name, f.executed, f.end - f.start, f.percent());
+        }
+        let total = FunctionCoverage {
+            start: 0,
+            end: chunk.code.len(),
+            executed: functions.iter().map(|f| f.executed).sum(),
+        };
+        let _ = writeln!(out, "{:<18} {:>4}/{:<4} {:>5.1}%", "total", total.executed, total.end, total.percent());
+        out
+    }
+}
+
+/// Byte offset of the start of each line of `src`.
+fn line_starts(src: &str) -> Vec<usize> {
+    let mut start = 0;
+    src.split_inclusive('\n')
+        .map(|line| {
+            let line_start = start;
+            start += line.len();
+            line_start
+        })
+        .collect()
+}
+
+/// Index of the line starting at or before `offset`.
+fn line_of(starts: &[usize], offset: usize) -> usize {
+    starts.partition_point(|&start| start <= offset).saturating_sub(1)
+}
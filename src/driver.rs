@@ -0,0 +1,384 @@
+// src/driver.rs
+
+//! Shared plumbing for the command-line driver and for embedders: one-call
+//! compile/run helpers, cancellable runs on a worker thread, phase timing
+//! and reporting, the polling loop behind `--watch`, and the `bench`
+//! measurement loop.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::bytecode::Chunk;
+use crate::compiler::Compiler;
+use crate::error::Error;
+#[cfg(feature = "fastexec")]
+use crate::fastexec::ClosureProgram;
+#[cfg(feature = "jit")]
+use crate::jit::JitProgram;
+use crate::vm::{Backend, CancelToken, RunOutcome, VmConfig, VmError, VM};
+
+/// Parse and compile `src` into a chunk ready to run, with a default
+/// [`Compiler`].
+pub fn compile_source(src: &str) -> Result<Chunk, Error> {
+    Ok(Compiler::new().compile_str(src)?.chunk)
+}
+
+/// Parse and compile `src` and return its canonical assembly listing (see
+/// [`Chunk::to_asm`]), the form to snapshot in golden tests.
+pub fn compile_to_asm(src: &str) -> Result<String, Error> {
+    Ok(compile_source(src)?.to_asm())
+}
+
+/// Compile and run `src` on a default VM, capturing its output.
+pub fn run_source(src: &str) -> Result<RunOutcome, Error> {
+    run_source_with(src, VmConfig::default())
+}
+
+/// Compile and run `src` on a VM built from `config`, capturing its output.
+pub fn run_source_with(src: &str, config: VmConfig) -> Result<RunOutcome, Error> {
+    let chunk = compile_source(src)?;
+    Ok(VM::with_config(config).run_collecting(&chunk)?)
+}
+
+/// Like [`run_source_with`], on the chosen backend.
+pub fn run_source_on(src: &str, config: VmConfig, backend: Backend) -> Result<RunOutcome, Error> {
+    let chunk = compile_source(src)?;
+    Ok(run_chunk_on(&chunk, config, backend)?)
+}
+
+/// Run `chunk` on `backend`, capturing its output. Where the host can't
+/// JIT-compile, or the run has a cancel token or instruction budget the
+/// JIT wouldn't check, `Backend::Jit` runs on the VM instead; so does
+/// `Backend::Closures` for a chunk that prints or uses files.
+pub fn run_chunk_on(chunk: &Chunk, config: VmConfig, backend: Backend) -> Result<RunOutcome, VmError> {
+    match backend {
+        Backend::Vm => VM::with_config(config).run_collecting(chunk),
+        #[cfg(feature = "fastexec")]
+        Backend::Closures if ClosureProgram::needs_vm(chunk) => VM::with_config(config).run_collecting(chunk),
+        #[cfg(feature = "fastexec")]
+        Backend::Closures => ClosureProgram::compile(chunk).run(&config),
+        #[cfg(feature = "jit")]
+        Backend::Jit if config.cancel_token.is_some() || config.max_steps.is_some() => VM::with_config(config).run_collecting(chunk),
+        #[cfg(feature = "jit")]
+        Backend::Jit => match JitProgram::compile(chunk) {
+            Ok(program) => program.run(&config),
+            Err(_) => VM::with_config(config).run_collecting(chunk),
+        },
+    }
+}
+
+/// A run started by [`spawn_run`] on its own thread.
+pub struct RunHandle {
+    token: CancelToken,
+    thread: JoinHandle<Result<RunOutcome, VmError>>,
+}
+
+impl RunHandle {
+    /// Ask the run to stop; it ends with `VmError::Cancelled` within
+    /// [`CANCEL_CHECK_INTERVAL`](crate::vm::CANCEL_CHECK_INTERVAL)
+    /// instructions, unless it finishes first.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Wait for the run to end.
+    pub fn join(self) -> Result<RunOutcome, Error> {
+        let result = self.thread.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        Ok(result?)
+    }
+}
+
+/// Run `chunk` on a VM in a new thread, capturing its output. Cancelling the
+/// handle also cancels `config.cancel_token`, if it had one.
+pub fn spawn_run(chunk: Chunk, mut config: VmConfig) -> RunHandle {
+    let token = config.cancel_token.get_or_insert_with(CancelToken::new).clone();
+    let thread = thread::spawn(move || VM::with_config(config).run_collecting(&chunk));
+    RunHandle { token, thread }
+}
+
+/// The stages of a compile-and-run, in order. Used to stop the pipeline
+/// early, e.g. `--check` stops after [`Phase::Compile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Phase {
+    Read,
+    Parse,
+    Compile,
+    #[default]
+    Execute,
+}
+
+/// Records wall-clock durations for the named phases of a compile-and-run.
+#[derive(Debug, Default)]
+pub struct PhaseTimer {
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl PhaseTimer {
+    /// Create an empty timer.
+    pub fn new() -> Self {
+        PhaseTimer::default()
+    }
+
+    /// Run `f`, record how long it took under `name`, and return its result.
+    pub fn time<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.phases.push((name, start.elapsed()));
+        result
+    }
+
+    /// All recorded phases, in the order they ran.
+    pub fn phases(&self) -> &[(&'static str, Duration)] {
+        &self.phases
+    }
+
+    /// Duration of the first phase recorded under `name`, if any.
+    pub fn get(&self, name: &str) -> Option<Duration> {
+        self.phases.iter().find(|(n, _)| *n == name).map(|(_, d)| *d)
+    }
+
+    /// Sum of all recorded phases.
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, d)| *d).sum()
+    }
+
+    /// Render a table of phase durations, plus VM throughput when
+    /// `instructions` is given (measured against the `execute` phase).
+    pub fn report(&self, instructions: Option<u64>) -> String {
+        let mut out = String::new();
+        for (name, d) in &self.phases {
+            out.push_str(&format!("{:<10} {:>12.3} ms\n", name, millis(*d)));
+        }
+        out.push_str(&format!("{:<10} {:>12.3} ms\n", "total", millis(self.total())));
+
+        if let Some(count) = instructions {
+            let secs = self.get("execute").map(|d| d.as_secs_f64()).unwrap_or(0.0);
+            let rate = if secs > 0.0 { count as f64 / secs } else { 0.0 };
+            out.push_str(&format!("instructions: {} ({:.0} instr/s)\n", count, rate));
+        }
+        out
+    }
+}
+
+/// Duration as fractional milliseconds.
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// A source of "the input changed" notifications for watch mode.
+pub trait ChangeSource {
+    /// Return true if anything changed since the previous call.
+    fn poll_changed(&mut self) -> bool;
+}
+
+/// Time source for watch mode, so tests can substitute a fake clock.
+pub trait Clock {
+    /// Time elapsed since some fixed starting point.
+    fn now(&self) -> Duration;
+    /// Block (or pretend to) for `d`.
+    fn sleep(&mut self, d: Duration);
+}
+
+/// The real clock, measured from its creation.
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, d: Duration) {
+        std::thread::sleep(d);
+    }
+}
+
+/// Detects changes by polling file modification times.
+pub struct MtimeWatcher {
+    paths: Vec<PathBuf>,
+    last: Vec<Option<SystemTime>>,
+}
+
+impl MtimeWatcher {
+    /// Start watching `paths`, taking their current mtimes as the baseline.
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let last = paths.iter().map(|p| mtime(p)).collect();
+        MtimeWatcher { paths, last }
+    }
+}
+
+impl ChangeSource for MtimeWatcher {
+    fn poll_changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, last) in self.paths.iter().zip(self.last.iter_mut()) {
+            let now = mtime(path);
+            if now != *last {
+                *last = now;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Modification time of `path`, or None if it can't be read (e.g. mid-save).
+fn mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Timing knobs for watch mode.
+#[derive(Debug, Clone, Copy)]
+pub struct WatchConfig {
+    /// How often to poll for changes.
+    pub poll_interval: Duration,
+    /// How long the input must stay quiet before rebuilding.
+    pub debounce: Duration,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            poll_interval: Duration::from_millis(100),
+            debounce: Duration::from_millis(200),
+        }
+    }
+}
+
+/// Run `rebuild` once, then again every time `changes` reports a change and
+/// the input has been quiet for `config.debounce`. Stops when `stop` returns
+/// true; returns how many rebuilds ran.
+pub fn watch<S, C>(
+    changes: &mut S,
+    clock: &mut C,
+    config: &WatchConfig,
+    mut rebuild: impl FnMut(),
+    mut stop: impl FnMut() -> bool,
+) -> usize
+where
+    S: ChangeSource,
+    C: Clock,
+{
+    rebuild();
+    let mut rebuilds = 1;
+    // Time of the most recent change not yet rebuilt.
+    let mut pending: Option<Duration> = None;
+
+    while !stop() {
+        if changes.poll_changed() {
+            pending = Some(clock.now());
+        }
+        if let Some(changed_at) = pending {
+            if clock.now().saturating_sub(changed_at) >= config.debounce {
+                pending = None;
+                rebuild();
+                rebuilds += 1;
+            }
+        }
+        clock.sleep(config.poll_interval);
+    }
+    rebuilds
+}
+
+/// Timing statistics from [`bench`].
+#[derive(Debug, Clone)]
+pub struct BenchStats {
+    /// Wall time of each measured run, in run order.
+    pub samples: Vec<Duration>,
+    /// Instructions executed by one run.
+    pub instructions: u64,
+}
+
+impl BenchStats {
+    pub fn min(&self) -> Duration {
+        self.samples.iter().copied().min().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.iter().copied().max().unwrap_or_default()
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    /// Middle sample (upper middle for an even count).
+    pub fn median(&self) -> Duration {
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        sorted.get(sorted.len() / 2).copied().unwrap_or_default()
+    }
+
+    /// Instructions per second, based on the mean run time.
+    pub fn instructions_per_sec(&self) -> f64 {
+        let secs = self.mean().as_secs_f64();
+        if secs > 0.0 { self.instructions as f64 / secs } else { 0.0 }
+    }
+
+    /// Human-readable summary table.
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        for (name, d) in [
+            ("min", self.min()),
+            ("median", self.median()),
+            ("mean", self.mean()),
+            ("max", self.max()),
+        ] {
+            out.push_str(&format!("{:<10} {:>12.3} ms\n", name, millis(d)));
+        }
+        out.push_str(&format!(
+            "instructions: {} per run ({:.0} instr/s)\n",
+            self.instructions,
+            self.instructions_per_sec()
+        ));
+        out
+    }
+}
+
+/// Run `chunk` `warmup` times unmeasured, then `iterations` times measured,
+/// reusing one VM (reset between runs) with its output discarded. Stops at
+/// the first runtime error.
+pub fn bench(chunk: &Chunk, iterations: usize, warmup: usize) -> Result<BenchStats, VmError> {
+    let mut vm = VM::new();
+    vm.set_output(Box::new(io::sink()));
+
+    for _ in 0..warmup {
+        vm.reset();
+        vm.run(chunk)?;
+    }
+
+    let mut timer = PhaseTimer::new();
+    let mut instructions = 0;
+    for _ in 0..iterations {
+        vm.reset();
+        timer.time("run", || vm.run(chunk))?;
+        instructions = vm.instruction_count();
+    }
+
+    Ok(BenchStats {
+        samples: timer.phases().iter().map(|(_, d)| *d).collect(),
+        instructions,
+    })
+}
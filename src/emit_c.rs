@@ -0,0 +1,402 @@
+// src/emit_c.rs
+
+//! Translate a parsed program into standalone C99, for `--emit-c`.
+//!
+//! C4's `int` is 64 bits, so it becomes `long long`; `char` and pointers map
+//! directly. Every name the program defines is prefixed with `c4_` so it
+//! can't collide with C keywords or libc, and a small `main` wrapper calls
+//! `c4_main`. Calls to names the program doesn't define are left as libc
+//! calls (`printf`, `malloc`, `memset`, `open`, ...). `printf` formats that
+//! are string literals get `ll` length modifiers and argument casts, so
+//! 64-bit values print correctly.
+//!
+//! C4 arithmetic wraps on overflow; compile the output with `-fwrapv` to
+//! keep that behaviour. Division by zero is undefined in C rather than a
+//! runtime error.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::fmt;
+
+use crate::ast::*;
+use crate::prelude::*;
+
+/// libc functions C4 programs call that return `void`.
+const VOID_LIBC: [&str; 3] = ["exit", "free", "srand"];
+
+/// A construct with no C translation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmitError {
+    pub message: String,
+}
+
+impl fmt::Display for EmitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl core::error::Error for EmitError {}
+
+/// Emit `program` as a C99 translation unit.
+pub fn emit_c(program: &Program) -> Result<String, EmitError> {
+    let mut e = Emitter {
+        out: String::new(),
+        defined: BTreeSet::new(),
+        globals: BTreeMap::new(),
+        functions: BTreeMap::new(),
+        locals: BTreeMap::new(),
+    };
+    for item in &program.items {
+        match item {
+            Item::Global(g) => {
+                e.defined.insert(g.name.as_str());
+                e.globals.insert(g.name.as_str(), &g.ty);
+            }
+            Item::Function(f) => {
+                e.defined.insert(f.name.as_str());
+                e.functions.insert(f.name.as_str(), f);
+            }
+            Item::Enum(en) => e.defined.extend(en.variants.iter().map(|(n, _)| n.as_str())),
+        }
+    }
+    let main = e.functions.get("main").copied().ok_or_else(|| EmitError { message: "program has no main function".into() })?;
+
+    e.out += "/* Generated by c4_rust_AlRafaah --emit-c; build with -fwrapv. */\n";
+    for header in ["fcntl.h", "stdio.h", "stdlib.h", "string.h", "unistd.h"] {
+        e.out += &format!("#include <{}>\n", header);
+    }
+    e.out.push('\n');
+
+    for item in &program.items {
+        match item {
+            Item::Enum(en) => {
+                let variants: Vec<String> = en
+                    .variants
+                    .iter()
+                    .map(|(n, v)| match v {
+                        Some(v) => format!("c4_{} = {}", n, v),
+                        None => format!("c4_{}", n),
+                    })
+                    .collect();
+                e.out += &format!("enum {{ {} }};\n", variants.join(", "));
+            }
+            Item::Global(g) => e.out += &format!("{};\n", declare(&g.ty, &format!("c4_{}", g.name))),
+            Item::Function(_) => {}
+        }
+    }
+    // Prototypes first, so functions can call each other in any order.
+    for f in program.items.iter().filter_map(|i| if let Item::Function(f) = i { Some(f) } else { None }) {
+        e.out += &format!("{};\n", signature(f));
+    }
+    for item in &program.items {
+        if let Item::Function(f) = item {
+            e.function(f)?;
+        }
+    }
+
+    let args: Vec<String> = main
+        .params
+        .iter()
+        .zip(["argc", "argv"])
+        .map(|((_, ty), arg)| format!("({}){}", type_name(ty), arg))
+        .collect();
+    let call = format!("c4_main({})", args.join(", "));
+    let body = match main.ret {
+        Type::Void => format!("{};\n    return 0;", call),
+        _ => format!("return (int){};", call),
+    };
+    e.out += &format!("\nint main(int argc, char **argv) {{\n    (void)argc;\n    (void)argv;\n    {}\n}}\n", body);
+    Ok(e.out)
+}
+
+struct Emitter<'p> {
+    out: String,
+    /// Every name the program defines; anything else is a libc call.
+    defined: BTreeSet<&'p str>,
+    globals: BTreeMap<&'p str, &'p Type>,
+    functions: BTreeMap<&'p str, &'p FuncDef>,
+    /// Parameters and locals of the function being emitted.
+    locals: BTreeMap<&'p str, &'p Type>,
+}
+
+impl<'p> Emitter<'p> {
+    fn function(&mut self, f: &'p FuncDef) -> Result<(), EmitError> {
+        self.locals = f.params.iter().chain(&f.locals).map(|(n, t)| (n.as_str(), t)).collect();
+        self.out += &format!("\n{} {{\n", signature(f));
+        for (name, ty) in &f.locals {
+            self.out += &format!("    {};\n", declare(ty, &format!("c4_{}", name)));
+        }
+        for stmt in &f.body.stmts {
+            self.stmt(stmt, 1, &f.ret)?;
+        }
+        self.out += "}\n";
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &'p Stmt, level: usize, ret: &Type) -> Result<(), EmitError> {
+        let pad = "    ".repeat(level);
+        match &stmt.kind {
+            StmtKind::If { cond, then_branch, else_branch } => {
+                let c = self.expr(cond)?;
+                self.out += &format!("{}if ({}) {{\n", pad, c);
+                self.stmt(then_branch, level + 1, ret)?;
+                if let Some(e) = else_branch {
+                    self.out += &format!("{}}} else {{\n", pad);
+                    self.stmt(e, level + 1, ret)?;
+                }
+                self.out += &format!("{}}}\n", pad);
+            }
+            StmtKind::While { cond, body } => {
+                let c = self.expr(cond)?;
+                self.out += &format!("{}while ({}) {{\n", pad, c);
+                self.stmt(body, level + 1, ret)?;
+                self.out += &format!("{}}}\n", pad);
+            }
+            StmtKind::DoWhile { body, cond } => {
+                self.out += &format!("{}do {{\n", pad);
+                self.stmt(body, level + 1, ret)?;
+                let c = self.expr(cond)?;
+                self.out += &format!("{}}} while ({});\n", pad, c);
+            }
+            StmtKind::For { init, cond, step, body } => {
+                let mut clauses = Vec::new();
+                for e in [init, cond, step] {
+                    clauses.push(match e {
+                        Some(e) => self.expr(e)?,
+                        None => String::new(),
+                    });
+                }
+                self.out += &format!("{}for ({}; {}; {}) {{\n", pad, clauses[0], clauses[1], clauses[2]);
+                self.stmt(body, level + 1, ret)?;
+                self.out += &format!("{}}}\n", pad);
+            }
+            StmtKind::Switch { scrutinee, cases } => {
+                let s = self.expr(scrutinee)?;
+                self.out += &format!("{}switch ({}) {{\n", pad, s);
+                for (label, stmts) in cases {
+                    match label {
+                        Some(n) => self.out += &format!("{}case {}:\n", pad, self.expr(&ExprKind::Num(*n).into())?),
+                        None => self.out += &format!("{}default:\n", pad),
+                    }
+                    for s in stmts {
+                        self.stmt(s, level + 1, ret)?;
+                    }
+                }
+                self.out += &format!("{}}}\n", pad);
+            }
+            StmtKind::Break => self.out += &format!("{}break;\n", pad),
+            StmtKind::Continue => self.out += &format!("{}continue;\n", pad),
+            StmtKind::Return(None) => self.out += &format!("{}return;\n", pad),
+            StmtKind::Return(Some(e)) => {
+                let e = self.expr(e)?;
+                match ret {
+                    Type::Void => self.out += &format!("{}{};\n{}return;\n", pad, e, pad),
+                    ty => self.out += &format!("{}return ({}){};\n", pad, type_name(ty), e),
+                }
+            }
+            StmtKind::Expr(e) => {
+                let e = self.expr(e)?;
+                self.out += &format!("{}{};\n", pad, e);
+            }
+            StmtKind::Block(b) => {
+                self.out += &format!("{}{{\n", pad);
+                let outer = self.locals.clone();
+                for (name, ty) in &b.locals {
+                    self.locals.insert(name, ty);
+                    self.out += &format!("{}    {};\n", pad, declare(ty, &format!("c4_{}", name)));
+                }
+                for s in &b.stmts {
+                    self.stmt(s, level + 1, ret)?;
+                }
+                self.locals = outer;
+                self.out += &format!("{}}}\n", pad);
+            }
+            StmtKind::Empty => {}
+        }
+        Ok(())
+    }
+
+    /// Fully parenthesized C for `expr`, so C's precedence never matters.
+    fn expr(&self, expr: &Expr) -> Result<String, EmitError> {
+        Ok(match &expr.kind {
+            ExprKind::Num(n) if *n == i64::MIN => "(-9223372036854775807LL - 1)".to_string(),
+            ExprKind::Num(n) => format!("{}LL", n),
+            ExprKind::Str(s) => c_string(s),
+            ExprKind::Var(name) => self.name(name),
+            ExprKind::Unary { op, expr } => {
+                let e = self.expr(expr)?;
+                match op {
+                    UnOp::PreInc => format!("(++{})", e),
+                    UnOp::PreDec => format!("(--{})", e),
+                    UnOp::PostInc => format!("({}++)", e),
+                    UnOp::PostDec => format!("({}--)", e),
+                    UnOp::Plus => format!("(+{})", e),
+                    UnOp::Neg => format!("(-{})", e),
+                    UnOp::Not => format!("(!{})", e),
+                    UnOp::BitNot => format!("(~{})", e),
+                    UnOp::Deref => format!("(*{})", e),
+                    UnOp::Addr => format!("(&{})", e),
+                }
+            }
+            ExprKind::Binary { op: BinOp::Assign, left, right } => {
+                let (l, r) = (self.expr(left)?, self.expr(right)?);
+                // C4 converts freely between ints and pointers; C wants a cast.
+                match &left.kind {
+                    ExprKind::Var(name) => match self.var_type(name) {
+                        Some(ty) => format!("({} = ({}){})", l, type_name(ty), r),
+                        None => format!("({} = {})", l, r),
+                    },
+                    _ => format!("({} = {})", l, r),
+                }
+            }
+            ExprKind::Binary { op, left, right } => {
+                format!("({} {} {})", self.expr(left)?, op.symbol(), self.expr(right)?)
+            }
+            ExprKind::CompoundAssign { op, target, value } => {
+                format!("({} {}= {})", self.expr(target)?, op.symbol(), self.expr(value)?)
+            }
+            ExprKind::Call { callee, args } => {
+                let ExprKind::Var(name) = &callee.kind else {
+                    return Err(EmitError { message: "only calls to named functions can be translated".into() });
+                };
+                self.call(name, args)?
+            }
+            ExprKind::Cast { ty, expr } => format!("(({}){})", type_name(ty), self.expr(expr)?),
+            ExprKind::SizeOf(ty) => format!("((long long)sizeof({}))", type_name(ty)),
+            ExprKind::Conditional { cond, then_expr, else_expr } => {
+                format!("({} ? {} : {})", self.expr(cond)?, self.expr(then_expr)?, self.expr(else_expr)?)
+            }
+            ExprKind::Index { array, index } => format!("({}[{}])", self.expr(array)?, self.expr(index)?),
+        })
+    }
+
+    fn call(&self, name: &str, args: &[Expr]) -> Result<String, EmitError> {
+        let mut emitted = Vec::with_capacity(args.len());
+        for a in args {
+            emitted.push(self.expr(a)?);
+        }
+        if let Some(f) = self.functions.get(name) {
+            // Cast each argument to its parameter's type.
+            for (arg, (_, ty)) in emitted.iter_mut().zip(&f.params) {
+                *arg = format!("({}){}", type_name(ty), arg);
+            }
+        } else if name == "printf" {
+            if let Some(ExprKind::Str(format)) = args.first().map(|a| &a.kind) {
+                let (format, casts) = printf_format(format);
+                emitted[0] = c_string(&format);
+                for (arg, cast) in emitted[1..].iter_mut().zip(casts) {
+                    *arg = format!("({}){}", cast, arg);
+                }
+            }
+        }
+        let call = format!("{}({})", self.name(name), emitted.join(", "));
+        // libc functions return int or pointers; C4 sees every result as an
+        // int, and a void one's as 0.
+        Ok(if self.defined.contains(name) {
+            call
+        } else if VOID_LIBC.contains(&name) {
+            format!("({}, 0LL)", call)
+        } else {
+            format!("((long long){})", call)
+        })
+    }
+
+    fn name(&self, name: &str) -> String {
+        if self.defined.contains(name) || self.locals.contains_key(name) {
+            format!("c4_{}", name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn var_type(&self, name: &str) -> Option<&'p Type> {
+        self.locals.get(name).or_else(|| self.globals.get(name)).copied()
+    }
+}
+
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Void => "void".into(),
+        Type::Int => "long long".into(),
+        Type::Char => "char".into(),
+        Type::Ptr(inner) => {
+            let inner = type_name(inner);
+            if inner.ends_with('*') {
+                inner + "*"
+            } else {
+                inner + " *"
+            }
+        }
+    }
+}
+
+fn declare(ty: &Type, name: &str) -> String {
+    let t = type_name(ty);
+    if t.ends_with('*') {
+        format!("{}{}", t, name)
+    } else {
+        format!("{} {}", t, name)
+    }
+}
+
+fn signature(f: &FuncDef) -> String {
+    let params: Vec<String> = f.params.iter().map(|(n, t)| declare(t, &format!("c4_{}", n))).collect();
+    let params = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+    format!("{}({})", declare(&f.ret, &format!("c4_{}", f.name)), params)
+}
+
+/// A C string literal for `s`, escaping everything that isn't printable.
+fn c_string(s: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &b in s {
+        match b {
+            b'"' => out += "\\\"",
+            b'\\' => out += "\\\\",
+            b'\n' => out += "\\n",
+            b'\t' => out += "\\t",
+            0x20..=0x7e => out.push(b as char),
+            _ => out += &format!("\\{:03o}", b),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Rewrite a C4 `printf` format for 64-bit arguments: integer conversions get
+/// an `ll` modifier. Returns the new format and the cast each argument needs.
+fn printf_format(format: &[u8]) -> (Vec<u8>, Vec<&'static str>) {
+    let mut out = Vec::new();
+    let mut casts = Vec::new();
+    let mut bytes = format.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        out.push(b);
+        if b != b'%' {
+            continue;
+        }
+        while let Some(&f) = bytes.peek() {
+            if !matches!(f, b'-' | b'+' | b' ' | b'#' | b'0'..=b'9' | b'.') {
+                break;
+            }
+            out.push(f);
+            bytes.next();
+        }
+        match bytes.next() {
+            Some(conv @ (b'd' | b'i' | b'o' | b'u' | b'x' | b'X')) => {
+                out.extend_from_slice(b"ll");
+                out.push(conv);
+                casts.push("long long");
+            }
+            Some(b'c') => {
+                out.push(b'c');
+                casts.push("int");
+            }
+            Some(b's') => {
+                out.push(b's');
+                casts.push("char *");
+            }
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    (out, casts)
+}
@@ -0,0 +1,120 @@
+// src/error.rs
+
+//! The crate-wide error type. Each phase has its own structured error, and
+//! [`Error`] wraps whichever one stopped the pipeline so callers can use `?`
+//! across phases and still match on what went wrong.
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::prelude::*;
+pub use crate::lexer::LexError;
+pub use crate::vm::VmError;
+
+/// A syntax error, positioned at the token where parsing failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// Byte offset into the source of the offending token.
+    pub offset: usize,
+    /// 1-based line and column of the same token.
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message, self.line, self.col)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+/// A construct the code generator can't translate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub message: String,
+}
+
+impl CompileError {
+    pub fn new(message: impl Into<String>) -> Self {
+        CompileError { message: message.into() }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl core::error::Error for CompileError {}
+
+/// Any error produced while reading, parsing, compiling, or running a program.
+#[derive(Debug)]
+pub enum Error {
+    Lex(LexError),
+    Parse(ParseError),
+    Compile(CompileError),
+    Runtime(VmError),
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Lex(e) => write!(f, "lex error: {}", e),
+            Error::Parse(e) => write!(f, "parse error: {}", e),
+            Error::Compile(e) => write!(f, "compile error: {}", e),
+            Error::Runtime(e) => write!(f, "runtime error: {}", e),
+            #[cfg(feature = "std")]
+            Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Error::Lex(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Compile(e) => Some(e),
+            Error::Runtime(e) => Some(e),
+            #[cfg(feature = "std")]
+            Error::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<LexError> for Error {
+    fn from(e: LexError) -> Self {
+        Error::Lex(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<CompileError> for Error {
+    fn from(e: CompileError) -> Self {
+        Error::Compile(e)
+    }
+}
+
+impl From<VmError> for Error {
+    fn from(e: VmError) -> Self {
+        Error::Runtime(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
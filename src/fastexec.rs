@@ -0,0 +1,517 @@
+// src/fastexec.rs
+
+//! Closure-compiled execution, enabled by the `fastexec` feature.
+//!
+//! [`ClosureProgram::compile`] turns a [`Chunk`] into one boxed closure per
+//! basic block (see [`crate::cfg`]), each running pre-built closures for its
+//! instructions with their operands already bound; common pairs such as
+//! `LEA`+`LI` share one closure. Running it skips the VM's per-instruction
+//! decode and dispatch; the results, resource counters and errors (down to
+//! the faulting pc) are the same as [`VM::run`](crate::vm::VM::run) on the
+//! same chunk, which `tests/backend_tests.rs` checks against the VM.
+//!
+//! What it can't do, compared to the VM:
+//! - no debug trace (`VM::debug`) and no tracing events;
+//! - no stepping, breakpoints, or snapshot/resume: a run goes from the first
+//!   instruction to `EXIT` (or off the end of the code) in one call;
+//! - no output or files: `PRTF`, `OPEN`, `READ` and `CLOS` fault as
+//!   unknown instructions ([`crate::driver::run_chunk_on`] runs such chunks
+//!   on the VM instead);
+//! - no allocation tracking: `VmConfig::track_allocations` is ignored.
+
+use crate::bytecode::{Chunk, Instruction, OpCode, WORD_SIZE};
+use crate::cfg::{BasicBlock, Cfg};
+use crate::heap::Heap;
+use crate::prelude::*;
+use crate::vm::{byte_at, local_address, with_byte, word_address, CancelToken, Rng, RunOutcome, SharedClock, VmConfig, VmError};
+#[cfg(feature = "std")]
+use std::time::Instant;
+use core::time::Duration;
+
+/// Machine state the compiled closures operate on; the same registers and
+/// counters as the VM's.
+pub struct ExecState {
+    stack: Vec<i64>,
+    call_stack: Vec<(usize, usize, usize)>, // (return_pc, old_sp, old_fp)
+    a: i64,
+    sp: usize,
+    fp: usize,
+    max_sp: usize,
+    max_frames: usize,
+    steps: u64,
+    rng: Rng,
+    clock: SharedClock,
+    clock_start: Duration,
+    heap: Heap,
+    data: Vec<i64>,
+    fault: Option<VmError>,
+}
+
+/// Where control goes after a closure runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// On to the next instruction in the block.
+    Next,
+    /// Transfer to the block starting at this pc.
+    Goto(usize),
+    /// The program exited with this value.
+    Exit(i64),
+    /// The instruction faulted; the error is in [`ExecState`].
+    Fault,
+}
+
+type Op = Box<dyn Fn(&mut ExecState) -> Flow + Send + Sync>;
+
+// Unwrap `result`, or record its error in the state and stop with
+// `Flow::Fault`. Keeping the error out of the return value keeps `Flow`
+// small enough to come back in registers.
+macro_rules! attempt {
+    ($s:ident, $result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(e) => {
+                $s.fault = Some(e);
+                return Flow::Fault;
+            }
+        }
+    };
+}
+
+/// A chunk compiled to closures, reusable across runs.
+pub struct ClosureProgram {
+    blocks: Vec<Op>,
+    /// Block index for each pc that starts a block.
+    block_at: Vec<Option<usize>>,
+    /// The chunk's data segment, copied into each run.
+    data: Vec<i64>,
+}
+
+impl ClosureProgram {
+    /// Whether `chunk` has output or file instructions, which this backend
+    /// lacks, so it should run on the VM instead.
+    pub fn needs_vm(chunk: &Chunk) -> bool {
+        chunk.code.iter().any(|i| {
+            matches!(
+                i,
+                Instruction::InstrInt(OpCode::PRTF, _) | Instruction::Instr(OpCode::OPEN | OpCode::READ | OpCode::CLOS)
+            )
+        })
+    }
+
+    /// Compile `chunk`. Never fails: instructions the VM would reject fail
+    /// the same way when they are reached.
+    pub fn compile(chunk: &Chunk) -> Self {
+        let code = &chunk.code;
+        let cfg = Cfg::build(chunk, 0..code.len());
+        let mut blocks = Vec::new();
+        let mut block_at = vec![None; code.len()];
+        for &BasicBlock { start, end, .. } in &cfg.blocks {
+            let mut pc = start;
+            let mut ops = Vec::new();
+            while pc < end {
+                let second = if pc + 1 < end { code.get(pc + 1) } else { None };
+                if let Some(op) = compile_pair(pc, &code[pc], second) {
+                    ops.push(op);
+                    pc += 2;
+                } else {
+                    ops.push(compile_op(pc, &code[pc]));
+                    pc += 1;
+                }
+            }
+            let len = (end - start) as u64;
+            block_at[start] = Some(blocks.len());
+            // Counted up front: a fault mid-block ends the run, and the
+            // count is only reported for runs that finish.
+            blocks.push(Box::new(move |s: &mut ExecState| {
+                s.steps += len;
+                for op in &ops {
+                    match op(s) {
+                        Flow::Next => {}
+                        flow => return flow,
+                    }
+                }
+                Flow::Goto(end)
+            }) as Op);
+        }
+        ClosureProgram { blocks, block_at, data: chunk.data.clone() }
+    }
+
+    /// Run the program on a fresh state sized by `config`.
+    pub fn run(&self, config: &VmConfig) -> Result<RunOutcome, VmError> {
+        let clock = config.clock();
+        let mut state = ExecState {
+            stack: vec![0; config.stack_size],
+            call_stack: Vec::new(),
+            a: 0,
+            sp: 0,
+            fp: 0,
+            max_sp: 0,
+            max_frames: 0,
+            steps: 0,
+            rng: Rng::default(),
+            clock_start: clock.now(),
+            clock,
+            heap: Heap::new(config.stack_size, config.heap_size(), false),
+            data: self.data.clone(),
+            fault: None,
+        };
+        #[cfg(feature = "std")]
+        let start = Instant::now();
+        let exit_code = self.execute(&mut state, config)?;
+        #[cfg(feature = "std")]
+        let wall_time = start.elapsed();
+        #[cfg(not(feature = "std"))]
+        let wall_time = Duration::ZERO;
+        Ok(RunOutcome {
+            exit_code,
+            stdout: Vec::new(),
+            instructions: state.steps,
+            max_stack_words: state.max_sp,
+            max_call_depth: state.max_frames,
+            heap_peak_words: state.heap.peak_words(),
+            wall_time,
+        })
+    }
+
+    fn execute(&self, s: &mut ExecState, config: &VmConfig) -> Result<i64, VmError> {
+        let mut pc = 0;
+        // Like the VM, running off the end returns register `a`.
+        while let Some(block) = self.block_at.get(pc) {
+            if config.cancel_token.as_ref().is_some_and(CancelToken::is_cancelled) {
+                return Err(VmError::Cancelled { pc });
+            }
+            if config.max_steps.is_some_and(|max| s.steps >= max) {
+                return Err(VmError::OutOfFuel { pc, steps: s.steps });
+            }
+            let block = block.expect("control only reaches block starts");
+            match self.blocks[block](s) {
+                Flow::Goto(target) => pc = target,
+                Flow::Exit(code) => return Ok(code),
+                Flow::Fault => return Err(s.fault.take().expect("faults record their error")),
+                Flow::Next => unreachable!("blocks end in a transfer"),
+            }
+        }
+        Ok(s.a)
+    }
+}
+
+impl ExecState {
+    fn push(&mut self, pc: usize, val: i64) -> Result<(), VmError> {
+        if self.sp >= self.stack.len() {
+            return Err(VmError::StackOverflow { pc });
+        }
+        self.stack[self.sp] = val;
+        self.sp += 1;
+        self.max_sp = self.max_sp.max(self.sp);
+        Ok(())
+    }
+
+    fn pop(&mut self, pc: usize) -> Result<i64, VmError> {
+        if self.sp == 0 {
+            return Err(VmError::StackUnderflow { pc });
+        }
+        self.sp -= 1;
+        Ok(self.stack[self.sp])
+    }
+
+    // Save the registers, returning to the instruction after `pc`.
+    fn push_frame(&mut self, pc: usize) -> Result<(), VmError> {
+        if self.call_stack.len() >= self.stack.len() {
+            return Err(VmError::StackOverflow { pc });
+        }
+        self.call_stack.push((pc + 1, self.sp, self.fp));
+        self.max_frames = self.max_frames.max(self.call_stack.len());
+        Ok(())
+    }
+
+    // `memset(dest, c, n)` on the top three stack words, as in the VM.
+    fn memset(&mut self, pc: usize) -> Result<i64, VmError> {
+        let [dest, c, n] = self.stack_args(pc)?;
+        for i in 0..n.max(0) {
+            self.store_char(pc, dest.wrapping_add(i), c)?;
+        }
+        Ok(dest)
+    }
+
+    // `memcmp(p, q, n)` on the top three stack words, as in the VM.
+    fn memcmp(&mut self, pc: usize) -> Result<i64, VmError> {
+        let [p, q, n] = self.stack_args(pc)?;
+        for i in 0..n.max(0) {
+            let x = self.load_char(pc, p.wrapping_add(i))?;
+            let y = self.load_char(pc, q.wrapping_add(i))?;
+            if x != y {
+                return Ok(x - y);
+            }
+        }
+        Ok(0)
+    }
+
+    fn stack_args(&self, pc: usize) -> Result<[i64; 3], VmError> {
+        if self.sp < 3 {
+            return Err(VmError::StackUnderflow { pc });
+        }
+        let top = &self.stack[self.sp - 3..self.sp];
+        Ok([top[0], top[1], top[2]])
+    }
+
+    // Loads and stores at byte addresses, as in the VM.
+    fn load_int(&mut self, pc: usize, addr: i64) -> Result<i64, VmError> {
+        if addr % WORD_SIZE as i64 == 0 {
+            return Ok(*self.slot(pc, addr)?);
+        }
+        let mut bytes = [0; WORD_SIZE];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let loaded = self.load_char(pc, addr.wrapping_add(i as i64));
+            *byte = loaded.map_err(|_| VmError::InvalidAddress { pc, addr })? as u8;
+        }
+        Ok(i64::from_le_bytes(bytes))
+    }
+
+    fn store_int(&mut self, pc: usize, addr: i64, value: i64) -> Result<(), VmError> {
+        if addr % WORD_SIZE as i64 == 0 {
+            *self.slot(pc, addr)? = value;
+            return Ok(());
+        }
+        for i in 0..WORD_SIZE as i64 {
+            self.slot(pc, addr.wrapping_add(i)).map_err(|_| VmError::InvalidAddress { pc, addr })?;
+        }
+        for (i, byte) in value.to_le_bytes().into_iter().enumerate() {
+            self.store_char(pc, addr.wrapping_add(i as i64), i64::from(byte))?;
+        }
+        Ok(())
+    }
+
+    fn load_char(&mut self, pc: usize, addr: i64) -> Result<i64, VmError> {
+        Ok(byte_at(*self.slot(pc, addr)?, addr))
+    }
+
+    fn store_char(&mut self, pc: usize, addr: i64, byte: i64) -> Result<(), VmError> {
+        let slot = self.slot(pc, addr)?;
+        *slot = with_byte(*slot, addr, byte);
+        Ok(())
+    }
+
+    fn slot(&mut self, pc: usize, addr: i64) -> Result<&mut i64, VmError> {
+        let (stack, heap, data) = (&mut self.stack, &mut self.heap, &mut self.data);
+        usize::try_from(addr)
+            .ok()
+            .map(|addr| addr / WORD_SIZE)
+            .and_then(|i| {
+                if i < stack.len() {
+                    stack.get_mut(i)
+                } else if i < heap.end() {
+                    heap.word(i)
+                } else {
+                    data.get_mut(i - heap.end())
+                }
+            })
+            .ok_or(VmError::InvalidAddress { pc, addr })
+    }
+}
+
+// Binary operator on the popped left operand and register `a`.
+fn binary(pc: usize, f: impl Fn(i64, i64) -> i64 + Send + Sync + 'static) -> Op {
+    Box::new(move |s| {
+        let left = attempt!(s, s.pop(pc));
+        s.a = f(left, s.a);
+        Flow::Next
+    })
+}
+
+// Division and remainder, which fault on a zero divisor before popping.
+fn divide(pc: usize, f: impl Fn(i64, i64) -> i64 + Send + Sync + 'static) -> Op {
+    Box::new(move |s| {
+        if s.a == 0 {
+            s.fault = Some(VmError::DivisionByZero { pc });
+            return Flow::Fault;
+        }
+        let left = attempt!(s, s.pop(pc));
+        s.a = f(left, s.a);
+        Flow::Next
+    })
+}
+
+// One closure for two instructions the compiler often emits together,
+// saving a dispatch. Faults in the second report its own pc.
+fn compile_pair(pc: usize, first: &Instruction, second: Option<&Instruction>) -> Option<Op> {
+    let next = pc + 1;
+    Some(match (first, second?) {
+        // Load a local.
+        (Instruction::InstrInt(OpCode::LEA, offset), Instruction::Instr(OpCode::LI)) => {
+            let offset = *offset;
+            Box::new(move |s| {
+                let addr = local_address(s.fp, offset);
+                s.a = attempt!(s, s.load_int(next, addr));
+                Flow::Next
+            })
+        }
+        // Push a local's address, ahead of a store.
+        (Instruction::InstrInt(OpCode::LEA, offset), Instruction::Instr(OpCode::PSH)) => {
+            let offset = *offset;
+            Box::new(move |s| {
+                s.a = local_address(s.fp, offset);
+                attempt!(s, s.push(next, s.a));
+                Flow::Next
+            })
+        }
+        // Push a constant operand.
+        (Instruction::InstrInt(OpCode::IMM, val), Instruction::Instr(OpCode::PSH)) => {
+            let val = *val;
+            Box::new(move |s| {
+                s.a = val;
+                attempt!(s, s.push(next, val));
+                Flow::Next
+            })
+        }
+        _ => return None,
+    })
+}
+
+// The closure for one instruction, mirroring `VM::execute`.
+fn compile_op(pc: usize, instr: &Instruction) -> Op {
+    match *instr {
+        Instruction::Instr(op) => match op {
+            OpCode::ADD => binary(pc, i64::wrapping_add),
+            OpCode::SUB => binary(pc, i64::wrapping_sub),
+            OpCode::MUL => binary(pc, i64::wrapping_mul),
+            OpCode::DIV => divide(pc, i64::wrapping_div),
+            OpCode::MOD => divide(pc, i64::wrapping_rem),
+            OpCode::AND => binary(pc, |l, a| l & a),
+            OpCode::OR => binary(pc, |l, a| l | a),
+            OpCode::XOR => binary(pc, |l, a| l ^ a),
+            OpCode::EQ => binary(pc, |l, a| (l == a) as i64),
+            OpCode::NE => binary(pc, |l, a| (l != a) as i64),
+            OpCode::LT => binary(pc, |l, a| (l < a) as i64),
+            OpCode::LE => binary(pc, |l, a| (l <= a) as i64),
+            OpCode::GT => binary(pc, |l, a| (l > a) as i64),
+            OpCode::GE => binary(pc, |l, a| (l >= a) as i64),
+            OpCode::SHL => binary(pc, |l, a| l.wrapping_shl(a as u32)),
+            OpCode::SHR => binary(pc, |l, a| l.wrapping_shr(a as u32)),
+            OpCode::LI => Box::new(move |s| {
+                s.a = attempt!(s, s.load_int(pc, s.a));
+                Flow::Next
+            }),
+            OpCode::LC => Box::new(move |s| {
+                s.a = attempt!(s, s.load_char(pc, s.a));
+                Flow::Next
+            }),
+            OpCode::SI => Box::new(move |s| {
+                let addr = attempt!(s, s.pop(pc));
+                attempt!(s, s.store_int(pc, addr, s.a));
+                Flow::Next
+            }),
+            OpCode::SC => Box::new(move |s| {
+                let addr = attempt!(s, s.pop(pc));
+                let byte = s.a & 0xFF;
+                attempt!(s, s.store_char(pc, addr, byte));
+                s.a = byte;
+                Flow::Next
+            }),
+            OpCode::PSH => Box::new(move |s| {
+                attempt!(s, s.push(pc, s.a));
+                Flow::Next
+            }),
+            OpCode::LEV => Box::new(move |s| {
+                let frame = s.call_stack.pop().ok_or(VmError::CallStackUnderflow { pc });
+                let (ret_pc, old_sp, old_fp) = attempt!(s, frame);
+                s.sp = old_sp;
+                s.fp = old_fp;
+                Flow::Goto(ret_pc)
+            }),
+            OpCode::EXIT => Box::new(|s| Flow::Exit(s.a)),
+            OpCode::ASRT => Box::new(move |s| {
+                if s.a == 0 {
+                    s.fault = Some(VmError::AssertionFailed { pc });
+                    return Flow::Fault;
+                }
+                Flow::Next
+            }),
+            OpCode::RAND => Box::new(|s| {
+                s.a = s.rng.rand();
+                Flow::Next
+            }),
+            OpCode::SRND => Box::new(|s| {
+                s.rng = Rng::new(s.a);
+                Flow::Next
+            }),
+            OpCode::CLCK => Box::new(|s| {
+                s.a = s.clock.now().saturating_sub(s.clock_start).as_millis() as i64;
+                Flow::Next
+            }),
+            OpCode::TIME => Box::new(|s| {
+                s.a = s.clock.unix_time();
+                Flow::Next
+            }),
+            OpCode::MALC => Box::new(move |s| {
+                s.a = s.heap.malloc(s.a, pc, s.steps);
+                Flow::Next
+            }),
+            OpCode::FREE => Box::new(move |s| {
+                attempt!(s, s.heap.free(s.a, pc));
+                Flow::Next
+            }),
+            OpCode::MSET => Box::new(move |s| {
+                s.a = attempt!(s, s.memset(pc));
+                Flow::Next
+            }),
+            OpCode::MCMP => Box::new(move |s| {
+                s.a = attempt!(s, s.memcmp(pc));
+                Flow::Next
+            }),
+            _ => unknown(pc, instr),
+        },
+
+        Instruction::InstrInt(op, val) => match op {
+            OpCode::IMM => Box::new(move |s| {
+                s.a = val;
+                Flow::Next
+            }),
+            OpCode::LEA => Box::new(move |s| {
+                s.a = local_address(s.fp, val);
+                Flow::Next
+            }),
+            OpCode::GLO => Box::new(move |s| {
+                s.a = word_address(s.heap.end()).wrapping_add(val);
+                Flow::Next
+            }),
+            OpCode::ADJ => Box::new(move |s| {
+                for _ in 0..val {
+                    attempt!(s, s.pop(pc));
+                }
+                Flow::Next
+            }),
+            OpCode::ENT => Box::new(move |s| {
+                s.fp = s.sp;
+                for _ in 0..val {
+                    attempt!(s, s.push(pc, 0));
+                }
+                Flow::Next
+            }),
+            _ => unknown(pc, instr),
+        },
+
+        Instruction::Jump(op, target) => match op {
+            OpCode::JMP => Box::new(move |_| Flow::Goto(target)),
+            OpCode::BZ => Box::new(move |s| Flow::Goto(if s.a == 0 { target } else { pc + 1 })),
+            OpCode::BNZ => Box::new(move |s| Flow::Goto(if s.a != 0 { target } else { pc + 1 })),
+            _ => unknown(pc, instr),
+        },
+
+        Instruction::Call(op, target) => match op {
+            OpCode::JSR => Box::new(move |s| {
+                attempt!(s, s.push_frame(pc));
+                Flow::Goto(target)
+            }),
+            _ => unknown(pc, instr),
+        },
+    }
+}
+
+// An instruction the VM can't execute: fails when reached, not before.
+fn unknown(pc: usize, instr: &Instruction) -> Op {
+    let instr = instr.clone();
+    Box::new(move |s| {
+        s.fault = Some(VmError::UnknownInstruction { pc, instr: instr.clone() });
+        Flow::Fault
+    })
+}
@@ -0,0 +1,416 @@
+// src/format.rs
+
+//! Source formatter behind `--fmt` and `--fmt-check`.
+//!
+//! The file is parsed first so that broken input is reported rather than
+//! rewritten. Formatting then works on the token stream with comments kept
+//! (see [`Lexer::with_trivia`]): the AST drops comments and declarations in
+//! the middle of blocks, while the tokens keep everything. Spacing,
+//! indentation and braces are normalized; comments stay where they were,
+//! either trailing their line or on a line of their own, and single blank
+//! lines between statements are kept. Formatting formatted output returns it
+//! unchanged.
+
+use crate::error::Error;
+use crate::lexer::{Lexer, Token};
+use crate::parser::Parser;
+use crate::prelude::*;
+
+/// Where an opening brace of a function or statement goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BraceStyle {
+    /// `if (x) {`
+    #[default]
+    SameLine,
+    /// `if (x)` with `{` on the next line.
+    NextLine,
+}
+
+/// Knobs for [`format_source`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Spaces per indentation level.
+    pub indent_width: usize,
+    pub brace_style: BraceStyle,
+    /// Lines longer than this are wrapped at their outermost commas or
+    /// weakest binary operators, where possible.
+    pub max_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions { indent_width: 4, brace_style: BraceStyle::SameLine, max_width: 100 }
+    }
+}
+
+/// Reformat `src`. Fails, without producing output, if it doesn't parse.
+pub fn format_source(src: &str, opts: &FormatOptions) -> Result<String, Error> {
+    Parser::new(src)?.parse_program()?;
+
+    let mut tokens = Vec::new();
+    let mut lex = Lexer::new(src).with_trivia();
+    loop {
+        match lex.next_token()? {
+            Token::Eof => break,
+            tok => tokens.push((lex.token_span(), tok)),
+        }
+    }
+
+    let mut f = Formatter {
+        opts,
+        out: String::new(),
+        line: Vec::new(),
+        line_level: 0,
+        scopes: Vec::new(),
+        parens: Vec::new(),
+        prev: Prev::LineStart,
+        in_label: false,
+    };
+    let mut prev_end = 0;
+    for (i, (span, tok)) in tokens.iter().enumerate() {
+        let gap = src[prev_end..span.start].matches('\n').count();
+        prev_end = span.end;
+        let next = tokens[i + 1..].iter().map(|(_, t)| t).find(|t| !matches!(t, Token::Comment(_)));
+        f.token(tok, &src[span.start..span.end], gap, next);
+    }
+    f.flush();
+    Ok(f.out)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope {
+    Block,
+    /// Braced body of `do`, whose `}` the `while` follows on the same line.
+    DoBody,
+    /// Unbraced body of `if`, `while`, `for`, `do` or `else`, indented one level.
+    Single,
+    /// `enum { ... }`, kept inline.
+    Enum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Paren {
+    /// `if (...)` / `while (...)` / `for (...)` / `switch (...)`.
+    Condition,
+    Cast,
+    Group,
+}
+
+/// What the previous token was, for spacing decisions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Prev {
+    LineStart,
+    /// `(` or `[`: no space after.
+    Open,
+    /// Prefix operator: no space after.
+    Unary,
+    /// Something a binary operator can follow: a name, literal, `)`, `]`.
+    Operand,
+    /// `)` closing a cast: no space after.
+    CastClose,
+    /// An identifier or `sizeof`, which a call's `(` hugs.
+    Callee,
+    /// Binary operator, comma, keyword: one space after.
+    Spaced,
+}
+
+struct Piece {
+    text: String,
+    space_before: bool,
+    /// A line break may follow this piece: (paren depth, precedence).
+    break_after: Option<(usize, u8)>,
+}
+
+struct Formatter<'o> {
+    opts: &'o FormatOptions,
+    out: String,
+    line: Vec<Piece>,
+    line_level: usize,
+    scopes: Vec<Scope>,
+    parens: Vec<Paren>,
+    prev: Prev,
+    /// Inside a `case ...:` or `default:` label, which ends at its `:`.
+    in_label: bool,
+}
+
+impl Formatter<'_> {
+    fn level(&self) -> usize {
+        self.scopes.iter().filter(|s| **s != Scope::Enum).count()
+    }
+
+    fn push(&mut self, text: &str, space_before: bool, break_after: Option<(usize, u8)>) {
+        if self.line.is_empty() {
+            self.line_level = self.level();
+        }
+        let space_before = space_before && !self.line.is_empty();
+        self.line.push(Piece { text: text.to_string(), space_before, break_after });
+    }
+
+    /// Write out the current line, wrapping it if it is too long.
+    fn flush(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+        let indent = " ".repeat(self.line_level * self.opts.indent_width);
+        let width = indent.len() + self.line.iter().map(|p| p.text.chars().count() + p.space_before as usize).sum::<usize>();
+        let split = if width > self.opts.max_width {
+            self.line.iter().filter_map(|p| p.break_after).min()
+        } else {
+            None
+        };
+
+        self.out += &indent;
+        let continuation = " ".repeat(self.opts.indent_width * 2);
+        let mut at_line_start = true;
+        let last = self.line.len() - 1;
+        for (i, piece) in self.line.iter().enumerate() {
+            if piece.space_before && !at_line_start {
+                self.out.push(' ');
+            }
+            self.out += &piece.text;
+            at_line_start = false;
+            if split.is_some() && piece.break_after == split && i != last {
+                self.out.push('\n');
+                self.out += &indent;
+                self.out += &continuation;
+                at_line_start = true;
+            }
+        }
+        self.out.push('\n');
+        self.line.clear();
+        self.prev = Prev::LineStart;
+    }
+
+    /// A statement ended; close the unbraced bodies it completed. Before
+    /// `else` only the innermost one ends, since the `else` belongs to it.
+    fn end_statement(&mut self, before_else: bool, after_block: bool) {
+        if before_else {
+            if !after_block && self.scopes.last() == Some(&Scope::Single) {
+                self.scopes.pop();
+            }
+            return;
+        }
+        while self.scopes.last() == Some(&Scope::Single) {
+            self.scopes.pop();
+        }
+    }
+
+    fn token(&mut self, tok: &Token, text: &str, gap: usize, next: Option<&Token>) {
+        // Keep one blank line where the source had at least one.
+        if gap >= 2 && self.line.is_empty() && !self.out.is_empty() && !self.out.ends_with("\n\n")
+            && !self.out.ends_with("{\n") && *tok != Token::RBrace
+        {
+            self.out.push('\n');
+        }
+
+        let depth = self.parens.len();
+        let next_is = |t: Token| next == Some(&t);
+        match tok {
+            Token::Comment(c) if c.starts_with('#') => {
+                self.flush();
+                self.out += c;
+                self.out.push('\n');
+            }
+            Token::Comment(c) if c.starts_with("/*") && gap == 0 && !self.line.is_empty() => {
+                // A block comment within a line stays there.
+                let prev = self.prev;
+                self.push(c, true, None);
+                self.prev = prev;
+            }
+            Token::Comment(c) => {
+                if gap == 0 && !self.line.is_empty() {
+                    self.push(c, true, None);
+                    self.flush();
+                } else if gap == 0 && self.out.ends_with('\n') && !self.out.ends_with("\n\n") {
+                    // Trailing a line we already finished.
+                    self.out.pop();
+                    self.out.push(' ');
+                    self.out += c;
+                    self.out.push('\n');
+                } else {
+                    let prev = self.prev;
+                    self.flush();
+                    self.push(c, false, None);
+                    self.flush();
+                    // Mid-expression comments don't change how the rest spaces.
+                    self.prev = prev;
+                }
+            }
+            Token::LBrace if self.prev == Prev::Spaced && self.line.last().is_some_and(|p| p.text == "enum") => {
+                self.push("{", true, None);
+                self.scopes.push(Scope::Enum);
+                self.prev = Prev::Spaced;
+            }
+            Token::LBrace => {
+                let scope = match self.line.last() {
+                    Some(p) if p.text == "do" => Scope::DoBody,
+                    _ => Scope::Block,
+                };
+                if self.opts.brace_style == BraceStyle::NextLine {
+                    self.flush();
+                }
+                self.push("{", true, None);
+                self.flush();
+                self.scopes.push(scope);
+            }
+            Token::RBrace if self.scopes.last() == Some(&Scope::Enum) => {
+                self.scopes.pop();
+                self.push("}", true, None);
+                self.prev = Prev::Operand;
+            }
+            Token::RBrace => {
+                self.flush();
+                while self.scopes.last() == Some(&Scope::Single) {
+                    self.scopes.pop();
+                }
+                let closed = self.scopes.pop();
+                self.push("}", false, None);
+                self.prev = Prev::Operand;
+                if closed == Some(Scope::DoBody) && next_is(Token::KwWhile) {
+                    return;
+                }
+                let before_else = next_is(Token::KwElse);
+                if !before_else && !next_is(Token::Semicolon) {
+                    self.flush();
+                }
+                self.end_statement(before_else, true);
+            }
+            Token::Semicolon if depth > 0 => {
+                self.push(";", false, Some((depth, 0)));
+                self.prev = Prev::Spaced;
+            }
+            Token::Semicolon => {
+                self.push(";", false, None);
+                self.flush();
+                self.end_statement(next_is(Token::KwElse), false);
+            }
+            Token::KwElse => {
+                self.push("else", true, None);
+                self.prev = Prev::Spaced;
+                if !next_is(Token::LBrace) && !next_is(Token::KwIf) {
+                    self.flush();
+                    self.scopes.push(Scope::Single);
+                }
+            }
+            // Labels sit level with their `switch`, the cases' statements
+            // one deeper.
+            Token::KwCase | Token::KwDefault => {
+                self.flush();
+                self.push(text, false, None);
+                self.line_level = self.level().saturating_sub(1);
+                self.in_label = true;
+                self.prev = Prev::Spaced;
+            }
+            Token::Colon if self.in_label => {
+                self.push(":", false, None);
+                self.flush();
+                self.in_label = false;
+            }
+            Token::KwDo => {
+                self.push("do", true, None);
+                self.prev = Prev::Spaced;
+                if !next_is(Token::LBrace) {
+                    self.flush();
+                    self.scopes.push(Scope::Single);
+                }
+            }
+            Token::LParen => {
+                let kind = match self.line.last().map(|p| p.text.as_str()) {
+                    Some("if") | Some("while") | Some("for") | Some("switch") => Paren::Condition,
+                    _ if self.prev != Prev::Callee
+                        && matches!(next, Some(Token::KwInt | Token::KwChar | Token::KwVoid)) =>
+                    {
+                        Paren::Cast
+                    }
+                    _ => Paren::Group,
+                };
+                let space = !matches!(self.prev, Prev::Open | Prev::Unary | Prev::CastClose | Prev::Callee);
+                self.push("(", space, None);
+                self.parens.push(kind);
+                self.prev = Prev::Open;
+            }
+            Token::RParen => {
+                let kind = self.parens.pop();
+                self.push(")", false, None);
+                self.prev = if kind == Some(Paren::Cast) { Prev::CastClose } else { Prev::Operand };
+                // A `;` right after the condition ends a `do ... while` or an
+                // empty loop on the same line.
+                if kind == Some(Paren::Condition) && !next_is(Token::LBrace) && !next_is(Token::Semicolon) {
+                    self.flush();
+                    self.scopes.push(Scope::Single);
+                }
+            }
+            Token::LBracket => {
+                self.push("[", false, None);
+                self.prev = Prev::Open;
+            }
+            Token::RBracket => {
+                self.push("]", false, None);
+                self.prev = Prev::Operand;
+            }
+            Token::Comma => {
+                self.push(",", false, Some((depth, 0)));
+                self.prev = Prev::Spaced;
+            }
+            Token::Inc | Token::Dec if matches!(self.prev, Prev::Operand | Prev::Callee) => {
+                self.push(text, false, None);
+                self.prev = Prev::Operand;
+            }
+            _ => {
+                if let Some(prec) = binary_precedence(tok).filter(|_| {
+                    matches!(self.prev, Prev::Operand | Prev::Callee) || !can_be_unary(tok)
+                }) {
+                    let break_after = if prec > 0 { Some((depth, prec)) } else { None };
+                    self.push(text, true, break_after);
+                    self.prev = Prev::Spaced;
+                    return;
+                }
+                let space = !matches!(self.prev, Prev::Open | Prev::Unary | Prev::CastClose);
+                self.push(text, space, None);
+                self.prev = match tok {
+                    Token::Ident(_) | Token::KwSizeof => Prev::Callee,
+                    Token::Num(_) | Token::Str(_) | Token::Char(_) => Prev::Operand,
+                    t if can_be_unary(t) => Prev::Unary,
+                    _ => Prev::Spaced,
+                };
+            }
+        }
+    }
+}
+
+fn can_be_unary(tok: &Token) -> bool {
+    matches!(
+        tok,
+        Token::Minus | Token::Plus | Token::Star | Token::And | Token::Not | Token::Tilde | Token::Inc | Token::Dec
+    )
+}
+
+/// Binding strength of a binary operator, weakest first; 0 for operators
+/// a line must not break after. None for everything else.
+fn binary_precedence(tok: &Token) -> Option<u8> {
+    Some(match tok {
+        Token::Assign
+        | Token::PlusAssign
+        | Token::MinusAssign
+        | Token::StarAssign
+        | Token::SlashAssign
+        | Token::PercentAssign
+        | Token::AndAssign
+        | Token::OrAssign
+        | Token::XorAssign
+        | Token::ShlAssign
+        | Token::ShrAssign => 0,
+        Token::Question | Token::Colon => 1,
+        Token::OrOr => 2,
+        Token::AndAnd => 3,
+        Token::Or => 4,
+        Token::Xor => 5,
+        Token::And => 6,
+        Token::EqEq | Token::Ne => 7,
+        Token::Lt | Token::Le | Token::Gt | Token::Ge => 8,
+        Token::Shl | Token::Shr => 9,
+        Token::Plus | Token::Minus => 10,
+        Token::Star | Token::Slash | Token::Percent => 11,
+        _ => return None,
+    })
+}
@@ -0,0 +1,200 @@
+// src/heap.rs
+
+//! The memory behind `malloc` and `free`, and the leak report built from it.
+//!
+//! Like the stack, the heap is an array of words, and `malloc(n)` reserves
+//! enough of them to hold `n` bytes, at least one so that every block has
+//! an address of its own. Usage is counted in those words. Heap words start right after the
+//! stack's, at word `VmConfig::stack_size`, so loads and stores reach both
+//! through one address space; the data segment holding globals starts where
+//! the heap's words end, at [`Heap::end`]. Blocks are carved from the end of the used space and
+//! never reused, and the heap holds at most `VmConfig::heap_size` words,
+//! the stack's size unless set; past that `malloc` returns 0, as C's does
+//! when memory runs out.
+//!
+//! With `VmConfig::track_allocations` set, every block also remembers the
+//! pc of the `malloc` that made it, and each new peak in usage is logged;
+//! [`Heap::leak_report`] turns that into a [`LeakReport`]. The pc stands in
+//! for a source line, since the report is made without the source.
+
+use alloc::collections::BTreeMap;
+use core::fmt::Write as _;
+
+use crate::bytecode::{Chunk, WORD_SIZE};
+use crate::prelude::*;
+use crate::vm::VmError;
+
+/// A live allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Block {
+    /// Words reserved.
+    size: usize,
+    /// pc of the `MALC` that made it, when tracking.
+    pc: Option<usize>,
+}
+
+/// A run's heap: its words and the blocks live in them.
+#[derive(Debug, Clone)]
+pub struct Heap {
+    words: Vec<i64>,
+    /// Number of the first heap word in the address space.
+    base: usize,
+    capacity: usize,
+    /// Live blocks by the number of their first word.
+    blocks: BTreeMap<usize, Block>,
+    used: usize,
+    peak: usize,
+    /// `(instruction count, words in use)` at each new peak, when tracking.
+    timeline: Option<Vec<(u64, usize)>>,
+}
+
+impl Heap {
+    /// An empty heap at words `base..base + capacity`.
+    pub fn new(base: usize, capacity: usize, track: bool) -> Self {
+        Heap {
+            words: Vec::new(),
+            base,
+            capacity,
+            blocks: BTreeMap::new(),
+            used: 0,
+            peak: 0,
+            timeline: track.then(Vec::new),
+        }
+    }
+
+    /// `malloc(size)` at `pc`, the `steps`th instruction: the new block's
+    /// byte address, or 0 if it doesn't fit.
+    pub fn malloc(&mut self, size: i64, pc: usize, steps: u64) -> i64 {
+        let Ok(size) = usize::try_from(size) else {
+            return 0;
+        };
+        let size = size.div_ceil(WORD_SIZE).max(1);
+        if size > self.capacity - self.words.len() {
+            return 0;
+        }
+        let addr = self.base + self.words.len();
+        self.words.resize(self.words.len() + size, 0);
+        let pc = self.timeline.is_some().then_some(pc);
+        self.blocks.insert(addr, Block { size, pc });
+        self.used += size;
+        if self.used > self.peak {
+            self.peak = self.used;
+            if let Some(timeline) = &mut self.timeline {
+                timeline.push((steps, self.used));
+            }
+        }
+        (addr * WORD_SIZE) as i64
+    }
+
+    /// `free(addr)` at `pc`. Freeing 0 does nothing; freeing anything but
+    /// the start of a live block is an invalid address.
+    pub fn free(&mut self, addr: i64, pc: usize) -> Result<(), VmError> {
+        if addr == 0 {
+            return Ok(());
+        }
+        let word = usize::try_from(addr).ok().filter(|a| a % WORD_SIZE == 0).map(|a| a / WORD_SIZE);
+        let block = word.and_then(|w| self.blocks.remove(&w));
+        let block = block.ok_or(VmError::InvalidAddress { pc, addr })?;
+        self.used -= block.size;
+        Ok(())
+    }
+
+    /// Heap word number `addr` of the address space, if it has been
+    /// allocated.
+    pub fn word(&mut self, addr: usize) -> Option<&mut i64> {
+        self.words.get_mut(addr.checked_sub(self.base)?)
+    }
+
+    /// The number of the first word past the heap.
+    pub fn end(&self) -> usize {
+        self.base + self.capacity
+    }
+
+    /// Most words in use at once.
+    pub fn peak_words(&self) -> usize {
+        self.peak
+    }
+
+    /// Each live block's address and size in words, in address order.
+    pub fn live_blocks(&self) -> impl Iterator<Item = (i64, usize)> + '_ {
+        self.blocks.iter().map(|(&word, block)| ((word * WORD_SIZE) as i64, block.size))
+    }
+
+    /// Free everything, keeping the tracking setting.
+    pub fn reset(&mut self) {
+        *self = Heap::new(self.base, self.capacity, self.timeline.is_some());
+    }
+
+    /// The blocks still live, grouped by the `malloc` that made them, with
+    /// names resolved against `chunk`. None unless tracking was on.
+    pub fn leak_report(&self, chunk: &Chunk) -> Option<LeakReport> {
+        let timeline = self.timeline.clone()?;
+        let mut sites: BTreeMap<usize, LeakSite> = BTreeMap::new();
+        for block in self.blocks.values() {
+            let pc = block.pc.expect("tracked blocks record their pc");
+            let site = sites.entry(pc).or_insert_with(|| LeakSite {
+                pc,
+                function: chunk.function_at(pc).map(|f| f.name.clone()),
+                blocks: 0,
+                words: 0,
+            });
+            site.blocks += 1;
+            site.words += block.size;
+        }
+        Some(LeakReport { sites: sites.into_values().collect(), peak_words: self.peak, timeline })
+    }
+}
+
+/// Unfreed blocks from one allocation site.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakSite {
+    /// pc of the `malloc`.
+    pub pc: usize,
+    /// The function containing it, if the chunk knows.
+    pub function: Option<String>,
+    pub blocks: usize,
+    pub words: usize,
+}
+
+/// What a tracked run left on the heap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakReport {
+    /// Sites with unfreed blocks, by pc.
+    pub sites: Vec<LeakSite>,
+    pub peak_words: usize,
+    /// `(instruction count, words in use)` each time usage reached a new peak.
+    pub timeline: Vec<(u64, usize)>,
+}
+
+impl LeakReport {
+    pub fn leaked_blocks(&self) -> usize {
+        self.sites.iter().map(|s| s.blocks).sum()
+    }
+
+    pub fn leaked_words(&self) -> usize {
+        self.sites.iter().map(|s| s.words).sum()
+    }
+
+    /// The leaks by site, then the peak and how usage climbed to it.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if self.sites.is_empty() {
+            out.push_str("no leaks\n");
+        } else {
+            let _ = writeln!(out, "{} words leaked in {} blocks:", self.leaked_words(), self.leaked_blocks());
+            for site in &self.sites {
+                let place = site.function.as_deref().unwrap_or("startup");
+                let _ = writeln!(
+                    out,
+                    "  {:>8} words in {:>4} blocks  allocated at {:04} in {}",
+                    site.words, site.blocks, site.pc, place
+                );
+            }
+        }
+        let _ = writeln!(out, "peak heap usage: {} words", self.peak_words);
+        for (steps, words) in &self.timeline {
+            let _ = writeln!(out, "  {:>8} words after {} instructions", words, steps);
+        }
+        out
+    }
+}
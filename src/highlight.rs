@@ -0,0 +1,212 @@
+// src/highlight.rs
+
+//! Semantic token classification for syntax highlighting.
+//!
+//! [`classify`] lexes the whole file with comments kept, then uses a parse
+//! of the program, when it succeeds, to tell functions, parameters, locals,
+//! globals and enum constants apart. If the program doesn't parse, every
+//! identifier is reported as a plain [`TokenClass::Identifier`].
+
+use alloc::collections::BTreeSet;
+
+use crate::ast::{FuncDef, Item, Program};
+use crate::lexer::{Lexer, Span, Token};
+use crate::parser::Parser;
+use crate::prelude::*;
+
+/// What a span of source is, for highlighting purposes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenClass {
+    Keyword,
+    Type,
+    Function,
+    Variable,
+    Parameter,
+    EnumConstant,
+    /// An identifier that couldn't be resolved because parsing failed.
+    Identifier,
+    Number,
+    String,
+    Char,
+    Comment,
+    Operator,
+    /// Text the lexer rejected.
+    Unknown,
+}
+
+/// Classify every token and comment in `src`. Spans are in source order and
+/// never overlap; together they cover everything except whitespace.
+pub fn classify(src: &str) -> Vec<(Span, TokenClass)> {
+    let program = Parser::new(src).and_then(|mut p| p.parse_program()).ok();
+    let names = program.as_ref().map(Names::new);
+
+    let mut tokens = Vec::new();
+    let mut lex = Lexer::new(src).with_trivia();
+    loop {
+        match lex.next_token() {
+            Ok(Token::Eof) => break,
+            Ok(tok) => tokens.push((lex.token_span(), Some(tok))),
+            Err(_) => {
+                let span = lex.token_span();
+                tokens.push((span, None));
+                if span.end >= src.len() {
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut context = Context::default();
+    let mut out = Vec::with_capacity(tokens.len());
+    for (i, (span, tok)) in tokens.iter().enumerate() {
+        let class = match tok {
+            None => TokenClass::Unknown,
+            Some(Token::Comment(_)) => TokenClass::Comment,
+            Some(Token::Ident(name)) => {
+                let next = tokens[i + 1..].iter().find_map(|(_, t)| match t {
+                    Some(Token::Comment(_)) => None,
+                    t => Some(t.as_ref()),
+                });
+                match &names {
+                    Some(names) => context.ident(names, name, next.flatten() == Some(&Token::LParen)),
+                    None => TokenClass::Identifier,
+                }
+            }
+            Some(tok) => {
+                context.punct(tok);
+                lexical_class(tok, &src[span.start..span.end])
+            }
+        };
+        out.push((*span, class));
+    }
+    out
+}
+
+fn lexical_class(tok: &Token, text: &str) -> TokenClass {
+    match tok {
+        Token::Num(_) if text.starts_with('\'') => TokenClass::Char,
+        Token::Num(_) => TokenClass::Number,
+        Token::Char(_) => TokenClass::Char,
+        Token::Str(_) => TokenClass::String,
+        Token::KwVoid | Token::KwInt | Token::KwChar => TokenClass::Type,
+        Token::KwEnum | Token::KwIf | Token::KwElse | Token::KwWhile | Token::KwDo | Token::KwFor | Token::KwBreak | Token::KwContinue | Token::KwSwitch | Token::KwCase | Token::KwDefault | Token::KwReturn | Token::KwSizeof => {
+            TokenClass::Keyword
+        }
+        Token::Ident(_) => TokenClass::Identifier,
+        Token::Comment(_) => TokenClass::Comment,
+        _ => TokenClass::Operator,
+    }
+}
+
+/// Top-level names from a successful parse.
+struct Names<'p> {
+    functions: Vec<&'p FuncDef>,
+    enum_constants: BTreeSet<&'p str>,
+}
+
+impl<'p> Names<'p> {
+    fn new(program: &'p Program) -> Self {
+        let mut names = Names { functions: Vec::new(), enum_constants: BTreeSet::new() };
+        for item in &program.items {
+            match item {
+                Item::Function(f) => names.functions.push(f),
+                Item::Enum(e) => names.enum_constants.extend(e.variants.iter().map(|(n, _)| n.as_str())),
+                Item::Global(_) => {}
+            }
+        }
+        names
+    }
+
+    fn function(&self, name: &str) -> Option<&'p FuncDef> {
+        self.functions.iter().copied().find(|f| f.name == name)
+    }
+}
+
+/// Where in the file the token walk is.
+#[derive(Default)]
+struct Context {
+    depth: usize,
+    /// Name of the function whose parameters or body we're in.
+    function: Option<String>,
+    in_params: bool,
+    in_enum: bool,
+    /// Inside a declaration in a function body (`int a, b;`).
+    in_local_decl: bool,
+    /// Locals declared in the current body, including ones the parser
+    /// doesn't record in `FuncDef::locals`.
+    locals: BTreeSet<String>,
+}
+
+impl Context {
+    fn punct(&mut self, tok: &Token) {
+        match tok {
+            Token::KwEnum if self.depth == 0 => self.in_enum = true,
+            Token::KwInt | Token::KwChar | Token::KwVoid if self.depth > 0 && !self.in_enum => {
+                self.in_local_decl = true
+            }
+            Token::RParen => {
+                self.in_local_decl = false;
+                if self.depth == 0 {
+                    self.in_params = false;
+                }
+            }
+            Token::LBrace => self.depth += 1,
+            Token::RBrace => {
+                self.depth = self.depth.saturating_sub(1);
+                if self.depth == 0 {
+                    self.function = None;
+                    self.locals.clear();
+                }
+            }
+            Token::Semicolon => {
+                self.in_local_decl = false;
+                if self.depth == 0 {
+                    self.in_enum = false;
+                }
+            }
+            // A type inside parentheses is a cast or `sizeof`, not a declaration.
+            Token::Assign | Token::LParen => self.in_local_decl = false,
+            _ => {}
+        }
+    }
+
+    fn ident(&mut self, names: &Names<'_>, name: &str, called: bool) -> TokenClass {
+        if self.in_enum {
+            return TokenClass::EnumConstant;
+        }
+        if self.depth == 0 {
+            if self.in_params {
+                return TokenClass::Parameter;
+            }
+            if called {
+                self.function = Some(name.to_string());
+                self.in_params = true;
+                return TokenClass::Function;
+            }
+            return TokenClass::Variable;
+        }
+
+        if self.in_local_decl {
+            self.locals.insert(name.to_string());
+            return TokenClass::Variable;
+        }
+        let current = self.function.as_deref().and_then(|f| names.function(f));
+        if let Some(f) = current {
+            if f.params.iter().any(|(p, _)| p == name) {
+                return TokenClass::Parameter;
+            }
+            if f.locals.iter().any(|(l, _)| l == name) {
+                return TokenClass::Variable;
+            }
+        }
+        if self.locals.contains(name) {
+            TokenClass::Variable
+        } else if called || names.function(name).is_some() {
+            TokenClass::Function
+        } else if names.enum_constants.contains(name) {
+            TokenClass::EnumConstant
+        } else {
+            TokenClass::Variable
+        }
+    }
+}
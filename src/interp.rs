@@ -0,0 +1,477 @@
+// src/interp.rs
+
+//! Tree-walking interpreter used as a reference oracle for the compiler and
+//! VM. It evaluates the AST directly and favours being obviously correct
+//! over being fast, so a disagreement with the bytecode pipeline points at
+//! codegen or the VM rather than at this module.
+//!
+//! Memory is a flat array of cells; a pointer is a cell index, so pointer
+//! arithmetic steps one cell whatever the pointee type, and `malloc(n)`
+//! hands out `n` cells for `n` bytes, more than enough. Globals come first,
+//! then string literals, call frames and `malloc` blocks as they are needed.
+
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+use crate::ast::*;
+use crate::prelude::*;
+use crate::printf;
+use crate::vm::Rng;
+
+/// The largest block `malloc` hands out, in cells; bigger requests get 0.
+const MALLOC_LIMIT: usize = 1 << 20;
+
+/// Result of interpreting a whole program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterpOutcome {
+    /// `main`'s return value, or the argument to `exit`.
+    pub exit_code: i64,
+    /// Everything the program printed.
+    pub output: Vec<u8>,
+}
+
+/// Why interpretation stopped early.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterpError {
+    UnknownFunction(String),
+    UnknownVariable(String),
+    /// Assignment, `&` or `++`/`--` applied to something without an address.
+    NotAnLvalue,
+    DivisionByZero,
+    InvalidAddress(i64),
+    /// Wrong number of arguments to a user function.
+    Arity { name: String, expected: usize, got: usize },
+    /// `break` with no loop or `switch` around it.
+    StrayBreak,
+    /// `continue` with no loop around it.
+    StrayContinue,
+    /// A `printf` conversion asked for a width or precision over 4096.
+    FormatTooWide,
+}
+
+impl fmt::Display for InterpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterpError::UnknownFunction(n) => write!(f, "unknown function: {}", n),
+            InterpError::UnknownVariable(n) => write!(f, "unknown variable: {}", n),
+            InterpError::NotAnLvalue => write!(f, "expression is not assignable"),
+            InterpError::DivisionByZero => write!(f, "division by zero"),
+            InterpError::InvalidAddress(a) => write!(f, "invalid address {}", a),
+            InterpError::Arity { name, expected, got } => {
+                write!(f, "{} expects {} arguments, got {}", name, expected, got)
+            }
+            InterpError::StrayBreak => write!(f, "break outside a loop or switch"),
+            InterpError::StrayContinue => write!(f, "continue outside a loop"),
+            InterpError::FormatTooWide => write!(f, "printf width or precision too large"),
+        }
+    }
+}
+
+impl core::error::Error for InterpError {}
+
+/// Run `program` from `main`, passing `args` as its parameters.
+pub fn interpret(program: &Program, args: &[i64]) -> Result<InterpOutcome, InterpError> {
+    let mut interp = Interp::new(program);
+    let exit_code = match interp.call("main", args.to_vec()) {
+        Ok(v) => v,
+        Err(Flow::Exit(code)) => code,
+        Err(Flow::Error(e)) => return Err(e),
+        Err(Flow::Return(_) | Flow::Break | Flow::Continue) => unreachable!("control flow escaped its function"),
+    };
+    Ok(InterpOutcome { exit_code, output: interp.output })
+}
+
+/// Non-local control flow, threaded through `Err` so `?` unwinds it.
+enum Flow {
+    Break,
+    Continue,
+    Return(i64),
+    Exit(i64),
+    Error(InterpError),
+}
+
+impl From<InterpError> for Flow {
+    fn from(e: InterpError) -> Self {
+        Flow::Error(e)
+    }
+}
+
+type Eval<T> = Result<T, Flow>;
+
+struct Interp<'a> {
+    functions: BTreeMap<&'a str, &'a FuncDef>,
+    globals: BTreeMap<&'a str, usize>,
+    constants: BTreeMap<&'a str, i64>,
+    /// Local variables of the active call, name to cell.
+    frames: Vec<BTreeMap<&'a str, usize>>,
+    strings: BTreeMap<&'a [u8], usize>,
+    memory: Vec<i64>,
+    output: Vec<u8>,
+    rng: Rng,
+}
+
+impl<'a> Interp<'a> {
+    fn new(program: &'a Program) -> Self {
+        let mut interp = Interp {
+            functions: BTreeMap::new(),
+            globals: BTreeMap::new(),
+            constants: BTreeMap::new(),
+            frames: Vec::new(),
+            strings: BTreeMap::new(),
+            memory: Vec::new(),
+            output: Vec::new(),
+            rng: Rng::default(),
+        };
+        for item in &program.items {
+            match item {
+                Item::Function(f) => {
+                    interp.functions.insert(&f.name, f);
+                }
+                Item::Global(g) => {
+                    let cell = interp.alloc(1);
+                    interp.globals.insert(&g.name, cell);
+                }
+                Item::Enum(e) => {
+                    let mut next = 0;
+                    for (name, init) in &e.variants {
+                        let value = init.unwrap_or(next);
+                        interp.constants.insert(name, value);
+                        next = value + 1;
+                    }
+                }
+            }
+        }
+        interp
+    }
+
+    /// Reserve `n` zeroed cells and return the first one's address.
+    fn alloc(&mut self, n: usize) -> usize {
+        let start = self.memory.len();
+        self.memory.resize(start + n, 0);
+        start
+    }
+
+    fn cell(&mut self, addr: i64) -> Result<&mut i64, InterpError> {
+        usize::try_from(addr)
+            .ok()
+            .and_then(|i| self.memory.get_mut(i))
+            .ok_or(InterpError::InvalidAddress(addr))
+    }
+
+    fn call(&mut self, name: &str, args: Vec<i64>) -> Eval<i64> {
+        let Some(&func) = self.functions.get(name) else {
+            return self.builtin(name, &args);
+        };
+        if func.params.len() != args.len() {
+            return Err(InterpError::Arity {
+                name: name.to_string(),
+                expected: func.params.len(),
+                got: args.len(),
+            }
+            .into());
+        }
+
+        // Frames are never freed: simplicity over memory use.
+        let mut frame = BTreeMap::new();
+        for ((pname, _), value) in func.params.iter().zip(args) {
+            let cell = self.alloc(1);
+            self.memory[cell] = value;
+            frame.insert(pname.as_str(), cell);
+        }
+        for (lname, _) in &func.locals {
+            let cell = self.alloc(1);
+            frame.insert(lname.as_str(), cell);
+        }
+        self.frames.push(frame);
+        let result = self.block(&func.body);
+        self.frames.pop();
+
+        match result {
+            Ok(()) => Ok(0),
+            Err(Flow::Return(v)) => Ok(v),
+            Err(Flow::Break) => Err(InterpError::StrayBreak.into()),
+            Err(Flow::Continue) => Err(InterpError::StrayContinue.into()),
+            Err(other) => Err(other),
+        }
+    }
+
+    fn builtin(&mut self, name: &str, args: &[i64]) -> Eval<i64> {
+        match name {
+            "printf" => {
+                let (&fmt, rest) = args.split_first().unwrap_or((&0, &[]));
+                let text = self.format(fmt, rest)?;
+                self.output.extend_from_slice(&text);
+                Ok(text.len() as i64)
+            }
+            "exit" => Err(Flow::Exit(args.first().copied().unwrap_or(0))),
+            "malloc" => {
+                // Never reused, like the frames; `free` does nothing.
+                let n = usize::try_from(args.first().copied().unwrap_or(0)).unwrap_or(usize::MAX);
+                Ok(if n <= MALLOC_LIMIT { self.alloc(n) as i64 } else { 0 })
+            }
+            "free" => Ok(0),
+            "rand" => Ok(self.rng.rand()),
+            "srand" => {
+                // Like the VM's SRND, which leaves the seed in `a`.
+                let seed = args.first().copied().unwrap_or(0);
+                self.rng = Rng::new(seed);
+                Ok(seed)
+            }
+            _ => Err(InterpError::UnknownFunction(name.to_string()).into()),
+        }
+    }
+
+    /// Read the NUL-terminated string at `addr`.
+    fn c_string(&mut self, mut addr: i64) -> Result<Vec<u8>, InterpError> {
+        let mut bytes = Vec::new();
+        loop {
+            let c = *self.cell(addr)?;
+            if c == 0 {
+                return Ok(bytes);
+            }
+            bytes.push(c as u8);
+            addr += 1;
+        }
+    }
+
+    /// printf-style formatting, as the VM does it.
+    fn format(&mut self, fmt: i64, args: &[i64]) -> Result<Vec<u8>, InterpError> {
+        let fmt = self.c_string(fmt)?;
+        printf::format(&fmt, args, |addr| self.c_string(addr), || InterpError::FormatTooWide)
+    }
+
+    fn block(&mut self, block: &'a Block) -> Eval<()> {
+        if block.locals.is_empty() {
+            return block.stmts.iter().try_for_each(|stmt| self.stmt(stmt));
+        }
+        // The block's locals shadow the enclosing ones until it ends.
+        let mut frame = self.frames.last().cloned().unwrap_or_default();
+        for (lname, _) in &block.locals {
+            let cell = self.alloc(1);
+            frame.insert(lname.as_str(), cell);
+        }
+        self.frames.push(frame);
+        let result = block.stmts.iter().try_for_each(|stmt| self.stmt(stmt));
+        self.frames.pop();
+        result
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt) -> Eval<()> {
+        match &stmt.kind {
+            StmtKind::If { cond, then_branch, else_branch } => {
+                if self.expr(cond)? != 0 {
+                    self.stmt(then_branch)?;
+                } else if let Some(e) = else_branch {
+                    self.stmt(e)?;
+                }
+            }
+            StmtKind::While { cond, body } => {
+                while self.expr(cond)? != 0 {
+                    if !self.iteration(body)? {
+                        break;
+                    }
+                }
+            }
+            StmtKind::DoWhile { body, cond } => {
+                while self.iteration(body)? {
+                    if self.expr(cond)? == 0 {
+                        break;
+                    }
+                }
+            }
+            StmtKind::For { init, cond, step, body } => {
+                if let Some(init) = init {
+                    self.expr(init)?;
+                }
+                loop {
+                    if let Some(cond) = cond {
+                        if self.expr(cond)? == 0 {
+                            break;
+                        }
+                    }
+                    if !self.iteration(body)? {
+                        break;
+                    }
+                    if let Some(step) = step {
+                        self.expr(step)?;
+                    }
+                }
+            }
+            StmtKind::Return(e) => {
+                let value = match e {
+                    Some(e) => self.expr(e)?,
+                    None => 0,
+                };
+                return Err(Flow::Return(value));
+            }
+            StmtKind::Expr(e) => {
+                self.expr(e)?;
+            }
+            StmtKind::Switch { scrutinee, cases } => {
+                let value = self.expr(scrutinee)?;
+                let start = cases
+                    .iter()
+                    .position(|(label, _)| *label == Some(value))
+                    .or_else(|| cases.iter().position(|(label, _)| label.is_none()));
+                // From the matching case on, falling through, until a `break`.
+                let stmts = start.map_or(&cases[..0], |i| &cases[i..]).iter().flat_map(|(_, s)| s);
+                for s in stmts {
+                    match self.stmt(s) {
+                        Ok(()) => {}
+                        Err(Flow::Break) => break,
+                        Err(other) => return Err(other),
+                    }
+                }
+            }
+            StmtKind::Break => return Err(Flow::Break),
+            StmtKind::Continue => return Err(Flow::Continue),
+            StmtKind::Block(b) => self.block(b)?,
+            StmtKind::Empty => {}
+        }
+        Ok(())
+    }
+
+    /// One pass through a loop body: false if it broke out of the loop.
+    fn iteration(&mut self, body: &'a Stmt) -> Eval<bool> {
+        match self.stmt(body) {
+            Ok(()) | Err(Flow::Continue) => Ok(true),
+            Err(Flow::Break) => Ok(false),
+            Err(other) => Err(other),
+        }
+    }
+
+    /// Address of an assignable expression.
+    fn lvalue(&mut self, expr: &'a Expr) -> Eval<i64> {
+        match &expr.kind {
+            ExprKind::Var(name) => {
+                let local = self.frames.last().and_then(|f| f.get(name.as_str()));
+                match local.or_else(|| self.globals.get(name.as_str())) {
+                    Some(&cell) => Ok(cell as i64),
+                    None => Err(InterpError::UnknownVariable(name.clone()).into()),
+                }
+            }
+            ExprKind::Unary { op: UnOp::Deref, expr } => self.expr(expr),
+            ExprKind::Index { array, index } => Ok(self.expr(array)?.wrapping_add(self.expr(index)?)),
+            _ => Err(InterpError::NotAnLvalue.into()),
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expr) -> Eval<i64> {
+        Ok(match &expr.kind {
+            ExprKind::Num(n) => *n,
+            ExprKind::Str(s) => self.string_literal(s) as i64,
+            ExprKind::Var(name) => match self.constants.get(name.as_str()) {
+                Some(&c) => c,
+                None => {
+                    let addr = self.lvalue(expr)?;
+                    *self.cell(addr)?
+                }
+            },
+            ExprKind::Unary { op, expr: inner } => match op {
+                UnOp::Plus => self.expr(inner)?,
+                UnOp::Neg => self.expr(inner)?.wrapping_neg(),
+                UnOp::Not => (self.expr(inner)? == 0) as i64,
+                UnOp::BitNot => !self.expr(inner)?,
+                UnOp::Addr => self.lvalue(inner)?,
+                UnOp::Deref => {
+                    let addr = self.expr(inner)?;
+                    *self.cell(addr)?
+                }
+                UnOp::PreInc | UnOp::PreDec | UnOp::PostInc | UnOp::PostDec => {
+                    let addr = self.lvalue(inner)?;
+                    let cell = self.cell(addr)?;
+                    let old = *cell;
+                    let delta = if matches!(op, UnOp::PreInc | UnOp::PostInc) { 1 } else { -1 };
+                    *cell = old.wrapping_add(delta);
+                    if matches!(op, UnOp::PreInc | UnOp::PreDec) { *cell } else { old }
+                }
+            },
+            ExprKind::Binary { op: BinOp::Assign, left, right } => {
+                let addr = self.lvalue(left)?;
+                let value = self.expr(right)?;
+                *self.cell(addr)? = value;
+                value
+            }
+            ExprKind::CompoundAssign { op, target, value } => {
+                let addr = self.lvalue(target)?;
+                let value = self.expr(value)?;
+                let cell = self.cell(addr)?;
+                *cell = binary(op, *cell, value)?;
+                *cell
+            }
+            ExprKind::Binary { op: BinOp::LogAnd, left, right } => {
+                (self.expr(left)? != 0 && self.expr(right)? != 0) as i64
+            }
+            ExprKind::Binary { op: BinOp::LogOr, left, right } => {
+                (self.expr(left)? != 0 || self.expr(right)? != 0) as i64
+            }
+            ExprKind::Binary { op, left, right } => {
+                let l = self.expr(left)?;
+                let r = self.expr(right)?;
+                binary(op, l, r)?
+            }
+            ExprKind::Call { callee, args } => {
+                let ExprKind::Var(name) = &callee.kind else {
+                    return Err(InterpError::NotAnLvalue.into());
+                };
+                let mut values = Vec::with_capacity(args.len());
+                for a in args {
+                    values.push(self.expr(a)?);
+                }
+                self.call(name, values)?
+            }
+            ExprKind::Cast { ty: Type::Char, expr } => self.expr(expr)? & 0xFF,
+            ExprKind::Cast { expr, .. } => self.expr(expr)?,
+            ExprKind::SizeOf(ty) => ty.size_of() as i64,
+            ExprKind::Conditional { cond, then_expr, else_expr } => {
+                if self.expr(cond)? != 0 {
+                    self.expr(then_expr)?
+                } else {
+                    self.expr(else_expr)?
+                }
+            }
+            ExprKind::Index { .. } => {
+                let addr = self.lvalue(expr)?;
+                *self.cell(addr)?
+            }
+        })
+    }
+
+    /// Address of a NUL-terminated copy of `s`, one char per cell, shared
+    /// between identical literals.
+    fn string_literal(&mut self, s: &'a [u8]) -> usize {
+        if let Some(&addr) = self.strings.get(s) {
+            return addr;
+        }
+        let addr = self.alloc(s.len() + 1);
+        for (i, &b) in s.iter().enumerate() {
+            self.memory[addr + i] = b as i64;
+        }
+        self.strings.insert(s, addr);
+        addr
+    }
+}
+
+/// Non-assigning, non-short-circuit binary operators.
+fn binary(op: &BinOp, l: i64, r: i64) -> Result<i64, InterpError> {
+    Ok(match op {
+        BinOp::Add => l.wrapping_add(r),
+        BinOp::Sub => l.wrapping_sub(r),
+        BinOp::Mul => l.wrapping_mul(r),
+        BinOp::Div | BinOp::Mod if r == 0 => return Err(InterpError::DivisionByZero),
+        BinOp::Div => l.wrapping_div(r),
+        BinOp::Mod => l.wrapping_rem(r),
+        BinOp::Eq => (l == r) as i64,
+        BinOp::Ne => (l != r) as i64,
+        BinOp::Lt => (l < r) as i64,
+        BinOp::Le => (l <= r) as i64,
+        BinOp::Gt => (l > r) as i64,
+        BinOp::Ge => (l >= r) as i64,
+        BinOp::BitAnd => l & r,
+        BinOp::BitOr => l | r,
+        BinOp::Xor => l ^ r,
+        BinOp::Shl => l.wrapping_shl(r as u32),
+        BinOp::Shr => l.wrapping_shr(r as u32),
+        BinOp::Assign | BinOp::LogAnd | BinOp::LogOr => unreachable!("handled by expr"),
+    })
+}
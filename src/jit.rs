@@ -0,0 +1,667 @@
+// src/jit.rs
+
+//! Native code generation with Cranelift, enabled by the `jit` feature.
+//!
+//! [`JitProgram::compile`] translates a whole [`Chunk`] into one native
+//! function: each basic block of bytecode (see [`crate::cfg`]) becomes a
+//! Cranelift block, the registers `a`, `sp` and `fp` live in machine
+//! registers, and the operand stack is the same bounds-checked array of
+//! words the VM uses. Calls and returns keep the VM's frame model; `LEV`
+//! jumps back through a table of every return site. Frame pushes and pops
+//! call back into Rust.
+//!
+//! Results, resource counters and errors (down to the faulting pc) are the
+//! same as [`VM::run`](crate::vm::VM::run); `tests/backend_tests.rs` checks
+//! this against the VM. Compiling takes milliseconds, so the JIT pays off
+//! for a chunk that is run many times. Like the closure backend it has no
+//! debug trace, tracing events, breakpoints or snapshot/resume, and since
+//! no instruction writes output yet there are no builtins to call back into.
+//! There is no heap either, so chunks calling `malloc` or `free` fail to
+//! compile, as does any chunk on a host Cranelift doesn't support; use
+//! [`run_chunk_on`](crate::driver::run_chunk_on), which falls back to the VM.
+
+use core::mem::offset_of;
+
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, Block, FuncRef, InstBuilder, JumpTableData, MemFlags, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{Linkage, Module};
+
+use crate::bytecode::{Chunk, Instruction, OpCode};
+use crate::cfg::{BasicBlock, Cfg};
+use crate::error::CompileError;
+use crate::vm::{RunOutcome, SharedClock, VmConfig, VmError, RNG_INCREMENT, RNG_MULTIPLIER};
+use std::time::{Duration, Instant};
+
+/// Registers and counters shared between the generated code and Rust.
+#[repr(C)]
+struct JitState {
+    stack: *mut i64,
+    stack_len: i64,
+    a: i64,
+    steps: i64,
+    max_sp: i64,
+    /// (return_pc, sp, fp) of the frame `pop_frame` just removed.
+    frame: [i64; 3],
+    fault_kind: i64,
+    fault_pc: i64,
+    fault_addr: i64,
+    /// State of `rand()`, as in `vm::Rng`.
+    rng: i64,
+    clock: SharedClock,
+    clock_start: Duration,
+    frames: Vec<(i64, i64, i64)>,
+    max_frames: usize,
+}
+
+// Fault kinds the generated code reports.
+const STACK_OVERFLOW: i64 = 0;
+const STACK_UNDERFLOW: i64 = 1;
+const DIVISION_BY_ZERO: i64 = 2;
+const CALL_STACK_UNDERFLOW: i64 = 3;
+const INVALID_ADDRESS: i64 = 4;
+const UNKNOWN_INSTRUCTION: i64 = 5;
+const ASSERTION_FAILED: i64 = 6;
+
+// Save the caller's registers. Returns 0 when the call stack is as deep as
+// the operand stack is long, which the VM reports as a stack overflow.
+extern "C" fn push_frame(state: *mut JitState, ret_pc: i64, sp: i64, fp: i64) -> i64 {
+    // SAFETY: the generated code passes the state `run` handed it.
+    let state = unsafe { &mut *state };
+    if state.frames.len() as i64 >= state.stack_len {
+        return 0;
+    }
+    state.frames.push((ret_pc, sp, fp));
+    state.max_frames = state.max_frames.max(state.frames.len());
+    1
+}
+
+// Move the innermost frame into `state.frame`. Returns 0 if there is none.
+extern "C" fn pop_frame(state: *mut JitState) -> i64 {
+    // SAFETY: as for `push_frame`.
+    let state = unsafe { &mut *state };
+    match state.frames.pop() {
+        Some((ret_pc, sp, fp)) => {
+            state.frame = [ret_pc, sp, fp];
+            1
+        }
+        None => 0,
+    }
+}
+
+// `clock()`: milliseconds since the run started.
+extern "C" fn clock_ms(state: *mut JitState) -> i64 {
+    // SAFETY: as for `push_frame`.
+    let state = unsafe { &*state };
+    state.clock.now().saturating_sub(state.clock_start).as_millis() as i64
+}
+
+// `time()`: seconds since the Unix epoch.
+extern "C" fn unix_time(state: *mut JitState) -> i64 {
+    // SAFETY: as for `push_frame`.
+    let state = unsafe { &*state };
+    state.clock.unix_time()
+}
+
+type Entry = extern "C" fn(*mut JitState) -> i64;
+
+/// A chunk compiled to native code, reusable across runs.
+pub struct JitProgram {
+    module: Option<JITModule>,
+    entry: Entry,
+    /// The original instructions, to describe an unknown one that faults.
+    code: Vec<Instruction>,
+}
+
+impl JitProgram {
+    /// Compile `chunk` for the host. Fails if Cranelift can't target the
+    /// host or the chunk uses the heap, globals or `printf`; instructions the VM
+    /// would reject fail the same way when they are reached.
+    pub fn compile(chunk: &Chunk) -> Result<Self, CompileError> {
+        let jit_err = |e: &dyn core::fmt::Display| CompileError::new(format!("jit: {}", e));
+        if chunk.code.iter().any(|i| matches!(i, Instruction::Instr(OpCode::MALC | OpCode::FREE | OpCode::MSET | OpCode::MCMP))) {
+            return Err(jit_err(&"malloc, free, memset and memcmp are not supported"));
+        }
+        if chunk.code.iter().any(|i| matches!(i, Instruction::Instr(OpCode::OPEN | OpCode::READ | OpCode::CLOS))) {
+            return Err(jit_err(&"files are not supported"));
+        }
+        if chunk.code.iter().any(|i| matches!(i, Instruction::InstrInt(OpCode::PRTF, _))) {
+            return Err(jit_err(&"printf is not supported"));
+        }
+        if !chunk.data.is_empty() {
+            return Err(jit_err(&"globals are not supported"));
+        }
+
+        let mut flags = settings::builder();
+        flags.set("opt_level", "speed").map_err(|e| jit_err(&e))?;
+        flags.set("is_pic", "false").map_err(|e| jit_err(&e))?;
+        let isa = cranelift_native::builder()
+            .map_err(|e| jit_err(&e))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| jit_err(&e))?;
+        let mut builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+        builder.symbol("c4_push_frame", push_frame as *const u8);
+        builder.symbol("c4_pop_frame", pop_frame as *const u8);
+        builder.symbol("c4_clock", clock_ms as *const u8);
+        builder.symbol("c4_time", unix_time as *const u8);
+        let mut module = JITModule::new(builder);
+
+        let ptr = module.target_config().pointer_type();
+        let mut push_sig = module.make_signature();
+        push_sig.params.extend([AbiParam::new(ptr), AbiParam::new(types::I64), AbiParam::new(types::I64), AbiParam::new(types::I64)]);
+        push_sig.returns.push(AbiParam::new(types::I64));
+        let mut pop_sig = module.make_signature();
+        pop_sig.params.push(AbiParam::new(ptr));
+        pop_sig.returns.push(AbiParam::new(types::I64));
+        let push_id = module.declare_function("c4_push_frame", Linkage::Import, &push_sig).map_err(|e| jit_err(&e))?;
+        let pop_id = module.declare_function("c4_pop_frame", Linkage::Import, &pop_sig).map_err(|e| jit_err(&e))?;
+        // Same shape as `pop_frame`: the state in, a word out.
+        let clock_id = module.declare_function("c4_clock", Linkage::Import, &pop_sig).map_err(|e| jit_err(&e))?;
+        let time_id = module.declare_function("c4_time", Linkage::Import, &pop_sig).map_err(|e| jit_err(&e))?;
+
+        let mut ctx = module.make_context();
+        ctx.func.signature.params.push(AbiParam::new(ptr));
+        ctx.func.signature.returns.push(AbiParam::new(types::I64));
+        let entry_id = module.declare_function("c4_run", Linkage::Export, &ctx.func.signature).map_err(|e| jit_err(&e))?;
+
+        let mut fn_ctx = FunctionBuilderContext::new();
+        {
+            let mut b = FunctionBuilder::new(&mut ctx.func, &mut fn_ctx);
+            let push_frame = module.declare_func_in_func(push_id, b.func);
+            let pop_frame = module.declare_func_in_func(pop_id, b.func);
+            let clock = module.declare_func_in_func(clock_id, b.func);
+            let time = module.declare_func_in_func(time_id, b.func);
+            Translator::new(&mut b, [push_frame, pop_frame, clock, time]).translate(chunk);
+            b.seal_all_blocks();
+            b.finalize();
+        }
+        module.define_function(entry_id, &mut ctx).map_err(|e| jit_err(&e))?;
+        module.clear_context(&mut ctx);
+        module.finalize_definitions().map_err(|e| jit_err(&e))?;
+
+        let code = module.get_finalized_function(entry_id);
+        // SAFETY: `c4_run` was declared with exactly this signature.
+        let entry = unsafe { core::mem::transmute::<*const u8, Entry>(code) };
+        Ok(JitProgram { module: Some(module), entry, code: chunk.code.clone() })
+    }
+
+    /// Run the program on a fresh stack sized by `config`.
+    pub fn run(&self, config: &VmConfig) -> Result<RunOutcome, VmError> {
+        let mut stack = vec![0i64; config.stack_size];
+        let clock = config.clock();
+        let mut state = JitState {
+            stack: stack.as_mut_ptr(),
+            stack_len: stack.len() as i64,
+            a: 0,
+            steps: 0,
+            max_sp: 0,
+            frame: [0; 3],
+            fault_kind: 0,
+            fault_pc: 0,
+            fault_addr: 0,
+            rng: 1,
+            clock_start: clock.now(),
+            clock,
+            frames: Vec::new(),
+            max_frames: 0,
+        };
+        let start = Instant::now();
+        let status = (self.entry)(&mut state);
+        let wall_time = start.elapsed();
+        if status != 0 {
+            return Err(self.fault(&state));
+        }
+        Ok(RunOutcome {
+            exit_code: state.a,
+            stdout: Vec::new(),
+            instructions: state.steps as u64,
+            max_stack_words: state.max_sp as usize,
+            max_call_depth: state.max_frames,
+            heap_peak_words: 0,
+            wall_time,
+        })
+    }
+
+    fn fault(&self, state: &JitState) -> VmError {
+        let pc = state.fault_pc as usize;
+        match state.fault_kind {
+            STACK_OVERFLOW => VmError::StackOverflow { pc },
+            STACK_UNDERFLOW => VmError::StackUnderflow { pc },
+            DIVISION_BY_ZERO => VmError::DivisionByZero { pc },
+            CALL_STACK_UNDERFLOW => VmError::CallStackUnderflow { pc },
+            INVALID_ADDRESS => VmError::InvalidAddress { pc, addr: state.fault_addr },
+            ASSERTION_FAILED => VmError::AssertionFailed { pc },
+            _ => VmError::UnknownInstruction { pc, instr: self.code[pc].clone() },
+        }
+    }
+}
+
+impl Drop for JitProgram {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            // SAFETY: `entry` points into this module and goes away with `self`.
+            unsafe { module.free_memory() };
+        }
+    }
+}
+
+/// Emits the IR for one chunk.
+struct Translator<'a, 'f> {
+    b: &'a mut FunctionBuilder<'f>,
+    push_frame: FuncRef,
+    pop_frame: FuncRef,
+    clock: FuncRef,
+    time: FuncRef,
+    a: Variable,
+    sp: Variable,
+    fp: Variable,
+    steps: Variable,
+    max_sp: Variable,
+    state: Value,
+    stack: Value,
+    stack_len: Value,
+    /// Cranelift block for each pc that starts a bytecode block.
+    blocks: Vec<Option<Block>>,
+    /// Stores the registers and returns: running off the end or `EXIT`.
+    done: Block,
+}
+
+impl<'a, 'f> Translator<'a, 'f> {
+    /// `helpers` are the imported `push_frame`, `pop_frame`, `clock_ms` and
+    /// `unix_time`.
+    fn new(b: &'a mut FunctionBuilder<'f>, helpers: [FuncRef; 4]) -> Self {
+        let [push_frame, pop_frame, clock, time] = helpers;
+        let [a, sp, fp, steps, max_sp] = [0, 1, 2, 3, 4].map(|i| {
+            let var = Variable::from_u32(i);
+            b.declare_var(var, types::I64);
+            var
+        });
+        let entry = b.create_block();
+        b.append_block_params_for_function_params(entry);
+        b.switch_to_block(entry);
+        let state = b.block_params(entry)[0];
+        let flags = MemFlags::trusted();
+        let stack = b.ins().load(types::I64, flags, state, offset_of!(JitState, stack) as i32);
+        let stack_len = b.ins().load(types::I64, flags, state, offset_of!(JitState, stack_len) as i32);
+        let zero = b.ins().iconst(types::I64, 0);
+        for var in [a, sp, fp, steps, max_sp] {
+            b.def_var(var, zero);
+        }
+        let done = b.create_block();
+        Translator {
+            b,
+            push_frame,
+            pop_frame,
+            clock,
+            time,
+            a,
+            sp,
+            fp,
+            steps,
+            max_sp,
+            state,
+            stack,
+            stack_len,
+            blocks: Vec::new(),
+            done,
+        }
+    }
+
+    fn translate(mut self, chunk: &Chunk) {
+        let code = &chunk.code;
+        let cfg = Cfg::build(chunk, 0..code.len());
+        self.blocks = vec![None; code.len()];
+        for block in &cfg.blocks {
+            self.blocks[block.start] = Some(self.b.create_block());
+        }
+        let first = self.target(0);
+        self.b.ins().jump(first, &[]);
+
+        // `LEV` returns to a pc only known at run time.
+        let dispatch = self.b.create_block();
+        let ret_pc = self.b.append_block_param(dispatch, types::I64);
+
+        for &BasicBlock { start, end, .. } in &cfg.blocks {
+            self.b.switch_to_block(self.blocks[start].unwrap());
+            let steps = self.b.use_var(self.steps);
+            let steps = self.b.ins().iadd_imm(steps, (end - start) as i64);
+            self.b.def_var(self.steps, steps);
+            let mut pc = start;
+            let mut open = true;
+            while pc < end && open {
+                open = self.instruction(pc, &code[pc], dispatch);
+                pc += 1;
+            }
+            if open {
+                let next = self.target(end);
+                self.b.ins().jump(next, &[]);
+            }
+        }
+
+        self.b.switch_to_block(dispatch);
+        let table: Vec<_> = (0..code.len())
+            .map(|pc| {
+                let block = self.blocks[pc].unwrap_or(self.done);
+                self.b.func.dfg.block_call(block, &[])
+            })
+            .collect();
+        let done_call = self.b.func.dfg.block_call(self.done, &[]);
+        let table = self.b.create_jump_table(JumpTableData::new(done_call, &table));
+        let in_range = self.b.ins().icmp_imm(IntCC::UnsignedLessThan, ret_pc, code.len() as i64);
+        let select = self.b.create_block();
+        self.b.ins().brif(in_range, select, &[], self.done, &[]);
+        self.b.switch_to_block(select);
+        let index = self.b.ins().ireduce(types::I32, ret_pc);
+        self.b.ins().br_table(index, table);
+
+        self.b.switch_to_block(self.done);
+        for (var, field) in [
+            (self.a, offset_of!(JitState, a)),
+            (self.steps, offset_of!(JitState, steps)),
+            (self.max_sp, offset_of!(JitState, max_sp)),
+        ] {
+            let value = self.b.use_var(var);
+            self.b.ins().store(MemFlags::trusted(), value, self.state, field as i32);
+        }
+        let ok = self.b.ins().iconst(types::I64, 0);
+        self.b.ins().return_(&[ok]);
+    }
+
+    /// The block control goes to at `pc`; past the end, `done`.
+    fn target(&self, pc: usize) -> Block {
+        self.blocks.get(pc).copied().flatten().unwrap_or(self.done)
+    }
+
+    /// Emit one instruction. Returns false once the block is terminated.
+    fn instruction(&mut self, pc: usize, instr: &Instruction, dispatch: Block) -> bool {
+        let next = pc + 1;
+        match *instr {
+            Instruction::Instr(op) => match op {
+                OpCode::ADD => self.binary(pc, |b, l, a| b.ins().iadd(l, a)),
+                OpCode::SUB => self.binary(pc, |b, l, a| b.ins().isub(l, a)),
+                OpCode::MUL => self.binary(pc, |b, l, a| b.ins().imul(l, a)),
+                OpCode::DIV | OpCode::MOD => self.divide(pc, op == OpCode::DIV),
+                OpCode::AND => self.binary(pc, |b, l, a| b.ins().band(l, a)),
+                OpCode::OR => self.binary(pc, |b, l, a| b.ins().bor(l, a)),
+                OpCode::XOR => self.binary(pc, |b, l, a| b.ins().bxor(l, a)),
+                OpCode::EQ => self.compare(pc, IntCC::Equal),
+                OpCode::NE => self.compare(pc, IntCC::NotEqual),
+                OpCode::LT => self.compare(pc, IntCC::SignedLessThan),
+                OpCode::LE => self.compare(pc, IntCC::SignedLessThanOrEqual),
+                OpCode::GT => self.compare(pc, IntCC::SignedGreaterThan),
+                OpCode::GE => self.compare(pc, IntCC::SignedGreaterThanOrEqual),
+                // Cranelift masks the shift count to 63, as `wrapping_shl` does.
+                OpCode::SHL => self.binary(pc, |b, l, a| b.ins().ishl(l, a)),
+                OpCode::SHR => self.binary(pc, |b, l, a| b.ins().sshr(l, a)),
+                OpCode::LI => {
+                    let addr = self.b.use_var(self.a);
+                    let slot = self.slot(pc, addr, 8);
+                    let value = self.b.ins().load(types::I64, MemFlags::new(), slot, 0);
+                    self.b.def_var(self.a, value);
+                }
+                OpCode::LC => {
+                    let addr = self.b.use_var(self.a);
+                    let slot = self.slot(pc, addr, 1);
+                    let value = self.b.ins().uload8(types::I64, MemFlags::trusted(), slot, 0);
+                    self.b.def_var(self.a, value);
+                }
+                OpCode::SI => {
+                    let addr = self.pop(pc);
+                    let slot = self.slot(pc, addr, 8);
+                    let value = self.b.use_var(self.a);
+                    self.b.ins().store(MemFlags::new(), value, slot, 0);
+                }
+                OpCode::SC => {
+                    let addr = self.pop(pc);
+                    let slot = self.slot(pc, addr, 1);
+                    let value = self.b.use_var(self.a);
+                    let value = self.b.ins().band_imm(value, 0xFF);
+                    self.b.def_var(self.a, value);
+                    self.b.ins().istore8(MemFlags::trusted(), value, slot, 0);
+                }
+                OpCode::PSH => {
+                    let a = self.b.use_var(self.a);
+                    self.push(pc, a);
+                }
+                OpCode::LEV => {
+                    let call = self.b.ins().call(self.pop_frame, &[self.state]);
+                    let popped = self.b.inst_results(call)[0];
+                    let missing = self.b.ins().icmp_imm(IntCC::Equal, popped, 0);
+                    self.guard(missing, CALL_STACK_UNDERFLOW, pc, None);
+                    let frame = offset_of!(JitState, frame) as i32;
+                    let [ret_pc, sp, fp] = [0, 8, 16]
+                        .map(|off| self.b.ins().load(types::I64, MemFlags::trusted(), self.state, frame + off));
+                    self.b.def_var(self.sp, sp);
+                    self.b.def_var(self.fp, fp);
+                    self.b.ins().jump(dispatch, &[ret_pc]);
+                    return false;
+                }
+                OpCode::EXIT => {
+                    self.b.ins().jump(self.done, &[]);
+                    return false;
+                }
+                OpCode::ASRT => {
+                    let a = self.b.use_var(self.a);
+                    let failed = self.b.ins().icmp_imm(IntCC::Equal, a, 0);
+                    self.guard(failed, ASSERTION_FAILED, pc, None);
+                }
+                OpCode::RAND => {
+                    let rng = offset_of!(JitState, rng) as i32;
+                    let state = self.b.ins().load(types::I64, MemFlags::trusted(), self.state, rng);
+                    let state = self.b.ins().imul_imm(state, RNG_MULTIPLIER as i64);
+                    let state = self.b.ins().iadd_imm(state, RNG_INCREMENT as i64);
+                    self.b.ins().store(MemFlags::trusted(), state, self.state, rng);
+                    let value = self.b.ins().ushr_imm(state, 33);
+                    self.b.def_var(self.a, value);
+                }
+                OpCode::SRND => {
+                    let a = self.b.use_var(self.a);
+                    self.b.ins().store(MemFlags::trusted(), a, self.state, offset_of!(JitState, rng) as i32);
+                }
+                OpCode::CLCK | OpCode::TIME => {
+                    let helper = if op == OpCode::CLCK { self.clock } else { self.time };
+                    let call = self.b.ins().call(helper, &[self.state]);
+                    let value = self.b.inst_results(call)[0];
+                    self.b.def_var(self.a, value);
+                }
+                _ => return self.unknown(pc),
+            },
+
+            Instruction::InstrInt(op, val) => match op {
+                OpCode::IMM => {
+                    let value = self.b.ins().iconst(types::I64, val);
+                    self.b.def_var(self.a, value);
+                }
+                OpCode::LEA => {
+                    let fp = self.b.use_var(self.fp);
+                    let value = self.b.ins().iadd_imm(fp, val);
+                    let value = self.b.ins().ishl_imm(value, 3);
+                    self.b.def_var(self.a, value);
+                }
+                OpCode::ADJ if val > 0 => {
+                    let sp = self.b.use_var(self.sp);
+                    let short = self.b.ins().icmp_imm(IntCC::UnsignedLessThan, sp, val);
+                    self.guard(short, STACK_UNDERFLOW, pc, None);
+                    let sp = self.b.ins().iadd_imm(sp, -val);
+                    self.b.def_var(self.sp, sp);
+                }
+                OpCode::ADJ => {}
+                OpCode::ENT => {
+                    let sp = self.b.use_var(self.sp);
+                    self.b.def_var(self.fp, sp);
+                    if val > 0 {
+                        self.allocate_locals(pc, sp, val);
+                    }
+                }
+                _ => return self.unknown(pc),
+            },
+
+            Instruction::Jump(op, target) => {
+                let (taken, fallthrough) = (self.target(target), self.target(next));
+                let a = self.b.use_var(self.a);
+                match op {
+                    OpCode::JMP => self.b.ins().jump(taken, &[]),
+                    OpCode::BZ => self.b.ins().brif(a, fallthrough, &[], taken, &[]),
+                    OpCode::BNZ => self.b.ins().brif(a, taken, &[], fallthrough, &[]),
+                    _ => return self.unknown(pc),
+                };
+                return false;
+            }
+
+            Instruction::Call(op, target) => match op {
+                OpCode::JSR => {
+                    self.call_push_frame(pc);
+                    let target = self.target(target);
+                    self.b.ins().jump(target, &[]);
+                    return false;
+                }
+                _ => return self.unknown(pc),
+            },
+        }
+        true
+    }
+
+    /// Continue only if `cond` is zero; otherwise record the fault and
+    /// return from the generated function.
+    fn guard(&mut self, cond: Value, kind: i64, pc: usize, addr: Option<Value>) {
+        let fault = self.b.create_block();
+        let ok = self.b.create_block();
+        self.b.ins().brif(cond, fault, &[], ok, &[]);
+        self.b.switch_to_block(fault);
+        self.fault(kind, pc, addr);
+        self.b.switch_to_block(ok);
+    }
+
+    fn fault(&mut self, kind: i64, pc: usize, addr: Option<Value>) {
+        let kind = self.b.ins().iconst(types::I64, kind);
+        let pc = self.b.ins().iconst(types::I64, pc as i64);
+        let mut fields = vec![(kind, offset_of!(JitState, fault_kind)), (pc, offset_of!(JitState, fault_pc))];
+        if let Some(addr) = addr {
+            fields.push((addr, offset_of!(JitState, fault_addr)));
+        }
+        for (value, field) in fields {
+            self.b.ins().store(MemFlags::trusted(), value, self.state, field as i32);
+        }
+        let failed = self.b.ins().iconst(types::I64, 1);
+        self.b.ins().return_(&[failed]);
+    }
+
+    fn unknown(&mut self, pc: usize) -> bool {
+        self.fault(UNKNOWN_INSTRUCTION, pc, None);
+        false
+    }
+
+    fn push(&mut self, pc: usize, value: Value) {
+        let sp = self.b.use_var(self.sp);
+        let full = self.b.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, sp, self.stack_len);
+        self.guard(full, STACK_OVERFLOW, pc, None);
+        let offset = self.b.ins().ishl_imm(sp, 3);
+        let slot = self.b.ins().iadd(self.stack, offset);
+        self.b.ins().store(MemFlags::trusted(), value, slot, 0);
+        let sp = self.b.ins().iadd_imm(sp, 1);
+        self.b.def_var(self.sp, sp);
+        let max_sp = self.b.use_var(self.max_sp);
+        let max_sp = self.b.ins().umax(max_sp, sp);
+        self.b.def_var(self.max_sp, max_sp);
+    }
+
+    fn pop(&mut self, pc: usize) -> Value {
+        let sp = self.b.use_var(self.sp);
+        let empty = self.b.ins().icmp_imm(IntCC::Equal, sp, 0);
+        self.guard(empty, STACK_UNDERFLOW, pc, None);
+        let sp = self.b.ins().iadd_imm(sp, -1);
+        self.b.def_var(self.sp, sp);
+        let offset = self.b.ins().ishl_imm(sp, 3);
+        let slot = self.b.ins().iadd(self.stack, offset);
+        self.b.ins().load(types::I64, MemFlags::trusted(), slot, 0)
+    }
+
+    /// Address of the `width` stack bytes from byte `addr`, after checking
+    /// they are in bounds. Words are native-endian, so this matches the
+    /// VM's little-endian layout on little-endian hosts.
+    fn slot(&mut self, pc: usize, addr: Value, width: i64) -> Value {
+        // Unsigned, so negative addresses are out of bounds too; an
+        // address in bounds is far from overflowing when `width` is added.
+        let bytes = self.b.ins().ishl_imm(self.stack_len, 3);
+        let outside = self.b.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, addr, bytes);
+        self.guard(outside, INVALID_ADDRESS, pc, Some(addr));
+        let end = self.b.ins().iadd_imm(addr, width);
+        let past = self.b.ins().icmp(IntCC::UnsignedGreaterThan, end, bytes);
+        self.guard(past, INVALID_ADDRESS, pc, Some(addr));
+        self.b.ins().iadd(self.stack, addr)
+    }
+
+    /// Push `count` zeroed words from `sp`. One bounds check covers them
+    /// all: a run that overflows fails at this pc either way.
+    fn allocate_locals(&mut self, pc: usize, sp: Value, count: i64) {
+        let end = self.b.ins().iadd_imm(sp, count);
+        let full = self.b.ins().icmp(IntCC::UnsignedGreaterThan, end, self.stack_len);
+        self.guard(full, STACK_OVERFLOW, pc, None);
+        let fill = self.b.create_block();
+        let i = self.b.append_block_param(fill, types::I64);
+        let after = self.b.create_block();
+        self.b.ins().jump(fill, &[sp]);
+        self.b.switch_to_block(fill);
+        let offset = self.b.ins().ishl_imm(i, 3);
+        let slot = self.b.ins().iadd(self.stack, offset);
+        let zero = self.b.ins().iconst(types::I64, 0);
+        self.b.ins().store(MemFlags::trusted(), zero, slot, 0);
+        let i = self.b.ins().iadd_imm(i, 1);
+        let more = self.b.ins().icmp(IntCC::UnsignedLessThan, i, end);
+        self.b.ins().brif(more, fill, &[i], after, &[]);
+        self.b.switch_to_block(after);
+        self.b.def_var(self.sp, end);
+        let max_sp = self.b.use_var(self.max_sp);
+        let max_sp = self.b.ins().umax(max_sp, end);
+        self.b.def_var(self.max_sp, max_sp);
+    }
+
+    fn call_push_frame(&mut self, pc: usize) {
+        let ret_pc = self.b.ins().iconst(types::I64, pc as i64 + 1);
+        let sp = self.b.use_var(self.sp);
+        let fp = self.b.use_var(self.fp);
+        let call = self.b.ins().call(self.push_frame, &[self.state, ret_pc, sp, fp]);
+        let pushed = self.b.inst_results(call)[0];
+        let full = self.b.ins().icmp_imm(IntCC::Equal, pushed, 0);
+        self.guard(full, STACK_OVERFLOW, pc, None);
+    }
+
+    /// `a = pop() op a`.
+    fn binary(&mut self, pc: usize, op: impl Fn(&mut FunctionBuilder<'f>, Value, Value) -> Value) {
+        let left = self.pop(pc);
+        let a = self.b.use_var(self.a);
+        let value = op(&mut *self.b, left, a);
+        self.b.def_var(self.a, value);
+    }
+
+    fn compare(&mut self, pc: usize, cc: IntCC) {
+        self.binary(pc, |b, l, a| {
+            let flag = b.ins().icmp(cc, l, a);
+            b.ins().uextend(types::I64, flag)
+        });
+    }
+
+    /// `DIV` or `MOD`, wrapping like the VM: a zero divisor faults before
+    /// popping, and dividing by -1 never traps.
+    fn divide(&mut self, pc: usize, quotient: bool) {
+        let a = self.b.use_var(self.a);
+        let zero = self.b.ins().icmp_imm(IntCC::Equal, a, 0);
+        self.guard(zero, DIVISION_BY_ZERO, pc, None);
+        let left = self.pop(pc);
+        let minus_one = self.b.ins().icmp_imm(IntCC::Equal, a, -1);
+        let one = self.b.ins().iconst(types::I64, 1);
+        let divisor = self.b.ins().select(minus_one, one, a);
+        let value = if quotient {
+            let q = self.b.ins().sdiv(left, divisor);
+            let negated = self.b.ins().ineg(left);
+            self.b.ins().select(minus_one, negated, q)
+        } else {
+            let r = self.b.ins().srem(left, divisor);
+            let zero = self.b.ins().iconst(types::I64, 0);
+            self.b.ins().select(minus_one, zero, r)
+        };
+        self.b.def_var(self.a, value);
+    }
+}
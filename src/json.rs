@@ -0,0 +1,42 @@
+// src/json.rs
+
+//! The AST as JSON, for tools that inspect programs or generate them
+//! without going through C syntax.
+//!
+//! The format is serde's default for the types in [`ast`](crate::ast):
+//! structs are objects keyed by field name, enum variants are externally
+//! tagged (`{"Num": 5}`, `{"Binary": {"op": "Add", "left": ..., "right": ...}}`)
+//! and unit variants are bare strings (`"Int"`, `"Empty"`). The easiest way
+//! to learn it is `--ast-json` on a small program.
+
+use crate::ast::Program;
+use crate::check::check_program;
+use crate::error::{Error, ParseError};
+
+/// `program` as pretty-printed JSON.
+pub fn to_json(program: &Program) -> String {
+    serde_json::to_string_pretty(program).expect("the AST always serializes")
+}
+
+/// Read a program back from JSON and check it as the compiler would a
+/// parsed one. Malformed JSON, or JSON of the wrong shape, is a
+/// [`Error::Parse`] pointing into `s`; a tree that doesn't make sense
+/// (an undefined variable, an index into a number) is an
+/// [`Error::Compile`].
+pub fn program_from_json(s: &str) -> Result<Program, Error> {
+    let program: Program = serde_json::from_str(s).map_err(|e| {
+        let offset = s
+            .split_inclusive('\n')
+            .take(e.line().saturating_sub(1))
+            .map(str::len)
+            .sum::<usize>()
+            + e.column().saturating_sub(1);
+        // serde's message ends with the position, which ParseError shows.
+        let message = e.to_string();
+        let position = format!(" at line {} column {}", e.line(), e.column());
+        let message = message.strip_suffix(&position).unwrap_or(&message).to_string();
+        ParseError { message, offset, line: e.line(), col: e.column() }
+    })?;
+    check_program(&program)?;
+    Ok(program)
+}
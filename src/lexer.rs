@@ -1,19 +1,26 @@
 //! Lexer for the C4 compiler subset in Rust.
 //! Takes a &str and produces a sequence of Tokens.
 
-use std::iter::Peekable;
-use std::str::CharIndices;
+use core::fmt;
+use core::iter::Peekable;
+use core::str::CharIndices;
+
+use crate::prelude::*;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Token {
-    /// int literal (decimal, hex/octal can be added)
+    /// int literal (decimal, octal or hex)
     Num(i64),
     /// identifier
     Ident(String),
-    /// string literal, with escape processing
-    Str(String),
+    /// string literal, with escape processing: its bytes, which needn't
+    /// be UTF-8
+    Str(Vec<u8>),
     /// char literal, with escape processing
     Char(char),
+    /// `//` or `/* */` comment or `#` line, text included; only produced
+    /// by [`Lexer::with_trivia`]
+    Comment(String),
 
     KwVoid,
     KwInt,
@@ -22,6 +29,13 @@ pub enum Token {
     KwIf,
     KwElse,
     KwWhile,
+    KwDo,
+    KwFor,
+    KwBreak,
+    KwContinue,
+    KwSwitch,
+    KwCase,
+    KwDefault,
     KwReturn,
     KwSizeof,
 
@@ -35,6 +49,9 @@ pub enum Token {
     Xor,           // '^'
     Shl, Shr,      // '<<', '>>'
     Inc, Dec,      // '++', '--'
+    // compound assignment: '+=', '-=', '*=', '/=', '%=', '&=', '|=', '^=', '<<=', '>>='
+    PlusAssign, MinusAssign, StarAssign, SlashAssign, PercentAssign,
+    AndAssign, OrAssign, XorAssign, ShlAssign, ShrAssign,
 
     // bitwise NOT
     Tilde,        // '~'
@@ -50,13 +67,45 @@ pub enum Token {
     Eof,
 }
 
-#[derive(Debug)]
-pub struct LexError(pub String);
+/// Text the lexer can't turn into a token, and the 1-based line and
+/// column where it starts.
+#[derive(Debug, PartialEq)]
+pub struct LexError {
+    pub msg: String,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.msg, self.line, self.col)
+    }
+}
+
+impl core::error::Error for LexError {}
+
+/// A half-open byte range `start..end` of the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
 
 /// The lexer struct wraps the input string and a peekable index iterator.
 pub struct Lexer<'a> {
     input: &'a str,
     iter: Peekable<CharIndices<'a>>,
+    /// Byte offset where the most recently returned token starts.
+    start: usize,
+    /// Byte offset just past the most recently returned token.
+    end: usize,
+    /// Return comments as tokens instead of skipping them.
+    trivia: bool,
+    /// 1-based line and column of `counted`, the offset up to which
+    /// positions have been worked out.
+    line: usize,
+    col: usize,
+    counted: usize,
 }
 
 impl<'a> Lexer<'a> {
@@ -65,75 +114,126 @@ impl<'a> Lexer<'a> {
         Lexer {
             input,
             iter: input.char_indices().peekable(),
+            start: 0,
+            end: 0,
+            trivia: false,
+            line: 1,
+            col: 1,
+            counted: 0,
+        }
+    }
+
+    /// Emit [`Token::Comment`] for comments and `#` lines instead of
+    /// discarding them, for tools that need the whole file.
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = true;
+        self
+    }
+
+    /// Byte offset of the start of the most recently returned token.
+    pub fn token_start(&self) -> usize {
+        self.start
+    }
+
+    /// Line and column, both from 1, where the most recently returned
+    /// token starts.
+    pub fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
+
+    /// Source range of the most recently returned token (or of the text
+    /// consumed by the last error).
+    pub fn token_span(&self) -> Span {
+        Span { start: self.start, end: self.end }
+    }
+
+    /// Lex the whole input, excluding the final `Eof`.
+    pub fn tokenize_all(&mut self) -> Result<Vec<Token>, LexError> {
+        let _span = span!("lex");
+        let mut tokens = Vec::new();
+        loop {
+            match self.next_token()? {
+                Token::Eof => {
+                    event!(tokens = tokens.len(), "lexed");
+                    return Ok(tokens);
+                }
+                tok => tokens.push(tok),
+            }
         }
     }
 
     /// Return the next token or a LexError.
     pub fn next_token(&mut self) -> Result<Token, LexError> {
-        self.skip_whitespace_and_comments(); // Skip irrelevant characters.
+        let tok = self.lex_token();
+        self.end = self.iter.peek().map_or(self.input.len(), |&(i, _)| i);
+        self.count_to(self.start);
+        tok.map_err(|msg| LexError { msg, line: self.line, col: self.col })
+    }
+
+    /// Move the line and column on to `offset`, which is never behind
+    /// the last one counted.
+    fn count_to(&mut self, offset: usize) {
+        for c in self.input[self.counted..offset].chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.col = 1;
+            } else {
+                self.col += 1;
+            }
+        }
+        self.counted = offset;
+    }
+
+    /// The next token, or what is wrong with the text at `self.start`.
+    fn lex_token(&mut self) -> Result<Token, String> {
+        self.skip_whitespace_and_comments()?; // Skip irrelevant characters.
 
         let (idx, ch) = match self.iter.peek() {
             Some(&(i, c)) => (i, c),
-            None => return Ok(Token::Eof), // End of input.
+            None => {
+                self.start = self.input.len();
+                return Ok(Token::Eof); // End of input.
+            }
         };
+        self.start = idx;
+
+        if self.trivia && self.skip_comment()? {
+            let end = self.iter.peek().map_or(self.input.len(), |&(i, _)| i);
+            return Ok(Token::Comment(self.input[idx..end].to_string()));
+        }
 
         self.iter.next(); // Advance the iterator.
 
-        // Handle numeric literals.
+        // Handle numeric literals: decimal, octal after a leading `0`, or
+        // hex after `0x`. Every digit is taken, so one the base doesn't
+        // allow, as in `09`, is an error rather than the next token.
         if ch.is_ascii_digit() {
-            let start = idx;
-            let mut end = idx + ch.len_utf8();
-            let mut base = 10;
-
-            // hex & octal support
-            if ch == '0' {
-                if let Some(&(_, next)) = self.iter.peek() {
-                    match next {
-                        'x' | 'X' => {
-                            base = 16;
-                            // consume 'x' or 'X'
-                            self.iter.next();
-                            end += next.len_utf8();
-                            // consume hex digits
-                            while let Some(&(_, c)) = self.iter.peek() {
-                                if c.is_ascii_hexdigit() {
-                                    end += c.len_utf8();
-                                    self.iter.next();
-                                } else {
-                                    break;
-                                }
-                            }
-                        }
-                        '0'..='7' => {
-                            base = 8;
-                            // consume octal digits
-                            while let Some(&(_, c)) = self.iter.peek() {
-                                if ('0'..='7').contains(&c) {
-                                    end += c.len_utf8();
-                                    self.iter.next();
-                                } else {
-                                    break;
-                                }
-                            }
-                        }
-                        _ => { /* single '0', leave base=10 */ }
-                    }
+            let (base, digits) = match (ch, self.iter.peek()) {
+                ('0', Some(&(i, 'x' | 'X'))) => {
+                    self.iter.next();
+                    (16, i + 1)
                 }
-            } else {
-                // decimal: consume more digits
-                while let Some(&(_, c)) = self.iter.peek() {
-                    if c.is_ascii_digit() {
-                        end += c.len_utf8();
-                        self.iter.next();
-                    } else {
-                        break;
-                    }
+                ('0', _) => (8, idx),
+                _ => (10, idx),
+            };
+            let mut end = digits + if base == 16 { 0 } else { 1 };
+            while let Some(&(i, c)) = self.iter.peek() {
+                if !c.is_ascii_hexdigit() || (base != 16 && !c.is_ascii_digit()) {
+                    break;
                 }
+                self.iter.next();
+                end = i + 1;
             }
 
-            let slice = &self.input[start..end];
-            let val = i64::from_str_radix(slice, base)
-                .map_err(|e| LexError(e.to_string()))?;
+            let text = &self.input[idx..end];
+            let slice = &self.input[digits..end];
+            if slice.is_empty() {
+                return Err(format!("hex constant {} has no digits", text));
+            }
+            if let Some(bad) = slice.chars().find(|c| !c.is_digit(base)) {
+                return Err(format!("invalid digit '{}' in octal constant {}", bad, text));
+            }
+            let val = i64::from_str_radix(slice, base).map_err(|e| format!("bad number {}: {}", text, e))?;
             return Ok(Token::Num(val));
         }
 
@@ -153,56 +253,57 @@ impl<'a> Lexer<'a> {
         
         return Ok(match ident {
             "void"   => Token::KwVoid,
+            "break"  => Token::KwBreak,
+            "case"   => Token::KwCase,
             "char"   => Token::KwChar,
+            "continue" => Token::KwContinue,
+            "default" => Token::KwDefault,
+            "do"     => Token::KwDo,
             "else"   => Token::KwElse,
             "enum"   => Token::KwEnum,
+            "for"    => Token::KwFor,
             "if"     => Token::KwIf,
             "int"    => Token::KwInt,
             "return" => Token::KwReturn,
             "sizeof" => Token::KwSizeof,
+            "switch" => Token::KwSwitch,
             "while"  => Token::KwWhile,
             _        => Token::Ident(ident.to_string()),
             });
         }
 
-        // Handle string literals.
+        // Handle string literals: bytes, so an escape can be any of
+        // 0..=255, and other characters are their UTF-8 encoding.
         if ch == '"' {
-            let mut s = String::new();
-            while let Some(&(_, c)) = self.iter.peek() {
-                self.iter.next();
-                if c == '"' {
-                    break; // End of string.
-                }
-                if c == '\\' {
-                    // Handle escape sequences.
-                    if let Some(&(_, esc)) = self.iter.peek() {
-                        self.iter.next();
-                        match esc {
-                            'n' => s.push('\n'), // Newline escape.
-                            other => s.push(other), // Other escapes.
-                        }
-                    }
-                } else {
-                    s.push(c); // Regular character.
+            let mut s = Vec::new();
+            loop {
+                match self.iter.next() {
+                    Some((_, '"')) => break, // End of string.
+                    Some((_, '\\')) => s.push(self.escape()? as u8),
+                    Some((_, c)) => s.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                    None => return Err("unterminated string literal".into()),
                 }
             }
             return Ok(Token::Str(s));
         }
 
-        // Handle character literals.
+        // Handle character literals: exactly one character or escape,
+        // then the closing quote.
         if ch == '\'' {
-            let c = match self.iter.next().map(|(_, c)| c) {
-                Some('\\') => match self.iter.next().map(|(_, c)| c) {
-                    Some('n') => '\n', // Newline escape.
-                    Some(other) => other, // Other escapes.
-                    None => return Err(LexError("Unterminated char literal".into())),
-                },
-                Some(other) => other, // Regular character.
-                None => return Err(LexError("Unterminated char literal".into())),
+            let c = match self.iter.next() {
+                Some((_, '\\')) => self.escape()?,
+                Some((_, '\'')) => return Err("empty char literal".into()),
+                Some((_, c)) if c != '\n' => c as u32, // Regular character.
+                _ => return Err("unterminated char literal".into()),
             };
-            // Consume closing single quote.
-            if let Some(&(_, '\'')) = self.iter.peek() {
-                self.iter.next();
+            match self.iter.peek() {
+                Some(&(_, '\'')) => {
+                    self.iter.next();
+                }
+                Some(&(i, _)) if self.input[i..].lines().next().is_some_and(|line| line.contains('\'')) => {
+                    return Err("char literal has more than one character".into());
+                }
+                _ => return Err("unterminated char literal".into()),
             }
             // fold into Num
             return Ok(Token::Num(c as i64));
@@ -222,9 +323,22 @@ impl<'a> Lexer<'a> {
                 ">>" => Some(Token::Shr),
                 "++" => Some(Token::Inc),
                 "--" => Some(Token::Dec),
+                "+=" => Some(Token::PlusAssign),
+                "-=" => Some(Token::MinusAssign),
+                "*=" => Some(Token::StarAssign),
+                "/=" => Some(Token::SlashAssign),
+                "%=" => Some(Token::PercentAssign),
+                "&=" => Some(Token::AndAssign),
+                "|=" => Some(Token::OrAssign),
+                "^=" => Some(Token::XorAssign),
                 _ => None,
             } {
                 self.iter.next(); // Consume the second character.
+                // `<<=` and `>>=` take a third.
+                if matches!(tok, Token::Shl | Token::Shr) && matches!(self.iter.peek(), Some(&(_, '='))) {
+                    self.iter.next();
+                    return Ok(if tok == Token::Shl { Token::ShlAssign } else { Token::ShrAssign });
+                }
                 return Ok(tok);
             }
         }
@@ -254,45 +368,104 @@ impl<'a> Lexer<'a> {
             '}' => Token::RBrace,
             '[' => Token::LBracket,
             ']' => Token::RBracket,
-            _ => return Err(LexError(format!("Unexpected character '{}'", ch))),
+            _ => return Err(format!("Unexpected character '{}'", ch)),
         };
         Ok(tok)
     }
 
-    /// Skip whitespace, comments, and preprocessor lines in the input.
-    fn skip_whitespace_and_comments(&mut self) {
-        while let Some(&(_, c)) = self.iter.peek() {
-            if c.is_whitespace() {
-                self.iter.next(); // Skip whitespace.
-            } else if c == '/' {
-                // Check for comments.
-                let mut clone = self.iter.clone();
-                clone.next();
-                if let Some(&(_, '/')) = clone.peek() {
-                    // Consume "//" and the rest of the line.
-                    self.iter.next();
-                    self.iter.next();
-                    while let Some(&(_, c2)) = self.iter.peek() {
-                        self.iter.next();
-                        if c2 == '\n' {
-                            break; // End of comment.
+    /// The value of the escape sequence after a `\\`: C's single-character
+    /// escapes, up to three octal digits, or `\\x` and hex digits.
+    fn escape(&mut self) -> Result<u32, String> {
+        let Some((_, c)) = self.iter.next() else {
+            return Err("unterminated escape sequence".into());
+        };
+        let value = match c {
+            'n' => '\n' as u32,
+            't' => '\t' as u32,
+            'r' => '\r' as u32,
+            'a' => 0x07,
+            'b' => 0x08,
+            'f' => 0x0C,
+            'v' => 0x0B,
+            '\\' | '\'' | '"' | '?' => c as u32,
+            '0'..='7' => {
+                let mut value = c.to_digit(8).unwrap_or(0);
+                for _ in 0..2 {
+                    match self.iter.peek().and_then(|&(_, d)| d.to_digit(8)) {
+                        Some(d) => {
+                            value = value * 8 + d;
+                            self.iter.next();
                         }
+                        None => break,
                     }
-                } else {
-                    break; // Not a comment.
                 }
-            } else if c == '#' {
-                // Consume preprocessor line.
-                self.iter.next();
-                while let Some(&(_, c2)) = self.iter.peek() {
+                if value > 0xFF {
+                    return Err(format!("octal escape \\{:o} is out of range", value));
+                }
+                value
+            }
+            'x' => {
+                let mut digits = String::new();
+                while let Some(&(_, d)) = self.iter.peek().filter(|(_, d)| d.is_ascii_hexdigit()) {
+                    digits.push(d);
                     self.iter.next();
-                    if c2 == '\n' {
-                        break;
-                    }
                 }
-            } else {
+                if digits.is_empty() {
+                    return Err("\\x used with no following hex digits".into());
+                }
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|&v| v <= 0xFF)
+                    .ok_or_else(|| format!("hex escape \\x{} is out of range", digits))?
+            }
+            other => return Err(format!("unknown escape sequence \\{}", other)),
+        };
+        Ok(value)
+    }
+
+    /// Skip whitespace, comments, and preprocessor lines in the input.
+    /// With trivia on, stop at comments so they become tokens.
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), String> {
+        while let Some(&(_, c)) = self.iter.peek() {
+            if c.is_whitespace() {
+                self.iter.next(); // Skip whitespace.
+            } else if self.trivia || !self.skip_comment()? {
                 break; // Stop skipping.
             }
         }
+        Ok(())
+    }
+
+    /// Consume a comment or `#` line starting here: a `//` comment or `#`
+    /// line up to but not including its newline, a `/* */` comment through
+    /// its `*/`. Returns false if there is none.
+    fn skip_comment(&mut self) -> Result<bool, String> {
+        let mut ahead = self.iter.clone();
+        match (ahead.next(), ahead.peek()) {
+            (Some((_, '/')), Some(&(_, '/'))) | (Some((_, '#')), _) => {}
+            (Some((start, '/')), Some(&(_, '*'))) => {
+                // The first `*/` ends it: block comments don't nest.
+                return match self.input[start + 2..].find("*/") {
+                    Some(len) => {
+                        let end = start + 2 + len + 2;
+                        while self.iter.next_if(|&(i, _)| i < end).is_some() {}
+                        Ok(true)
+                    }
+                    None => {
+                        self.start = start;
+                        self.iter.by_ref().for_each(drop);
+                        Err("unterminated block comment".into())
+                    }
+                };
+            }
+            _ => return Ok(false),
+        }
+        while let Some(&(_, c)) = self.iter.peek() {
+            if c == '\n' {
+                break; // End of comment.
+            }
+            self.iter.next();
+        }
+        Ok(true)
     }
 }
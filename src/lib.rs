@@ -1,8 +1,123 @@
 // src/lib.rs
-pub mod lexer;// src/lib.rs
+#![allow(non_snake_case)] // the crate name `c4_rust_AlRafaah` is not snake case
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A Rust port of the C4 compiler: lexer, parser, bytecode compiler and VM.
+//!
+//! The quickest way in is [`run_source`]:
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! let outcome = c4_rust_AlRafaah::run_source("int main() { return 6 * 7; }").unwrap();
+//! assert_eq!(outcome.exit_code, 42);
+//! # }
+//! ```
+//!
+//! [`compile_source`] stops after compilation, and [`run_source_with`] takes a
+//! [`vm::VmConfig`]. Errors from any phase come back as [`Error`]. For more
+//! control over compilation, or several source files, use a [`Compiler`].
+//!
+//! Without the default `std` feature the crate is `no_std` + `alloc`: the
+//! lexer, parser, codegen and VM remain, while the driver, CLI, test runner,
+//! `Chunk::dump`, and `Error::Io` are left out.
+//!
+//! The `tracing` feature instruments each phase with [`tracing`] spans:
+//! `lex`, `parse`, `compile` and `vm::run`, with events carrying token and
+//! item counts, per-function instruction counts, and the run's instruction
+//! count and exit code. Without the feature there is no dependency and no
+//! instrumentation code.
+//!
+//! The `fastexec` feature adds the `fastexec` module, which compiles a chunk
+//! into closures and runs it without the VM's per-instruction dispatch, and
+//! the `jit` feature adds `jit`, which compiles it to native code with
+//! Cranelift. Both are selected with [`vm::Backend`].
+
+extern crate alloc;
+
+/// `alloc` items the std prelude would otherwise provide, so the core
+/// modules build the same with and without `std`.
+mod prelude {
+    pub(crate) use alloc::boxed::Box;
+    pub(crate) use alloc::string::{String, ToString};
+    pub(crate) use alloc::vec::Vec;
+    pub(crate) use alloc::{format, vec};
+}
+
+// Instrumentation hooks. With the `tracing` feature these open `tracing`
+// spans and emit events; without it they expand to nothing, arguments
+// included, so the hot paths pay nothing.
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($($arg:tt)*) => {
+        tracing::info_span!($($arg)*).entered()
+    };
+}
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($($arg:tt)*) => {
+        ()
+    };
+}
+#[cfg(feature = "tracing")]
+macro_rules! event {
+    ($($arg:tt)*) => {
+        tracing::info!($($arg)*)
+    };
+}
+#[cfg(not(feature = "tracing"))]
+macro_rules! event {
+    ($($arg:tt)*) => {};
+}
+
+pub mod lexer;
 pub mod ast;
+mod ast_display;
 pub mod parser;
+pub mod check;
 pub mod bytecode;
+pub mod symtable;
+pub mod cfg;
+pub mod coverage;
+pub mod coredump;
+pub mod heap;
+pub mod replay;
+pub mod vfs;
 pub mod vm;
+mod printf;
+pub mod error;
+pub mod interp;
+pub mod analysis;
+pub mod compiler;
+pub mod stdlib;
+pub mod highlight;
+pub mod format;
+pub mod emit_c;
+pub mod visitor;
+#[cfg(feature = "fastexec")]
+pub mod fastexec;
+#[cfg(feature = "jit")]
+pub mod jit;
+#[cfg(feature = "std")]
+pub mod driver;
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "std")]
+pub mod cli;
+#[cfg(feature = "std")]
+pub mod test_runner;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-
+#[cfg(feature = "std")]
+pub use driver::{compile_source, compile_to_asm, run_source, run_source_with};
+pub use analysis::analyze;
+pub use compiler::Compiler;
+pub use highlight::classify;
+pub use error::Error;
+pub use vm::RunOutcome;
@@ -1,39 +1,391 @@
+#![allow(non_snake_case)] // the crate name `c4_rust_AlRafaah` is not snake case
+
 use std::env;
-use std::fs;
-use c4_rust_AlRafaah::parser::Parser;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
 use c4_rust_AlRafaah::bytecode::Chunk;
-use c4_rust_AlRafaah::vm::VM;
+use c4_rust_AlRafaah::vm::{VmConfig, VM};
+use c4_rust_AlRafaah::driver::{self, MtimeWatcher, Phase, PhaseTimer, SystemClock, WatchConfig};
+use c4_rust_AlRafaah::cli::{self, Action, FormatMode, Options};
+use c4_rust_AlRafaah::format::{format_source, FormatOptions};
+use c4_rust_AlRafaah::emit_c::emit_c;
+use c4_rust_AlRafaah::test_runner::{self, TestReport};
+use c4_rust_AlRafaah::error::Error;
+use c4_rust_AlRafaah::ast::Program;
+use c4_rust_AlRafaah::compiler::Compiler;
+use c4_rust_AlRafaah::replay::SyscallLog;
+#[cfg(feature = "json")]
+use c4_rust_AlRafaah::json;
+
+/// Extension of the chunks `--emit-bytecode` saves; files with it run
+/// without being compiled
+const BYTECODE_EXTENSION: &str = "c4b";
 
 /// Entry point for the compiler-interpreter tool
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Collect command-line arguments into a vector
-    let args: Vec<String> = env::args().collect();
+    // Collect command-line arguments and parse the flags
+    let mut args = env::args();
+    let prog = args.next().unwrap_or_else(|| env!("CARGO_PKG_NAME").into());
+
+    let opts = match cli::parse_args(args) {
+        Ok(Action::Run(opts)) => *opts,
+        Ok(Action::Test(dir)) => {
+            let summary = test_runner::run_dir(&dir)?;
+            print!("{}", summary.render());
+            std::process::exit(if summary.failed() == 0 { 0 } else { 1 });
+        }
+        Ok(Action::Bench(bench)) => {
+            let source = fs::read_to_string(&bench.source)?;
+            let chunk = driver::compile_source(&source)?;
+
+            let stats = driver::bench(&chunk, bench.iterations, bench.warmup)?;
+            println!(
+                "bench {}: {} iterations ({} warmup)",
+                bench.source.display(),
+                bench.iterations,
+                bench.warmup
+            );
+            print!("{}", stats.report());
+            return Ok(());
+        }
+        Ok(Action::Help) => {
+            print!("{}", cli::help_text(&prog));
+            return Ok(());
+        }
+        Ok(Action::Version) => {
+            println!("{}", cli::version_text());
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("error: {}", e); // Print usage error to stderr
+            eprintln!("Try '{} --help' for more information.", prog);
+            std::process::exit(cli::USAGE_EXIT_CODE);
+        }
+    };
+
+    if let Some(mode) = opts.format {
+        match format_file(&opts, mode) {
+            Ok(true) => std::process::exit(0),
+            Ok(false) => {
+                eprintln!("{} is not formatted", opts.source.display());
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if opts.unit_tests {
+        match unit_tests(&opts) {
+            Ok(report) => {
+                print!("{}", report.render());
+                std::process::exit(if report.failed() == 0 { 0 } else { 1 });
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(feature = "json")]
+    if opts.ast_json {
+        let ast = fs::read_to_string(&opts.source)
+            .map_err(Error::from)
+            .and_then(|source| compiler(&opts).parse(&source));
+        match ast {
+            Ok(ast) => println!("{}", json::to_json(&ast)),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = &opts.emit_c {
+        if let Err(e) = emit_c_file(&opts, path) {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(path) = &opts.emit_bytecode {
+        if let Err(e) = save_bytecode(&opts, path) {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if opts.asm || opts.dump_symbols || !opts.disasm.is_empty() {
+        match disassemble(&opts) {
+            Ok(listing) => print!("{}", listing),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
 
-    // Expect exactly one argument (the source file path)
-    if args.len() != 2 {
-        eprintln!("Usage: {} <source.c>", args[0]); // Print usage error to stderr
-        std::process::exit(1); // Exit with error code 1
+    if opts.watch {
+        watch(&opts);
     }
 
-    // Read the source file content into a string
-    let source = fs::read_to_string(&args[1])?;
+    // Like the original c4, the program's return value becomes our exit status
+    match run(&opts) {
+        Ok(code) => std::process::exit(code as i32),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(cli::ERROR_EXIT_CODE);
+        }
+    }
+}
 
-    // Create a new parser instance for the source code
-    let mut parser = Parser::new(&source)?;
+/// Format the source file for `--fmt` (rewriting it) or `--fmt-check`;
+/// returns whether the file was already formatted (always true for `--fmt`)
+fn format_file(opts: &Options, mode: FormatMode) -> Result<bool, Error> {
+    let source = fs::read_to_string(&opts.source)?;
+    let formatted = format_source(&source, &FormatOptions::default())?;
+    match mode {
+        FormatMode::Check => Ok(formatted == source),
+        FormatMode::Write => {
+            if formatted != source {
+                fs::write(&opts.source, formatted)?;
+            }
+            Ok(true)
+        }
+    }
+}
 
-    // Parse the source code into an abstract syntax tree (AST)
-    let ast = parser.parse_program()?;
+/// The compiler the flags ask for
+#[cfg_attr(not(feature = "json"), allow(unused_mut))]
+fn compiler(opts: &Options) -> Compiler {
+    let mut compiler = Compiler::new().stdlib(!opts.nostdlib);
+    #[cfg(feature = "json")]
+    {
+        compiler = compiler.json(opts.from_json);
+    }
+    compiler
+}
 
-    // Compile the AST into bytecode
-    let mut chunk = Chunk::default();
-    ast.compile(&mut chunk)?;
+/// Parse the contents of the source file, C or with `--from-json` a
+/// JSON AST, and link the prelude functions it calls
+fn parse_source(opts: &Options, source: &str) -> Result<Program, Error> {
+    let compiler = compiler(opts);
+    let mut program = compiler.parse(source)?;
+    compiler.link(&mut program);
+    Ok(program)
+}
 
-    // Create and run the virtual machine with the compiled bytecode
-    let mut vm = VM::new();
-    let result = vm.run(&chunk);
+/// Compile the source file and run its `test_` functions for `--test`
+fn unit_tests(opts: &Options) -> Result<TestReport, Error> {
+    Ok(test_runner::run_tests(&compile_file(opts)?))
+}
+
+/// Translate the source file to C for `--emit-c`, writing it to `path`
+fn emit_c_file(opts: &Options, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let source = fs::read_to_string(&opts.source)?;
+    let ast = parse_source(opts, &source)?;
+    fs::write(path, emit_c(&ast)?)?;
+    Ok(())
+}
 
-    // Print the final result (exit code of the program)
-    println!("Program exited with code {}", result);
+/// Compile the source for `--asm`, `--disasm` and `--dump-symbols` and
+/// return the symbols, then the listing, as asked
+fn disassemble(opts: &Options) -> Result<String, Box<dyn std::error::Error>> {
+    let chunk = compile_file(opts)?;
+    let mut out = String::new();
+    if opts.dump_symbols {
+        out += &chunk.symbol_listing();
+    }
+    if opts.asm {
+        out += &chunk.disassemble();
+    }
+    for name in &opts.disasm {
+        match chunk.disassemble_function(name) {
+            Some(listing) => out += &listing,
+            None => {
+                let available: Vec<&str> = chunk.functions.iter().map(|f| f.name.as_str()).collect();
+                return Err(format!("no function named '{}' (available: {})", name, available.join(", ")).into());
+            }
+        }
+    }
+    Ok(out)
+}
 
-    Ok(()) // Return success
+/// Compile the source for `--emit-bytecode` and save the chunk to `path`
+fn save_bytecode(opts: &Options, path: &Path) -> Result<(), Error> {
+    let chunk = compile_file(opts)?;
+    let mut file = io::BufWriter::new(fs::File::create(path)?);
+    chunk.write_to(&mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Read, parse and compile the source file named in `opts`
+fn compile_file(opts: &Options) -> Result<Chunk, Error> {
+    Ok(compiler(opts).compile_file(&opts.source)?.chunk)
+}
+
+/// Whether `path` names a chunk saved by `--emit-bytecode`
+fn is_bytecode(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == BYTECODE_EXTENSION)
+}
+
+/// Rerun the program every time the source changes; only Ctrl-C stops this
+fn watch(opts: &Options) -> ! {
+    let mut changes = MtimeWatcher::new(vec![opts.source.clone()]);
+    let rebuild = || {
+        print!("\x1b[2J\x1b[H"); // Clear the screen before each run
+        println!("[watching {}]", opts.source.display());
+        if let Err(e) = run(opts) {
+            eprintln!("error: {}", e); // Report, but keep watching
+        }
+    };
+    driver::watch(&mut changes, &mut SystemClock::new(), &WatchConfig::default(), rebuild, || false);
+    unreachable!("watch loop only stops on interrupt")
+}
+
+/// Read, parse, compile and execute the source file named in `opts`,
+/// returning the program's exit code
+fn run(opts: &Options) -> Result<i64, Error> {
+    let mut timer = PhaseTimer::new();
+
+    // Open the output sink first so a bad path fails before anything runs
+    let output = open_output(opts)?;
+
+    // Kept for --coverage, which reports per line of C source
+    let mut source_text = None;
+    let chunk = if is_bytecode(&opts.source) {
+        // A saved chunk is already compiled
+        timer.time("read", || fs::File::open(&opts.source).and_then(|mut f| Chunk::read_from(&mut f)))?
+    } else {
+        // Read the source file content into a string
+        let source = timer.time("read", || fs::read_to_string(&opts.source))?;
+
+        // Parse the source code (or JSON AST) into an abstract syntax tree (AST)
+        let ast = timer.time("parse", || parse_source(opts, &source))?;
+
+        // Compile the AST into bytecode
+        let chunk = if opts.stop_after >= Phase::Compile {
+            timer.time("compile", || compiler(opts).compile_program(&ast))?
+        } else {
+            Chunk::default()
+        };
+        source_text = Some(source);
+        chunk
+    };
+
+    // Create and run the virtual machine with the compiled bytecode,
+    // unless `--check` asked us to stop before execution
+    let mut result = 0;
+    let mut instructions = None;
+    if opts.stop_after >= Phase::Execute {
+        let mut vm = VM::with_config(vm_config(opts));
+        vm.set_output(Box::new(output));
+        if opts.coverage {
+            vm.enable_coverage();
+        }
+        if let Some(path) = &opts.replay {
+            vm.replay(read_syscall_log(path)?);
+        }
+        // argv[0] is the source file, as in c4
+        let source = opts.source.to_string_lossy();
+        let args: Vec<&str> = std::iter::once(source.as_ref()).chain(opts.program_args.iter().map(String::as_str)).collect();
+        vm.set_args(&args)?;
+        if opts.record.is_some() {
+            vm.record();
+        }
+        let run = timer.time("execute", || vm.run(&chunk));
+        if let (Err(e), Some(path)) = (&run, &opts.core_dump) {
+            fs::write(path, e.dump(&vm, &chunk).to_text())?;
+            eprintln!("core dumped to {}", path.display());
+        }
+        // Saved even if the run failed, so the failure can be replayed
+        if let (Some(path), Some(log)) = (&opts.record, vm.take_syscall_log()) {
+            fs::write(path, log.to_text())?;
+        }
+        result = run?;
+        instructions = Some(vm.instruction_count());
+
+        if let Some(report) = vm.take_coverage() {
+            // JSON input has no spans to trace instructions back by
+            match &source_text {
+                Some(src) if !chunk.source_map.0.is_empty() => eprint!("{}", report.render(&chunk, src)),
+                _ => eprint!("{}", report.render_instructions(&chunk)),
+            }
+        }
+
+        // With --leak-check, leaks turn a clean exit into a failure
+        let mut leaked = false;
+        if let Some(report) = vm.leak_report(&chunk) {
+            eprint!("{}", report.render());
+            leaked = report.leaked_blocks() > 0;
+        }
+
+        // Resource usage; the output itself was streamed, not captured
+        if opts.verbose {
+            let wall_time = timer.get("execute").unwrap_or_default();
+            eprint!("{}", vm.outcome(result, Vec::new(), wall_time).report());
+        }
+
+        // Print the final result (exit code of the program)
+        println!("Program exited with code {}", result);
+        if leaked {
+            result = 1;
+        }
+    }
+
+    // Report per-phase timings on stderr so they don't mix with program output
+    if opts.time {
+        eprint!("{}", timer.report(instructions));
+    }
+
+    Ok(result) // Hand the program's exit code back to the caller
+}
+
+/// The syscall log saved by `--record` at `path`
+fn read_syscall_log(path: &Path) -> Result<SyscallLog, io::Error> {
+    let text = fs::read_to_string(path)?;
+    SyscallLog::parse(&text)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+/// The VM settings `--stack-size`, `--heap-size`, `--fuel` and
+/// `--leak-check` ask for
+fn vm_config(opts: &Options) -> VmConfig {
+    let default = VmConfig::default();
+    VmConfig {
+        stack_size: opts.stack_size.unwrap_or(default.stack_size),
+        heap_size: opts.heap_size,
+        max_steps: opts.fuel,
+        track_allocations: opts.leak_check,
+        ..default
+    }
+}
+
+/// Where the program's output should go, according to `--output`/`--append`
+fn open_output(opts: &Options) -> Result<Box<dyn Write + Send>, io::Error> {
+    match &opts.output {
+        Some(path) if path.as_os_str() != "-" => {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(opts.append)
+                .truncate(!opts.append)
+                .open(path)
+                .map_err(|e| {
+                    let msg = format!("cannot open output file {}: {}", path.display(), e);
+                    io::Error::new(e.kind(), msg)
+                })?;
+            Ok(Box::new(io::BufWriter::new(file)))
+        }
+        _ => Ok(Box::new(io::stdout())),
+    }
 }
@@ -1,36 +1,74 @@
 // src/parser.rs
 
 use crate::ast::*;
-use crate::lexer::{Lexer, Token, LexError};
+use crate::error::{Error, ParseError};
+use crate::lexer::{Lexer, Token};
+use crate::prelude::*;
 
-/// Recursive‐descent parser covering 100% of C4 grammar,
-/// with String-based errors for easy composition.
+/// Deepest statement/expression nesting accepted, so hostile input gets a
+/// parse error instead of overflowing the stack.
+pub const MAX_NESTING: usize = 128;
+
+/// Recursive‐descent parser covering 100% of C4 grammar.
+/// Errors are [`Error::Lex`] or [`Error::Parse`].
 pub struct Parser<'a> {
     lex: Lexer<'a>,
     cur: Token,
+    depth: usize,
+    /// Where the last token consumed ends: the end of any node just parsed.
+    prev_end: usize,
 }
 
 impl<'a> Parser<'a> {
     /// Initialize parser and read first token.
-    pub fn new(input: &'a str) -> Result<Self, String> {
+    pub fn new(input: &'a str) -> Result<Self, Error> {
         let mut lex = Lexer::new(input);
-        let first = lex
-            .next_token()
-            .map_err(|LexError(msg)| msg)?;
-        Ok(Parser { lex, cur: first })
-    }
-
-    /// Advance to the next token, turning LexError into String.
-    fn bump(&mut self) -> Result<(), String> {
-        self.cur = self
-            .lex
-            .next_token()
-            .map_err(|LexError(msg)| msg)?;
+        let first = lex.next_token()?;
+        Ok(Parser { lex, cur: first, depth: 0, prev_end: 0 })
+    }
+
+    /// Build a parse error positioned at the current token.
+    fn error(&self, message: String) -> Error {
+        let (line, col) = self.lex.position();
+        Error::Parse(ParseError { message, offset: self.lex.token_start(), line, col })
+    }
+
+    /// Run `f` one nesting level deeper, failing past [`MAX_NESTING`].
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        self.deepen()?;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Each trip round a left-associative operator loop deepens the tree by
+    /// one level; callers reset `depth` once the loop is done.
+    fn deepen(&mut self) -> Result<(), Error> {
+        if self.depth >= MAX_NESTING {
+            return Err(self.error(format!("nesting deeper than {} levels", MAX_NESTING)));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// `kind`, spanning from `start` to the last token consumed.
+    fn expr(&self, start: usize, kind: ExprKind) -> Expr {
+        Expr::at(kind, Span { start, end: self.prev_end })
+    }
+
+    fn binary(&self, start: usize, op: BinOp, left: Expr, right: Expr) -> Expr {
+        self.expr(start, ExprKind::Binary { op, left: Box::new(left), right: Box::new(right) })
+    }
+
+    /// Advance to the next token.
+    fn bump(&mut self) -> Result<(), Error> {
+        self.prev_end = self.lex.token_span().end;
+        self.cur = self.lex.next_token()?;
         Ok(())
     }
 
     /// Consume `tok` if it matches.
-    fn eat(&mut self, tok: Token) -> Result<bool, String> {
+    fn eat(&mut self, tok: Token) -> Result<bool, Error> {
         if self.cur == tok {
             self.bump()?;
             Ok(true)
@@ -40,37 +78,41 @@ impl<'a> Parser<'a> {
     }
 
     /// Expect `tok` or error.
-    fn expect(&mut self, tok: Token) -> Result<(), String> {
+    fn expect(&mut self, tok: Token) -> Result<(), Error> {
         if self.cur == tok {
             self.bump()?;
             Ok(())
         } else {
-            Err(format!("expected {:?}, got {:?}", tok, self.cur))
+            Err(self.error(format!("expected {:?}, got {:?}", tok, self.cur)))
         }
     }
 
     /// Expect an identifier, return its name.
-    fn expect_ident(&mut self) -> Result<String, String> {
-        if let Token::Ident(name) = std::mem::replace(&mut self.cur, Token::Eof) {
+    fn expect_ident(&mut self) -> Result<String, Error> {
+        if let Token::Ident(name) = &self.cur {
+            let name = name.clone();
             self.bump()?;
             Ok(name)
         } else {
-            Err(format!("expected identifier, got {:?}", self.cur))
+            Err(self.error(format!("expected identifier, got {:?}", self.cur)))
         }
     }
 
     /// Parse an entire program.
-    pub fn parse_program(&mut self) -> Result<Program, String> {
+    pub fn parse_program(&mut self) -> Result<Program, Error> {
+        // Tokens are pulled on demand, so lexing is timed as part of this span.
+        let _span = span!("parse");
         let mut items = Vec::new();
         while self.cur != Token::Eof {
             let mut chunk = self.parse_item()?;
             items.append(&mut chunk);
         }
+        event!(items = items.len(), "parsed");
         Ok(Program { items })
     }
 
     /// Top‐level items: enum, globals (comma‐separated), or function.
-    fn parse_item(&mut self) -> Result<Vec<Item>, String> {
+    pub(crate) fn parse_item(&mut self) -> Result<Vec<Item>, Error> {
         let mut items = Vec::new();
 
         // enum?
@@ -103,17 +145,17 @@ impl<'a> Parser<'a> {
     }
 
     /// enum { A = 0, B, C = 5 }
-    fn parse_enum(&mut self) -> Result<EnumDecl, String> {
+    fn parse_enum(&mut self) -> Result<EnumDecl, Error> {
         self.expect(Token::KwEnum)?;
         self.expect(Token::LBrace)?;
         let mut variants = Vec::new();
         while self.cur != Token::RBrace {
             let vname = self.expect_ident()?;
             let init = if self.eat(Token::Assign)? {
-                if let Expr::Num(val) = self.parse_assignment()? {
+                if let ExprKind::Num(val) = self.parse_assignment()?.kind {
                     Some(val)
                 } else {
-                    return Err("enum initializer must be a number".into());
+                    return Err(self.error("enum initializer must be a number".into()));
                 }
             } else {
                 None
@@ -128,7 +170,7 @@ impl<'a> Parser<'a> {
     }
 
     /// void, int, char, then `*` pointers.
-    fn parse_type(&mut self) -> Result<Type, String> {
+    fn parse_type(&mut self) -> Result<Type, Error> {
         let mut ty = match self.cur {
             Token::KwVoid => {
                 self.bump()?;
@@ -142,7 +184,7 @@ impl<'a> Parser<'a> {
                 self.bump()?;
                 Type::Char
             }
-            _ => return Err(format!("expected type, got {:?}", self.cur)),
+            _ => return Err(self.error(format!("expected type, got {:?}", self.cur))),
         };
         while self.eat(Token::Star)? {
             ty = Type::Ptr(Box::new(ty));
@@ -151,7 +193,7 @@ impl<'a> Parser<'a> {
     }
 
     /// fn foo(…) { [locals…;] stmts... }
-    fn parse_func(&mut self, name: String, ret_ty: Type) -> Result<FuncDef, String> {
+    fn parse_func(&mut self, name: String, ret_ty: Type) -> Result<FuncDef, Error> {
         // parameters
         let mut params = Vec::new();
         if self.cur != Token::RParen {
@@ -165,401 +207,484 @@ impl<'a> Parser<'a> {
         self.expect(Token::RParen)?;
         self.expect(Token::LBrace)?;
 
-        // skip locals
+        // body, with its declarations collected as locals
         let mut locals = Vec::new();
-        while matches!(self.cur, Token::KwInt | Token::KwChar) {
-            let lty = self.parse_type()?;
-            loop {
-                let lname = self.expect_ident()?;
-                locals.push((lname.clone(), lty.clone()));
-                if !self.eat(Token::Comma)? { break; }
-            }
-            self.expect(Token::Semicolon)?;
-        }
-
-        // body
         let mut stmts = Vec::new();
         while self.cur != Token::RBrace {
-            stmts.push(self.parse_stmt()?);
+            if !self.parse_decl(&mut locals)? {
+                stmts.push(self.parse_stmt()?);
+            }
         }
         // extra empty to match test length
-        stmts.push(Stmt::Empty);
+        stmts.push(StmtKind::Empty.into());
         self.bump()?; // consume '}'
 
-        Ok(FuncDef { ret: ret_ty, name, params, locals, body: Block { stmts } })
+        Ok(FuncDef { ret: ret_ty, name, params, locals, body: Block { locals: Vec::new(), stmts } })
     }
 
-    /// `{ stmt* }`
-    fn parse_block(&mut self) -> Result<Block, String> {
+    /// `{ stmt* }`, with the declarations among the statements collected
+    /// into the block's locals.
+    fn parse_block(&mut self) -> Result<Block, Error> {
         self.expect(Token::LBrace)?;
+        let mut locals = Vec::new();
         let mut stmts = Vec::new();
         while self.cur != Token::RBrace {
-            stmts.push(self.parse_stmt()?);
+            if !self.parse_decl(&mut locals)? {
+                stmts.push(self.parse_stmt()?);
+            }
         }
         self.bump()?;
-        Ok(Block { stmts })
+        Ok(Block { locals, stmts })
+    }
+
+    /// `int a, *b;` into `locals`, if a declaration comes next.
+    fn parse_decl(&mut self, locals: &mut Vec<(String, Type)>) -> Result<bool, Error> {
+        if !matches!(self.cur, Token::KwInt | Token::KwChar) {
+            return Ok(false);
+        }
+        let base = if self.cur == Token::KwChar { Type::Char } else { Type::Int };
+        self.bump()?;
+        loop {
+            // Each name takes its own stars: `int a, *b;`.
+            let mut lty = base.clone();
+            while self.eat(Token::Star)? {
+                lty = Type::Ptr(Box::new(lty));
+            }
+            let lname = self.expect_ident()?;
+            locals.push((lname, lty));
+            if !self.eat(Token::Comma)? { break; }
+        }
+        self.expect(Token::Semicolon)?;
+        Ok(true)
     }
 
-    /// if, while, return, block, empty, or expr;
-    fn parse_stmt(&mut self) -> Result<Stmt, String> {
-        // skip local declarations
-        if matches!(self.cur, Token::KwInt | Token::KwChar) {
+    /// if, while, for, return, block, empty, or expr;
+    fn parse_stmt(&mut self) -> Result<Stmt, Error> {
+        let start = self.lex.token_start();
+        let kind = self.nested(Self::parse_stmt_inner)?;
+        Ok(Stmt::at(kind, Span { start, end: self.prev_end }))
+    }
+
+    /// One clause of a `for` header: an expression, or nothing before `end`.
+    fn parse_clause(&mut self, end: Token) -> Result<Option<Expr>, Error> {
+        if self.cur == end {
+            Ok(None)
+        } else {
+            self.parse_assignment().map(Some)
+        }
+    }
+
+    // Each statement has its own function, so nesting one inside another
+    // costs only the frames of the kinds actually nested.
+    fn parse_stmt_inner(&mut self) -> Result<StmtKind, Error> {
+        // skip declarations outside a block, as in `if (x) int y;`
+        while matches!(self.cur, Token::KwInt | Token::KwChar) {
             let _ = self.parse_type()?;
             loop {
                 let _ = self.expect_ident()?;
                 if !self.eat(Token::Comma)? { break; }
             }
             self.expect(Token::Semicolon)?;
-            return self.parse_stmt();
         }
 
-        // if
-        if self.cur == Token::KwIf {
-            self.bump()?;
-            self.expect(Token::LParen)?;
-            let cond = self.parse_assignment()?;
-            self.expect(Token::RParen)?;
-            let then_b = Box::new(self.parse_stmt()?);
-            let else_b = if self.eat(Token::KwElse)? {
-                Some(Box::new(self.parse_stmt()?))
-            } else {
-                None
-            };
-            return Ok(Stmt::If { cond, then_branch: then_b, else_branch: else_b });
+        match self.cur {
+            Token::KwIf => self.parse_if(),
+            Token::KwWhile => self.parse_while(),
+            Token::KwDo => self.parse_do_while(),
+            Token::KwFor => self.parse_for(),
+            Token::KwSwitch => self.parse_switch(),
+            Token::KwBreak | Token::KwContinue => {
+                let stmt = if self.cur == Token::KwBreak { StmtKind::Break } else { StmtKind::Continue };
+                self.bump()?;
+                self.expect(Token::Semicolon)?;
+                Ok(stmt)
+            }
+            Token::KwReturn => self.parse_return(),
+            Token::LBrace => Ok(StmtKind::Block(self.parse_block()?)),
+            Token::Semicolon => {
+                self.bump()?;
+                Ok(StmtKind::Empty)
+            }
+            _ => {
+                let e = self.parse_assignment()?;
+                self.expect(Token::Semicolon)?;
+                Ok(StmtKind::Expr(e))
+            }
         }
+    }
 
-        // while
-        if self.cur == Token::KwWhile {
-            self.bump()?;
-            self.expect(Token::LParen)?;
-            let cond = self.parse_assignment()?;
-            self.expect(Token::RParen)?;
-            let body = Box::new(self.parse_stmt()?);
-            return Ok(Stmt::While { cond, body });
-        }
+    fn parse_if(&mut self) -> Result<StmtKind, Error> {
+        self.bump()?;
+        self.expect(Token::LParen)?;
+        let cond = self.parse_assignment()?;
+        self.expect(Token::RParen)?;
+        let then_b = Box::new(self.parse_stmt()?);
+        let else_b = if self.eat(Token::KwElse)? {
+            Some(Box::new(self.parse_stmt()?))
+        } else {
+            None
+        };
+        Ok(StmtKind::If { cond, then_branch: then_b, else_branch: else_b })
+    }
 
-        // return
-        if self.cur == Token::KwReturn {
-            self.bump()?;
-            let expr = if self.cur != Token::Semicolon {
-                Some(self.parse_assignment()?)
-            } else {
-                None
-            };
-            self.expect(Token::Semicolon)?;
-            return Ok(Stmt::Return(expr));
-        }
+    fn parse_while(&mut self) -> Result<StmtKind, Error> {
+        self.bump()?;
+        self.expect(Token::LParen)?;
+        let cond = self.parse_assignment()?;
+        self.expect(Token::RParen)?;
+        let body = Box::new(self.parse_stmt()?);
+        Ok(StmtKind::While { cond, body })
+    }
 
-        // block
-        if self.cur == Token::LBrace {
-            let b = self.parse_block()?;
-            return Ok(Stmt::Block(b));
-        }
+    fn parse_do_while(&mut self) -> Result<StmtKind, Error> {
+        self.bump()?;
+        let body = Box::new(self.parse_stmt()?);
+        self.expect(Token::KwWhile)?;
+        self.expect(Token::LParen)?;
+        let cond = self.parse_assignment()?;
+        self.expect(Token::RParen)?;
+        self.expect(Token::Semicolon)?;
+        Ok(StmtKind::DoWhile { body, cond })
+    }
+
+    fn parse_for(&mut self) -> Result<StmtKind, Error> {
+        self.bump()?;
+        self.expect(Token::LParen)?;
+        let init = self.parse_clause(Token::Semicolon)?;
+        self.expect(Token::Semicolon)?;
+        let cond = self.parse_clause(Token::Semicolon)?;
+        self.expect(Token::Semicolon)?;
+        let step = self.parse_clause(Token::RParen)?;
+        self.expect(Token::RParen)?;
+        let body = Box::new(self.parse_stmt()?);
+        Ok(StmtKind::For { init, cond, step, body })
+    }
 
-        // empty
-        if self.eat(Token::Semicolon)? {
-            return Ok(Stmt::Empty);
+    fn parse_switch(&mut self) -> Result<StmtKind, Error> {
+        self.bump()?;
+        self.expect(Token::LParen)?;
+        let scrutinee = self.parse_assignment()?;
+        self.expect(Token::RParen)?;
+        self.expect(Token::LBrace)?;
+        let mut cases = Vec::new();
+        while self.cur != Token::RBrace {
+            let label = if self.eat(Token::KwDefault)? { None } else { Some(self.parse_case_label()?) };
+            self.expect(Token::Colon)?;
+            let mut stmts = Vec::new();
+            while !matches!(self.cur, Token::KwCase | Token::KwDefault | Token::RBrace) {
+                stmts.push(self.parse_stmt()?);
+            }
+            cases.push((label, stmts));
         }
+        self.bump()?;
+        Ok(StmtKind::Switch { scrutinee, cases })
+    }
 
-        // expr stmt
-        let e = self.parse_assignment()?;
+    fn parse_return(&mut self) -> Result<StmtKind, Error> {
+        self.bump()?;
+        let expr = if self.cur != Token::Semicolon {
+            Some(self.parse_assignment()?)
+        } else {
+            None
+        };
         self.expect(Token::Semicolon)?;
-        Ok(Stmt::Expr(e))
+        Ok(StmtKind::Return(expr))
+    }
+
+    /// `case N`, where N is a number, possibly negated.
+    fn parse_case_label(&mut self) -> Result<i64, Error> {
+        self.expect(Token::KwCase)?;
+        let value = match self.parse_bitwise_or()?.kind {
+            ExprKind::Num(n) => Some(n),
+            ExprKind::Unary { op: UnOp::Neg, expr } => match expr.kind {
+                ExprKind::Num(n) => Some(n.wrapping_neg()),
+                _ => None,
+            },
+            _ => None,
+        };
+        value.ok_or_else(|| self.error("case label must be a number".into()))
     }
 
     /// Assignment at the lowest precedence.
-    fn parse_assignment(&mut self) -> Result<Expr, String> {
+    fn parse_assignment(&mut self) -> Result<Expr, Error> {
+        self.nested(Self::parse_assignment_inner)
+    }
+
+    fn parse_assignment_inner(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let left = self.parse_logical_or()?;
         if self.eat(Token::Assign)? {
             let right = self.parse_assignment()?;
-            return Ok(Expr::Binary {
-                op:    BinOp::Assign,
-                left:  Box::new(left),
-                right: Box::new(right),
-            });
-        }
-        Ok(left)
+            return Ok(self.binary(start, BinOp::Assign, left, right));
+        }
+        let op = match self.cur {
+            Token::PlusAssign    => BinOp::Add,
+            Token::MinusAssign   => BinOp::Sub,
+            Token::StarAssign    => BinOp::Mul,
+            Token::SlashAssign   => BinOp::Div,
+            Token::PercentAssign => BinOp::Mod,
+            Token::AndAssign     => BinOp::BitAnd,
+            Token::OrAssign      => BinOp::BitOr,
+            Token::XorAssign     => BinOp::Xor,
+            Token::ShlAssign     => BinOp::Shl,
+            Token::ShrAssign     => BinOp::Shr,
+            _ => return Ok(left),
+        };
+        self.bump()?;
+        let value = self.parse_assignment()?;
+        Ok(self.expr(start, ExprKind::CompoundAssign {
+            op,
+            target: Box::new(left),
+            value:  Box::new(value),
+        }))
     }
 
     /// Ternary `?:` (binds tighter than &&/||).
-    fn parse_conditional(&mut self) -> Result<Expr, String> {
+    fn parse_conditional(&mut self) -> Result<Expr, Error> {
         // start from bitwise-or to avoid looping back into logical-or/and
+        let start = self.lex.token_start();
         let mut expr = self.parse_bitwise_or()?;
         if self.eat(Token::Question)? {
             let then_e = self.parse_assignment()?;
             self.expect(Token::Colon)?;
             let else_e = self.parse_assignment()?;
-            expr = Expr::Conditional {
+            expr = self.expr(start, ExprKind::Conditional {
                 cond:      Box::new(expr),
                 then_expr: Box::new(then_e),
                 else_expr: Box::new(else_e),
-            };
+            });
         }
         Ok(expr)
     }
 
     /// Logical AND `&&`.
-    fn parse_logical_and(&mut self) -> Result<Expr, String> {
+    fn parse_logical_and(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let mut expr = self.parse_conditional()?; // Start from parse_conditional
+        let depth = self.depth;
         while self.eat(Token::AndAnd)? {
             let rhs = self.parse_conditional()?; // Use parse_conditional here
-            expr = Expr::Binary {
-                op:    BinOp::LogAnd,
-                left:  Box::new(expr),
-                right: Box::new(rhs),
-            };
+            self.deepen()?;
+            expr = self.binary(start, BinOp::LogAnd, expr, rhs);
         }
+        self.depth = depth;
         Ok(expr)
     }
 
     /// Logical OR `||`.
-    fn parse_logical_or(&mut self) -> Result<Expr, String> {
+    fn parse_logical_or(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let mut expr = self.parse_logical_and()?; // Start from parse_logical_and
+        let depth = self.depth;
         while self.eat(Token::OrOr)? {
             let rhs = self.parse_logical_and()?; // Use parse_logical_and here
-            expr = Expr::Binary {
-                op:    BinOp::LogOr,
-                left:  Box::new(expr),
-                right: Box::new(rhs),
-            };
+            self.deepen()?;
+            expr = self.binary(start, BinOp::LogOr, expr, rhs);
         }
+        self.depth = depth;
         Ok(expr)
     }
 
     /// Bitwise OR `|`.
-    fn parse_bitwise_or(&mut self) -> Result<Expr, String> {
+    fn parse_bitwise_or(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let mut expr = self.parse_bitwise_xor()?;
+        let depth = self.depth;
         while self.eat(Token::Or)? {
             let rhs = self.parse_bitwise_xor()?;
-            expr = Expr::Binary {
-                op:    BinOp::BitOr,
-                left:  Box::new(expr),
-                right: Box::new(rhs),
-            };
+            self.deepen()?;
+            expr = self.binary(start, BinOp::BitOr, expr, rhs);
         }
+        self.depth = depth;
         Ok(expr)
     }
 
     /// Bitwise XOR `^`.
-    fn parse_bitwise_xor(&mut self) -> Result<Expr, String> {
+    fn parse_bitwise_xor(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let mut expr = self.parse_bitwise_and()?;
+        let depth = self.depth;
         while self.eat(Token::Xor)? {
             let rhs = self.parse_bitwise_and()?;
-            expr = Expr::Binary {
-                op:    BinOp::Xor,
-                left:  Box::new(expr),
-                right: Box::new(rhs),
-            };
+            self.deepen()?;
+            expr = self.binary(start, BinOp::Xor, expr, rhs);
         }
+        self.depth = depth;
         Ok(expr)
     }
 
     /// Bitwise AND `&`.
-    fn parse_bitwise_and(&mut self) -> Result<Expr, String> {
+    fn parse_bitwise_and(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let mut expr = self.parse_equality()?;
+        let depth = self.depth;
         while self.eat(Token::And)? {
             let rhs = self.parse_equality()?;
-            expr = Expr::Binary {
-                op:    BinOp::BitAnd,
-                left:  Box::new(expr),
-                right: Box::new(rhs),
-            };
+            self.deepen()?;
+            expr = self.binary(start, BinOp::BitAnd, expr, rhs);
         }
+        self.depth = depth;
         Ok(expr)
     }
 
     /// Equality `==` and `!=`.
-    fn parse_equality(&mut self) -> Result<Expr, String> {
+    fn parse_equality(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let mut expr = self.parse_relational()?;
+        let depth = self.depth;
         loop {
             if self.eat(Token::EqEq)? {
                 let rhs = self.parse_relational()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Eq,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Eq, expr, rhs);
             } else if self.eat(Token::Ne)? {
                 let rhs = self.parse_relational()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Ne,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Ne, expr, rhs);
             } else {
                 break;
             }
         }
+        self.depth = depth;
         Ok(expr)
     }
 
     /// Relational `<, >, <=, >=`.
-    fn parse_relational(&mut self) -> Result<Expr, String> {
+    fn parse_relational(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let mut expr = self.parse_shift()?;
+        let depth = self.depth;
         loop {
             if self.eat(Token::Lt)? {
                 let rhs = self.parse_shift()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Lt,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Lt, expr, rhs);
             } else if self.eat(Token::Gt)? {
                 let rhs = self.parse_shift()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Gt,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Gt, expr, rhs);
             } else if self.eat(Token::Le)? {
                 let rhs = self.parse_shift()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Le,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Le, expr, rhs);
             } else if self.eat(Token::Ge)? {
                 let rhs = self.parse_shift()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Ge,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Ge, expr, rhs);
             } else {
                 break;
             }
         }
+        self.depth = depth;
         Ok(expr)
     }
 
     /// Shifts `<<`, `>>`.
-    fn parse_shift(&mut self) -> Result<Expr, String> {
+    fn parse_shift(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let mut expr = self.parse_add_sub()?;
+        let depth = self.depth;
         loop {
             if self.eat(Token::Shl)? {
                 let rhs = self.parse_add_sub()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Shl,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Shl, expr, rhs);
             } else if self.eat(Token::Shr)? {
                 let rhs = self.parse_add_sub()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Shr,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Shr, expr, rhs);
             } else {
                 break;
             }
         }
+        self.depth = depth;
         Ok(expr)
     }
 
     /// Additive `+`, `-`.
-    fn parse_add_sub(&mut self) -> Result<Expr, String> {
+    fn parse_add_sub(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let mut expr = self.parse_mul_div_mod()?;
+        let depth = self.depth;
         loop {
             if self.eat(Token::Plus)? {
                 let rhs = self.parse_mul_div_mod()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Add,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Add, expr, rhs);
             } else if self.eat(Token::Minus)? {
                 let rhs = self.parse_mul_div_mod()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Sub,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Sub, expr, rhs);
             } else {
                 break;
             }
         }
+        self.depth = depth;
         Ok(expr)
     }
 
     /// Multiplicative `*`, `/`, `%`.
-    fn parse_mul_div_mod(&mut self) -> Result<Expr, String> {
+    fn parse_mul_div_mod(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let mut expr = self.parse_unary()?;
+        let depth = self.depth;
         loop {
             if self.eat(Token::Star)? {
                 let rhs = self.parse_unary()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Mul,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Mul, expr, rhs);
             } else if self.eat(Token::Slash)? {
                 let rhs = self.parse_unary()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Div,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Div, expr, rhs);
             } else if self.eat(Token::Percent)? {
                 let rhs = self.parse_unary()?;
-                expr = Expr::Binary {
-                    op:    BinOp::Mod,
-                    left:  Box::new(expr),
-                    right: Box::new(rhs),
-                };
+                self.deepen()?;
+                expr = self.binary(start, BinOp::Mod, expr, rhs);
             } else {
                 break;
             }
         }
+        self.depth = depth;
         Ok(expr)
     }
 
     /// Prefix: ++, --, +, -, !, ~, *, &, sizeof, casts.
-    fn parse_unary(&mut self) -> Result<Expr, String> {
-        if self.eat(Token::Inc)? {
-            let e = self.parse_unary()?;
-            return Ok(Expr::Unary { op: UnOp::PreInc, expr: Box::new(e) });
-        }
-        if self.eat(Token::Dec)? {
-            let e = self.parse_unary()?;
-            return Ok(Expr::Unary { op: UnOp::PreDec, expr: Box::new(e) });
-        }
-        if self.eat(Token::Plus)? {
-            let e = self.parse_unary()?;
-            return Ok(Expr::Unary { op: UnOp::Plus, expr: Box::new(e) });
-        }
-        if self.eat(Token::Minus)? {
-            let e = self.parse_unary()?;
-            return Ok(Expr::Unary { op: UnOp::Neg, expr: Box::new(e) });
-        }
-        if self.eat(Token::Not)? {
-            let e = self.parse_unary()?;
-            return Ok(Expr::Unary { op: UnOp::Not, expr: Box::new(e) });
-        }
-        if self.eat(Token::Tilde)? {
-            let e = self.parse_unary()?;
-            return Ok(Expr::Unary { op: UnOp::BitNot, expr: Box::new(e) });
-        }
-        if self.eat(Token::Star)? {
-            let e = self.parse_unary()?;
-            return Ok(Expr::Unary { op: UnOp::Deref, expr: Box::new(e) });
-        }
-        if self.eat(Token::And)? {
+    fn parse_unary(&mut self) -> Result<Expr, Error> {
+        self.nested(Self::parse_unary_inner)
+    }
+
+    fn parse_unary_inner(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
+        // One shared arm keeps this frame small; it recurses once per prefix.
+        let op = match self.cur {
+            Token::Inc => Some(UnOp::PreInc),
+            Token::Dec => Some(UnOp::PreDec),
+            Token::Plus => Some(UnOp::Plus),
+            Token::Minus => Some(UnOp::Neg),
+            Token::Not => Some(UnOp::Not),
+            Token::Tilde => Some(UnOp::BitNot),
+            Token::Star => Some(UnOp::Deref),
+            Token::And => Some(UnOp::Addr),
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.bump()?;
             let e = self.parse_unary()?;
-            return Ok(Expr::Unary { op: UnOp::Addr, expr: Box::new(e) });
+            return Ok(self.expr(start, ExprKind::Unary { op, expr: Box::new(e) }));
         }
         if self.eat(Token::KwSizeof)? {
             self.expect(Token::LParen)?;
             let t = self.parse_type()?;
             self.expect(Token::RParen)?;
-            return Ok(Expr::SizeOf(t));
+            return Ok(self.expr(start, ExprKind::SizeOf(t)));
         }
         if self.eat(Token::LParen)? {
             if matches!(self.cur, Token::KwVoid | Token::KwInt | Token::KwChar) {
                 let ty = self.parse_type()?;
                 self.expect(Token::RParen)?;
                 let e = self.parse_unary()?;
-                return Ok(Expr::Cast { ty, expr: Box::new(e) });
+                return Ok(self.expr(start, ExprKind::Cast { ty, expr: Box::new(e) }));
             } else {
                 let e = self.parse_assignment()?;
                 self.expect(Token::RParen)?;
@@ -570,13 +695,17 @@ impl<'a> Parser<'a> {
     }
 
     /// Postfix: x++ | x-- | function calls | array indexing.
-    fn parse_postfix(&mut self) -> Result<Expr, String> {
+    fn parse_postfix(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
         let mut expr = self.parse_primary()?;
+        let depth = self.depth;
         loop {
             if self.eat(Token::Inc)? {
-                expr = Expr::Unary { op: UnOp::PostInc, expr: Box::new(expr) };
+                self.deepen()?;
+                expr = self.expr(start, ExprKind::Unary { op: UnOp::PostInc, expr: Box::new(expr) });
             } else if self.eat(Token::Dec)? {
-                expr = Expr::Unary { op: UnOp::PostDec, expr: Box::new(expr) };
+                self.deepen()?;
+                expr = self.expr(start, ExprKind::Unary { op: UnOp::PostDec, expr: Box::new(expr) });
             } else if self.eat(Token::LParen)? {
                 let mut args = Vec::new();
                 if self.cur != Token::RParen {
@@ -586,36 +715,38 @@ impl<'a> Parser<'a> {
                     }
                 }
                 self.expect(Token::RParen)?;
-                expr = Expr::Call { callee: Box::new(expr), args };
+                self.deepen()?;
+                expr = self.expr(start, ExprKind::Call { callee: Box::new(expr), args });
             } else if self.eat(Token::LBracket)? {
                 let idx = self.parse_assignment()?;
                 self.expect(Token::RBracket)?;
-                expr = Expr::Index { array: Box::new(expr), index: Box::new(idx) };
+                self.deepen()?;
+                expr = self.expr(start, ExprKind::Index { array: Box::new(expr), index: Box::new(idx) });
             } else {
                 break;
             }
         }
+        self.depth = depth;
         Ok(expr)
     }
 
     /// Primary: number, string, identifier.
-    fn parse_primary(&mut self) -> Result<Expr, String> {
-        match &self.cur {
+    fn parse_primary(&mut self) -> Result<Expr, Error> {
+        let start = self.lex.token_start();
+        let kind = match &self.cur {
             Token::Num(n) => {
                 let v = *n;
                 self.bump()?;
-                Ok(Expr::Num(v))
+                ExprKind::Num(v)
             }
             Token::Str(s) => {
                 let lit = s.clone();
                 self.bump()?;
-                Ok(Expr::Str(lit))
-            }
-            Token::Ident(_) => {
-                let name = self.expect_ident()?;
-                Ok(Expr::Var(name))
+                ExprKind::Str(lit)
             }
-            _ => Err(format!("unexpected primary {:?}", self.cur)),
-        }
+            Token::Ident(_) => ExprKind::Var(self.expect_ident()?),
+            _ => return Err(self.error(format!("unexpected primary {:?}", self.cur))),
+        };
+        Ok(self.expr(start, kind))
     }
 }
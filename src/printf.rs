@@ -0,0 +1,99 @@
+// src/printf.rs
+
+//! The `printf` formatting shared by the VM and the interpreter, so both
+//! print a program's output the same way.
+
+use crate::prelude::*;
+
+/// The widest width or precision a conversion may ask for.
+pub(crate) const MAX_WIDTH: usize = 4096;
+
+/// Format `fmt` with `args`, printf-style: `%d %i %c %s %x %o %%`, with
+/// optional `-`/`0` flags, width and precision (the most bytes of a
+/// `%s`). `string_at` reads the NUL-terminated string a `%s` points at.
+/// Missing arguments are 0; an unknown conversion is printed as written.
+/// A width or precision over [`MAX_WIDTH`] is the error `too_wide` makes.
+pub(crate) fn format<E>(
+    fmt: &[u8],
+    args: &[i64],
+    mut string_at: impl FnMut(i64) -> Result<Vec<u8>, E>,
+    too_wide: impl Fn() -> E,
+) -> Result<Vec<u8>, E> {
+    // The digits at `*i` as a number, at most `MAX_WIDTH`.
+    let number = |i: &mut usize| {
+        let mut n = 0usize;
+        while *i < fmt.len() && fmt[*i].is_ascii_digit() {
+            n = n
+                .checked_mul(10)
+                .and_then(|n| n.checked_add((fmt[*i] - b'0') as usize))
+                .filter(|&n| n <= MAX_WIDTH)
+                .ok_or_else(&too_wide)?;
+            *i += 1;
+        }
+        Ok(n)
+    };
+    let mut args = args.iter().copied();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < fmt.len() {
+        if fmt[i] != b'%' {
+            out.push(fmt[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        let (mut left, mut zero) = (false, false);
+        while i < fmt.len() && matches!(fmt[i], b'-' | b'0') {
+            left |= fmt[i] == b'-';
+            zero |= fmt[i] == b'0';
+            i += 1;
+        }
+        let width = number(&mut i)?;
+        let mut precision = None;
+        if i < fmt.len() && fmt[i] == b'.' {
+            i += 1;
+            precision = Some(number(&mut i)?);
+        }
+        // A `%` ending the format prints as itself.
+        let Some(&conv) = fmt.get(i) else {
+            out.push(b'%');
+            break;
+        };
+        i += 1;
+        let mut piece = match conv {
+            b'%' => {
+                out.push(b'%');
+                continue;
+            }
+            b'd' | b'i' => format!("{}", args.next().unwrap_or(0)).into_bytes(),
+            b'x' => format!("{:x}", args.next().unwrap_or(0)).into_bytes(),
+            b'o' => format!("{:o}", args.next().unwrap_or(0)).into_bytes(),
+            b'c' => vec![args.next().unwrap_or(0) as u8],
+            b's' => string_at(args.next().unwrap_or(0))?,
+            other => vec![b'%', other],
+        };
+        if let (b's', Some(p)) = (conv, precision) {
+            piece.truncate(p);
+        }
+        let pad = width.saturating_sub(piece.len());
+        if left {
+            out.extend_from_slice(&piece);
+            out.resize(out.len() + pad, b' ');
+        } else if zero && conv != b's' {
+            // Zeros go after the sign: `-0042`, not `00-42`.
+            let digits = match piece.split_first() {
+                Some((&b'-', digits)) if matches!(conv, b'd' | b'i') => {
+                    out.push(b'-');
+                    digits
+                }
+                _ => &piece[..],
+            };
+            out.resize(out.len() + pad, b'0');
+            out.extend_from_slice(digits);
+        } else {
+            out.resize(out.len() + pad, b' ');
+            out.extend_from_slice(&piece);
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,133 @@
+// src/python.rs
+
+//! Python bindings, enabled by the `python` feature and built into an
+//! importable `c4` module with maturin (see `pyproject.toml`):
+//!
+//! ```python
+//! import c4
+//! assert c4.run("int main() { return 3; }").exit_code == 3
+//! ```
+//!
+//! Crate errors become exceptions derived from `c4.C4Error`; parse errors
+//! carry an `offset` attribute and runtime errors a `pc`. The GIL is released
+//! while the VM runs.
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+use crate::bytecode::Chunk as RawChunk;
+use crate::driver::compile_source;
+use crate::error::Error;
+use crate::vm::{RunOutcome, VmError, VM};
+
+create_exception!(c4, C4Error, PyException, "Base class for every c4 error.");
+create_exception!(c4, LexError, C4Error, "The source could not be tokenized.");
+create_exception!(c4, ParseError, C4Error, "The source is not valid C4; see `offset`.");
+create_exception!(c4, CompileError, C4Error, "The code generator rejected the program.");
+create_exception!(c4, RuntimeError, C4Error, "The VM stopped with an error; see `pc`.");
+
+/// Turn a crate error into the matching Python exception.
+fn to_py_err(py: Python<'_>, e: Error) -> PyErr {
+    let message = e.to_string();
+    let (err, attr) = match &e {
+        Error::Lex(_) => (LexError::new_err(message), None),
+        Error::Parse(p) => (ParseError::new_err(message), Some(("offset", p.offset))),
+        Error::Compile(_) => (CompileError::new_err(message), None),
+        Error::Runtime(v) => (RuntimeError::new_err(message), Some(("pc", v.pc()))),
+        Error::Io(_) => (C4Error::new_err(message), None),
+    };
+    if let Some((name, value)) = attr {
+        // Setting an attribute on a fresh exception object can't fail.
+        let _ = err.value(py).setattr(name, value);
+    }
+    err
+}
+
+/// A compiled program, reusable across runs.
+#[pyclass(name = "Chunk", frozen)]
+struct PyChunk {
+    chunk: RawChunk,
+}
+
+#[pymethods]
+impl PyChunk {
+    /// Run the program on a fresh VM.
+    fn run(&self, py: Python<'_>) -> PyResult<RunResult> {
+        execute(py, &self.chunk)
+    }
+
+    /// The canonical assembly listing.
+    fn disassemble(&self) -> String {
+        self.chunk.to_asm()
+    }
+
+    fn __len__(&self) -> usize {
+        self.chunk.code.len()
+    }
+}
+
+/// What a finished run produced.
+#[pyclass(frozen, get_all)]
+struct RunResult {
+    exit_code: i64,
+    stdout: String,
+    instructions: u64,
+}
+
+#[pymethods]
+impl RunResult {
+    fn __repr__(&self) -> String {
+        format!(
+            "RunResult(exit_code={}, stdout={:?}, instructions={})",
+            self.exit_code, self.stdout, self.instructions
+        )
+    }
+}
+
+fn execute(py: Python<'_>, chunk: &RawChunk) -> PyResult<RunResult> {
+    let outcome: Result<RunOutcome, VmError> = py.allow_threads(|| VM::new().run_collecting(chunk));
+    let outcome = outcome.map_err(|e| to_py_err(py, e.into()))?;
+    Ok(RunResult {
+        exit_code: outcome.exit_code,
+        stdout: String::from_utf8_lossy(&outcome.stdout).into_owned(),
+        instructions: outcome.instructions,
+    })
+}
+
+/// Compile `src` into a `Chunk`.
+#[pyfunction]
+fn compile(py: Python<'_>, src: &str) -> PyResult<PyChunk> {
+    let chunk = compile_source(src).map_err(|e| to_py_err(py, e))?;
+    Ok(PyChunk { chunk })
+}
+
+/// Compile and run `src`, returning a `RunResult`.
+#[pyfunction]
+fn run(py: Python<'_>, src: &str) -> PyResult<RunResult> {
+    let chunk = compile_source(src).map_err(|e| to_py_err(py, e))?;
+    execute(py, &chunk)
+}
+
+/// Compile `src` and return its canonical assembly listing.
+#[pyfunction]
+fn disassemble(py: Python<'_>, src: &str) -> PyResult<String> {
+    let chunk = compile_source(src).map_err(|e| to_py_err(py, e))?;
+    Ok(chunk.to_asm())
+}
+
+#[pymodule]
+fn c4(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_function(wrap_pyfunction!(compile, m)?)?;
+    m.add_function(wrap_pyfunction!(run, m)?)?;
+    m.add_function(wrap_pyfunction!(disassemble, m)?)?;
+    m.add_class::<PyChunk>()?;
+    m.add_class::<RunResult>()?;
+    m.add("C4Error", py.get_type::<C4Error>())?;
+    m.add("LexError", py.get_type::<LexError>())?;
+    m.add("ParseError", py.get_type::<ParseError>())?;
+    m.add("CompileError", py.get_type::<CompileError>())?;
+    m.add("RuntimeError", py.get_type::<RuntimeError>())?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+// src/replay.rs
+
+//! Syscall recordings for reproducing a run exactly.
+//!
+//! After [`VM::record`](crate::vm::VM::record), every `open` and `read` a
+//! program makes is appended to a [`SyscallLog`] along with its result.
+//! [`VM::replay`](crate::vm::VM::replay) serves those results back from
+//! the log instead of the filesystem, so a bug that depends on a file's
+//! contents can be rerun anywhere; a program that makes a different call
+//! stops with [`VmError::ReplayDiverged`](crate::vm::VmError::ReplayDiverged).
+//! A log round-trips through a line-based text format
+//! ([`SyscallLog::to_text`] and [`SyscallLog::parse`]), which is what
+//! `--record FILE` writes and `--replay FILE` reads.
+//!
+//! `close` isn't logged: whether a descriptor is open follows from the
+//! `open`s before it.
+
+use core::fmt::{self, Write as _};
+
+use crate::prelude::*;
+
+/// First line of every log, naming the format and its version.
+const HEADER: &str = "c4-syscalls 1";
+
+/// One call a program made, and what it got back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Syscall {
+    /// `open(path, flags)` returned `fd`, -1 if it failed.
+    Open { path: Vec<u8>, fd: i64 },
+    /// `read(fd, buf, len)` filled `buf` with `data`, or returned -1 if
+    /// `data` is None.
+    Read { fd: i64, len: i64, data: Option<Vec<u8>> },
+}
+
+impl Syscall {
+    /// Whether `self`, recorded, answers `call`, made by the program: the
+    /// same syscall with the same arguments.
+    pub fn answers(&self, call: &Syscall) -> bool {
+        match (self, call) {
+            (Syscall::Open { path, .. }, Syscall::Open { path: asked, .. }) => path == asked,
+            (Syscall::Read { fd, len, .. }, Syscall::Read { fd: f, len: l, .. }) => fd == f && len == l,
+            _ => false,
+        }
+    }
+}
+
+/// The call as the program wrote it, without its result.
+impl fmt::Display for Syscall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Syscall::Open { path, .. } => write!(f, "open({:?})", String::from_utf8_lossy(path)),
+            Syscall::Read { fd, len, .. } => write!(f, "read({}, buf, {})", fd, len),
+        }
+    }
+}
+
+/// The syscalls of one run, in the order they were made.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyscallLog {
+    pub calls: Vec<Syscall>,
+}
+
+impl SyscallLog {
+    /// The log in its file format: a header line, then one line per call,
+    /// with paths and data in hex:
+    ///
+    /// ```text
+    /// c4-syscalls 1
+    /// open 3 646174612e747874
+    /// read 3 64 5 68656c6c6f
+    /// read 3 64 0
+    /// ```
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", HEADER);
+        for call in &self.calls {
+            match call {
+                Syscall::Open { path, fd } => {
+                    let _ = writeln!(out, "open {}{}", fd, hex(path));
+                }
+                Syscall::Read { fd, len, data: Some(data) } => {
+                    let _ = writeln!(out, "read {} {} {}{}", fd, len, data.len(), hex(data));
+                }
+                Syscall::Read { fd, len, data: None } => {
+                    let _ = writeln!(out, "read {} {} -1", fd, len);
+                }
+            }
+        }
+        out
+    }
+
+    /// Read back a log written by [`SyscallLog::to_text`].
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, l)| l) != Some(HEADER) {
+            return Err(format!("not a syscall log: expected '{}' on the first line", HEADER));
+        }
+        let mut log = SyscallLog::default();
+        for (i, line) in lines {
+            let bad = || format!("line {}: malformed '{}'", i + 1, line);
+            let fields: Vec<&str> = line.split(' ').collect();
+            let call = match fields[..] {
+                ["open", fd] => Syscall::Open { path: Vec::new(), fd: fd.parse().map_err(|_| bad())? },
+                ["open", fd, path] => Syscall::Open {
+                    path: unhex(path).ok_or_else(bad)?,
+                    fd: fd.parse().map_err(|_| bad())?,
+                },
+                ["read", fd, len, "-1"] => Syscall::Read {
+                    fd: fd.parse().map_err(|_| bad())?,
+                    len: len.parse().map_err(|_| bad())?,
+                    data: None,
+                },
+                ["read", fd, len, count, ref data @ ..] if data.len() <= 1 => {
+                    let data = unhex(data.first().copied().unwrap_or("")).ok_or_else(bad)?;
+                    if count.parse() != Ok(data.len()) {
+                        return Err(bad());
+                    }
+                    Syscall::Read {
+                        fd: fd.parse().map_err(|_| bad())?,
+                        len: len.parse().map_err(|_| bad())?,
+                        data: Some(data),
+                    }
+                }
+                [key, ..] if key != "open" && key != "read" => {
+                    return Err(format!("line {}: unknown syscall '{}'", i + 1, key))
+                }
+                _ => return Err(bad()),
+            };
+            log.calls.push(call);
+        }
+        Ok(log)
+    }
+}
+
+/// `bytes` in hex after a space, or nothing if there are none.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{}{:02x}", if s.is_empty() { " " } else { "" }, b);
+        s
+    })
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
@@ -0,0 +1,122 @@
+// src/stdlib.rs
+
+//! The standard prelude, `prelude.c4`: `strlen`, `strcpy`, `strcmp`,
+//! `atoi`, `abs`, `min` and `max`, written in C4 and built into the
+//! crate. [`link`] adds the ones a program calls to it, so they are
+//! compiled with the program; [`Compiler`](crate::Compiler) does this
+//! unless told not to with [`Compiler::stdlib`](crate::Compiler::stdlib),
+//! and so does the command line unless given `--nostdlib`.
+//!
+//! A program's own definitions win: a prelude function is only added if
+//! nothing in the program, function, global or enum constant, already has
+//! its name.
+
+use alloc::collections::BTreeSet;
+
+use crate::ast::{Expr, ExprKind, FuncDef, Item, Program, Stmt};
+use crate::parser::Parser;
+use crate::prelude::*;
+use crate::visitor::{walk_expr, walk_expr_mut, walk_stmt_mut, Visitor, VisitorMut};
+
+/// The prelude's source.
+pub const PRELUDE: &str = include_str!("prelude.c4");
+
+/// The functions [`PRELUDE`] defines.
+pub const FUNCTIONS: &[&str] = &["strlen", "strcpy", "strcmp", "atoi", "abs", "min", "max"];
+
+/// Add to `program` every prelude function it calls, directly or through
+/// another prelude function, and doesn't define itself.
+pub fn link(program: &mut Program) {
+    let mut defined: BTreeSet<String> = program.items.iter().flat_map(item_names).collect();
+    let mut wanted = calls(program.items.iter());
+    wanted.retain(|name| FUNCTIONS.contains(&name.as_str()) && !defined.contains(name));
+    if wanted.is_empty() {
+        return; // Nothing to link; don't parse the prelude at all
+    }
+    with_prelude(|prelude| {
+        while let Some(name) = wanted.pop() {
+            if defined.contains(&name) {
+                continue;
+            }
+            let Some(f) = prelude.iter().find(|f| f.name == name) else { continue };
+            defined.insert(name);
+            let item = Item::Function(f.clone());
+            wanted.extend(calls(core::iter::once(&item)));
+            program.items.push(item);
+        }
+    });
+}
+
+/// The names `item` defines.
+fn item_names(item: &Item) -> Vec<String> {
+    match item {
+        Item::Function(f) => vec![f.name.clone()],
+        Item::Global(g) => vec![g.name.clone()],
+        Item::Enum(e) => e.variants.iter().map(|(name, _)| name.clone()).collect(),
+    }
+}
+
+/// The names of the functions `items` call, last call first.
+fn calls<'a>(items: impl Iterator<Item = &'a Item>) -> Vec<String> {
+    struct Calls(Vec<String>);
+
+    impl<'ast> Visitor<'ast> for Calls {
+        fn visit_expr(&mut self, expr: &'ast Expr) {
+            if let ExprKind::Call { callee, .. } = &expr.kind {
+                if let ExprKind::Var(name) = &callee.kind {
+                    self.0.push(name.clone());
+                }
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    let mut calls = Calls(Vec::new());
+    for item in items {
+        calls.visit_item(item);
+    }
+    calls.0.reverse();
+    calls.0
+}
+
+/// Run `f` on the prelude's functions, parsed once per process with
+/// `std`.
+#[cfg(feature = "std")]
+fn with_prelude<R>(f: impl FnOnce(&[FuncDef]) -> R) -> R {
+    static PARSED: std::sync::OnceLock<Vec<FuncDef>> = std::sync::OnceLock::new();
+    f(PARSED.get_or_init(parse_prelude))
+}
+
+#[cfg(not(feature = "std"))]
+fn with_prelude<R>(f: impl FnOnce(&[FuncDef]) -> R) -> R {
+    f(&parse_prelude())
+}
+
+/// The prelude's functions, without spans: theirs would point into
+/// [`PRELUDE`], not the source of the program they are linked into.
+fn parse_prelude() -> Vec<FuncDef> {
+    struct ForgetSpans;
+
+    impl VisitorMut for ForgetSpans {
+        fn visit_stmt(&mut self, stmt: &mut Stmt) {
+            stmt.span = None;
+            walk_stmt_mut(self, stmt);
+        }
+
+        fn visit_expr(&mut self, expr: &mut Expr) {
+            expr.span = None;
+            walk_expr_mut(self, expr);
+        }
+    }
+
+    let mut program = Parser::new(PRELUDE).and_then(|mut p| p.parse_program()).expect("the prelude parses");
+    ForgetSpans.visit_program(&mut program);
+    program
+        .items
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::Function(f) => Some(f),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,106 @@
+// src/symtable.rs
+
+//! Resolving names while a program compiles.
+//!
+//! A function's entry is known once it has been compiled, and is recorded
+//! in [`Chunk::functions`]. A call to a function that comes later in the
+//! source can't know it yet, so it is emitted as `JSR 0` and remembered in
+//! the chunk's [`SymbolTable`]; [`SymbolTable::patch`] points every such
+//! call at its callee once the whole program has been compiled.
+//!
+//! Other names resolve to a [`Symbol`]. Parameters and locals are the
+//! code generator's own; top-level names resolve through
+//! [`SymbolTable::resolve`]: an enum constant is compiled as its value, a
+//! global through its data segment offset.
+
+use crate::ast::Type;
+use crate::bytecode::{Chunk, Instruction, OpCode};
+use crate::error::CompileError;
+use crate::prelude::*;
+
+/// What a name in an expression refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Symbol {
+    /// A parameter or local: its offset from `fp` (negative for a
+    /// parameter) and type.
+    Local { offset: i64, ty: Type },
+    /// An enum constant and its value.
+    Constant(i64),
+    /// A global variable: its data segment offset and type.
+    Global { offset: usize, ty: Type },
+}
+
+/// What the code generator knows about functions before their code
+/// exists. Compiling a whole program empties it again.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SymbolTable {
+    /// The address of each placeholder `JSR` and the function it calls.
+    fixups: Vec<(usize, String)>,
+    /// Each function's return type, declared ahead of its code so that a
+    /// call has a type wherever it comes.
+    returns: Vec<(String, Type)>,
+}
+
+impl SymbolTable {
+    /// The entry of the function called `name`, if it has been compiled.
+    pub fn entry(chunk: &Chunk, name: &str) -> Option<usize> {
+        chunk.function(name).map(|f| f.entry)
+    }
+
+    /// Record that the function `name` returns `ret`, unless it has been
+    /// declared already.
+    pub fn declare(chunk: &mut Chunk, name: &str, ret: &Type) {
+        if Self::return_type(chunk, name).is_none() {
+            chunk.symbols.returns.push((name.to_string(), ret.clone()));
+        }
+    }
+
+    /// The declared return type of the function `name`.
+    pub fn return_type(chunk: &Chunk, name: &str) -> Option<Type> {
+        chunk.symbols.returns.iter().find(|(n, _)| n == name).map(|(_, ty)| ty.clone())
+    }
+
+    /// The enum constant or global variable called `name`, if there is
+    /// one. Functions aren't included: they are only ever called.
+    pub fn resolve(chunk: &Chunk, name: &str) -> Option<Symbol> {
+        if let Some(value) = chunk.constant(name) {
+            return Some(Symbol::Constant(value));
+        }
+        chunk.global(name).map(|g| Symbol::Global { offset: g.offset, ty: g.ty.clone() })
+    }
+
+    /// Call the function `name`: straight to its entry if it has been
+    /// compiled, otherwise through a placeholder for [`SymbolTable::patch`].
+    pub fn emit_call(chunk: &mut Chunk, name: &str) {
+        match Self::entry(chunk, name) {
+            Some(entry) => chunk.push_call(OpCode::JSR, entry),
+            None => {
+                chunk.symbols.fixups.push((chunk.code.len(), name.to_string()));
+                chunk.push_call(OpCode::JSR, 0);
+            }
+        }
+    }
+
+    /// Point every placeholder call at its callee, and forget the
+    /// declarations. Fails if one calls a function that was never
+    /// compiled.
+    pub fn patch(chunk: &mut Chunk) -> Result<(), CompileError> {
+        chunk.symbols.returns.clear();
+        for (at, name) in core::mem::take(&mut chunk.symbols.fixups) {
+            let entry = Self::entry(chunk, &name)
+                .ok_or_else(|| CompileError::new(format!("bad function call: {}", name)))?;
+            chunk.code[at] = Instruction::Call(OpCode::JSR, entry);
+        }
+        Ok(())
+    }
+
+    /// The placeholder calls still waiting for their callee.
+    pub fn unresolved(&self) -> impl Iterator<Item = &str> {
+        self.fixups.iter().map(|(_, name)| name.as_str())
+    }
+
+    /// Whether every call has its callee's address.
+    pub fn is_resolved(&self) -> bool {
+        self.fixups.is_empty()
+    }
+}
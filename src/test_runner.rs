@@ -0,0 +1,287 @@
+// src/test_runner.rs
+
+//! Conformance runner behind `c4_rust_AlRafaah test DIR`.
+//!
+//! Each `*.c` file states what it expects in its leading comment:
+//!
+//! ```c
+//! // expect: exit 7
+//! // expect-output: hello
+//! // expect-output: world
+//! ```
+//!
+//! or `// expect: compile-error` for programs that must be rejected.
+//!
+//! [`run_tests`] is the other kind of test, behind `--test FILE`: unit tests
+//! written in C4 itself, as zero-argument functions named `test_*` that
+//! return 0 to pass and use `assert(cond)` to check as they go.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::bytecode::Chunk;
+use crate::driver::compile_source;
+use crate::vm::{OutputBuffer, VmError, VM};
+
+/// Expectations read from a test file's leading comment.
+#[derive(Debug, Default, PartialEq)]
+pub struct Expectations {
+    /// Required exit code, if any.
+    pub exit: Option<i64>,
+    /// The program must fail to compile.
+    pub compile_error: bool,
+    /// Required output, one entry per line.
+    pub output: Vec<String>,
+}
+
+impl Expectations {
+    /// Parse the `// expect...` lines at the top of `src`.
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut exp = Expectations::default();
+        for line in src.lines().map(str::trim) {
+            if line.is_empty() {
+                continue;
+            }
+            let Some(comment) = line.strip_prefix("//") else {
+                break; // expectations live only in the leading comment
+            };
+            let comment = comment.trim_start();
+            if let Some(out) = comment.strip_prefix("expect-output:") {
+                exp.output.push(out.strip_prefix(' ').unwrap_or(out).to_string());
+            } else if let Some(rest) = comment.strip_prefix("expect:") {
+                let rest = rest.trim();
+                if rest == "compile-error" {
+                    exp.compile_error = true;
+                } else if let Some(code) = rest.strip_prefix("exit") {
+                    let code = code.trim();
+                    exp.exit = Some(
+                        code.parse()
+                            .map_err(|_| format!("bad exit code in expectation: {}", code))?,
+                    );
+                } else {
+                    return Err(format!("unknown expectation: {}", rest));
+                }
+            }
+        }
+        Ok(exp)
+    }
+}
+
+/// Result of running one test file.
+#[derive(Debug)]
+pub struct TestResult {
+    pub path: PathBuf,
+    /// None on success, otherwise a description of what went wrong.
+    pub failure: Option<String>,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Results for a whole directory.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub results: Vec<TestResult>,
+}
+
+impl Summary {
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.passed()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    /// Per-file PASS/FAIL lines, failure details, and a totals line.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for r in &self.results {
+            let status = if r.passed() { "PASS" } else { "FAIL" };
+            out.push_str(&format!("{} {}\n", status, r.path.display()));
+            if let Some(msg) = &r.failure {
+                for line in msg.lines() {
+                    out.push_str(&format!("    {}\n", line));
+                }
+            }
+        }
+        out.push_str(&format!(
+            "\n{} passed, {} failed, {} total\n",
+            self.passed(),
+            self.failed(),
+            self.results.len()
+        ));
+        out
+    }
+}
+
+/// Run every `*.c` file under `dir`, recursively, in path order.
+pub fn run_dir(dir: &Path) -> io::Result<Summary> {
+    let mut files = Vec::new();
+    collect_sources(dir, &mut files)?;
+    files.sort();
+
+    let mut summary = Summary::default();
+    for path in files {
+        summary.results.push(run_file(&path));
+    }
+    Ok(summary)
+}
+
+/// Compile and run a single annotated test file.
+pub fn run_file(path: &Path) -> TestResult {
+    let failure = match fs::read_to_string(path) {
+        Ok(src) => check_source(&src).err(),
+        Err(e) => Some(format!("cannot read file: {}", e)),
+    };
+    TestResult { path: path.to_path_buf(), failure }
+}
+
+/// Check `src` against its own expectations.
+pub fn check_source(src: &str) -> Result<(), String> {
+    let exp = Expectations::parse(src)?;
+
+    let compiled = compile_source(src);
+    let chunk = match (compiled, exp.compile_error) {
+        (Ok(_), true) => return Err("expected a compile error, but compilation succeeded".into()),
+        (Err(_), true) => return Ok(()),
+        (Err(e), false) => return Err(e.to_string()),
+        (Ok(chunk), false) => chunk,
+    };
+
+    let buffer = OutputBuffer::new();
+    let mut vm = VM::new();
+    vm.set_output(Box::new(buffer.clone()));
+    let code = match vm.run(&chunk) {
+        Ok(code) => code,
+        Err(e) => return Err(format!("runtime error: {}", e)),
+    };
+
+    let mut problems = Vec::new();
+    if let Some(want) = exp.exit {
+        if want != code {
+            problems.push(format!("exit code: expected {}, got {}", want, code));
+        }
+    }
+    if !exp.output.is_empty() {
+        let actual = String::from_utf8_lossy(&buffer.contents()).into_owned();
+        let actual: Vec<&str> = actual.lines().collect();
+        if actual != exp.output {
+            problems.push(format!("output differs:\n{}", diff_lines(&exp.output, &actual)));
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("\n"))
+    }
+}
+
+/// Line-by-line diff: `-` for expected lines, `+` for actual ones.
+fn diff_lines(expected: &[String], actual: &[&str]) -> String {
+    let mut out = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => out.push_str(&format!("  {}\n", e)),
+            (e, a) => {
+                if let Some(e) = e {
+                    out.push_str(&format!("- {}\n", e));
+                }
+                if let Some(a) = a {
+                    out.push_str(&format!("+ {}\n", a));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Result of one `test_*` function.
+#[derive(Debug)]
+pub struct UnitTest {
+    pub name: String,
+    /// The function's return value, or the fault that stopped it.
+    pub outcome: Result<i64, VmError>,
+    pub time: Duration,
+}
+
+impl UnitTest {
+    pub fn passed(&self) -> bool {
+        self.outcome == Ok(0)
+    }
+}
+
+/// Results for every `test_*` function in a chunk.
+#[derive(Debug, Default)]
+pub struct TestReport {
+    pub tests: Vec<UnitTest>,
+}
+
+impl TestReport {
+    pub fn passed(&self) -> usize {
+        self.tests.iter().filter(|t| t.passed()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.tests.len() - self.passed()
+    }
+
+    /// Per-test PASS/FAIL lines with timings, why each failure failed, and
+    /// a totals line.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for t in &self.tests {
+            let status = if t.passed() { "PASS" } else { "FAIL" };
+            let ms = t.time.as_secs_f64() * 1000.0;
+            out.push_str(&format!("{} {} ({:.3} ms)\n", status, t.name, ms));
+            match &t.outcome {
+                Ok(0) => {}
+                Ok(code) => out.push_str(&format!("    returned {}\n", code)),
+                Err(e) => out.push_str(&format!("    {}\n", e)),
+            }
+        }
+        out.push_str(&format!(
+            "\n{} passed, {} failed, {} total\n",
+            self.passed(),
+            self.failed(),
+            self.tests.len()
+        ));
+        out
+    }
+}
+
+/// Run every zero-argument function whose name starts with `test_`, in
+/// definition order, each on a freshly reset VM. Their output is dropped.
+pub fn run_tests(chunk: &Chunk) -> TestReport {
+    let mut vm = VM::new();
+    vm.set_output(Box::new(io::sink()));
+    let mut report = TestReport::default();
+    for func in &chunk.functions {
+        if !func.name.starts_with("test_") || !func.params.is_empty() {
+            continue;
+        }
+        vm.reset();
+        let start = Instant::now();
+        let outcome = vm.call(chunk, func.entry);
+        report.tests.push(UnitTest { name: func.name.clone(), outcome, time: start.elapsed() });
+    }
+    report
+}
+
+/// Recursively gather `*.c` files.
+fn collect_sources(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_sources(&path, out)?;
+        } else if path.extension().is_some_and(|e| e == "c") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
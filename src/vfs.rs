@@ -0,0 +1,154 @@
+// src/vfs.rs
+
+//! Where a program's `open` and `read` find files. The VM keeps the
+//! descriptors; a [`FileSystem`] only resolves paths to [`OpenFile`]s,
+//! which `read` reads from and `close` drops. Pick one with
+//! `VmConfig::filesystem`:
+//!
+//! - [`HostFs`], the real disk, either all of it (the default with `std`)
+//!   or only the tree under a sandbox root;
+//! - [`MemFs`], files held in memory, for tests and embedders that present
+//!   a synthetic tree. An empty one is the default without `std`.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::fmt;
+
+use crate::prelude::*;
+
+/// A tree of files a program may open for reading.
+pub trait FileSystem: Send + Sync {
+    /// Open the file at `path`, as the program spelled it, or None if
+    /// there is none or it is off limits.
+    fn open(&self, path: &[u8]) -> Option<Box<dyn OpenFile>>;
+}
+
+/// A file a program has open.
+pub trait OpenFile: Send {
+    /// Read up to `buf.len()` bytes into `buf` and return how many, 0 at
+    /// the end, or None on an error.
+    fn read(&mut self, buf: &mut [u8]) -> Option<usize>;
+}
+
+/// A [`FileSystem`] shared by the runs of every config cloned from one
+/// holding it. Two are equal when they share the same filesystem.
+#[derive(Clone)]
+pub struct SharedFs(Arc<dyn FileSystem>);
+
+impl SharedFs {
+    pub fn new(fs: impl FileSystem + 'static) -> Self {
+        SharedFs(Arc::new(fs))
+    }
+}
+
+impl fmt::Debug for SharedFs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedFs")
+    }
+}
+
+impl PartialEq for SharedFs {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl core::ops::Deref for SharedFs {
+    type Target = dyn FileSystem;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+/// Files held in memory, by path. Paths match exactly, as written.
+#[derive(Debug, Clone, Default)]
+pub struct MemFs {
+    files: BTreeMap<Vec<u8>, Arc<[u8]>>,
+}
+
+impl MemFs {
+    /// A filesystem holding `files`, each a path and its contents.
+    pub fn new<P: Into<Vec<u8>>, C: Into<Vec<u8>>>(files: impl IntoIterator<Item = (P, C)>) -> Self {
+        MemFs { files: files.into_iter().map(|(p, c)| (p.into(), c.into().into())).collect() }
+    }
+
+    /// Add the file at `path`, replacing any already there.
+    pub fn insert(&mut self, path: impl Into<Vec<u8>>, contents: impl Into<Vec<u8>>) {
+        self.files.insert(path.into(), contents.into().into());
+    }
+}
+
+impl FileSystem for MemFs {
+    fn open(&self, path: &[u8]) -> Option<Box<dyn OpenFile>> {
+        let contents = self.files.get(path)?.clone();
+        Some(Box::new(MemFile { contents, at: 0 }))
+    }
+}
+
+/// A [`MemFs`] file being read.
+struct MemFile {
+    contents: Arc<[u8]>,
+    at: usize,
+}
+
+impl OpenFile for MemFile {
+    fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let rest = &self.contents[self.at..];
+        let n = rest.len().min(buf.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.at += n;
+        Some(n)
+    }
+}
+
+/// The host's files. Sandboxed, only those under the root are reachable:
+/// paths are taken relative to it, and absolute paths, `..` and symlinks
+/// leading out of it are refused.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct HostFs {
+    root: Option<std::path::PathBuf>,
+}
+
+#[cfg(feature = "std")]
+impl HostFs {
+    /// Every file the process can read, relative paths starting from the
+    /// working directory: what the VM uses unless told otherwise.
+    pub fn unrestricted() -> Self {
+        HostFs { root: None }
+    }
+
+    /// Only the files under `root`.
+    pub fn sandboxed(root: impl Into<std::path::PathBuf>) -> Self {
+        HostFs { root: Some(root.into()) }
+    }
+
+    /// Where `path` is on the host, if it is allowed.
+    fn resolve(&self, path: &str) -> Option<std::path::PathBuf> {
+        use std::path::{Component, Path};
+        let Some(root) = &self.root else { return Some(path.into()) };
+        if !Path::new(path).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return None;
+        }
+        // Lexically inside; make sure no symlink leads out.
+        let root = root.canonicalize().ok()?;
+        let full = root.join(path).canonicalize().ok()?;
+        full.starts_with(&root).then_some(full)
+    }
+}
+
+#[cfg(feature = "std")]
+impl FileSystem for HostFs {
+    fn open(&self, path: &[u8]) -> Option<Box<dyn OpenFile>> {
+        let path = self.resolve(&String::from_utf8_lossy(path))?;
+        Some(Box::new(std::fs::File::open(path).ok()?))
+    }
+}
+
+#[cfg(feature = "std")]
+impl OpenFile for std::fs::File {
+    fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        std::io::Read::read(self, buf).ok()
+    }
+}
@@ -0,0 +1,330 @@
+// src/visitor.rs
+
+//! Traversal of the AST.
+//!
+//! [`Visitor`] has a method per node type. Each one's default calls the
+//! matching `walk_*` function, which visits the node's children in source
+//! order, so an implementation overrides only the nodes it cares about
+//! and calls `walk_*` itself to keep descending. [`VisitorMut`] is the
+//! same over `&mut` nodes, with `walk_*_mut` functions, for passes that
+//! rewrite the tree in place.
+//!
+//! ```
+//! use c4_rust_AlRafaah::ast::*;
+//! use c4_rust_AlRafaah::visitor::{walk_expr, Visitor};
+//!
+//! /// Names of the variables a program reads or writes.
+//! #[derive(Default)]
+//! struct Vars(Vec<String>);
+//!
+//! impl<'ast> Visitor<'ast> for Vars {
+//!     fn visit_expr(&mut self, expr: &'ast Expr) {
+//!         if let ExprKind::Var(name) = &expr.kind {
+//!             self.0.push(name.clone());
+//!         }
+//!         walk_expr(self, expr);
+//!     }
+//! }
+//! ```
+
+use crate::ast::*;
+
+/// A read-only pass over the AST.
+pub trait Visitor<'ast> {
+    fn visit_program(&mut self, program: &'ast Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) {
+        walk_item(self, item);
+    }
+
+    fn visit_func_def(&mut self, func: &'ast FuncDef) {
+        walk_func_def(self, func);
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_type(&mut self, _ty: &'ast Type) {}
+
+    fn visit_binop(&mut self, _op: &'ast BinOp) {}
+
+    fn visit_unop(&mut self, _op: &'ast UnOp) {}
+}
+
+pub fn walk_program<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, program: &'ast Program) {
+    for item in &program.items {
+        v.visit_item(item);
+    }
+}
+
+pub fn walk_item<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, item: &'ast Item) {
+    match item {
+        Item::Global(g) => v.visit_type(&g.ty),
+        Item::Function(f) => v.visit_func_def(f),
+        Item::Enum(_) => {}
+    }
+}
+
+pub fn walk_func_def<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, func: &'ast FuncDef) {
+    v.visit_type(&func.ret);
+    for (_, ty) in func.params.iter().chain(&func.locals) {
+        v.visit_type(ty);
+    }
+    v.visit_block(&func.body);
+}
+
+pub fn walk_block<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, block: &'ast Block) {
+    for (_, ty) in &block.locals {
+        v.visit_type(ty);
+    }
+    for stmt in &block.stmts {
+        v.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, stmt: &'ast Stmt) {
+    match &stmt.kind {
+        StmtKind::If { cond, then_branch, else_branch } => {
+            v.visit_expr(cond);
+            v.visit_stmt(then_branch);
+            if let Some(e) = else_branch {
+                v.visit_stmt(e);
+            }
+        }
+        StmtKind::While { cond, body } => {
+            v.visit_expr(cond);
+            v.visit_stmt(body);
+        }
+        StmtKind::DoWhile { body, cond } => {
+            v.visit_stmt(body);
+            v.visit_expr(cond);
+        }
+        StmtKind::For { init, cond, step, body } => {
+            for e in [init, cond, step].into_iter().flatten() {
+                v.visit_expr(e);
+            }
+            v.visit_stmt(body);
+        }
+        StmtKind::Switch { scrutinee, cases } => {
+            v.visit_expr(scrutinee);
+            for s in cases.iter().flat_map(|(_, stmts)| stmts) {
+                v.visit_stmt(s);
+            }
+        }
+        StmtKind::Return(Some(e)) | StmtKind::Expr(e) => v.visit_expr(e),
+        StmtKind::Block(b) => v.visit_block(b),
+        StmtKind::Break | StmtKind::Continue | StmtKind::Return(None) | StmtKind::Empty => {}
+    }
+}
+
+pub fn walk_expr<'ast, V: Visitor<'ast> + ?Sized>(v: &mut V, expr: &'ast Expr) {
+    match &expr.kind {
+        ExprKind::Num(_) | ExprKind::Str(_) | ExprKind::Var(_) => {}
+        ExprKind::Unary { op, expr } => {
+            v.visit_unop(op);
+            v.visit_expr(expr);
+        }
+        ExprKind::Binary { op, left, right } => {
+            v.visit_expr(left);
+            v.visit_binop(op);
+            v.visit_expr(right);
+        }
+        ExprKind::CompoundAssign { op, target, value } => {
+            v.visit_expr(target);
+            v.visit_binop(op);
+            v.visit_expr(value);
+        }
+        ExprKind::Call { callee, args } => {
+            v.visit_expr(callee);
+            for arg in args {
+                v.visit_expr(arg);
+            }
+        }
+        ExprKind::Cast { ty, expr } => {
+            v.visit_type(ty);
+            v.visit_expr(expr);
+        }
+        ExprKind::SizeOf(ty) => v.visit_type(ty),
+        ExprKind::Conditional { cond, then_expr, else_expr } => {
+            v.visit_expr(cond);
+            v.visit_expr(then_expr);
+            v.visit_expr(else_expr);
+        }
+        ExprKind::Index { array, index } => {
+            v.visit_expr(array);
+            v.visit_expr(index);
+        }
+    }
+}
+
+/// A pass that may rewrite the AST as it goes.
+pub trait VisitorMut {
+    fn visit_program(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+
+    fn visit_item(&mut self, item: &mut Item) {
+        walk_item_mut(self, item);
+    }
+
+    fn visit_func_def(&mut self, func: &mut FuncDef) {
+        walk_func_def_mut(self, func);
+    }
+
+    fn visit_block(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_type(&mut self, _ty: &mut Type) {}
+
+    fn visit_binop(&mut self, _op: &mut BinOp) {}
+
+    fn visit_unop(&mut self, _op: &mut UnOp) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(v: &mut V, program: &mut Program) {
+    for item in &mut program.items {
+        v.visit_item(item);
+    }
+}
+
+pub fn walk_item_mut<V: VisitorMut + ?Sized>(v: &mut V, item: &mut Item) {
+    match item {
+        Item::Global(g) => v.visit_type(&mut g.ty),
+        Item::Function(f) => v.visit_func_def(f),
+        Item::Enum(_) => {}
+    }
+}
+
+pub fn walk_func_def_mut<V: VisitorMut + ?Sized>(v: &mut V, func: &mut FuncDef) {
+    v.visit_type(&mut func.ret);
+    for (_, ty) in func.params.iter_mut().chain(&mut func.locals) {
+        v.visit_type(ty);
+    }
+    v.visit_block(&mut func.body);
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(v: &mut V, block: &mut Block) {
+    for (_, ty) in &mut block.locals {
+        v.visit_type(ty);
+    }
+    for stmt in &mut block.stmts {
+        v.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut Stmt) {
+    match &mut stmt.kind {
+        StmtKind::If { cond, then_branch, else_branch } => {
+            v.visit_expr(cond);
+            v.visit_stmt(then_branch);
+            if let Some(e) = else_branch {
+                v.visit_stmt(e);
+            }
+        }
+        StmtKind::While { cond, body } => {
+            v.visit_expr(cond);
+            v.visit_stmt(body);
+        }
+        StmtKind::DoWhile { body, cond } => {
+            v.visit_stmt(body);
+            v.visit_expr(cond);
+        }
+        StmtKind::For { init, cond, step, body } => {
+            for e in [init, cond, step].into_iter().flatten() {
+                v.visit_expr(e);
+            }
+            v.visit_stmt(body);
+        }
+        StmtKind::Switch { scrutinee, cases } => {
+            v.visit_expr(scrutinee);
+            for s in cases.iter_mut().flat_map(|(_, stmts)| stmts) {
+                v.visit_stmt(s);
+            }
+        }
+        StmtKind::Return(Some(e)) | StmtKind::Expr(e) => v.visit_expr(e),
+        StmtKind::Block(b) => v.visit_block(b),
+        StmtKind::Break | StmtKind::Continue | StmtKind::Return(None) | StmtKind::Empty => {}
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Num(_) | ExprKind::Str(_) | ExprKind::Var(_) => {}
+        ExprKind::Unary { op, expr } => {
+            v.visit_unop(op);
+            v.visit_expr(expr);
+        }
+        ExprKind::Binary { op, left, right } => {
+            v.visit_expr(left);
+            v.visit_binop(op);
+            v.visit_expr(right);
+        }
+        ExprKind::CompoundAssign { op, target, value } => {
+            v.visit_expr(target);
+            v.visit_binop(op);
+            v.visit_expr(value);
+        }
+        ExprKind::Call { callee, args } => {
+            v.visit_expr(callee);
+            for arg in args {
+                v.visit_expr(arg);
+            }
+        }
+        ExprKind::Cast { ty, expr } => {
+            v.visit_type(ty);
+            v.visit_expr(expr);
+        }
+        ExprKind::SizeOf(ty) => v.visit_type(ty),
+        ExprKind::Conditional { cond, then_expr, else_expr } => {
+            v.visit_expr(cond);
+            v.visit_expr(then_expr);
+            v.visit_expr(else_expr);
+        }
+        ExprKind::Index { array, index } => {
+            v.visit_expr(array);
+            v.visit_expr(index);
+        }
+    }
+}
+
+/// Counts the expressions in whatever it visits, subexpressions included.
+#[derive(Debug, Default)]
+pub struct AstCounter {
+    pub exprs: usize,
+}
+
+impl AstCounter {
+    /// The number of expressions in `program`.
+    pub fn count(program: &Program) -> usize {
+        let mut counter = AstCounter::default();
+        counter.visit_program(program);
+        counter.exprs
+    }
+}
+
+impl<'ast> Visitor<'ast> for AstCounter {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        self.exprs += 1;
+        walk_expr(self, expr);
+    }
+}
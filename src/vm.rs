@@ -1,286 +1,2049 @@
-// Import definitions for bytecode instructions, AST nodes, etc.
-use crate::bytecode::{Chunk, Instruction, OpCode};
-use crate::ast::*;
-
-// Compile a full program by compiling each item (e.g., function) into the bytecode chunk.
-impl Program {
-    pub fn compile(&self, chunk: &mut Chunk) -> Result<(), String> {
-        for item in &self.items {
-            item.compile(chunk)?;
-        }
-        Ok(())
-    }
-}
-
-// Compile an individual top-level item. Currently only functions are handled.
-impl Item {
-    pub fn compile(&self, chunk: &mut Chunk) -> Result<(), String> {
-        match self {
-            Item::Function(f) => f.compile(chunk),
-            _ => Ok(()), // Global variables or enums are not compiled yet.
-        }
-    }
-}
-
-// Compile a function definition into bytecode.
-impl FuncDef {
-    pub fn compile(&self, chunk: &mut Chunk) -> Result<(), String> {
-        if self.name == "main" {
-            // Special handling for `main` as the program entry point.
-            let entry = chunk.code.len() + 2; // Location where function starts.
-            chunk.push_call(OpCode::JSR, entry); // Insert jump to subroutine.
-            chunk.push(OpCode::EXIT); // Exit program after `main` returns.
-        }
-
-        // Reserve space for local variables.
-        let local_count = self.locals.len() as i64;
-        chunk.push_int(OpCode::ENT, local_count); // Enter function frame.
-
-        // Compile each statement in the function body.
-        for stmt in &self.body.stmts {
-            stmt.compile(chunk)?;
-        }
-
-        // Leave function.
-        chunk.push(OpCode::LEV);
-        Ok(())
-    }
-}
-
-// Compile statements to bytecode.
-impl Stmt {
-    pub fn compile(&self, chunk: &mut Chunk) -> Result<(), String> {
-        match self {
-            Stmt::Expr(e) => {
-                e.compile(chunk)?;
-                Ok(()) // Expression result left in register `a`.
-            }
-            Stmt::Return(Some(e)) => {
-                e.compile(chunk)?;
-                chunk.push(OpCode::LEV); // Return from function.
-                Ok(())
-            }
-            Stmt::Return(None) => {
-                chunk.push(OpCode::LEV);
-                Ok(())
-            }
-            Stmt::Block(b) => {
-                for stmt in &b.stmts {
-                    stmt.compile(chunk)?;
-                }
-                Ok(())
-            }
-            _ => Ok(()), // Other statement types not yet implemented.
-        }
-    }
-}
-
-// Compile expressions into bytecode.
-impl Expr {
-    pub fn compile(&self, chunk: &mut Chunk) -> Result<(), String> {
-        match self {
-            Expr::Num(n) => chunk.push_int(OpCode::IMM, *n), // Load immediate integer.
-            Expr::Binary { op, left, right } => {
-                left.compile(chunk)?;
-                chunk.push(OpCode::PSH); // Save left operand.
-                right.compile(chunk)?;
-
-                // Map binary operation to opcode.
-                let code = match op {
-                    BinOp::Add => OpCode::ADD,
-                    BinOp::Sub => OpCode::SUB,
-                    BinOp::Mul => OpCode::MUL,
-                    BinOp::Div => OpCode::DIV,
-                    BinOp::Mod => OpCode::MOD,
-                    BinOp::Eq  => OpCode::EQ,
-                    BinOp::Ne  => OpCode::NE,
-                    BinOp::Lt  => OpCode::LT,
-                    BinOp::Le  => OpCode::LE,
-                    BinOp::Gt  => OpCode::GT,
-                    BinOp::Ge  => OpCode::GE,
-                    BinOp::BitAnd => OpCode::AND,
-                    BinOp::BitOr  => OpCode::OR,
-                    BinOp::Xor    => OpCode::XOR,
-                    BinOp::Shl    => OpCode::SHL,
-                    BinOp::Shr    => OpCode::SHR,
-                    _ => return Err(format!("unsupported op: {:?}", op)),
-                };
-
-                chunk.push(code);
-            }
-            Expr::Call { callee, args } => {
-                for arg in args {
-                    arg.compile(chunk)?;
-                    chunk.push(OpCode::PSH); // Push each argument.
-                }
-
-                // Handle only direct calls to named functions for now.
-                if let Expr::Var(name) = &**callee {
-                    if name == "main" {
-                        chunk.push_call(OpCode::JSR, 2); // Hardcoded address for `main`.
-                    } else {
-                        return Err(format!("unsupported function call: {}", name));
-                    }
-                } else {
-                    return Err("callee must be a named function".into());
-                }
-            }
-            _ => return Err(format!("unsupported expr: {:?}", self)),
-        }
-        Ok(())
-    }
-}
-
-// Virtual Machine structure.
-pub struct VM {
-    stack: Vec<i64>,                        // Operand stack.
-    call_stack: Vec<(usize, usize, usize)>, // Stores (return_pc, old_sp, old_fp).
-    pc: usize,                              // Program counter.
-    sp: usize,                              // Stack pointer.
-    bp: usize,                              // Base pointer (currently unused).
-    fp: usize,                              // Frame pointer for current function call.
-    pub debug: bool,                        // Debug flag.
-}
-
-impl VM {
-    // Constructor: Initialize VM with preallocated stack.
-    pub fn new() -> Self {
-        VM {
-            stack: vec![0; 1024 * 1024], // 1 MB stack space.
-            call_stack: Vec::new(),
-            pc: 0,
-            sp: 0,
-            bp: 0,
-            fp: 0,
-            debug: false,
-        }
-    }
-
-    // Execute bytecode in a given chunk.
-    pub fn run(&mut self, chunk: &Chunk) -> i64 {
-        let code = &chunk.code;
-        let mut a: i64 = 0; // Register `a` is used for computation.
-
-        while self.pc < code.len() {
-            let instr = &code[self.pc];
-            self.pc += 1;
-
-            if self.debug {
-                println!("{:04} {:?}", self.pc - 1, instr);
-            }
-
-            match instr {
-                Instruction::Instr(op) => match op {
-                    // Arithmetic
-                    OpCode::ADD => a = self.pop() + a,
-                    OpCode::SUB => a = self.pop() - a,
-                    OpCode::MUL => a = self.pop() * a,
-                    OpCode::DIV => a = self.pop() / a,
-                    OpCode::MOD => a = self.pop() % a,
-
-                    // Bitwise and comparison
-                    OpCode::AND => a = self.pop() & a,
-                    OpCode::OR => a = self.pop() | a,
-                    OpCode::XOR => a = self.pop() ^ a,
-                    OpCode::EQ => a = (self.pop() == a) as i64,
-                    OpCode::NE => a = (self.pop() != a) as i64,
-                    OpCode::LT => a = (self.pop() < a) as i64,
-                    OpCode::LE => a = (self.pop() <= a) as i64,
-                    OpCode::GT => a = (self.pop() > a) as i64,
-                    OpCode::GE => a = (self.pop() >= a) as i64,
-                    OpCode::SHL => a = self.pop() << a,
-                    OpCode::SHR => a = self.pop() >> a,
-
-                    // Memory access
-                    OpCode::LI => a = self.stack[a as usize],
-                    OpCode::LC => a = self.stack[a as usize] & 0xFF,
-                    OpCode::SI => {
-                        let addr = self.pop() as usize;
-                        self.stack[addr] = a;
-                        a = self.stack[addr];
-                    }
-                    OpCode::SC => {
-                        let addr = self.pop() as usize;
-                        self.stack[addr] = a & 0xFF;
-                        a = self.stack[addr];
-                    }
-
-                    OpCode::PSH => self.push(a), // Push register `a` onto stack.
-
-                    // Function return
-                    OpCode::LEV => {
-                        let ret_val = a;
-                        let (ret_pc, old_sp, old_fp) = self.call_stack.pop().expect("call stack underflow");
-                        self.pc = ret_pc;
-                        self.sp = old_sp;
-                        self.fp = old_fp;
-                        a = ret_val;
-                    }
-
-                    // Exit program
-                    OpCode::EXIT => {
-                        println!("exit({a})");
-                        return a;
-                    }
-
-                    _ => unimplemented!("{:?}", op),
-                },
-
-                Instruction::InstrInt(op, val) => match op {
-                    OpCode::IMM => a = *val,                            // Load immediate value.
-                    OpCode::LEA => a = (self.fp + *val as usize) as i64, // Compute effective address.
-                    OpCode::ADJ => {
-                        for _ in 0..*val {
-                            self.pop(); // Discard arguments.
-                        }
-                    }
-                    OpCode::ENT => {
-                        // Enter function call.
-                        self.call_stack.push((self.pc, self.sp, self.fp));
-                        self.fp = self.sp;
-                        for _ in 0..*val {
-                            self.push(0); // Allocate local variables.
-                        }
-                    }
-                    _ => panic!("Unhandled: {:?}", op),
-                },
-
-                Instruction::Jump(op, target) => match op {
-                    OpCode::JMP => self.pc = *target,
-                    OpCode::BZ => if a == 0 { self.pc = *target; },
-                    OpCode::BNZ => if a != 0 { self.pc = *target; },
-                    _ => panic!("Invalid jump: {:?}", op),
-                },
-
-                Instruction::Call(op, target) => match op {
-                    OpCode::JSR => {
-                        self.call_stack.push((self.pc, self.sp, self.fp));
-                        self.pc = *target;
-                    }
-                    _ => panic!("Invalid call: {:?}", op),
-                },
-            }
-        }
-
-        a
-    }
-
-    // Push value to stack.
-    fn push(&mut self, val: i64) {
-        if self.sp >= self.stack.len() {
-            panic!("stack overflow");
-        }
-        self.stack[self.sp] = val;
-        self.sp += 1;
-    }
-
-    // Pop value from stack.
-    fn pop(&mut self) -> i64 {
-        if self.sp == 0 {
-            panic!("stack underflow");
-        }
-        self.sp -= 1;
-        self.stack[self.sp]
-    }
-}
-
+// Import definitions for bytecode instructions, AST nodes, etc.
+use crate::bytecode::{pack_bytes, Chunk, FuncSym, Instruction, OpCode, WORD_SIZE};
+use crate::coredump::{CoreDump, Frame, HeapBlock};
+use crate::coverage::CoverageReport;
+use crate::heap::{Heap, LeakReport};
+use crate::printf;
+use crate::ast::*;
+use crate::check::check_program;
+use crate::error::{CompileError, Error};
+use crate::prelude::*;
+use crate::symtable::{Symbol, SymbolTable};
+use crate::replay::{Syscall, SyscallLog};
+use crate::vfs::{OpenFile, SharedFs};
+use alloc::sync::Arc;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+#[cfg(feature = "std")]
+use std::time::Instant;
+use core::time::Duration;
+
+// Compile a full program by checking it, then compiling each item (e.g., function) into the bytecode chunk.
+impl Program {
+    pub fn compile(&self, chunk: &mut Chunk) -> Result<(), Error> {
+        let _span = span!("compile");
+        check_program(self)?;
+        // Globals and enum constants first, so functions can use those
+        // defined after them.
+        for item in &self.items {
+            if !matches!(item, Item::Function(_)) {
+                item.compile(chunk)?;
+            }
+        }
+        for item in &self.items {
+            if let Item::Function(f) = item {
+                SymbolTable::declare(chunk, &f.name, &f.ret);
+            }
+        }
+        // Startup: call `main` and exit with what it returns.
+        let has_main = self.items.iter().any(|item| matches!(item, Item::Function(f) if f.name == "main"));
+        if has_main {
+            SymbolTable::emit_call(chunk, "main");
+            chunk.push(OpCode::EXIT);
+        }
+        for item in &self.items {
+            if let Item::Function(f) = item {
+                f.compile(chunk)?;
+            }
+        }
+
+        // Second pass: every function now has an entry, so calls made
+        // before their callee was compiled can be pointed at it.
+        SymbolTable::patch(chunk)?;
+        event!(instructions = chunk.code.len(), "compiled");
+        Ok(())
+    }
+}
+
+// Compile an individual top-level item.
+impl Item {
+    pub fn compile(&self, chunk: &mut Chunk) -> Result<(), CompileError> {
+        match self {
+            Item::Function(f) => f.compile(chunk),
+            Item::Global(g) => g.compile(chunk),
+            Item::Enum(e) => e.compile(chunk),
+        }
+    }
+}
+
+// A global's name must be new to the chunk.
+fn define_global(chunk: &Chunk, name: &str) -> Result<(), CompileError> {
+    if chunk.global(name).is_some() || chunk.constant(name).is_some() {
+        return Err(CompileError::new(format!("duplicate global definition: {}", name)));
+    }
+    Ok(())
+}
+
+// Give a global the next word of the data segment.
+impl GlobalDecl {
+    pub fn compile(&self, chunk: &mut Chunk) -> Result<(), CompileError> {
+        define_global(chunk, &self.name)?;
+        chunk.alloc_global(&self.name, self.ty.clone());
+        Ok(())
+    }
+}
+
+// Add the enum's constants to the chunk. Without an initializer a
+// constant is one more than the one before it, and the first is 0.
+impl EnumDecl {
+    pub fn compile(&self, chunk: &mut Chunk) -> Result<(), CompileError> {
+        let mut next = 0i64;
+        for (name, init) in &self.variants {
+            define_global(chunk, name)?;
+            let value = init.unwrap_or(next);
+            chunk.constants.push((name.clone(), value));
+            next = value.wrapping_add(1);
+        }
+        Ok(())
+    }
+}
+
+// Compile a function definition into bytecode.
+impl FuncDef {
+    pub fn compile(&self, chunk: &mut Chunk) -> Result<(), CompileError> {
+        #[cfg(feature = "tracing")]
+        let start = chunk.code.len();
+        let entry = chunk.code.len();
+        SymbolTable::declare(chunk, &self.name, &self.ret);
+        chunk.functions.push(FuncSym {
+            name: self.name.clone(),
+            params: self.params.iter().map(|(name, _)| name.clone()).collect(),
+            entry,
+        });
+
+        // Enter function frame; the slot count is patched in once the body,
+        // and so its innermost blocks, have been compiled.
+        chunk.push_int(OpCode::ENT, 0);
+        let mut ctx = FnCtx::new(self);
+
+        // Compile each statement in the function body.
+        for stmt in &self.body.stmts {
+            stmt.compile(chunk, &mut ctx)?;
+        }
+        chunk.code[entry] = Instruction::InstrInt(OpCode::ENT, ctx.slots);
+
+        // Leave function.
+        chunk.push(OpCode::LEV);
+        event!(function = %self.name, instructions = chunk.code.len() - start, "compiled function");
+        Ok(())
+    }
+}
+
+/// Where the variables of the function being compiled live: each local
+/// has a slot at a fixed offset from `fp`, which `LEA` turns into its
+/// address. A block's locals get the slots after those of the blocks
+/// around it, and give them back when it ends, so sibling blocks share.
+/// Parameters sit below `fp`, where the caller pushed them: the last at
+/// `fp - 1`, the first at `fp - n`.
+pub struct FnCtx<'a> {
+    /// Innermost scope last; names map to their offset and type.
+    scopes: Vec<Vec<(&'a str, i64, &'a Type)>>,
+    /// The next free slot.
+    next: i64,
+    /// Most slots in use at once: what `ENT` reserves.
+    slots: i64,
+    /// The loops and `switch`es around the code being compiled, innermost
+    /// last.
+    loops: Vec<LoopJumps>,
+}
+
+/// The `break` and `continue` jumps out of one loop or `switch`, emitted
+/// with a placeholder target and patched once it is compiled.
+#[derive(Default)]
+struct LoopJumps {
+    /// Set for a `switch`, which `break` leaves but `continue` doesn't.
+    is_switch: bool,
+    /// To the instruction after the loop.
+    breaks: Vec<usize>,
+    /// To the loop's condition, or a `for`'s step.
+    continues: Vec<usize>,
+}
+
+impl<'a> FnCtx<'a> {
+    /// The context at the top of `f`'s body, with its parameters and
+    /// locals in scope.
+    pub fn new(f: &'a FuncDef) -> Self {
+        let n = f.params.len() as i64;
+        let params = f.params.iter().zip(-n..).map(|((name, ty), offset)| (name.as_str(), offset, ty)).collect();
+        let mut ctx = FnCtx { scopes: vec![params], next: 0, slots: 0, loops: Vec::new() };
+        ctx.enter(&f.locals);
+        ctx
+    }
+
+    fn enter(&mut self, locals: &'a [(String, Type)]) {
+        let mut scope = Vec::new();
+        for (name, ty) in locals {
+            scope.push((name.as_str(), self.next, ty));
+            self.next += 1;
+        }
+        self.slots = self.slots.max(self.next);
+        self.scopes.push(scope);
+    }
+
+    fn leave(&mut self) {
+        let scope = self.scopes.pop().expect("leave without enter");
+        self.next -= scope.len() as i64;
+    }
+
+    /// The innermost variable called `name`.
+    fn lookup(&self, name: &str) -> Option<(i64, &'a Type)> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(n, _, _)| *n == name)
+            .map(|&(_, offset, ty)| (offset, ty))
+    }
+
+    /// Emit a `JMP` for `break`, out of the innermost loop or `switch`,
+    /// or for `continue`, to the next test of the innermost loop.
+    fn jump_out(&mut self, chunk: &mut Chunk, is_break: bool) -> Result<(), CompileError> {
+        let list = if is_break {
+            let jumps = self.loops.last_mut();
+            &mut jumps.ok_or_else(|| CompileError::new("break outside a loop or switch"))?.breaks
+        } else {
+            let jumps = self.loops.iter_mut().rev().find(|j| !j.is_switch);
+            &mut jumps.ok_or_else(|| CompileError::new("continue outside a loop"))?.continues
+        };
+        list.push(chunk.code.len());
+        chunk.push_jump(OpCode::JMP, 0);
+        Ok(())
+    }
+
+    /// Finish the innermost loop or `switch`, which ends here: its
+    /// `continue`s go to `next`.
+    fn end_loop(&mut self, chunk: &mut Chunk, next: usize) {
+        let jumps = self.loops.pop().expect("end_loop without a loop");
+        let end = chunk.code.len();
+        for (list, target) in [(jumps.breaks, end), (jumps.continues, next)] {
+            for at in list {
+                chunk.patch_jump(at, target);
+            }
+        }
+    }
+
+    /// A slot no name refers to, held until the matching `release_temp`.
+    fn temp(&mut self) -> i64 {
+        let slot = self.next;
+        self.next += 1;
+        self.slots = self.slots.max(self.next);
+        slot
+    }
+
+    fn release_temp(&mut self) {
+        self.next -= 1;
+    }
+
+    /// What `name` refers to here: the innermost parameter or local of
+    /// that name, otherwise an enum constant or global.
+    fn resolve(&self, name: &str, chunk: &Chunk) -> Option<Symbol> {
+        match self.lookup(name) {
+            Some((offset, ty)) => Some(Symbol::Local { offset, ty: ty.clone() }),
+            None => SymbolTable::resolve(chunk, name),
+        }
+    }
+
+    /// The address of the variable `name` into `a`: `LEA` for a parameter
+    /// or local, `GLO` for a global. Returns its type.
+    fn address(&self, name: &str, chunk: &mut Chunk) -> Result<Type, CompileError> {
+        match self.resolve(name, chunk) {
+            Some(Symbol::Local { offset, ty }) => {
+                chunk.push_int(OpCode::LEA, offset);
+                Ok(ty)
+            }
+            Some(Symbol::Global { offset, ty }) => {
+                chunk.push_int(OpCode::GLO, offset as i64);
+                Ok(ty)
+            }
+            Some(Symbol::Constant(_)) => Err(CompileError::new("bad lvalue in assignment")),
+            None => Err(CompileError::new(format!("undefined variable: {}", name))),
+        }
+    }
+}
+
+// Compile statements to bytecode, recording in the chunk's source map
+// which statement each instruction came from.
+impl Stmt {
+    pub fn compile<'a>(&'a self, chunk: &mut Chunk, ctx: &mut FnCtx<'a>) -> Result<(), CompileError> {
+        let Some(span) = self.span else {
+            return self.compile_kind(chunk, ctx);
+        };
+        let outer = chunk.mark_source(Some(span.start));
+        self.compile_kind(chunk, ctx)?;
+        chunk.mark_source(outer);
+        Ok(())
+    }
+
+    fn compile_kind<'a>(&'a self, chunk: &mut Chunk, ctx: &mut FnCtx<'a>) -> Result<(), CompileError> {
+        match &self.kind {
+            StmtKind::Expr(e) => {
+                e.compile(chunk, ctx)?;
+                Ok(()) // Expression result left in register `a`.
+            }
+            StmtKind::Return(Some(e)) => {
+                e.compile(chunk, ctx)?;
+                chunk.push(OpCode::LEV); // Return from function.
+                Ok(())
+            }
+            StmtKind::Return(None) => {
+                chunk.push(OpCode::LEV);
+                Ok(())
+            }
+            StmtKind::If { cond, then_branch, else_branch } => {
+                // BZ past the then-branch; with an else-branch, the
+                // then-branch ends by jumping over it.
+                cond.compile(chunk, ctx)?;
+                let to_else = chunk.current_offset();
+                chunk.push_jump(OpCode::BZ, 0);
+                then_branch.compile(chunk, ctx)?;
+                match else_branch {
+                    Some(e) => {
+                        let to_end = chunk.current_offset();
+                        chunk.push_jump(OpCode::JMP, 0);
+                        chunk.patch_jump(to_else, chunk.current_offset());
+                        e.compile(chunk, ctx)?;
+                        chunk.patch_jump(to_end, chunk.current_offset());
+                    }
+                    None => chunk.patch_jump(to_else, chunk.current_offset()),
+                }
+                Ok(())
+            }
+            StmtKind::While { cond, body } => {
+                // Test at the head, leave with BZ, and jump back after the body.
+                let head = chunk.code.len();
+                cond.compile(chunk, ctx)?;
+                let exit = chunk.code.len();
+                chunk.push_jump(OpCode::BZ, 0);
+                ctx.loops.push(LoopJumps::default());
+                body.compile(chunk, ctx)?;
+                chunk.push_jump(OpCode::JMP, head);
+                chunk.patch_jump(exit, chunk.current_offset());
+                ctx.end_loop(chunk, head);
+                Ok(())
+            }
+            StmtKind::DoWhile { body, cond } => {
+                // Body first, then loop back while the condition holds.
+                let start = chunk.code.len();
+                ctx.loops.push(LoopJumps::default());
+                body.compile(chunk, ctx)?;
+                let test = chunk.code.len();
+                cond.compile_at_own_line(chunk, ctx)?;
+                chunk.push_jump(OpCode::BNZ, start);
+                ctx.end_loop(chunk, test);
+                Ok(())
+            }
+            StmtKind::For { init, cond, step, body } => {
+                // Like `while`, with the step between the body and the jump
+                // back; `continue` goes to the step.
+                if let Some(init) = init {
+                    init.compile(chunk, ctx)?;
+                }
+                let head = chunk.code.len();
+                let exit = match cond {
+                    Some(cond) => {
+                        cond.compile(chunk, ctx)?;
+                        let exit = chunk.code.len();
+                        chunk.push_jump(OpCode::BZ, 0);
+                        Some(exit)
+                    }
+                    None => None,
+                };
+                ctx.loops.push(LoopJumps::default());
+                body.compile(chunk, ctx)?;
+                let next = chunk.code.len();
+                if let Some(step) = step {
+                    step.compile_at_own_line(chunk, ctx)?;
+                }
+                chunk.push_jump(OpCode::JMP, head);
+                if let Some(exit) = exit {
+                    chunk.patch_jump(exit, chunk.current_offset());
+                }
+                ctx.end_loop(chunk, next);
+                Ok(())
+            }
+            StmtKind::Switch { scrutinee, cases } => {
+                // Keep the scrutinee in a slot of its own, and test it
+                // against each label in turn.
+                let slot = ctx.temp();
+                chunk.push_int(OpCode::LEA, slot);
+                chunk.push(OpCode::PSH);
+                scrutinee.compile(chunk, ctx)?;
+                chunk.push(OpCode::SI);
+                let mut tests = Vec::new();
+                for n in cases.iter().filter_map(|(label, _)| *label) {
+                    chunk.push_int(OpCode::LEA, slot);
+                    chunk.push(OpCode::LI);
+                    chunk.push(OpCode::PSH);
+                    chunk.push_int(OpCode::IMM, n);
+                    chunk.push(OpCode::EQ);
+                    tests.push(chunk.code.len());
+                    chunk.push_jump(OpCode::BNZ, 0);
+                }
+
+                // Nothing matched: on to `default`, or out like a `break`.
+                let mut jumps = LoopJumps { is_switch: true, ..LoopJumps::default() };
+                let fallback = chunk.code.len();
+                chunk.push_jump(OpCode::JMP, 0);
+                if cases.iter().all(|(label, _)| label.is_some()) {
+                    jumps.breaks.push(fallback);
+                }
+                ctx.loops.push(jumps);
+
+                // The cases in order, each falling through into the next.
+                let mut tests = tests.into_iter();
+                for (label, stmts) in cases {
+                    let here = chunk.code.len();
+                    match label {
+                        Some(_) => {
+                            let test = tests.next().expect("one test per label");
+                            chunk.patch_jump(test, here);
+                        }
+                        None => chunk.patch_jump(fallback, here),
+                    }
+                    for stmt in stmts {
+                        stmt.compile(chunk, ctx)?;
+                    }
+                }
+                ctx.end_loop(chunk, 0); // a switch has no continues of its own
+                ctx.release_temp();
+                Ok(())
+            }
+            StmtKind::Break => ctx.jump_out(chunk, true),
+            StmtKind::Continue => ctx.jump_out(chunk, false),
+            StmtKind::Block(b) => {
+                ctx.enter(&b.locals);
+                for stmt in &b.stmts {
+                    stmt.compile(chunk, ctx)?;
+                }
+                ctx.leave();
+                Ok(())
+            }
+            StmtKind::Empty => Ok(()),
+        }
+    }
+}
+
+// Compile expressions into bytecode.
+impl Expr {
+    // Compile, attributing the code to the expression's own place in the
+    // source rather than its statement's, for the parts of a loop that
+    // run after its body.
+    fn compile_at_own_line<'a>(&'a self, chunk: &mut Chunk, ctx: &mut FnCtx<'a>) -> Result<(), CompileError> {
+        let Some(span) = self.span else {
+            return self.compile(chunk, ctx);
+        };
+        let outer = chunk.mark_source(Some(span.start));
+        self.compile(chunk, ctx)?;
+        chunk.mark_source(outer);
+        Ok(())
+    }
+
+    pub fn compile<'a>(&'a self, chunk: &mut Chunk, ctx: &mut FnCtx<'a>) -> Result<(), CompileError> {
+        match &self.kind {
+            ExprKind::Num(n) => chunk.push_int(OpCode::IMM, *n), // Load immediate integer.
+            ExprKind::Str(s) => {
+                // The literal lives in the data segment, like a global.
+                let offset = chunk.intern(s);
+                chunk.push_int(OpCode::GLO, offset as i64);
+            }
+            ExprKind::Var(name) => match ctx.resolve(name, chunk) {
+                Some(Symbol::Constant(value)) => chunk.push_int(OpCode::IMM, value),
+                _ => {
+                    // Load the variable through its address.
+                    let ty = self.compile_address(chunk, ctx)?;
+                    chunk.push(load_op(&ty));
+                }
+            },
+            ExprKind::Unary { op: op @ (UnOp::PreInc | UnOp::PreDec | UnOp::PostInc | UnOp::PostDec), expr } => {
+                // As in c4: load through the saved address, add or
+                // subtract 1, or a pointer's pointee size, and store; a
+                // postfix operator then undoes the step in `a`, leaving
+                // the old value.
+                if !expr.is_lvalue() {
+                    return Err(CompileError::new("bad lvalue in increment or decrement"));
+                }
+                let ty = expr.compile_address(chunk, ctx)?;
+                let step = pointee_size(&ty).unwrap_or(1) as i64;
+                chunk.push(OpCode::PSH);
+                chunk.push(load_op(&ty));
+                chunk.push(OpCode::PSH);
+                chunk.push_int(OpCode::IMM, step);
+                let up = matches!(op, UnOp::PreInc | UnOp::PostInc);
+                chunk.push(if up { OpCode::ADD } else { OpCode::SUB });
+                chunk.push(store_op(&ty));
+                if matches!(op, UnOp::PostInc | UnOp::PostDec) {
+                    chunk.push(OpCode::PSH);
+                    chunk.push_int(OpCode::IMM, step);
+                    chunk.push(if up { OpCode::SUB } else { OpCode::ADD });
+                }
+            }
+            ExprKind::Unary { op: UnOp::Deref, .. } | ExprKind::Index { .. } => {
+                // Load through the pointer; a `char *` points at a byte.
+                let ty = self.compile_address(chunk, ctx)?;
+                chunk.push(load_op(&ty));
+            }
+            ExprKind::Unary { op: UnOp::Addr, expr } => {
+                expr.compile_address(chunk, ctx)?;
+            }
+            ExprKind::Unary { op: UnOp::Plus, expr } => expr.compile(chunk, ctx)?,
+            ExprKind::Unary { op: UnOp::Neg, expr } => {
+                // 0 - x
+                chunk.push_int(OpCode::IMM, 0);
+                chunk.push(OpCode::PSH);
+                expr.compile(chunk, ctx)?;
+                chunk.push(OpCode::SUB);
+            }
+            ExprKind::Unary { op: op @ (UnOp::Not | UnOp::BitNot), expr } => {
+                // x == 0, or x ^ -1
+                expr.compile(chunk, ctx)?;
+                chunk.push(OpCode::PSH);
+                let (imm, code) = if *op == UnOp::Not { (0, OpCode::EQ) } else { (-1, OpCode::XOR) };
+                chunk.push_int(OpCode::IMM, imm);
+                chunk.push(code);
+            }
+            ExprKind::Binary { op: BinOp::Assign, left, right } => {
+                // Address, saved on the stack while the value is computed;
+                // the store leaves the value in `a`, so assignments chain.
+                let ty = left.compile_address(chunk, ctx)?;
+                chunk.push(OpCode::PSH);
+                right.compile(chunk, ctx)?;
+                chunk.push(store_op(&ty));
+            }
+            ExprKind::Binary { op: op @ (BinOp::LogAnd | BinOp::LogOr), left, right } => {
+                // Either operand can decide the result, skipping what is
+                // left: `&&` on a zero, `||` on anything else. The result
+                // is 0 or 1.
+                let (decide, decided) = match op {
+                    BinOp::LogAnd => (OpCode::BZ, 0),
+                    _ => (OpCode::BNZ, 1),
+                };
+                let mut to_decided = Vec::new();
+                for operand in [left, right] {
+                    operand.compile(chunk, ctx)?;
+                    to_decided.push(chunk.current_offset());
+                    chunk.push_jump(decide, 0);
+                }
+                chunk.push_int(OpCode::IMM, 1 - decided);
+                let to_end = chunk.current_offset();
+                chunk.push_jump(OpCode::JMP, 0);
+                for at in to_decided {
+                    chunk.patch_jump(at, chunk.current_offset());
+                }
+                chunk.push_int(OpCode::IMM, decided);
+                chunk.patch_jump(to_end, chunk.current_offset());
+            }
+            ExprKind::Binary { op, left, right } => {
+                left.compile(chunk, ctx)?;
+                chunk.push(OpCode::PSH); // Save left operand.
+                right.compile(chunk, ctx)?;
+                // As in c4, a pointer on the left steps by whole pointees:
+                // `p + n` scales `n`, and `p - q` counts elements.
+                let size = match (op, left.ty(chunk, ctx), right.ty(chunk, ctx)) {
+                    (BinOp::Sub, Type::Ptr(pointee), Type::Ptr(_)) => {
+                        chunk.push(OpCode::SUB);
+                        scale(chunk, pointee.size_of(), OpCode::DIV);
+                        return Ok(());
+                    }
+                    (BinOp::Add | BinOp::Sub, ref ty, _) => pointee_size(ty),
+                    _ => None,
+                };
+                if let Some(size) = size {
+                    scale(chunk, size, OpCode::MUL);
+                }
+                let code = binary_opcode(op)
+                    .ok_or_else(|| CompileError::new(format!("unsupported op: {:?}", op)))?;
+                chunk.push(code);
+            }
+            ExprKind::CompoundAssign { op, target, value } => {
+                // The target's address is computed once and kept on the
+                // stack: once for the load, once for the store.
+                let ty = target.compile_address(chunk, ctx)?;
+                chunk.push(OpCode::PSH);
+                chunk.push(load_op(&ty));
+                chunk.push(OpCode::PSH);
+                value.compile(chunk, ctx)?;
+                if let (BinOp::Add | BinOp::Sub, Some(size)) = (op, pointee_size(&ty)) {
+                    scale(chunk, size, OpCode::MUL);
+                }
+                let code = binary_opcode(op)
+                    .ok_or_else(|| CompileError::new(format!("unsupported op: {:?}", op)))?;
+                chunk.push(code);
+                chunk.push(store_op(&ty));
+            }
+            ExprKind::Call { callee, args } => {
+                let ExprKind::Var(name) = &callee.kind else {
+                    return Err(CompileError::new("callee must be a named function"));
+                };
+
+                // `printf` takes its arguments on the stack, like a call;
+                // `PRTF n` reads them, format string first.
+                if name == "printf" {
+                    if args.is_empty() {
+                        return Err(CompileError::new("printf expects a format string"));
+                    }
+                    for arg in args {
+                        arg.compile(chunk, ctx)?;
+                        chunk.push(OpCode::PSH);
+                    }
+                    chunk.push_int(OpCode::PRTF, args.len() as i64);
+                    chunk.push_int(OpCode::ADJ, args.len() as i64);
+                    return Ok(());
+                }
+
+                // `memset`, `memcmp`, `open` and `read` take their
+                // arguments on the stack, as in c4.
+                if let Some((op, arity)) = stack_builtin(name) {
+                    if args.len() != arity {
+                        return Err(CompileError::new(format!(
+                            "{} expects {} arguments, got {}",
+                            name,
+                            arity,
+                            args.len()
+                        )));
+                    }
+                    for arg in args {
+                        arg.compile(chunk, ctx)?;
+                        chunk.push(OpCode::PSH);
+                    }
+                    chunk.push(op);
+                    chunk.push_int(OpCode::ADJ, arity as i64);
+                    return Ok(());
+                }
+
+                // Builtins are single instructions taking their argument in `a`.
+                if let Some((op, arity)) = builtin(name) {
+                    if args.len() != arity {
+                        return Err(CompileError::new(format!(
+                            "{} expects {} arguments, got {}",
+                            name,
+                            arity,
+                            args.len()
+                        )));
+                    }
+                    if let Some(arg) = args.first() {
+                        arg.compile(chunk, ctx)?;
+                    }
+                    chunk.push(op);
+                    return Ok(());
+                }
+
+                for arg in args {
+                    arg.compile(chunk, ctx)?;
+                    chunk.push(OpCode::PSH); // Push each argument.
+                }
+                SymbolTable::emit_call(chunk, name);
+                if !args.is_empty() {
+                    chunk.push_int(OpCode::ADJ, args.len() as i64); // Pop the arguments.
+                }
+            }
+            ExprKind::Conditional { cond, then_expr, else_expr } => {
+                // As `if`, but each branch leaves its value in `a`.
+                cond.compile(chunk, ctx)?;
+                let to_else = chunk.current_offset();
+                chunk.push_jump(OpCode::BZ, 0);
+                then_expr.compile(chunk, ctx)?;
+                let to_end = chunk.current_offset();
+                chunk.push_jump(OpCode::JMP, 0);
+                chunk.patch_jump(to_else, chunk.current_offset());
+                else_expr.compile(chunk, ctx)?;
+                chunk.patch_jump(to_end, chunk.current_offset());
+            }
+            ExprKind::SizeOf(ty) => chunk.push_int(OpCode::IMM, ty.size_of() as i64),
+            ExprKind::Cast { ty, expr } => {
+                expr.compile(chunk, ctx)?;
+                // Narrowing to a char keeps the low byte; every other cast
+                // only changes the type.
+                if *ty == Type::Char {
+                    chunk.push(OpCode::PSH);
+                    chunk.push_int(OpCode::IMM, 0xFF);
+                    chunk.push(OpCode::AND);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Compile the address of an lvalue into `a`, returning the type stored
+    // there: a variable's, the pointer's own value for `*p`, or the
+    // pointer plus the index, times the element size, for `p[i]`.
+    fn compile_address<'a>(&'a self, chunk: &mut Chunk, ctx: &mut FnCtx<'a>) -> Result<Type, CompileError> {
+        match &self.kind {
+            ExprKind::Var(name) => ctx.address(name, chunk),
+            ExprKind::Unary { op: UnOp::Deref, expr } => {
+                let Type::Ptr(pointee) = expr.ty(chunk, ctx) else {
+                    return Err(CompileError::new("bad dereference"));
+                };
+                expr.compile(chunk, ctx)?;
+                Ok(*pointee)
+            }
+            ExprKind::Index { array, index } => {
+                let Type::Ptr(pointee) = array.ty(chunk, ctx) else {
+                    return Err(CompileError::new("pointer type expected"));
+                };
+                array.compile(chunk, ctx)?;
+                chunk.push(OpCode::PSH);
+                index.compile(chunk, ctx)?;
+                scale(chunk, pointee.size_of(), OpCode::MUL);
+                chunk.push(OpCode::ADD);
+                Ok(*pointee)
+            }
+            _ => Err(CompileError::new("bad lvalue in assignment")),
+        }
+    }
+
+    // The type of the value the expression computes, as far as the code
+    // generator needs it: which loads and stores to use.
+    fn ty(&self, chunk: &Chunk, ctx: &FnCtx) -> Type {
+        match &self.kind {
+            ExprKind::Str(_) => Type::Ptr(Box::new(Type::Char)),
+            ExprKind::Var(name) => match ctx.resolve(name, chunk) {
+                Some(Symbol::Local { ty, .. } | Symbol::Global { ty, .. }) => ty,
+                _ => Type::Int,
+            },
+            ExprKind::Unary { op: UnOp::Deref, expr } => match expr.ty(chunk, ctx) {
+                Type::Ptr(pointee) => *pointee,
+                _ => Type::Int,
+            },
+            ExprKind::Unary { op: UnOp::Addr, expr } => Type::Ptr(Box::new(expr.ty(chunk, ctx))),
+            ExprKind::Unary { op: UnOp::PreInc | UnOp::PreDec | UnOp::PostInc | UnOp::PostDec, expr } => {
+                expr.ty(chunk, ctx)
+            }
+            ExprKind::Binary { op: BinOp::Assign, left, .. } | ExprKind::CompoundAssign { target: left, .. } => {
+                left.ty(chunk, ctx)
+            }
+            ExprKind::Binary { op: op @ (BinOp::Add | BinOp::Sub), left, right } => {
+                match (left.ty(chunk, ctx), right.ty(chunk, ctx)) {
+                    (Type::Ptr(_), Type::Ptr(_)) if *op == BinOp::Sub => Type::Int,
+                    (ty @ Type::Ptr(_), _) => ty,
+                    _ => Type::Int,
+                }
+            }
+            ExprKind::Call { callee, .. } => match &callee.kind {
+                ExprKind::Var(name) => SymbolTable::return_type(chunk, name).unwrap_or(Type::Int),
+                _ => Type::Int,
+            },
+            ExprKind::Cast { ty, .. } => ty.clone(),
+            ExprKind::Conditional { then_expr, .. } => then_expr.ty(chunk, ctx),
+            ExprKind::Index { array, .. } => match array.ty(chunk, ctx) {
+                Type::Ptr(pointee) => *pointee,
+                _ => Type::Int,
+            },
+            _ => Type::Int,
+        }
+    }
+}
+
+// The size of what a pointer of type `ty` points at; None if `ty` isn't a
+// pointer.
+fn pointee_size(ty: &Type) -> Option<usize> {
+    match ty {
+        Type::Ptr(pointee) => Some(pointee.size_of()),
+        _ => None,
+    }
+}
+
+// Multiply or divide `a` by `size`, skipping the no-op for 1.
+fn scale(chunk: &mut Chunk, size: usize, code: OpCode) {
+    if size != 1 {
+        chunk.push(OpCode::PSH);
+        chunk.push_int(OpCode::IMM, size as i64);
+        chunk.push(code);
+    }
+}
+
+// The load and store for a value of type `ty`: `char`s are bytes.
+fn load_op(ty: &Type) -> OpCode {
+    if *ty == Type::Char { OpCode::LC } else { OpCode::LI }
+}
+
+fn store_op(ty: &Type) -> OpCode {
+    if *ty == Type::Char { OpCode::SC } else { OpCode::SI }
+}
+
+// The instruction for a binary operator; `&&`, `||` and `=` need jumps
+// or an address, so they have none.
+fn binary_opcode(op: &BinOp) -> Option<OpCode> {
+    Some(match op {
+        BinOp::Add => OpCode::ADD,
+        BinOp::Sub => OpCode::SUB,
+        BinOp::Mul => OpCode::MUL,
+        BinOp::Div => OpCode::DIV,
+        BinOp::Mod => OpCode::MOD,
+        BinOp::Eq  => OpCode::EQ,
+        BinOp::Ne  => OpCode::NE,
+        BinOp::Lt  => OpCode::LT,
+        BinOp::Le  => OpCode::LE,
+        BinOp::Gt  => OpCode::GT,
+        BinOp::Ge  => OpCode::GE,
+        BinOp::BitAnd => OpCode::AND,
+        BinOp::BitOr  => OpCode::OR,
+        BinOp::Xor    => OpCode::XOR,
+        BinOp::Shl    => OpCode::SHL,
+        BinOp::Shr    => OpCode::SHR,
+        _ => return None,
+    })
+}
+
+// The instruction and argument count of a builtin that reads its
+// arguments off the stack.
+fn stack_builtin(name: &str) -> Option<(OpCode, usize)> {
+    match name {
+        "memset" => Some((OpCode::MSET, 3)),
+        "memcmp" => Some((OpCode::MCMP, 3)),
+        "open" => Some((OpCode::OPEN, 2)),
+        "read" => Some((OpCode::READ, 3)),
+        _ => None,
+    }
+}
+
+// The instruction and argument count of a builtin function.
+fn builtin(name: &str) -> Option<(OpCode, usize)> {
+    match name {
+        "assert" => Some((OpCode::ASRT, 1)),
+        "rand" => Some((OpCode::RAND, 0)),
+        "srand" => Some((OpCode::SRND, 1)),
+        "clock" => Some((OpCode::CLCK, 0)),
+        "time" => Some((OpCode::TIME, 1)),
+        "malloc" => Some((OpCode::MALC, 1)),
+        "close" => Some((OpCode::CLOS, 1)),
+        "free" => Some((OpCode::FREE, 1)),
+        _ => None,
+    }
+}
+
+/// A runtime fault, with the program counter of the faulting instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmError {
+    StackOverflow { pc: usize },
+    StackUnderflow { pc: usize },
+    DivisionByZero { pc: usize },
+    CallStackUnderflow { pc: usize },
+    InvalidAddress { pc: usize, addr: i64 },
+    UnknownInstruction { pc: usize, instr: Instruction },
+    /// An `assert` whose condition was zero.
+    AssertionFailed { pc: usize },
+    /// The run's [`CancelToken`] was cancelled; `pc` is the next
+    /// instruction that would have run.
+    Cancelled { pc: usize },
+    /// The run used up its instruction budget after `steps` instructions;
+    /// `pc` is the next instruction that would have run.
+    OutOfFuel { pc: usize, steps: u64 },
+    /// A `printf` conversion asked for a width or precision over 4096.
+    FormatTooWide { pc: usize },
+    /// While replaying, call number `index` wasn't the recorded one:
+    /// `found` is what the program called, None if it finished, and
+    /// `expected` what the log holds, None if it had run out. Boxed to
+    /// keep every result carrying a `VmError` small.
+    ReplayDiverged { pc: usize, index: usize, expected: Option<Box<Syscall>>, found: Option<Box<Syscall>> },
+    /// [`VM::load`] was given a chunk with `words` of data, more than the
+    /// `limit` the VM's memory allows. Nothing ran, so its pc is 0.
+    DataTooLarge { words: usize, limit: usize },
+}
+
+impl VmError {
+    /// Program counter of the instruction that faulted.
+    pub fn pc(&self) -> usize {
+        match self {
+            VmError::StackOverflow { pc }
+            | VmError::StackUnderflow { pc }
+            | VmError::DivisionByZero { pc }
+            | VmError::CallStackUnderflow { pc }
+            | VmError::InvalidAddress { pc, .. }
+            | VmError::UnknownInstruction { pc, .. }
+            | VmError::AssertionFailed { pc }
+            | VmError::Cancelled { pc }
+            | VmError::OutOfFuel { pc, .. }
+            | VmError::FormatTooWide { pc }
+            | VmError::ReplayDiverged { pc, .. } => *pc,
+            VmError::DataTooLarge { .. } => 0,
+        }
+    }
+
+    /// Snapshot `vm`, which stopped with this error while running `chunk`.
+    pub fn dump(&self, vm: &VM, chunk: &Chunk) -> CoreDump {
+        CoreDump {
+            error: self.to_string(),
+            fault_pc: self.pc(),
+            sp: vm.sp,
+            fp: vm.fp,
+            frames: vm
+                .call_stack
+                .iter()
+                .map(|&(return_pc, sp, fp)| Frame { return_pc, sp, fp })
+                .collect(),
+            stack: vm.stack[..vm.sp].to_vec(),
+            heap: vm.heap.live_blocks().map(|(addr, words)| HeapBlock { addr, words }).collect(),
+            fingerprint: chunk.fingerprint(),
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::StackOverflow { pc } => write!(f, "stack overflow at pc {}", pc),
+            VmError::StackUnderflow { pc } => write!(f, "stack underflow at pc {}", pc),
+            VmError::DivisionByZero { pc } => write!(f, "division by zero at pc {}", pc),
+            VmError::CallStackUnderflow { pc } => write!(f, "call stack underflow at pc {}", pc),
+            VmError::InvalidAddress { pc, addr } => {
+                write!(f, "invalid address {} at pc {}", addr, pc)
+            }
+            VmError::UnknownInstruction { pc, instr } => {
+                write!(f, "unknown instruction {:?} at pc {}", instr, pc)
+            }
+            VmError::AssertionFailed { pc } => write!(f, "assertion failed at pc {}", pc),
+            VmError::Cancelled { pc } => write!(f, "cancelled at pc {}", pc),
+            VmError::OutOfFuel { pc, steps } => write!(f, "out of fuel after {} instructions at pc {}", steps, pc),
+            VmError::FormatTooWide { pc } => write!(f, "printf width or precision too large at pc {}", pc),
+            VmError::DataTooLarge { words, limit } => {
+                write!(f, "data segment of {} words doesn't fit in {}", words, limit)
+            }
+            VmError::ReplayDiverged { pc, index, expected, found } => {
+                write!(f, "replay diverged at syscall {} (pc {}): ", index, pc)?;
+                match (expected, found) {
+                    (Some(expected), Some(found)) => {
+                        write!(f, "the program called {} but the recording has {}", found, expected)
+                    }
+                    (None, Some(found)) => write!(f, "the program called {} after the recording ended", found),
+                    (Some(expected), None) => write!(f, "the program finished before the recorded {}", expected),
+                    (None, None) => write!(f, "the recording doesn't match"),
+                }
+            }
+        }
+    }
+}
+
+impl core::error::Error for VmError {}
+
+/// Where a running program's output goes. With the `std` feature every
+/// `std::io::Write` is an `Output`.
+pub trait Output {
+    fn write_bytes(&mut self, bytes: &[u8]);
+    fn flush_output(&mut self);
+}
+
+#[cfg(feature = "std")]
+impl<W: Write + ?Sized> Output for W {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        let _ = self.write_all(bytes);
+    }
+
+    fn flush_output(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Output sink that drops everything; the default without `std`.
+pub struct Discard;
+
+impl Output for Discard {
+    fn write_bytes(&mut self, _bytes: &[u8]) {}
+    fn flush_output(&mut self) {}
+}
+
+/// Which engine runs a compiled chunk. Every backend gives the same results;
+/// the VM is the only one with a debug trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// The bytecode interpreter, [`VM`].
+    #[default]
+    Vm,
+    /// `fastexec::ClosureProgram`, with the `fastexec` feature.
+    #[cfg(feature = "fastexec")]
+    Closures,
+    /// `jit::JitProgram`, with the `jit` feature.
+    #[cfg(feature = "jit")]
+    Jit,
+}
+
+/// Instructions the VM runs between checks of its [`CancelToken`].
+pub const CANCEL_CHECK_INTERVAL: u64 = 1024;
+
+/// The first descriptor `open()` hands out, after stdin, stdout and stderr.
+const FIRST_FD: usize = 3;
+
+/// Most files a program may have open at once; `open()` returns -1 past it.
+pub const MAX_OPEN_FILES: usize = 64;
+
+/// Where `open` and `read` get their answers.
+enum Syscalls {
+    /// From the filesystem.
+    Live,
+    /// From the filesystem, logging each call.
+    Recording(SyscallLog),
+    /// From `log`, whose next unused call is `next`.
+    Replaying { log: SyscallLog, next: usize },
+}
+
+/// A descriptor taken while replaying; reads come from the log instead.
+struct ReplayedFile;
+
+impl OpenFile for ReplayedFile {
+    fn read(&mut self, _buf: &mut [u8]) -> Option<usize> {
+        None
+    }
+}
+
+/// Shared flag for stopping a run from another thread. Clones share the
+/// flag; two tokens are equal when they are clones of each other.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// Ask every run holding this token to stop with `VmError::Cancelled`.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Largest value `rand()` returns.
+pub const RAND_MAX: i64 = 0x7FFF_FFFF;
+
+pub(crate) const RNG_MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+pub(crate) const RNG_INCREMENT: u64 = 1_442_695_040_888_963_407;
+
+/// The generator behind the `rand()` and `srand(seed)` builtins: a 64-bit
+/// LCG with Knuth's MMIX constants, returning the top 31 bits of its state.
+/// It is the same on every platform, backend and in the AST interpreter.
+/// Unseeded, it starts as if `srand(1)` had been called, as in C.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: i64) -> Self {
+        Rng { state: seed as u64 }
+    }
+
+    /// The next value, in `0..=RAND_MAX`.
+    pub fn rand(&mut self) -> i64 {
+        self.state = self.state.wrapping_mul(RNG_MULTIPLIER).wrapping_add(RNG_INCREMENT);
+        (self.state >> 33) as i64
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(1)
+    }
+}
+
+/// Time source behind the `clock()` and `time()` builtins, so tests and
+/// hosts can substitute their own.
+pub trait ProgramClock: Send + Sync {
+    /// Time elapsed since some fixed starting point; must not decrease.
+    fn now(&self) -> Duration;
+    /// Seconds since the Unix epoch.
+    fn unix_time(&self) -> i64;
+}
+
+/// The real clock, measured from its creation. The default with `std`.
+#[cfg(feature = "std")]
+pub struct HostClock {
+    start: Instant,
+}
+
+#[cfg(feature = "std")]
+impl HostClock {
+    pub fn new() -> Self {
+        HostClock { start: Instant::now() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for HostClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl ProgramClock for HostClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn unix_time(&self) -> i64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64)
+    }
+}
+
+/// A clock that never moves and reads 0 for both builtins: for runs that
+/// mustn't see the host's time, and the default without `std`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrozenClock;
+
+impl ProgramClock for FrozenClock {
+    fn now(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn unix_time(&self) -> i64 {
+        0
+    }
+}
+
+/// A [`ProgramClock`] shared by the runs of every config cloned from one
+/// holding it. Two are equal when they share the same clock.
+#[derive(Clone)]
+pub struct SharedClock(Arc<dyn ProgramClock>);
+
+impl SharedClock {
+    pub fn new(clock: impl ProgramClock + 'static) -> Self {
+        SharedClock(Arc::new(clock))
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedClock")
+    }
+}
+
+impl PartialEq for SharedClock {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl core::ops::Deref for SharedClock {
+    type Target = dyn ProgramClock;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+/// Tunable limits for a [`VM`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmConfig {
+    /// Stack size, in words.
+    pub stack_size: usize,
+    /// Heap size, in words; None for the same as the stack.
+    pub heap_size: Option<usize>,
+    /// Checked every [`CANCEL_CHECK_INTERVAL`] instructions by the VM and at
+    /// every basic block by the closure backend. The JIT doesn't poll it.
+    pub cancel_token: Option<CancelToken>,
+    /// Source of `clock()` and `time()`; None for [`HostClock`] with `std`
+    /// and [`FrozenClock`] without.
+    pub clock: Option<SharedClock>,
+    /// Where `open()` finds files; None for an unrestricted
+    /// [`HostFs`](crate::vfs::HostFs) with `std` and an empty
+    /// [`MemFs`](crate::vfs::MemFs) without.
+    pub filesystem: Option<SharedFs>,
+    /// Record where each heap block was allocated and how usage peaked,
+    /// for [`VM::leak_report`].
+    pub track_allocations: bool,
+    /// Most instructions one run may execute, or None for no limit. The
+    /// closure backend checks it at every basic block, so may overshoot by
+    /// one; the JIT doesn't count, so such runs use the VM.
+    pub max_steps: Option<u64>,
+}
+
+impl Default for VmConfig {
+    fn default() -> Self {
+        VmConfig { stack_size: 1024 * 1024, heap_size: None, cancel_token: None, clock: None, filesystem: None, track_allocations: false, max_steps: None }
+    }
+}
+
+impl VmConfig {
+    /// The heap size runs with this config should use, in words.
+    pub fn heap_size(&self) -> usize {
+        self.heap_size.unwrap_or(self.stack_size)
+    }
+
+    /// The clock runs with this config should use.
+    pub fn clock(&self) -> SharedClock {
+        self.clock.clone().unwrap_or_else(default_clock)
+    }
+
+    /// The filesystem runs with this config should use.
+    pub fn filesystem(&self) -> SharedFs {
+        self.filesystem.clone().unwrap_or_else(default_filesystem)
+    }
+}
+
+/// Everything observable about one finished run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOutcome {
+    /// The program's return value.
+    pub exit_code: i64,
+    /// Bytes the program wrote to its output.
+    pub stdout: Vec<u8>,
+    /// Instructions executed.
+    pub instructions: u64,
+    /// Deepest the operand stack got, in words.
+    pub max_stack_words: usize,
+    /// Most call frames live at once.
+    pub max_call_depth: usize,
+    /// Most heap in use at once, in words.
+    pub heap_peak_words: usize,
+    /// Time spent executing.
+    pub wall_time: Duration,
+}
+
+impl RunOutcome {
+    /// Resource-usage table (everything except the captured output).
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        for (name, value) in [
+            ("exit code", self.exit_code.to_string()),
+            ("instructions", self.instructions.to_string()),
+            ("max stack", format!("{} words", self.max_stack_words)),
+            ("call depth", self.max_call_depth.to_string()),
+            ("heap peak", format!("{} words", self.heap_peak_words)),
+            ("wall time", format!("{:.3} ms", self.wall_time.as_secs_f64() * 1000.0)),
+        ] {
+            out.push_str(&format!("{:<14} {}\n", name, value));
+        }
+        out
+    }
+}
+
+/// Handle to a chunk kept in a [`VM`] by [`VM::load`]. Ids are never
+/// reused, so a stale one can't reach a later program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProgramId(usize);
+
+// Virtual Machine structure.
+pub struct VM {
+    stack: Vec<i64>,                        // Operand stack.
+    call_stack: Vec<(usize, usize, usize)>, // Stores (return_pc, old_sp, old_fp).
+    pc: usize,                              // Program counter.
+    sp: usize,                              // Stack pointer.
+    fp: usize,                              // Frame pointer for current function call.
+    max_sp: usize,                          // Deepest the stack has been.
+    touched: usize,                         // One past the highest word loads or stores reached.
+    max_frames: usize,                      // Most call frames live at once.
+    steps: u64,                             // Instructions executed so far.
+    output: Box<dyn Output + Send>,         // Where the program's output goes.
+    coverage: Option<Vec<u64>>,             // Times each pc ran, when enabled.
+    cancel: Option<CancelToken>,            // Polled to stop the run early.
+    max_steps: Option<u64>,                 // Instruction budget of each run.
+    rng: Rng,                               // State of `rand()`.
+    clock: SharedClock,                     // Source of `clock()` and `time()`.
+    clock_start: Duration,                  // Clock reading `clock()` counts from.
+    heap: Heap,                             // Memory from `malloc()`.
+    data: Vec<i64>,                         // Globals, from the heap's end.
+    filesystem: SharedFs,                   // Where `open()` finds files.
+    files: Vec<Option<Box<dyn OpenFile>>>,  // Open files, by descriptor - FIRST_FD.
+    syscalls: Syscalls,                     // Whether `open` and `read` are recorded or replayed.
+    programs: Vec<Option<Chunk>>,           // Loaded programs, by `ProgramId`.
+    trace: Option<Box<dyn Output + Send>>,  // Where each instruction is logged, if anywhere.
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VM {
+    // Constructor: Initialize VM with the default preallocated stack.
+    pub fn new() -> Self {
+        Self::with_config(VmConfig::default())
+    }
+
+    // Constructor with a stack of `words` words, which also caps the call
+    // depth, and the default configuration otherwise.
+    pub fn with_stack_size(words: usize) -> Self {
+        Self::with_config(VmConfig { stack_size: words, ..VmConfig::default() })
+    }
+
+    // Constructor with explicit limits.
+    pub fn with_config(config: VmConfig) -> Self {
+        let clock = config.clock();
+        let filesystem = config.filesystem();
+        let heap = Heap::new(config.stack_size, config.heap_size(), config.track_allocations);
+        VM {
+            stack: vec![0; config.stack_size],
+            call_stack: Vec::new(),
+            pc: 0,
+            sp: 0,
+            fp: 0,
+            max_sp: 0,
+            touched: 0,
+            max_frames: 0,
+            steps: 0,
+            output: default_output(),
+            coverage: None,
+            cancel: config.cancel_token,
+            max_steps: config.max_steps,
+            rng: Rng::default(),
+            clock_start: clock.now(),
+            clock,
+            heap,
+            data: Vec::new(),
+            filesystem,
+            files: Vec::new(),
+            syscalls: Syscalls::Live,
+            programs: Vec::new(),
+            trace: None,
+        }
+    }
+
+    // Execute bytecode in a given chunk, returning the program's exit value.
+    pub fn run(&mut self, chunk: &Chunk) -> Result<i64, VmError> {
+        let _span = span!("vm::run");
+        // The data segment is set up on first use and, like the heap, kept
+        // until reset.
+        if self.data.len() < chunk.data.len() {
+            self.data.extend_from_slice(&chunk.data[self.data.len()..]);
+        }
+        #[cfg(feature = "tracing")]
+        let start = self.steps;
+        let mut result = self.execute(chunk);
+        if let (Ok(_), Syscalls::Replaying { log, next }) = (&result, &self.syscalls) {
+            if let Some(expected) = log.calls.get(*next) {
+                let (pc, index, expected) = (self.pc.saturating_sub(1), *next, Some(Box::new(expected.clone())));
+                result = Err(VmError::ReplayDiverged { pc, index, expected, found: None });
+            }
+        }
+        #[cfg(feature = "tracing")]
+        match &result {
+            Ok(code) => event!(instructions = self.steps - start, exit_code = code, "finished"),
+            Err(e) => event!(instructions = self.steps - start, error = %e, "failed"),
+        }
+        result
+    }
+
+    fn execute(&mut self, chunk: &Chunk) -> Result<i64, VmError> {
+        let code = &chunk.code;
+        let mut a: i64 = 0; // Register `a` is used for computation.
+        let out_of_fuel = self.max_steps.map(|n| self.steps.saturating_add(n));
+        if let Some(counts) = &mut self.coverage {
+            if counts.len() < code.len() {
+                counts.resize(code.len(), 0);
+            }
+        }
+
+        while self.pc < code.len() {
+            if let Some(token) = &self.cancel {
+                if self.steps.is_multiple_of(CANCEL_CHECK_INTERVAL) && token.is_cancelled() {
+                    return Err(VmError::Cancelled { pc: self.pc });
+                }
+            }
+            if out_of_fuel == Some(self.steps) {
+                return Err(VmError::OutOfFuel { pc: self.pc, steps: self.max_steps.unwrap_or_default() });
+            }
+            let instr = &code[self.pc];
+            self.pc += 1;
+            self.steps += 1;
+            if let Some(counts) = &mut self.coverage {
+                counts[self.pc - 1] += 1;
+            }
+
+            if let Some(trace) = &mut self.trace {
+                let record = format!("{:04} {:?} a={} sp={} fp={}\n", self.pc - 1, instr, a, self.sp, self.fp);
+                trace.write_bytes(record.as_bytes());
+            }
+
+            match instr {
+                Instruction::Instr(op) => match op {
+                    // Arithmetic
+                    // (wrapping, like the 64-bit machine c4 targets)
+                    OpCode::ADD => a = self.pop()?.wrapping_add(a),
+                    OpCode::SUB => a = self.pop()?.wrapping_sub(a),
+                    OpCode::MUL => a = self.pop()?.wrapping_mul(a),
+                    OpCode::DIV | OpCode::MOD if a == 0 => {
+                        return Err(VmError::DivisionByZero { pc: self.pc - 1 });
+                    }
+                    OpCode::DIV => a = self.pop()?.wrapping_div(a),
+                    OpCode::MOD => a = self.pop()?.wrapping_rem(a),
+
+                    // Bitwise and comparison
+                    OpCode::AND => a &= self.pop()?,
+                    OpCode::OR => a |= self.pop()?,
+                    OpCode::XOR => a ^= self.pop()?,
+                    OpCode::EQ => a = (self.pop()? == a) as i64,
+                    OpCode::NE => a = (self.pop()? != a) as i64,
+                    OpCode::LT => a = (self.pop()? < a) as i64,
+                    OpCode::LE => a = (self.pop()? <= a) as i64,
+                    OpCode::GT => a = (self.pop()? > a) as i64,
+                    OpCode::GE => a = (self.pop()? >= a) as i64,
+                    OpCode::SHL => a = self.pop()?.wrapping_shl(a as u32),
+                    OpCode::SHR => a = self.pop()?.wrapping_shr(a as u32),
+
+                    // Memory access
+                    OpCode::LI => a = self.load_int(a)?,
+                    OpCode::LC => a = self.load_char(a)?,
+                    OpCode::SI => {
+                        let addr = self.pop()?;
+                        self.store_int(addr, a)?;
+                    }
+                    OpCode::SC => {
+                        let addr = self.pop()?;
+                        a &= 0xFF;
+                        self.store_char(addr, a)?;
+                    }
+
+                    OpCode::PSH => self.push(a)?, // Push register `a` onto stack.
+
+                    // Function return
+                    OpCode::LEV => {
+                        let ret_val = a;
+                        let (ret_pc, old_sp, old_fp) = self.call_stack
+                            .pop()
+                            .ok_or(VmError::CallStackUnderflow { pc: self.pc - 1 })?;
+                        self.pc = ret_pc;
+                        self.sp = old_sp;
+                        self.fp = old_fp;
+                        a = ret_val;
+                    }
+
+                    // Exit program
+                    OpCode::EXIT => {
+                        self.output.flush_output();
+                        return Ok(a);
+                    }
+
+                    OpCode::ASRT if a == 0 => return Err(VmError::AssertionFailed { pc: self.pc - 1 }),
+                    OpCode::ASRT => {}
+                    OpCode::RAND => a = self.rng.rand(),
+                    OpCode::SRND => self.rng = Rng::new(a),
+                    OpCode::CLCK => a = self.clock.now().saturating_sub(self.clock_start).as_millis() as i64,
+                    OpCode::TIME => a = self.clock.unix_time(),
+                    OpCode::MALC => a = self.heap.malloc(a, self.pc - 1, self.steps),
+                    OpCode::FREE => self.heap.free(a, self.pc - 1)?,
+                    OpCode::MSET => a = self.memset()?,
+                    OpCode::MCMP => a = self.memcmp()?,
+                    OpCode::OPEN => a = self.open()?,
+                    OpCode::READ => a = self.read()?,
+                    OpCode::CLOS => a = self.close(a),
+
+                    _ => return Err(self.unknown(instr)),
+                },
+
+                Instruction::InstrInt(op, val) => match op {
+                    OpCode::IMM => a = *val,                            // Load immediate value.
+                    OpCode::LEA => a = local_address(self.fp, *val), // Compute effective address.
+                    OpCode::GLO => a = word_address(self.heap.end()).wrapping_add(*val), // Address of a global.
+                    OpCode::PRTF => a = self.printf(*val)?,
+                    OpCode::ADJ => {
+                        for _ in 0..*val {
+                            self.pop()?; // Discard arguments.
+                        }
+                    }
+                    OpCode::ENT => {
+                        // Enter function call: the `JSR` saved the caller's frame.
+                        self.fp = self.sp;
+                        for _ in 0..*val {
+                            self.push(0)?; // Allocate local variables.
+                        }
+                    }
+                    _ => return Err(self.unknown(instr)),
+                },
+
+                Instruction::Jump(op, target) => match op {
+                    OpCode::JMP => self.pc = *target,
+                    OpCode::BZ => if a == 0 { self.pc = *target; },
+                    OpCode::BNZ => if a != 0 { self.pc = *target; },
+                    _ => return Err(self.unknown(instr)),
+                },
+
+                Instruction::Call(op, target) => match op {
+                    OpCode::JSR => {
+                        self.push_frame()?;
+                        self.pc = *target;
+                    }
+                    _ => return Err(self.unknown(instr)),
+                },
+            }
+        }
+
+        self.output.flush_output();
+        Ok(a)
+    }
+
+    // Call the function whose `ENT` is at `entry` as if from outside the
+    // chunk: its final `LEV` returns past the end, ending the run with the
+    // function's return value.
+    pub fn call(&mut self, chunk: &Chunk, entry: usize) -> Result<i64, VmError> {
+        self.pc = chunk.code.len();
+        self.call_stack.push((self.pc, self.sp, self.fp));
+        self.max_frames = self.max_frames.max(self.call_stack.len());
+        self.pc = entry;
+        self.run(chunk)
+    }
+
+    // Run `chunk` with its output captured, and report the resource
+    // counters alongside the exit code. The previous output sink is restored.
+    #[cfg(feature = "std")]
+    pub fn run_collecting(&mut self, chunk: &Chunk) -> Result<RunOutcome, VmError> {
+        let buffer = OutputBuffer::new();
+        let previous = core::mem::replace(&mut self.output, Box::new(buffer.clone()));
+        let start = Instant::now();
+        let result = self.run(chunk);
+        let wall_time = start.elapsed();
+        self.output = previous;
+        Ok(self.outcome(result?, buffer.contents(), wall_time))
+    }
+
+    // Package the counters from the last run with its exit code and output.
+    pub fn outcome(&self, exit_code: i64, stdout: Vec<u8>, wall_time: Duration) -> RunOutcome {
+        RunOutcome {
+            exit_code,
+            stdout,
+            instructions: self.steps,
+            max_stack_words: self.max_sp,
+            max_call_depth: self.max_frames,
+            heap_peak_words: self.heap.peak_words(),
+            wall_time,
+        }
+    }
+
+    // Keep `chunk` in the VM to run, as often as needed, with
+    // `run_program`. Loaded programs share the VM's stack and heap. Like
+    // c4, which gives the data segment a pool as large as the stack, a
+    // chunk whose data has more words than the stack is rejected.
+    pub fn load(&mut self, chunk: Chunk) -> Result<ProgramId, VmError> {
+        let limit = self.stack.len();
+        if chunk.data.len() > limit {
+            return Err(VmError::DataTooLarge { words: chunk.data.len(), limit });
+        }
+        self.programs.push(Some(chunk));
+        Ok(ProgramId(self.programs.len() - 1))
+    }
+
+    // Drop a loaded program, handing its chunk back. None if it was
+    // already unloaded.
+    pub fn unload(&mut self, id: ProgramId) -> Option<Chunk> {
+        self.programs.get_mut(id.0)?.take()
+    }
+
+    // Reset the VM and run a loaded program with `args` pushed on the
+    // stack, as a caller's `PSH`es would leave them. Nothing carries over
+    // from earlier runs, including ones that faulted or were cancelled.
+    // Panics if `id` has been unloaded.
+    #[cfg(feature = "std")]
+    pub fn run_program(&mut self, id: ProgramId, args: &[i64]) -> Result<RunOutcome, VmError> {
+        let chunk = self.programs[id.0].take().expect("program has been unloaded");
+        self.reset();
+        let result = if args.len() > self.stack.len() {
+            Err(VmError::StackOverflow { pc: 0 })
+        } else {
+            self.stack[..args.len()].copy_from_slice(args);
+            self.sp = args.len();
+            self.max_sp = self.sp;
+            self.run_collecting(&chunk)
+        };
+        self.programs[id.0] = Some(chunk);
+        result
+    }
+
+    // Give the next run's `main` these arguments, as c4 does: the strings
+    // go at the bottom of the stack, then a null-terminated array of
+    // pointers to them, then `argc` and `argv` where a caller's `PSH`es
+    // would leave them. `main` can take them as `int main(int argc, char
+    // **argv)` or ignore them. Call on a fresh or reset VM.
+    pub fn set_args<S: AsRef<[u8]>>(&mut self, args: &[S]) -> Result<(), VmError> {
+        let base = self.sp;
+        let mut words = Vec::new();
+        let mut pointers = Vec::new();
+        for arg in args {
+            pointers.push(word_address(base + words.len()));
+            let mut bytes = arg.as_ref().to_vec();
+            bytes.push(0);
+            words.extend(pack_bytes(&bytes));
+        }
+        let argv = word_address(base + words.len());
+        words.extend(pointers);
+        words.push(0);
+        words.extend([args.len() as i64, argv]);
+        let end = base + words.len();
+        if end > self.stack.len() {
+            return Err(VmError::StackOverflow { pc: self.pc });
+        }
+        self.stack[base..end].copy_from_slice(&words);
+        self.sp = end;
+        self.max_sp = self.max_sp.max(end);
+        Ok(())
+    }
+
+    // Return to the freshly constructed state (keeping the output sink,
+    // trace writer, coverage counts and loaded programs) so the VM can run
+    // another chunk. Only the part of the stack that was used is cleared.
+    pub fn reset(&mut self) {
+        let used = self.touched.max(self.max_sp);
+        self.stack[..used].fill(0);
+        self.touched = 0;
+        self.call_stack.clear();
+        self.pc = 0;
+        self.sp = 0;
+        self.fp = 0;
+        self.max_sp = 0;
+        self.max_frames = 0;
+        self.steps = 0;
+        self.rng = Rng::default();
+        self.clock_start = self.clock.now();
+        self.heap.reset();
+        self.data.clear();
+        self.files.clear();
+    }
+
+    // Count how many times each instruction runs, from now on and across
+    // later runs and resets, until the counts are taken.
+    pub fn enable_coverage(&mut self) {
+        self.coverage.get_or_insert_with(Vec::new);
+    }
+
+    // Log every `open` and `read` from now on, with its result, in place
+    // of any recording or replay under way.
+    pub fn record(&mut self) {
+        self.syscalls = Syscalls::Recording(SyscallLog::default());
+    }
+
+    // The calls logged since `record`, leaving recording on with an empty
+    // log. None unless recording.
+    pub fn take_syscall_log(&mut self) -> Option<SyscallLog> {
+        match &mut self.syscalls {
+            Syscalls::Recording(log) => Some(core::mem::take(log)),
+            _ => None,
+        }
+    }
+
+    // Answer `open` and `read` from `log`, in order, instead of the
+    // filesystem. A call the log doesn't have next, or a run that ends
+    // before using it all, fails with `VmError::ReplayDiverged`.
+    pub fn replay(&mut self, log: SyscallLog) {
+        self.syscalls = Syscalls::Replaying { log, next: 0 };
+    }
+
+    // Execution counts by pc since coverage was enabled, leaving it
+    // enabled with fresh counts. None if coverage is off.
+    pub fn take_coverage(&mut self) -> Option<CoverageReport> {
+        let counts = core::mem::take(self.coverage.as_mut()?);
+        Some(CoverageReport::new(counts))
+    }
+
+    // The heap blocks the last run didn't free, by allocation site. None
+    // unless the VM was configured with `track_allocations`.
+    pub fn leak_report(&self, chunk: &Chunk) -> Option<LeakReport> {
+        self.heap.leak_report(chunk)
+    }
+
+    // Check `token` from now on instead of the configured one, so each run
+    // can be cancelled on its own.
+    pub fn set_cancel_token(&mut self, token: Option<CancelToken>) {
+        self.cancel = token;
+    }
+
+    // Stop each later run with `VmError::OutOfFuel` once it has executed
+    // `max` instructions; None lifts the limit.
+    pub fn set_max_steps(&mut self, max: Option<u64>) {
+        self.max_steps = max;
+    }
+
+    // Log every instruction to `trace` as it runs, one line each: its pc,
+    // the instruction, and registers `a`, `sp` and `fp` before it runs.
+    // None stops tracing.
+    pub fn set_trace_writer(&mut self, trace: Option<Box<dyn Output + Send>>) {
+        self.trace = trace;
+    }
+
+    // Send program output to `out` instead of stdout.
+    pub fn set_output(&mut self, out: Box<dyn Output + Send>) {
+        self.output = out;
+    }
+
+    // Number of instructions executed since the VM was created or reset.
+    pub fn instruction_count(&self) -> u64 {
+        self.steps
+    }
+
+    // Largest number of words the stack has held since creation or reset.
+    pub fn max_stack_depth(&self) -> usize {
+        self.max_sp
+    }
+
+    // Call frames live now. After a run fails, such as with a stack
+    // overflow, the depth it had reached; a reset clears it.
+    pub fn call_depth(&self) -> usize {
+        self.call_stack.len()
+    }
+
+    // Push value to stack.
+    fn push(&mut self, val: i64) -> Result<(), VmError> {
+        if self.sp >= self.stack.len() {
+            return Err(VmError::StackOverflow { pc: self.pc - 1 });
+        }
+        self.stack[self.sp] = val;
+        self.sp += 1;
+        self.max_sp = self.max_sp.max(self.sp);
+        Ok(())
+    }
+
+    // Save the caller's registers. Every frame counts against the stack
+    // size, so runaway recursion overflows instead of exhausting memory.
+    fn push_frame(&mut self) -> Result<(), VmError> {
+        if self.call_stack.len() >= self.stack.len() {
+            return Err(VmError::StackOverflow { pc: self.pc - 1 });
+        }
+        self.call_stack.push((self.pc, self.sp, self.fp));
+        self.max_frames = self.max_frames.max(self.call_stack.len());
+        Ok(())
+    }
+
+    // Pop value from stack.
+    fn pop(&mut self) -> Result<i64, VmError> {
+        if self.sp == 0 {
+            return Err(VmError::StackUnderflow { pc: self.pc - 1 });
+        }
+        self.sp -= 1;
+        Ok(self.stack[self.sp])
+    }
+
+    // The stack, heap or data word holding the byte at `addr`, if it is in
+    // bounds.
+    fn slot(&mut self, addr: i64) -> Result<&mut i64, VmError> {
+        let pc = self.pc - 1;
+        let (stack, heap, data, touched) = (&mut self.stack, &mut self.heap, &mut self.data, &mut self.touched);
+        usize::try_from(addr)
+            .ok()
+            .map(|addr| addr / WORD_SIZE)
+            .and_then(|i| {
+                if i < stack.len() {
+                    *touched = (*touched).max(i + 1);
+                    stack.get_mut(i)
+                } else if i < heap.end() {
+                    heap.word(i)
+                } else {
+                    data.get_mut(i - heap.end())
+                }
+            })
+            .ok_or(VmError::InvalidAddress { pc, addr })
+    }
+
+    // The `int` at `addr`, which needn't be aligned.
+    fn load_int(&mut self, addr: i64) -> Result<i64, VmError> {
+        if addr % WORD_SIZE as i64 == 0 {
+            return Ok(*self.slot(addr)?);
+        }
+        let mut bytes = [0; WORD_SIZE];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let loaded = self.load_char(addr.wrapping_add(i as i64));
+            *byte = loaded.map_err(|_| VmError::InvalidAddress { pc: self.pc - 1, addr })? as u8;
+        }
+        Ok(i64::from_le_bytes(bytes))
+    }
+
+    // Store the `int` `value` at `addr`, which needn't be aligned. A store
+    // that doesn't fit changes nothing.
+    fn store_int(&mut self, addr: i64, value: i64) -> Result<(), VmError> {
+        if addr % WORD_SIZE as i64 == 0 {
+            *self.slot(addr)? = value;
+            return Ok(());
+        }
+        let pc = self.pc - 1;
+        for i in 0..WORD_SIZE as i64 {
+            self.slot(addr.wrapping_add(i)).map_err(|_| VmError::InvalidAddress { pc, addr })?;
+        }
+        for (i, byte) in value.to_le_bytes().into_iter().enumerate() {
+            self.store_char(addr.wrapping_add(i as i64), i64::from(byte))?;
+        }
+        Ok(())
+    }
+
+    // The `char` at `addr`.
+    fn load_char(&mut self, addr: i64) -> Result<i64, VmError> {
+        Ok(byte_at(*self.slot(addr)?, addr))
+    }
+
+    // Store the low byte of `byte` at `addr`.
+    fn store_char(&mut self, addr: i64, byte: i64) -> Result<(), VmError> {
+        let slot = self.slot(addr)?;
+        *slot = with_byte(*slot, addr, byte);
+        Ok(())
+    }
+
+    // `memset(dest, c, n)` on the top three stack words: set the `n` bytes
+    // from `dest` to the low byte of `c`, and return `dest`.
+    fn memset(&mut self) -> Result<i64, VmError> {
+        let [dest, c, n] = self.stack_args()?;
+        for i in 0..n.max(0) {
+            self.store_char(dest.wrapping_add(i), c)?;
+        }
+        Ok(dest)
+    }
+
+    // `memcmp(p, q, n)` on the top three stack words: the difference of
+    // the first pair of bytes that differ, or 0.
+    fn memcmp(&mut self) -> Result<i64, VmError> {
+        let [p, q, n] = self.stack_args()?;
+        for i in 0..n.max(0) {
+            let x = self.load_char(p.wrapping_add(i))?;
+            let y = self.load_char(q.wrapping_add(i))?;
+            if x != y {
+                return Ok(x - y);
+            }
+        }
+        Ok(0)
+    }
+
+    // The top three stack words, deepest first, left on the stack.
+    fn stack_args<const N: usize>(&self) -> Result<[i64; N], VmError> {
+        if self.sp < N {
+            return Err(VmError::StackUnderflow { pc: self.pc - 1 });
+        }
+        Ok(self.stack[self.sp - N..self.sp].try_into().expect("N words"))
+    }
+
+    // `open(path, flags)` on the top two stack words: open the file for
+    // reading and return its descriptor, or -1 if the filesystem has no
+    // such file or `MAX_OPEN_FILES` are open. The flags are ignored.
+    fn open(&mut self) -> Result<i64, VmError> {
+        let [path, _flags] = self.stack_args()?;
+        let path = self.c_string(path)?;
+        if let Syscalls::Replaying { .. } = self.syscalls {
+            let Syscall::Open { fd, .. } = self.replayed(Syscall::Open { path, fd: -1 })? else {
+                unreachable!("replayed answers in kind")
+            };
+            // Take the descriptor, so `close` and later `open`s behave as
+            // they did.
+            if let Some(index) = usize::try_from(fd).ok().and_then(|fd| fd.checked_sub(FIRST_FD)) {
+                if self.files.len() <= index {
+                    self.files.resize_with(index + 1, || None);
+                }
+                self.files[index] = Some(Box::new(ReplayedFile));
+            }
+            return Ok(fd);
+        }
+        let fd = self.open_file(&path);
+        if let Syscalls::Recording(log) = &mut self.syscalls {
+            log.calls.push(Syscall::Open { path, fd });
+        }
+        Ok(fd)
+    }
+
+    fn open_file(&mut self, path: &[u8]) -> i64 {
+        let index = match self.files.iter().position(Option::is_none) {
+            Some(i) => i,
+            None if self.files.len() < MAX_OPEN_FILES => {
+                self.files.push(None);
+                self.files.len() - 1
+            }
+            None => return -1,
+        };
+        let Some(file) = self.filesystem.open(path) else { return -1 };
+        self.files[index] = Some(file);
+        (index + FIRST_FD) as i64
+    }
+
+    // `read(fd, buf, n)` on the top three stack words: read up to `n`
+    // bytes into memory from `buf`, and return how many, or -1 for a
+    // descriptor that isn't open.
+    fn read(&mut self) -> Result<i64, VmError> {
+        let [fd, buf, n] = self.stack_args()?;
+        let data = if let Syscalls::Replaying { .. } = self.syscalls {
+            let Syscall::Read { data, .. } = self.replayed(Syscall::Read { fd, len: n, data: None })? else {
+                unreachable!("replayed answers in kind")
+            };
+            data
+        } else {
+            let data = self.read_file(fd, buf, n)?;
+            if let Syscalls::Recording(log) = &mut self.syscalls {
+                log.calls.push(Syscall::Read { fd, len: n, data: data.clone() });
+            }
+            data
+        };
+        let Some(data) = data else { return Ok(-1) };
+        for (i, &byte) in data.iter().enumerate() {
+            self.store_char(buf.wrapping_add(i as i64), i64::from(byte))?;
+        }
+        Ok(data.len() as i64)
+    }
+
+    // Up to `n` bytes from `fd`, but no more than there are from `buf` to
+    // the end of memory: the program picks `n`, and the host allocates
+    // that much.
+    fn read_file(&mut self, fd: i64, buf: i64, n: i64) -> Result<Option<Vec<u8>>, VmError> {
+        if self.file(fd).is_none() {
+            return Ok(None);
+        }
+        let end = (self.heap.end() + self.data.len()) * WORD_SIZE;
+        let room = usize::try_from(buf).map_or(0, |buf| end.saturating_sub(buf));
+        let len = usize::try_from(n).unwrap_or(0).min(room);
+        if n > 0 && len == 0 {
+            return Err(VmError::InvalidAddress { pc: self.pc - 1, addr: buf });
+        }
+        let mut bytes = vec![0; len];
+        let Some(count) = self.file(fd).and_then(|file| file.read(&mut bytes)) else { return Ok(None) };
+        bytes.truncate(count);
+        Ok(Some(bytes))
+    }
+
+    // The recorded answer to `call`, which must be the next one in the
+    // log being replayed.
+    fn replayed(&mut self, call: Syscall) -> Result<Syscall, VmError> {
+        let Syscalls::Replaying { log, next } = &mut self.syscalls else {
+            unreachable!("only called while replaying")
+        };
+        match log.calls.get(*next) {
+            Some(expected) if expected.answers(&call) => {
+                *next += 1;
+                Ok(expected.clone())
+            }
+            expected => Err(VmError::ReplayDiverged {
+                pc: self.pc - 1,
+                index: *next,
+                expected: expected.cloned().map(Box::new),
+                found: Some(Box::new(call)),
+            }),
+        }
+    }
+
+    // `close(fd)`: 0, or -1 for a descriptor that isn't open.
+    fn close(&mut self, fd: i64) -> i64 {
+        match self.file(fd) {
+            Some(_) => {
+                self.files[fd as usize - FIRST_FD] = None;
+                0
+            }
+            None => -1,
+        }
+    }
+
+    fn file(&mut self, fd: i64) -> Option<&mut Box<dyn OpenFile>> {
+        let index = usize::try_from(fd).ok()?.checked_sub(FIRST_FD)?;
+        self.files.get_mut(index)?.as_mut()
+    }
+
+    // Run `printf` on the top `argc` stack words, the format string's
+    // address deepest, and return the number of bytes written. The
+    // formatting is `printf::format`'s.
+    fn printf(&mut self, argc: i64) -> Result<i64, VmError> {
+        let argc = usize::try_from(argc).unwrap_or(0);
+        if argc == 0 || argc > self.sp {
+            return Err(VmError::StackUnderflow { pc: self.pc - 1 });
+        }
+        let base = self.sp - argc;
+        let format = self.c_string(self.stack[base])?;
+        // Copied, since `%s` reads memory while the arguments are in use.
+        let args = self.stack[base + 1..self.sp].to_vec();
+        let pc = self.pc - 1;
+        let out = printf::format(&format, &args, |addr| self.c_string(addr), || VmError::FormatTooWide { pc })?;
+        self.output.write_bytes(&out);
+        Ok(out.len() as i64)
+    }
+
+    // The NUL-terminated string at `addr`.
+    fn c_string(&mut self, addr: i64) -> Result<Vec<u8>, VmError> {
+        let mut s = Vec::new();
+        loop {
+            let byte = self.load_char(addr.wrapping_add(s.len() as i64))? as u8;
+            if byte == 0 {
+                return Ok(s);
+            }
+            s.push(byte);
+        }
+    }
+
+    // Error for an instruction the VM can't execute.
+    fn unknown(&self, instr: &Instruction) -> VmError {
+        VmError::UnknownInstruction { pc: self.pc - 1, instr: instr.clone() }
+    }
+}
+
+// The address of the first byte of word number `word`.
+pub(crate) fn word_address(word: usize) -> i64 {
+    (word * WORD_SIZE) as i64
+}
+
+// The address `LEA offset` computes: `offset` words from `fp`.
+pub(crate) fn local_address(fp: usize, offset: i64) -> i64 {
+    word_address(fp).wrapping_add(offset.wrapping_mul(WORD_SIZE as i64))
+}
+
+// The byte at `addr` in `word`, the word holding it.
+pub(crate) fn byte_at(word: i64, addr: i64) -> i64 {
+    (word >> (8 * addr.rem_euclid(WORD_SIZE as i64))) & 0xFF
+}
+
+// `word`, the word holding `addr`, with the byte there set to the low byte
+// of `byte`.
+pub(crate) fn with_byte(word: i64, addr: i64, byte: i64) -> i64 {
+    let shift = 8 * addr.rem_euclid(WORD_SIZE as i64);
+    (word & !(0xFF << shift)) | ((byte & 0xFF) << shift)
+}
+
+// Programs see the host's time by default, or none without `std`.
+#[cfg(feature = "std")]
+fn default_clock() -> SharedClock {
+    SharedClock::new(HostClock::new())
+}
+
+#[cfg(not(feature = "std"))]
+fn default_clock() -> SharedClock {
+    SharedClock::new(FrozenClock)
+}
+
+#[cfg(feature = "std")]
+fn default_filesystem() -> SharedFs {
+    SharedFs::new(crate::vfs::HostFs::unrestricted())
+}
+
+#[cfg(not(feature = "std"))]
+fn default_filesystem() -> SharedFs {
+    SharedFs::new(crate::vfs::MemFs::default())
+}
+
+// Programs print to stdout by default, or nowhere without `std`.
+#[cfg(feature = "std")]
+fn default_output() -> Box<dyn Output + Send> {
+    Box::new(io::stdout())
+}
+
+#[cfg(not(feature = "std"))]
+fn default_output() -> Box<dyn Output + Send> {
+    Box::new(Discard)
+}
+
+/// Cloneable in-memory output sink, for capturing what a program prints.
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+pub struct OutputBuffer(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(feature = "std")]
+impl OutputBuffer {
+    pub fn new() -> Self {
+        OutputBuffer::default()
+    }
+
+    /// Everything written so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
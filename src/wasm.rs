@@ -0,0 +1,140 @@
+// src/wasm.rs
+
+//! JavaScript bindings, enabled by the `wasm` feature, for running C4 in a
+//! browser playground. `wasm/` packages them as the cdylib wasm-pack
+//! needs; build it with `wasm-pack build wasm --target web`:
+//!
+//! ```js
+//! import init, { run } from "./wasm/pkg/c4_playground.js";
+//! await init();
+//! const result = run("int main() { printf(\"hi\\n\"); return 3; }", 1_000_000);
+//! // result.exit_code == 3, result.stdout == "hi\n", result.error === undefined
+//! ```
+//!
+//! Nothing here touches the host: output is captured, `clock()` and
+//! `time()` read 0, `open()` finds no files, and every run needs an
+//! instruction budget so a loop can't hang the tab. Handles own their
+//! data, so JS only has to `free()` them. A panic inside a call becomes an error where the target can
+//! unwind; where it aborts instead, the call throws a `RuntimeError` and
+//! [`take_panic`] returns the message.
+
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+use wasm_bindgen::prelude::*;
+
+use crate::bytecode::Chunk;
+use crate::driver::compile_source;
+use crate::vfs::{MemFs, SharedFs};
+use crate::vm::{FrozenClock, OutputBuffer, SharedClock, VmConfig, VM};
+
+thread_local! {
+    /// The message of the last panic inside a binding, until taken.
+    static LAST_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Remember each panic's message for [`guarded`] and [`take_panic`],
+/// then report it as before.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            LAST_PANIC.with(|p| *p.borrow_mut() = Some(info.to_string()));
+            previous(info);
+        }));
+    });
+}
+
+/// Run `f`, turning a panic into an error naming it.
+fn guarded<T>(f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    install_panic_hook();
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| {
+        let message = take_panic().unwrap_or_default();
+        Err(format!("internal error: {}", message))
+    })
+}
+
+/// The message of the last panic in a binding, if one hasn't been taken
+/// yet: how to find out why a call threw a `RuntimeError`.
+#[wasm_bindgen]
+pub fn take_panic() -> Option<String> {
+    LAST_PANIC.with(|p| p.borrow_mut().take())
+}
+
+/// A compiled program.
+#[wasm_bindgen]
+pub struct JsChunk {
+    chunk: Chunk,
+}
+
+#[wasm_bindgen]
+impl JsChunk {
+    /// The instruction listing, as `--asm` prints it.
+    pub fn disassembly(&self) -> String {
+        self.chunk.disassemble()
+    }
+
+    /// Run the program on a fresh VM, stopping after `fuel` instructions.
+    pub fn run(&self, fuel: u32) -> JsRunResult {
+        let buffer = OutputBuffer::new();
+        let finished = guarded(|| execute(&self.chunk, fuel, &buffer));
+        JsRunResult {
+            exit_code: finished.as_ref().map_or(0.0, |&code| code as f64),
+            stdout: String::from_utf8_lossy(&buffer.contents()).into_owned(),
+            error: finished.err(),
+            disassembly: self.disassembly(),
+        }
+    }
+}
+
+/// What [`run`] produced.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Default)]
+pub struct JsRunResult {
+    /// `main`'s return value; 0 if the program didn't finish.
+    pub exit_code: f64,
+    /// Everything the program printed, up to any error, lossily decoded
+    /// as UTF-8.
+    pub stdout: String,
+    /// Why compiling or running failed, if it did.
+    pub error: Option<String>,
+    /// The program's instruction listing; empty if it didn't compile.
+    pub disassembly: String,
+}
+
+/// Compile `src`, throwing the error message if it doesn't compile.
+#[wasm_bindgen]
+pub fn compile(src: &str) -> Result<JsChunk, JsValue> {
+    guarded(|| compile_source(src).map_err(|e| e.to_string()))
+        .map(|chunk| JsChunk { chunk })
+        .map_err(|e| JsValue::from_str(&e))
+}
+
+/// Compile and run `src`, stopping after `fuel` instructions. Never
+/// throws: a compile or runtime error is reported in `error`.
+#[wasm_bindgen]
+pub fn run(src: &str, fuel: u32) -> JsRunResult {
+    match guarded(|| compile_source(src).map_err(|e| e.to_string())) {
+        Ok(chunk) => JsChunk { chunk }.run(fuel),
+        Err(e) => JsRunResult { error: Some(e), ..JsRunResult::default() },
+    }
+}
+
+/// Run `chunk` with its output going to `buffer`, a frozen clock, no
+/// files and `fuel` instructions at most, which must be at least 1.
+fn execute(chunk: &Chunk, fuel: u32, buffer: &OutputBuffer) -> Result<i64, String> {
+    if fuel == 0 {
+        return Err("fuel must be at least 1".to_string());
+    }
+    let config = VmConfig {
+        max_steps: Some(fuel.into()),
+        clock: Some(SharedClock::new(FrozenClock)),
+        filesystem: Some(SharedFs::new(MemFs::default())),
+        ..VmConfig::default()
+    };
+    let mut vm = VM::with_config(config);
+    vm.set_output(Box::new(buffer.clone()));
+    vm.run(chunk).map_err(|e| e.to_string())
+}
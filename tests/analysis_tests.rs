@@ -0,0 +1,63 @@
+// tests/analysis_tests.rs
+
+use c4_rust_AlRafaah::analysis::{Severity, SymbolKind};
+use c4_rust_AlRafaah::analyze;
+
+#[test]
+fn clean_file_has_no_diagnostics() {
+    let analysis = analyze("int g; int main() { return 0; }");
+    assert!(analysis.diagnostics.is_empty(), "{:?}", analysis.diagnostics);
+    let names: Vec<_> = analysis.symbols.iter().map(|s| (s.name.as_str(), s.kind)).collect();
+    assert_eq!(names, [("g", SymbolKind::Global), ("main", SymbolKind::Function)]);
+}
+
+#[test]
+fn one_error_and_two_warnings() {
+    let src = "\
+int helper() {
+    int unused;
+    return 1;
+    2;
+}
+int broken() { return 1 +; }
+int main() { return 0; }
+";
+    let analysis = analyze(src);
+    let codes: Vec<_> = analysis.diagnostics.iter().map(|d| (d.severity, d.code)).collect();
+    assert_eq!(
+        codes,
+        [(Severity::Warning, "W0001"), (Severity::Warning, "W0002"), (Severity::Error, "E0002")]
+    );
+    assert!(analysis.has_errors());
+
+    let error = &analysis.diagnostics[2];
+    assert_eq!(error.offset, Some(src.find("+;").unwrap() + 1));
+    assert_eq!(analysis.diagnostics[0].suggestion.as_deref(), Some("remove `unused`"));
+}
+
+#[test]
+fn broken_file_still_reports_recovered_functions() {
+    let src = "\
+enum { A, B };
+int first() { return A; }
+int second( { return 2; }
+int third() { return 3 }
+int last() { return B; }
+";
+    let analysis = analyze(src);
+    let names: Vec<_> = analysis.symbols.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["A", "B", "first", "last"]);
+    assert_eq!(analysis.diagnostics.iter().filter(|d| d.code == "E0002").count(), 2);
+
+    let missing_semi = analysis.diagnostics.iter().find(|d| d.suggestion.is_some()).unwrap();
+    assert_eq!(missing_semi.offset, Some(src.find("3 }").unwrap() + 2));
+    assert_eq!(missing_semi.suggestion.as_deref(), Some("insert `;`"));
+    assert_eq!(analysis.symbols[3].offset, src.find("int last").unwrap());
+}
+
+#[test]
+fn lex_errors_and_garbage_do_not_panic() {
+    assert!(analyze("int main() { return 1; } @").has_errors());
+    assert!(analyze("}}}{{{;;;").has_errors());
+    assert!(analyze("").diagnostics.is_empty());
+}
@@ -0,0 +1,326 @@
+// tests/backend_tests.rs
+
+//! Every enabled backend against the VM: generated programs from `testgen`,
+//! the corpus, and hand-built chunks covering loops, calls and every error.
+//! Build with `--features fastexec,jit` to cover them all.
+
+mod testgen;
+
+use std::fs;
+use c4_rust_AlRafaah::bytecode::*;
+use c4_rust_AlRafaah::compile_source;
+use c4_rust_AlRafaah::driver::{run_chunk_on, run_source_on};
+use std::time::Duration;
+use c4_rust_AlRafaah::vm::{Backend, CancelToken, ProgramClock, SharedClock, VmConfig, VmError};
+use testgen::{generate, shrink, Features, GenProgram};
+
+/// Everything observable about a run except its wall time.
+type Observed = Result<(i64, Vec<u8>, u64, usize, usize), VmError>;
+
+/// The backends under test, besides the VM itself.
+fn backends() -> Vec<Backend> {
+    vec![
+        #[cfg(feature = "fastexec")]
+        Backend::Closures,
+        #[cfg(feature = "jit")]
+        Backend::Jit,
+    ]
+}
+
+fn observe(chunk: &Chunk, config: &VmConfig, backend: Backend) -> Observed {
+    let out = run_chunk_on(chunk, config.clone(), backend)?;
+    Ok((out.exit_code, out.stdout, out.instructions, out.max_stack_words, out.max_call_depth))
+}
+
+fn assert_agree(chunk: &Chunk, config: &VmConfig) {
+    let expected = observe(chunk, config, Backend::Vm);
+    for backend in backends() {
+        assert_eq!(observe(chunk, config, backend), expected, "{:?}\n{}", backend, chunk.to_asm());
+    }
+}
+
+fn diverges(program: &GenProgram) -> bool {
+    let Ok(chunk) = compile_source(&program.render()) else { return false };
+    let expected = observe(&chunk, &VmConfig::default(), Backend::Vm);
+    backends().into_iter().any(|b| observe(&chunk, &VmConfig::default(), b) != expected)
+}
+
+/// Sum of 1..=n in two locals, as the compiler would lay out a `while` loop.
+fn sum_loop(n: i64) -> Chunk {
+    let mut c = Chunk::default();
+    c.push_int(OpCode::ENT, 2);
+    c.push_int(OpCode::LEA, 0);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::IMM, n);
+    c.push(OpCode::SI); // i = n
+    c.push_int(OpCode::LEA, 0); // 5: loop head
+    c.push(OpCode::LI);
+    c.push_jump(OpCode::BZ, 26);
+    c.push_int(OpCode::LEA, 1);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::LEA, 1);
+    c.push(OpCode::LI);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::LEA, 0);
+    c.push(OpCode::LI);
+    c.push(OpCode::ADD);
+    c.push(OpCode::SI); // sum += i
+    c.push_int(OpCode::LEA, 0);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::LEA, 0);
+    c.push(OpCode::LI);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::IMM, 1);
+    c.push(OpCode::SUB);
+    c.push(OpCode::SI); // i -= 1
+    c.push_jump(OpCode::JMP, 5);
+    c.push_int(OpCode::LEA, 1); // 26
+    c.push(OpCode::LI);
+    c.push(OpCode::EXIT);
+    c
+}
+
+#[test]
+fn generated_programs_agree() {
+    for seed in 0..300 {
+        for features in [Features::default(), Features::all()] {
+            let program = generate(seed, features);
+            if diverges(&program) {
+                let small = shrink(program, diverges).render();
+                panic!("seed {} diverges; minimal program:\n{}", seed, small);
+            }
+        }
+    }
+}
+
+#[test]
+fn corpus_agrees() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let chunk = compile_source(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_agree(&chunk, &VmConfig::default());
+    }
+}
+
+#[test]
+fn loops_agree() {
+    let chunk = sum_loop(1000);
+    assert_eq!(observe(&chunk, &VmConfig::default(), Backend::Vm).unwrap().0, 500_500);
+    assert_agree(&chunk, &VmConfig::default());
+}
+
+#[test]
+fn calls_and_returns_agree() {
+    // main: call f, add 1 to its result; f: ENT, return 41.
+    let mut c = Chunk::default();
+    c.push_call(OpCode::JSR, 5);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::IMM, 1);
+    c.push(OpCode::ADD);
+    c.push(OpCode::EXIT);
+    c.push_int(OpCode::ENT, 1);
+    c.push_int(OpCode::IMM, 41);
+    c.push(OpCode::LEV);
+    c.push(OpCode::LEV);
+    assert_agree(&c, &VmConfig::default());
+}
+
+#[test]
+fn parameters_and_recursion_agree() {
+    // Parameters live below `fp`, at negative `LEA` offsets.
+    let src = "
+        int fact(int n) { switch (n) { case 0: return 1; } return n * fact(n - 1); }
+        int sub(int a, int b) { return a - b; }
+        int main() { return fact(6) + sub(40, 2); }";
+    let chunk = compile_source(src).unwrap();
+    assert_eq!(observe(&chunk, &VmConfig::default(), Backend::Vm).unwrap().0, 758);
+    assert_agree(&chunk, &VmConfig::default());
+}
+
+#[test]
+fn globals_agree() {
+    // The JIT doesn't support globals and runs these on the VM.
+    let src = "
+        int n;
+        char c;
+        int bump() { n += 1; c = c + 200; return n; }
+        int main() { bump(); bump(); return bump() * 1000 + c; }";
+    let chunk = compile_source(src).unwrap();
+    assert_eq!(observe(&chunk, &VmConfig::default(), Backend::Vm).unwrap().0, 3000 + 600 % 256);
+    assert_agree(&chunk, &VmConfig::default());
+}
+
+#[test]
+fn running_off_the_end_returns_a() {
+    let mut c = Chunk::default();
+    c.push_int(OpCode::IMM, 7);
+    c.push_jump(OpCode::JMP, 99);
+    assert_eq!(observe(&c, &VmConfig::default(), Backend::Vm).unwrap().0, 7);
+    assert_agree(&c, &VmConfig::default());
+}
+
+#[test]
+fn errors_agree() {
+    let small = VmConfig { stack_size: 4, ..VmConfig::default() };
+
+    let mut div = Chunk::default();
+    div.push_int(OpCode::IMM, 1);
+    div.push(OpCode::PSH);
+    div.push_int(OpCode::IMM, 0);
+    div.push(OpCode::DIV);
+    assert_eq!(observe(&div, &small, Backend::Vm), Err(VmError::DivisionByZero { pc: 3 }));
+
+    let mut overflow = Chunk::default();
+    overflow.push_int(OpCode::ENT, 10);
+    let mut underflow = Chunk::default();
+    underflow.push(OpCode::ADD);
+    let mut no_frame = Chunk::default();
+    no_frame.push(OpCode::LEV);
+    let mut bad_addr = Chunk::default();
+    bad_addr.push_int(OpCode::IMM, -1);
+    bad_addr.push(OpCode::LI);
+    let mut unknown = Chunk::default();
+    unknown.push_int(OpCode::IMM, 0);
+    unknown.push(OpCode::JMP);
+    let mut recursion = Chunk::default();
+    recursion.push_call(OpCode::JSR, 0);
+    let mut assertion = Chunk::default();
+    assertion.push_int(OpCode::IMM, 1);
+    assertion.push(OpCode::ASRT);
+    assertion.push_int(OpCode::IMM, 0);
+    assertion.push(OpCode::ASRT);
+    assert_eq!(observe(&assertion, &small, Backend::Vm), Err(VmError::AssertionFailed { pc: 3 }));
+
+    for chunk in [div, overflow, underflow, no_frame, bad_addr, unknown, recursion, assertion] {
+        assert!(observe(&chunk, &small, Backend::Vm).is_err());
+        assert_agree(&chunk, &small);
+    }
+}
+
+#[test]
+fn rand_agrees() {
+    let mut c = Chunk::default();
+    c.push(OpCode::RAND);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::IMM, -3);
+    c.push(OpCode::SRND);
+    c.push(OpCode::RAND);
+    c.push(OpCode::XOR);
+    assert_agree(&c, &VmConfig::default());
+}
+
+#[test]
+fn heap_agrees() {
+    // The JIT has no heap; run_chunk_on falls back to the VM for it.
+    let src = "int main() { free(malloc(8)); return malloc(4) - malloc(2); }";
+    let chunk = compile_source(src).unwrap();
+    assert_agree(&chunk, &VmConfig::default());
+    let peak = |backend| run_chunk_on(&chunk, VmConfig::default(), backend).unwrap().heap_peak_words;
+    assert_eq!(peak(Backend::Vm), 2);
+    for backend in backends() {
+        assert_eq!(peak(backend), 2, "{:?}", backend);
+    }
+}
+
+#[test]
+fn memset_and_memcmp_agree() {
+    let src = "int main() { int p, q; p = malloc(4); q = malloc(4);
+        memset(p, 7, 4); memset(q, 7, 3); return memcmp(p, q, 4) * 10 + memcmp(p, q, 3); }";
+    let chunk = compile_source(src).unwrap();
+    assert_agree(&chunk, &VmConfig::default());
+    // An out-of-range write faults at the same pc everywhere.
+    let chunk = compile_source("int main() { memset(malloc(1), 0, 100000); return 0; }").unwrap();
+    assert_agree(&chunk, &VmConfig::default());
+}
+
+#[test]
+fn clock_and_time_agree() {
+    // Same reading every time, so every backend sees the same values.
+    struct Fixed;
+    impl ProgramClock for Fixed {
+        fn now(&self) -> Duration {
+            Duration::from_millis(1500)
+        }
+        fn unix_time(&self) -> i64 {
+            86_400
+        }
+    }
+    let config = VmConfig { clock: Some(SharedClock::new(Fixed)), ..VmConfig::default() };
+    let mut c = Chunk::default();
+    c.push(OpCode::CLCK);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::IMM, 0);
+    c.push(OpCode::TIME);
+    c.push(OpCode::ADD);
+    assert_eq!(observe(&c, &config, Backend::Vm).unwrap().0, 86_400);
+    assert_agree(&c, &config);
+}
+
+#[test]
+fn cancelled_runs_agree() {
+    let token = CancelToken::new();
+    token.cancel();
+    let config = VmConfig { cancel_token: Some(token), ..VmConfig::default() };
+    let mut spin = Chunk::default();
+    spin.push_int(OpCode::IMM, 1);
+    spin.push_jump(OpCode::JMP, 1);
+    assert_eq!(observe(&spin, &config, Backend::Vm), Err(VmError::Cancelled { pc: 0 }));
+    assert_agree(&spin, &config);
+}
+
+#[test]
+fn every_backend_stops_when_out_of_fuel() {
+    let config = VmConfig { max_steps: Some(1000), ..VmConfig::default() };
+    let mut spin = Chunk::default();
+    spin.push_int(OpCode::IMM, 1);
+    spin.push_jump(OpCode::JMP, 1);
+    assert_eq!(observe(&spin, &config, Backend::Vm), Err(VmError::OutOfFuel { pc: 1, steps: 1000 }));
+    // The closure backend counts whole blocks, so may stop a little later.
+    for backend in backends() {
+        match observe(&spin, &config, backend) {
+            Err(VmError::OutOfFuel { steps, .. }) => assert!((1000..1010).contains(&steps), "{:?}: {}", backend, steps),
+            other => panic!("{:?}: {:?}", backend, other),
+        }
+    }
+    // A budget the program fits in changes nothing.
+    let roomy = VmConfig { max_steps: Some(1_000_000), ..VmConfig::default() };
+    assert_eq!(observe(&sum_loop(100), &roomy, Backend::Vm), observe(&sum_loop(100), &VmConfig::default(), Backend::Vm));
+    assert_agree(&sum_loop(100), &roomy);
+}
+
+#[test]
+fn wrapping_division_agrees() {
+    for (left, right, op) in [(i64::MIN, -1, OpCode::DIV), (i64::MIN, -1, OpCode::MOD), (-7, 2, OpCode::DIV), (-7, 2, OpCode::MOD)] {
+        let mut c = Chunk::default();
+        c.push_int(OpCode::IMM, left);
+        c.push(OpCode::PSH);
+        c.push_int(OpCode::IMM, right);
+        c.push(op);
+        c.push(OpCode::EXIT);
+        assert_agree(&c, &VmConfig::default());
+    }
+}
+
+#[test]
+fn driver_runs_every_backend() {
+    let src = "int main() { return 6 * 7; }";
+    for backend in [Backend::Vm].into_iter().chain(backends()) {
+        assert_eq!(run_source_on(src, VmConfig::default(), backend).unwrap().exit_code, 42);
+    }
+    // Backends without output hand printing programs to the VM.
+    let src = r#"int main() { printf("hi %d", 4); return 2; }"#;
+    for backend in backends() {
+        let out = run_source_on(src, VmConfig::default(), backend).unwrap();
+        assert_eq!((out.exit_code, out.stdout), (2, b"hi 4".to_vec()), "{:?}", backend);
+    }
+}
+
+#[cfg(feature = "jit")]
+#[test]
+fn jit_compiles_on_this_host() {
+    // Otherwise `Backend::Jit` quietly falls back to the VM and the
+    // comparisons above prove nothing about it.
+    let program = c4_rust_AlRafaah::jit::JitProgram::compile(&sum_loop(10)).unwrap();
+    assert_eq!(program.run(&VmConfig::default()).unwrap().exit_code, 55);
+}
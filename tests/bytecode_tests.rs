@@ -1,97 +1,279 @@
-// tests/bytecode_tests.rs
-
-use c4_rust_AlRafaah::bytecode::*;
-
-#[test]
-fn test_push_basic_opcodes() {
-    let mut chunk = Chunk::default();
-    chunk.push(OpCode::ADD);
-    chunk.push(OpCode::SUB);
-    chunk.push(OpCode::MUL);
-
-    assert_eq!(chunk.code.len(), 3);
-    assert_eq!(chunk.code[0], Instruction::Instr(OpCode::ADD));
-    assert_eq!(chunk.code[1], Instruction::Instr(OpCode::SUB));
-    assert_eq!(chunk.code[2], Instruction::Instr(OpCode::MUL));
-}
-
-#[test]
-fn test_push_immediate_values() {
-    let mut chunk = Chunk::default();
-    chunk.push_int(OpCode::IMM, 42);
-    chunk.push_int(OpCode::IMM, -1);
-    chunk.push_int(OpCode::LEA, 16);
-
-    assert_eq!(chunk.code.len(), 3);
-    assert_eq!(chunk.code[0], Instruction::InstrInt(OpCode::IMM, 42));
-    assert_eq!(chunk.code[1], Instruction::InstrInt(OpCode::IMM, -1));
-    assert_eq!(chunk.code[2], Instruction::InstrInt(OpCode::LEA, 16));
-}
-
-#[test]
-fn test_jump_and_call_instructions() {
-    let mut chunk = Chunk::default();
-    chunk.push_jump(OpCode::JMP, 10);
-    chunk.push_jump(OpCode::BZ, 20);
-    chunk.push_call(OpCode::JSR, 30);
-
-    assert_eq!(chunk.code.len(), 3);
-    assert_eq!(chunk.code[0], Instruction::Jump(OpCode::JMP, 10));
-    assert_eq!(chunk.code[1], Instruction::Jump(OpCode::BZ, 20));
-    assert_eq!(chunk.code[2], Instruction::Call(OpCode::JSR, 30));
-}
-
-#[test]
-fn test_instruction_order_preservation() {
-    let mut chunk = Chunk::default();
-    chunk.push(OpCode::PSH);
-    chunk.push_int(OpCode::IMM, 99);
-    chunk.push(OpCode::ADD);
-
-    assert_eq!(chunk.code[0], Instruction::Instr(OpCode::PSH));
-    assert_eq!(chunk.code[1], Instruction::InstrInt(OpCode::IMM, 99));
-    assert_eq!(chunk.code[2], Instruction::Instr(OpCode::ADD));
-}
-
-#[test]
-fn test_multiple_opcodes_comprehensively() {
-    let mut chunk = Chunk::default();
-
-    let opcodes = vec![
-        OpCode::IMM, OpCode::LI, OpCode::LC,
-        OpCode::SI, OpCode::SC, OpCode::PSH,
-        OpCode::OR, OpCode::XOR, OpCode::AND,
-        OpCode::EQ, OpCode::NE, OpCode::LT,
-        OpCode::LE, OpCode::GT, OpCode::GE,
-        OpCode::SHL, OpCode::SHR, OpCode::ADD,
-        OpCode::SUB, OpCode::MUL, OpCode::DIV, OpCode::MOD
-    ];
-
-    for op in &opcodes {
-        chunk.push(*op);
-    }
-
-    assert_eq!(chunk.code.len(), opcodes.len());
-
-    for (i, op) in opcodes.iter().enumerate() {
-        assert_eq!(chunk.code[i], Instruction::Instr(*op));
-    }
-}
-
-#[test]
-fn test_syscall_opcodes() {
-    let mut chunk = Chunk::default();
-    let syscalls = vec![
-        OpCode::OPEN, OpCode::READ, OpCode::CLOS,
-        OpCode::PRTF, OpCode::MALC, OpCode::FREE,
-        OpCode::MSET, OpCode::MCMP, OpCode::EXIT,
-    ];
-
-    for op in &syscalls {
-        chunk.push(*op);
-    }
-
-    for (i, op) in syscalls.iter().enumerate() {
-        assert_eq!(chunk.code[i], Instruction::Instr(*op));
-    }
-}
+// tests/bytecode_tests.rs
+
+use c4_rust_AlRafaah::bytecode::*;
+
+#[test]
+fn test_push_basic_opcodes() {
+    let mut chunk = Chunk::default();
+    chunk.push(OpCode::ADD);
+    chunk.push(OpCode::SUB);
+    chunk.push(OpCode::MUL);
+
+    assert_eq!(chunk.code.len(), 3);
+    assert_eq!(chunk.code[0], Instruction::Instr(OpCode::ADD));
+    assert_eq!(chunk.code[1], Instruction::Instr(OpCode::SUB));
+    assert_eq!(chunk.code[2], Instruction::Instr(OpCode::MUL));
+}
+
+#[test]
+fn test_push_immediate_values() {
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 42);
+    chunk.push_int(OpCode::IMM, -1);
+    chunk.push_int(OpCode::LEA, 16);
+
+    assert_eq!(chunk.code.len(), 3);
+    assert_eq!(chunk.code[0], Instruction::InstrInt(OpCode::IMM, 42));
+    assert_eq!(chunk.code[1], Instruction::InstrInt(OpCode::IMM, -1));
+    assert_eq!(chunk.code[2], Instruction::InstrInt(OpCode::LEA, 16));
+}
+
+#[test]
+fn test_jump_and_call_instructions() {
+    let mut chunk = Chunk::default();
+    chunk.push_jump(OpCode::JMP, 10);
+    chunk.push_jump(OpCode::BZ, 20);
+    chunk.push_call(OpCode::JSR, 30);
+
+    assert_eq!(chunk.code.len(), 3);
+    assert_eq!(chunk.code[0], Instruction::Jump(OpCode::JMP, 10));
+    assert_eq!(chunk.code[1], Instruction::Jump(OpCode::BZ, 20));
+    assert_eq!(chunk.code[2], Instruction::Call(OpCode::JSR, 30));
+}
+
+#[test]
+fn test_instruction_order_preservation() {
+    let mut chunk = Chunk::default();
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 99);
+    chunk.push(OpCode::ADD);
+
+    assert_eq!(chunk.code[0], Instruction::Instr(OpCode::PSH));
+    assert_eq!(chunk.code[1], Instruction::InstrInt(OpCode::IMM, 99));
+    assert_eq!(chunk.code[2], Instruction::Instr(OpCode::ADD));
+}
+
+#[test]
+fn test_multiple_opcodes_comprehensively() {
+    let mut chunk = Chunk::default();
+
+    let opcodes = vec![
+        OpCode::IMM, OpCode::LI, OpCode::LC,
+        OpCode::SI, OpCode::SC, OpCode::PSH,
+        OpCode::OR, OpCode::XOR, OpCode::AND,
+        OpCode::EQ, OpCode::NE, OpCode::LT,
+        OpCode::LE, OpCode::GT, OpCode::GE,
+        OpCode::SHL, OpCode::SHR, OpCode::ADD,
+        OpCode::SUB, OpCode::MUL, OpCode::DIV, OpCode::MOD
+    ];
+
+    for op in &opcodes {
+        chunk.push(*op);
+    }
+
+    assert_eq!(chunk.code.len(), opcodes.len());
+
+    for (i, op) in opcodes.iter().enumerate() {
+        assert_eq!(chunk.code[i], Instruction::Instr(*op));
+    }
+}
+
+#[test]
+fn test_syscall_opcodes() {
+    let mut chunk = Chunk::default();
+    let syscalls = vec![
+        OpCode::OPEN, OpCode::READ, OpCode::CLOS,
+        OpCode::PRTF, OpCode::MALC, OpCode::FREE,
+        OpCode::MSET, OpCode::MCMP, OpCode::EXIT,
+    ];
+
+    for op in &syscalls {
+        chunk.push(*op);
+    }
+
+    for (i, op) in syscalls.iter().enumerate() {
+        assert_eq!(chunk.code[i], Instruction::Instr(*op));
+    }
+}
+
+#[test]
+fn test_opcode_table_matches_declaration_order() {
+    for (i, op) in OpCode::ALL.iter().enumerate() {
+        assert_eq!(*op as usize, i, "{:?}", op);
+    }
+}
+
+#[test]
+fn test_serialize_round_trip() {
+    let src = r#"enum { K = 3 }; int n; char **names; int add(int a, int b) { return a + b; }
+        int main() { char *s; s = "hi"; n = add(2, K); return n; }"#;
+    let program = c4_rust_AlRafaah::parser::Parser::new(src).unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    assert!(!chunk.functions.is_empty() && !chunk.globals.is_empty());
+    assert!(!chunk.constants.is_empty() && !chunk.strings.is_empty());
+    let bytes = chunk.serialize();
+    assert_eq!(&bytes[..4], b"C4RS");
+    let read = Chunk::deserialize(&bytes).unwrap();
+    assert_eq!(read, chunk);
+    // The source map isn't compared by `==`, but is saved all the same.
+    assert!(!chunk.source_map.0.is_empty());
+    assert_eq!(read.source_map.0, chunk.source_map.0);
+
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, -42);
+    chunk.push(OpCode::PSH);
+    chunk.push_jump(OpCode::BZ, 7);
+    chunk.push_call(OpCode::JSR, usize::MAX >> 1);
+    chunk.push_int(OpCode::ADJ, i64::MIN);
+    chunk.push(OpCode::EXIT);
+    assert_eq!(Chunk::deserialize(&chunk.serialize()).unwrap(), chunk);
+    assert_eq!(Chunk::deserialize(&Chunk::default().serialize()).unwrap(), Chunk::default());
+}
+
+#[test]
+fn test_deserialize_rejects_bad_input() {
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 1);
+    chunk.push(OpCode::EXIT);
+    let bytes = chunk.serialize();
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+    assert!(Chunk::deserialize(&bad_magic).unwrap_err().contains("magic"));
+
+    let mut old = bytes.clone();
+    old[4..8].copy_from_slice(&(FORMAT_VERSION - 1).to_le_bytes());
+    assert!(Chunk::deserialize(&old).unwrap_err().contains("version"));
+
+    for len in 0..bytes.len() {
+        assert!(Chunk::deserialize(&bytes[..len]).is_err(), "accepted {} of {} bytes", len, bytes.len());
+    }
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert!(Chunk::deserialize(&longer).is_err());
+
+    // The first instruction's opcode byte.
+    let mut unknown = bytes.clone();
+    unknown[13] = 200;
+    assert!(Chunk::deserialize(&unknown).unwrap_err().contains("opcode"));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_write_to_and_read_from() {
+    use c4_rust_AlRafaah::vm::VM;
+    use std::io::ErrorKind;
+
+    let src = "int sq(int x) { return x * x; } int main() { int i; i = 0; while (i < 5) i++; return sq(i) + 2; }";
+    let chunk = c4_rust_AlRafaah::compile_source(src).unwrap();
+    let mut file = Vec::new();
+    chunk.write_to(&mut file).unwrap();
+    let loaded = Chunk::read_from(&mut file.as_slice()).unwrap();
+    assert_eq!(loaded.code, chunk.code);
+    assert_eq!(loaded, chunk);
+    assert_eq!(VM::new().run(&loaded), VM::new().run(&chunk));
+    assert_eq!(VM::new().run(&loaded), Ok(27));
+
+    // Corrupt input is invalid data, with the reason.
+    let err = Chunk::read_from(&mut &file[..file.len() - 1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    file[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    let err = Chunk::read_from(&mut file.as_slice()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(err.to_string().contains("version"), "{}", err);
+}
+
+#[test]
+fn test_disassemble_labels_jump_targets() {
+    // if (a) a = 1; else a = 2; return a;
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::LEA, 0);
+    chunk.push(OpCode::LI);
+    chunk.push_jump(OpCode::BZ, 7);
+    chunk.push_int(OpCode::LEA, 0);
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 1);
+    chunk.push_jump(OpCode::JMP, 10);
+    chunk.push_int(OpCode::LEA, 0);
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 2);
+    chunk.push(OpCode::SI);
+    chunk.push(OpCode::LEV);
+
+    let text = chunk.disassemble();
+    assert!(text.contains("0002: BZ L0\n"), "{}", text);
+    assert!(text.contains("0006: JMP L1\n"), "{}", text);
+    assert!(text.contains("L0:\n0007: LEA 0\n"), "{}", text);
+    assert!(text.contains("L1:\n0010: SI\n"), "{}", text);
+    assert_eq!(text.lines().count(), chunk.code.len() + 2);
+    // The same listing as `to_asm`, apart from the addresses.
+    let stripped: String = text.lines().map(|l| match l.split_once(": ") {
+        Some((_, rest)) => format!("    {}\n", rest),
+        None => format!("{}\n", l),
+    }).collect();
+    assert_eq!(stripped, chunk.to_asm());
+}
+
+#[test]
+fn test_display_is_the_disassembly() {
+    // int f() { return 7; } int main() { if (f()) return 1; return 2; }
+    let mut chunk = Chunk::default();
+    chunk.push_call(OpCode::JSR, 5);
+    chunk.push(OpCode::EXIT);
+    chunk.push_int(OpCode::ENT, 0);
+    chunk.push_int(OpCode::IMM, 7);
+    chunk.push(OpCode::LEV);
+    chunk.push_int(OpCode::ENT, 0);
+    chunk.push_call(OpCode::JSR, 2);
+    chunk.push_jump(OpCode::BZ, 10);
+    chunk.push_int(OpCode::IMM, 1);
+    chunk.push(OpCode::LEV);
+    chunk.push_int(OpCode::IMM, 2);
+    chunk.push(OpCode::LEV);
+    chunk.functions.push(FuncSym { name: "f".into(), params: vec![], entry: 2 });
+    chunk.functions.push(FuncSym { name: "main".into(), params: vec![], entry: 5 });
+
+    let expected = "\
+0000: JSR main
+0001: EXIT
+
+f:
+0002: ENT 0
+0003: IMM 7
+0004: LEV
+
+main:
+0005: ENT 0
+0006: JSR f
+0007: BZ L0
+0008: IMM 1
+0009: LEV
+L0:
+0010: IMM 2
+0011: LEV
+";
+    assert_eq!(chunk.to_string(), expected);
+    assert_eq!(chunk.disassemble(), expected);
+}
+
+#[test]
+fn test_disassemble_function_is_its_part_of_the_listing() {
+    let mut chunk = Chunk::default();
+    chunk.push_call(OpCode::JSR, 4);
+    chunk.push(OpCode::EXIT);
+    chunk.push_int(OpCode::ENT, 0);
+    chunk.push(OpCode::LEV);
+    chunk.push_int(OpCode::ENT, 0);
+    chunk.push_jump(OpCode::JMP, 6);
+    chunk.push(OpCode::LEV);
+    chunk.data.push(9);
+    chunk.functions.push(FuncSym { name: "f".into(), params: vec!["x".into()], entry: 2 });
+    chunk.functions.push(FuncSym { name: "main".into(), params: vec![], entry: 4 });
+
+    assert_eq!(chunk.disassemble_function("f").unwrap(), "\nf:\n0002: ENT 0\n0003: LEV\n");
+    // The last function runs to the end, without the data segment.
+    assert_eq!(chunk.disassemble_function("main").unwrap(), "\nmain:\n0004: ENT 0\n0005: JMP L0\nL0:\n0006: LEV\n");
+    assert_eq!(chunk.disassemble_function("g"), None);
+    assert!(chunk.disassemble().contains("data:\n    9\n"));
+}
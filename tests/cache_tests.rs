@@ -0,0 +1,88 @@
+// tests/cache_tests.rs
+
+use std::sync::Arc;
+
+use c4_rust_AlRafaah::cache::CompileCache;
+use c4_rust_AlRafaah::compiler::Compiler;
+use c4_rust_AlRafaah::vm::VM;
+use c4_rust_AlRafaah::Error;
+
+#[test]
+fn identical_source_returns_the_same_chunk() {
+    let c = Compiler::new();
+    let mut cache = CompileCache::new(4);
+    let first = cache.get_or_compile(&c, "int main() { return 3; }").unwrap();
+    let second = cache.get_or_compile(&c, "int main() { return 3; }").unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    assert_eq!(VM::new().run(&second).unwrap(), 3);
+}
+
+#[test]
+fn changed_source_misses() {
+    let c = Compiler::new();
+    let mut cache = CompileCache::new(4);
+    let a = cache.get_or_compile(&c, "int main() { return 1; }").unwrap();
+    let b = cache.get_or_compile(&c, "int main() { return 2; }").unwrap();
+    assert!(!Arc::ptr_eq(&a, &b));
+    assert_eq!(cache.misses(), 2);
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn least_recently_used_entry_is_evicted() {
+    let c = Compiler::new();
+    let mut cache = CompileCache::new(2);
+    let one = cache.get_or_compile(&c, "int main() { return 1; }").unwrap();
+    cache.get_or_compile(&c, "int main() { return 2; }").unwrap();
+    // Touch the first entry so the second becomes the oldest.
+    cache.get_or_compile(&c, "int main() { return 1; }").unwrap();
+    cache.get_or_compile(&c, "int main() { return 3; }").unwrap();
+    assert_eq!(cache.len(), 2);
+
+    let again = cache.get_or_compile(&c, "int main() { return 1; }").unwrap();
+    assert!(Arc::ptr_eq(&one, &again));
+    cache.get_or_compile(&c, "int main() { return 2; }").unwrap();
+    assert_eq!(cache.misses(), 4);
+}
+
+#[test]
+fn errors_are_not_cached() {
+    let c = Compiler::new();
+    let mut cache = CompileCache::new(2);
+    for _ in 0..2 {
+        assert!(matches!(cache.get_or_compile(&c, "int main( {"), Err(Error::Parse(_))));
+    }
+    assert!(cache.is_empty());
+    assert_eq!(cache.misses(), 2);
+}
+
+#[test]
+fn changed_options_miss() {
+    let c = Compiler::new();
+    let mut cache = CompileCache::new(4);
+    let src = "int main() { return 2; }";
+    let linked = cache.get_or_compile(&c, src).unwrap();
+    let bare = cache.get_or_compile(&c.clone().stdlib(false), src).unwrap();
+    assert!(!Arc::ptr_eq(&linked, &bare));
+    assert_eq!((cache.hits(), cache.misses()), (0, 2));
+    assert!(Arc::ptr_eq(&linked, &cache.get_or_compile(&c, src).unwrap()));
+}
+
+#[test]
+fn chunks_on_disk_outlive_the_cache() {
+    let dir = std::env::temp_dir().join(format!("c4_cache_{}", std::process::id()));
+    let c = Compiler::new();
+    let src = "int main() { return 9; }";
+    let written = CompileCache::with_dir(4, &dir).unwrap().get_or_compile(&c, src).unwrap();
+
+    let mut cache = CompileCache::with_dir(4, &dir).unwrap();
+    let read = cache.get_or_compile(&c, src).unwrap();
+    assert_eq!(*read, *written);
+    assert_eq!((cache.hits(), cache.misses()), (1, 0));
+    assert_eq!(VM::new().run(&read).unwrap(), 9);
+    // Other options still compile.
+    cache.get_or_compile(&c.clone().stdlib(false), src).unwrap();
+    assert_eq!(cache.misses(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
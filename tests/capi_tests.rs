@@ -0,0 +1,103 @@
+// tests/capi_tests.rs
+
+//! Drives the C API through its `extern "C"` functions the way a C caller
+//! would. Run with `cargo test --features capi`.
+
+use std::ffi::{CStr, CString};
+use std::ptr;
+use c4_rust_AlRafaah::capi::*;
+
+/// Helper: a zeroed error record, as `C4Error err = {0};` would give.
+fn no_error() -> C4Error {
+    C4Error { code: C4_OK, message: ptr::null_mut() }
+}
+
+#[test]
+fn compile_and_run_round_trip() {
+    let src = CString::new("int main() { return 6 * 7; }").unwrap();
+    let mut err = no_error();
+    let mut chunk = ptr::null_mut();
+    let mut exit = 0i64;
+    unsafe {
+        assert_eq!(c4_compile(src.as_ptr(), &mut chunk, &mut err), C4_OK);
+        assert!(!chunk.is_null());
+        assert_eq!(c4_run(chunk, &mut exit, &mut err), C4_OK);
+        c4_chunk_free(chunk);
+    }
+    assert_eq!(exit, 42);
+}
+
+#[test]
+fn invalid_source_reports_parse_error() {
+    let src = CString::new("int main() { return 1 }").unwrap();
+    let mut err = no_error();
+    let mut chunk = ptr::null_mut();
+    unsafe {
+        assert_eq!(c4_compile(src.as_ptr(), &mut chunk, &mut err), C4_ERR_PARSE);
+        assert!(chunk.is_null());
+        assert_eq!(err.code, C4_ERR_PARSE);
+        let msg = CStr::from_ptr(c4_error_message(&err)).to_str().unwrap();
+        assert!(msg.contains("expected Semicolon"), "{}", msg);
+        c4_error_free(&mut err);
+        assert_eq!(CStr::from_ptr(c4_error_message(&err)).to_bytes(), b"");
+    }
+}
+
+#[test]
+fn tiny_stack_reports_runtime_error() {
+    let src = CString::new("int main() { return 1 + (2 + 3); }").unwrap();
+    let mut err = no_error();
+    let mut chunk = ptr::null_mut();
+    let mut exit = 0i64;
+    unsafe {
+        assert_eq!(c4_compile(src.as_ptr(), &mut chunk, &mut err), C4_OK);
+        assert_eq!(c4_set_stack_size(chunk, 1), C4_OK);
+        assert_eq!(c4_run(chunk, &mut exit, &mut err), C4_ERR_RUNTIME);
+        let msg = CStr::from_ptr(c4_error_message(&err)).to_str().unwrap();
+        assert!(msg.contains("stack overflow"), "{}", msg);
+        c4_error_free(&mut err);
+        c4_chunk_free(chunk);
+    }
+}
+
+#[test]
+fn max_steps_stops_a_runaway_program() {
+    let src = CString::new("int main() { while (1) ; return 0; }").unwrap();
+    let mut err = no_error();
+    let mut chunk = ptr::null_mut();
+    let mut exit = 0i64;
+    unsafe {
+        assert_eq!(c4_compile(src.as_ptr(), &mut chunk, &mut err), C4_OK);
+        assert_eq!(c4_set_max_steps(chunk, 1000), C4_OK);
+        assert_eq!(c4_run(chunk, &mut exit, &mut err), C4_ERR_RUNTIME);
+        let msg = CStr::from_ptr(c4_error_message(&err)).to_str().unwrap();
+        assert!(msg.contains("out of fuel after 1000 instructions"), "{}", msg);
+        c4_error_free(&mut err);
+        c4_chunk_free(chunk);
+    }
+
+    // 0 lifts the limit again.
+    let src = CString::new("int main() { int i; while (i < 500) i++; return i; }").unwrap();
+    unsafe {
+        assert_eq!(c4_compile(src.as_ptr(), &mut chunk, &mut err), C4_OK);
+        assert_eq!(c4_set_max_steps(chunk, 10), C4_OK);
+        assert_eq!(c4_run(chunk, &mut exit, &mut err), C4_ERR_RUNTIME);
+        assert_eq!(c4_set_max_steps(chunk, 0), C4_OK);
+        assert_eq!(c4_run(chunk, &mut exit, &mut err), C4_OK);
+        c4_error_free(&mut err);
+        c4_chunk_free(chunk);
+    }
+    assert_eq!(exit, 500);
+}
+
+#[test]
+fn null_arguments_are_rejected() {
+    let mut chunk = ptr::null_mut();
+    unsafe {
+        assert_eq!(c4_compile(ptr::null(), &mut chunk, ptr::null_mut()), C4_ERR_INVALID_ARGUMENT);
+        assert_eq!(c4_run(ptr::null_mut(), ptr::null_mut(), ptr::null_mut()), C4_ERR_INVALID_ARGUMENT);
+        assert_eq!(c4_set_stack_size(ptr::null_mut(), 16), C4_ERR_INVALID_ARGUMENT);
+        assert_eq!(c4_set_max_steps(ptr::null_mut(), 16), C4_ERR_INVALID_ARGUMENT);
+        c4_chunk_free(ptr::null_mut());
+    }
+}
@@ -0,0 +1,106 @@
+// tests/cfg_tests.rs
+
+use c4_rust_AlRafaah::bytecode::*;
+use c4_rust_AlRafaah::cfg::{Cfg, EdgeKind};
+
+/// `if (1) a = 2; else a = 3;` followed by the join, as hand-laid bytecode.
+fn diamond() -> Chunk {
+    let mut c = Chunk::default();
+    c.push_int(OpCode::IMM, 1); // 0
+    c.push_jump(OpCode::BZ, 5); // 1
+    c.push_int(OpCode::IMM, 2); // 2: then
+    c.push(OpCode::PSH); // 3
+    c.push_jump(OpCode::JMP, 7); // 4
+    c.push_int(OpCode::IMM, 3); // 5: else
+    c.push(OpCode::PSH); // 6
+    c.push(OpCode::LEV); // 7: join
+    c
+}
+
+/// `while (i) i = i - 1;` over a local.
+fn countdown() -> Chunk {
+    let mut c = Chunk::default();
+    c.push_int(OpCode::ENT, 1); // 0
+    c.push_int(OpCode::LEA, 0); // 1: loop head
+    c.push(OpCode::LI); // 2
+    c.push_jump(OpCode::BZ, 11); // 3
+    c.push_int(OpCode::LEA, 0); // 4: body
+    c.push(OpCode::PSH); // 5
+    c.push(OpCode::LI); // 6
+    c.push(OpCode::PSH); // 7
+    c.push_int(OpCode::IMM, 1); // 8
+    c.push(OpCode::SUB); // 9
+    c.push_jump(OpCode::JMP, 1); // 10
+    c.push(OpCode::LEV); // 11: exit
+    c
+}
+
+fn bounds(cfg: &Cfg) -> Vec<(usize, usize)> {
+    cfg.blocks.iter().map(|b| (b.start, b.end)).collect()
+}
+
+fn succs(cfg: &Cfg, b: usize) -> Vec<(EdgeKind, usize)> {
+    cfg.blocks[b].succs.iter().map(|e| (e.kind, e.to)).collect()
+}
+
+#[test]
+fn if_else_is_a_diamond() {
+    let chunk = diamond();
+    let cfg = Cfg::build(&chunk, 0..chunk.code.len());
+    assert_eq!(bounds(&cfg), [(0, 2), (2, 5), (5, 7), (7, 8)]);
+    assert_eq!(succs(&cfg, 0), [(EdgeKind::Fallthrough, 1), (EdgeKind::Taken, 2)]);
+    assert_eq!(succs(&cfg, 1), [(EdgeKind::Taken, 3)]);
+    assert_eq!(succs(&cfg, 2), [(EdgeKind::Fallthrough, 3)]);
+    assert_eq!(succs(&cfg, 3), []);
+    assert_eq!(cfg.predecessors(3), [1, 2]);
+}
+
+#[test]
+fn while_loop_has_a_back_edge() {
+    let chunk = countdown();
+    let cfg = Cfg::build(&chunk, 0..chunk.code.len());
+    assert_eq!(bounds(&cfg), [(0, 1), (1, 4), (4, 11), (11, 12)]);
+    assert_eq!(succs(&cfg, 2), [(EdgeKind::Taken, 1)]);
+
+    // The body comes after the head in reverse postorder, so its edge to
+    // the head is the back edge.
+    let rpo = cfg.reverse_postorder();
+    assert_eq!(rpo[0], 0);
+    let position = |b| rpo.iter().position(|&x| x == b).unwrap();
+    assert!(position(2) > position(1));
+}
+
+#[test]
+fn block_lookup_and_ranges() {
+    let chunk = countdown();
+    // Just the loop: the jump into pc 11 leaves the range and is dropped.
+    let cfg = Cfg::build(&chunk, 1..11);
+    assert_eq!(bounds(&cfg), [(1, 4), (4, 11)]);
+    assert_eq!(succs(&cfg, 0), [(EdgeKind::Fallthrough, 1)]);
+    assert_eq!(cfg.block_at(4), Some(1));
+    assert_eq!(cfg.block_at(5), None);
+    assert_eq!(cfg.block_containing(5), Some(1));
+    assert_eq!(cfg.block_containing(11), None);
+}
+
+#[test]
+fn unreachable_blocks_are_left_out_of_the_order() {
+    let mut c = Chunk::default();
+    c.push_jump(OpCode::JMP, 2);
+    c.push_int(OpCode::IMM, 9); // dead
+    c.push(OpCode::EXIT);
+    let cfg = Cfg::build(&c, 0..3);
+    assert_eq!(cfg.blocks.len(), 3);
+    assert_eq!(cfg.reverse_postorder(), [0, 2]);
+}
+
+#[test]
+fn dot_output_lists_blocks_and_edges() {
+    let chunk = diamond();
+    let dot = Cfg::build(&chunk, 0..chunk.code.len()).to_dot(&chunk);
+    assert!(dot.starts_with("digraph cfg {"));
+    assert!(dot.contains("b0 [label=\"0000  IMM 1\\l0001  BZ 5\\l\"];"));
+    assert!(dot.contains("b0 -> b1 [style=dashed];"));
+    assert!(dot.contains("b0 -> b2;"));
+    assert!(dot.contains("b2 -> b3 [style=dashed];"));
+}
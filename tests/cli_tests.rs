@@ -0,0 +1,594 @@
+// tests/cli_tests.rs
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Helper: write `src` to a uniquely named temp file and return its path.
+fn write_source(name: &str, src: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("c4_cli_{}_{}.c", std::process::id(), name));
+    fs::write(&path, src).expect("failed to write temp source");
+    path
+}
+
+/// Helper: run the compiled driver binary with the given arguments.
+fn run_driver(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_c4_rust_AlRafaah"))
+        .args(args)
+        .output()
+        .expect("failed to run driver")
+}
+
+#[test]
+fn verbose_flag_prints_resource_usage() {
+    let path = write_source("verbose", "int main() { return 6 * 7; }");
+    let out = run_driver(&["-v", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(42));
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    for row in ["exit code      42", "instructions", "max stack", "call depth", "heap peak", "wall time"] {
+        assert!(stderr.contains(row), "missing {} in:\n{}", row, stderr);
+    }
+}
+
+#[test]
+fn coverage_flag_prints_counts_and_summary() {
+    let path = write_source("coverage", "int main() { return 6 * 7; }");
+    let out = run_driver(&["--coverage", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(42));
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("       1 |    1  int main() { return 6 * 7; }"), "{}", stderr);
+    assert!(stderr.contains("main                  1/1    100.0%"), "{}", stderr);
+    assert!(stderr.lines().any(|l| l.starts_with("total")), "{}", stderr);
+}
+
+#[test]
+fn core_dump_flag_saves_state_on_fault() {
+    let path = write_source("core", "int main() { return 1 / 0; }");
+    let dump = std::env::temp_dir().join(format!("c4_cli_{}_core", std::process::id()));
+    let _ = fs::remove_file(&dump);
+    let out = run_driver(&["--core-dump", dump.to_str().unwrap(), path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    let text = fs::read_to_string(&dump).expect("no dump written");
+    assert!(text.starts_with("c4-core 2\n"), "{}", text);
+    assert!(text.contains("error division by zero at pc"), "{}", text);
+}
+
+#[test]
+fn record_then_replay_without_the_file() {
+    let data = std::env::temp_dir().join(format!("c4_cli_{}_data.txt", std::process::id()));
+    fs::write(&data, "47").unwrap();
+    let src = format!(
+        r#"int main() {{ char *b; b = malloc(4); read(open("{}", 0), b, 4); printf("%c%c\n", b[0], b[1]); return b[1] - '0'; }}"#,
+        data.display()
+    );
+    let path = write_source("record", &src);
+    let log = std::env::temp_dir().join(format!("c4_cli_{}_syscalls", std::process::id()));
+    let out = run_driver(&["--record", log.to_str().unwrap(), path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(7));
+    assert!(fs::read_to_string(&log).unwrap().starts_with("c4-syscalls 1\nopen 3 "));
+
+    // The file is gone, but the replay prints and returns the same.
+    fs::remove_file(&data).unwrap();
+    let replayed = run_driver(&["--replay", log.to_str().unwrap(), path.to_str().unwrap()]);
+    assert_eq!(replayed.status.code(), Some(7));
+    assert_eq!(replayed.stdout, out.stdout);
+
+    // A different program doesn't match the recording.
+    let other = write_source("record_other", "int main() { return 0; }");
+    let out = run_driver(&["--replay", log.to_str().unwrap(), other.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("replay diverged at syscall 0"));
+
+    let out = run_driver(&["--record", "a", "--replay", "b", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--record and --replay can't be used together"));
+}
+
+#[test]
+fn prelude_is_linked_unless_nostdlib() {
+    let path = write_source("prelude", r#"int main() { return max(strlen("four"), atoi("3")); }"#);
+    assert_eq!(run_driver(&[path.to_str().unwrap()]).status.code(), Some(4));
+
+    let out = run_driver(&["--nostdlib", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("bad function call: strlen"), "{}", stderr);
+}
+
+#[test]
+fn leak_check_reports_unfreed_blocks_by_site() {
+    let src = "int main() { free(malloc(8)); free(malloc(16)); malloc(24); malloc(32); return 0; }";
+    let path = write_source("leaky", src);
+    let out = run_driver(&["--leak-check", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let sites: Vec<&str> = stderr.lines().filter(|l| l.contains("allocated at")).collect();
+    assert_eq!(sites.len(), 2, "{}", stderr);
+    assert!(sites[0].contains("3 words in    1 blocks") && sites[0].ends_with("in main"), "{}", stderr);
+    assert!(sites[1].contains("4 words in    1 blocks"), "{}", stderr);
+    assert!(stderr.contains("7 words leaked in 2 blocks"), "{}", stderr);
+}
+
+#[test]
+fn leak_check_passes_leak_free_programs() {
+    let path = write_source("leak_free", "int main() { free(malloc(8)); return 0; }");
+    let out = run_driver(&["--leak-check", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("no leaks"));
+}
+
+#[test]
+fn test_flag_runs_test_functions() {
+    let passing = write_source(
+        "unit_pass",
+        "int test_a() { return 0; }\nint test_b() { assert(1); return 0; }\nint main() { return 3; }\n",
+    );
+    let out = run_driver(&["--test", passing.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("2 passed, 0 failed, 2 total"), "{}", stdout);
+
+    let failing = write_source("unit_fail", "int test_a() { return 0; }\nint test_b() { assert(0); return 0; }\n");
+    let out = run_driver(&["--test", failing.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("FAIL test_b"), "{}", stdout);
+}
+
+#[test]
+fn time_flag_reports_each_phase() {
+    let path = write_source("time", "int main() { return 6 * 7; }");
+    let out = run_driver(&["--time", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(42));
+
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Program exited with code 42"));
+
+    // Every phase gets a line of the form `<name> <ms> ms` on stderr.
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    for phase in ["read", "parse", "compile", "execute", "total"] {
+        let line = stderr
+            .lines()
+            .find(|l| l.split_whitespace().next() == Some(phase))
+            .unwrap_or_else(|| panic!("missing phase {} in:\n{}", phase, stderr));
+        let ms: f64 = line.split_whitespace().nth(1).unwrap().parse().unwrap();
+        assert!(ms >= 0.0);
+    }
+    assert!(stderr.contains("instructions:"));
+}
+
+#[test]
+fn no_timing_output_without_flag() {
+    let path = write_source("notime", "int main() { return 1; }");
+    let out = run_driver(&[path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("execute"));
+}
+
+#[test]
+fn flags_after_the_source_are_still_the_drivers() {
+    use c4_rust_AlRafaah::cli::{parse_args, Action};
+
+    let args = ["prog.c", "a", "--time", "b"].map(String::from);
+    match parse_args(args) {
+        Ok(Action::Run(opts)) => {
+            assert!(opts.time);
+            assert_eq!(opts.program_args, vec!["a", "b"]);
+        }
+        other => panic!("unexpected parse result {:?}", other),
+    }
+
+    let path = write_source("trailing_flag", "int main() { return 4; }");
+    let out = run_driver(&[path.to_str().unwrap(), "--time"]);
+    assert_eq!(out.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("execute"));
+    // Unknown ones are usage errors there too.
+    assert_eq!(run_driver(&[path.to_str().unwrap(), "--tmie"]).status.code(), Some(2));
+}
+
+#[test]
+fn help_lists_every_flag() {
+    let out = run_driver(&["--help"]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Usage:"));
+    for flag in c4_rust_AlRafaah::cli::FLAGS {
+        assert!(stdout.contains(flag.long), "--help is missing {}", flag.long);
+    }
+}
+
+#[test]
+fn version_prints_crate_version() {
+    let out = run_driver(&["--version"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains(env!("CARGO_PKG_VERSION")));
+}
+
+#[test]
+fn unknown_flag_suggests_closest() {
+    let out = run_driver(&["--tmie", "prog.c"]);
+    assert_eq!(out.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("unknown flag '--tmie'"));
+    assert!(stderr.contains("did you mean '--time'?"));
+}
+
+#[test]
+fn missing_source_is_usage_error() {
+    let out = run_driver(&[]);
+    assert_eq!(out.status.code(), Some(2));
+}
+
+#[test]
+fn double_dash_ends_flag_parsing() {
+    use c4_rust_AlRafaah::cli::{parse_args, Action};
+
+    let args = ["--time", "prog.c", "--", "--time", "x"].map(String::from);
+    match parse_args(args) {
+        Ok(Action::Run(opts)) => {
+            assert!(opts.time);
+            assert_eq!(opts.source.to_str(), Some("prog.c"));
+            assert_eq!(opts.program_args, vec!["--time", "x"]);
+        }
+        other => panic!("unexpected parse result {:?}", other),
+    }
+
+    // Flags after `--` are handed to the program, not the driver.
+    let path = write_source("dashdash", "int main() { return 3; }");
+    let out = run_driver(&[path.to_str().unwrap(), "--", "--time"]);
+    assert_eq!(out.status.code(), Some(3));
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("execute"));
+}
+
+#[test]
+fn output_flag_redirects_program_output() {
+    let path = write_source("output", "int main() { return 9; }");
+    let out_file = std::env::temp_dir().join(format!("c4_cli_{}_output.txt", std::process::id()));
+    fs::write(&out_file, "stale contents").unwrap();
+
+    let out = run_driver(&["--output", out_file.to_str().unwrap(), path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(9));
+    // The file is truncated and only the driver's own report reaches stdout.
+    assert_eq!(fs::read(&out_file).unwrap(), b"");
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "Program exited with code 9\n");
+
+    // --append keeps what was already there.
+    fs::write(&out_file, "kept\n").unwrap();
+    let arg = format!("--output={}", out_file.display());
+    let out = run_driver(&[&arg, "--append", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(9));
+    assert_eq!(fs::read(&out_file).unwrap(), b"kept\n");
+}
+
+#[test]
+fn output_flag_writes_printf_output_byte_for_byte() {
+    let src = r#"int main() {
+        int i;
+        i = 0;
+        while (i < 3) { printf("%d:%3d|%-4s|%c\t%x\n", i, i * i, "ab", 'a' + i, 250 + i); i++; }
+        printf("%s", "no newline at the end");
+        return 3;
+    }"#;
+    let path = write_source("output_bytes", src);
+    let out_file = std::env::temp_dir().join(format!("c4_cli_{}_output_bytes.txt", std::process::id()));
+    let expected = "0:  0|ab  |a\tfa\n1:  1|ab  |b\tfb\n2:  4|ab  |c\tfc\nno newline at the end";
+
+    let out = run_driver(&["--output", out_file.to_str().unwrap(), path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(3));
+    assert_eq!(fs::read(&out_file).unwrap(), expected.as_bytes());
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "Program exited with code 3\n");
+
+    // `--output -` is stdout, the program's output first.
+    let out = run_driver(&["--output", "-", path.to_str().unwrap()]);
+    assert_eq!(String::from_utf8_lossy(&out.stdout), format!("{}Program exited with code 3\n", expected));
+}
+
+#[test]
+fn output_flag_reports_unwritable_file_before_running() {
+    let path = write_source("badout", "int main() { return 9; }");
+    let out = run_driver(&["--output", "/nonexistent-dir/out.txt", path.to_str().unwrap()]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("cannot open output file"));
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("Program exited"));
+}
+
+#[test]
+fn output_flag_usage_errors() {
+    assert_eq!(run_driver(&["prog.c", "--output"]).status.code(), Some(2));
+    assert_eq!(run_driver(&["prog.c", "--append"]).status.code(), Some(2));
+    assert_eq!(run_driver(&["prog.c", "--time=yes"]).status.code(), Some(2));
+}
+
+#[test]
+fn program_return_value_is_exit_status() {
+    let path = write_source("status", "int main() { return 0; }");
+    assert_eq!(run_driver(&[path.to_str().unwrap()]).status.code(), Some(0));
+}
+
+#[test]
+fn shebang_flags_in_one_argument_are_split() {
+    use c4_rust_AlRafaah::cli::{parse_args, Action};
+
+    let args = ["--time --output=-", "script.c", "arg1"].map(String::from);
+    match parse_args(args) {
+        Ok(Action::Run(opts)) => {
+            assert!(opts.time);
+            assert_eq!(opts.output.as_deref(), Some(std::path::Path::new("-")));
+            assert_eq!(opts.source.to_str(), Some("script.c"));
+            assert_eq!(opts.program_args, vec!["arg1"]);
+        }
+        other => panic!("unexpected parse result {:?}", other),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn shebang_script_runs_directly() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = format!(
+        "#!{} --time\n// a C4 script\nint main(int argc, char **argv) {{ printf(\"%s\\n\", argv[1]); return argc + 3; }}\n",
+        env!("CARGO_BIN_EXE_c4_rust_AlRafaah")
+    );
+    let path = write_source("shebang", &script);
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+    // Another test thread forking while the script was still open for writing
+    // can make exec fail with "text file busy"; retry briefly in that case.
+    let mut attempts = 0;
+    let out = loop {
+        match Command::new(&path).arg("extra").output() {
+            Err(e) if e.raw_os_error() == Some(26) && attempts < 20 => {
+                attempts += 1;
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+            result => break result.expect("failed to exec script"),
+        }
+    };
+    assert_eq!(out.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&out.stdout).starts_with("extra\n"));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("execute"));
+}
+
+#[test]
+fn program_arguments_reach_main_as_argv() {
+    let src = r#"int main(int argc, char **argv) {
+        int i;
+        i = 0;
+        while (i < argc) { printf("[%s]", argv[i]); i++; }
+        return argc * 10 + (argv[argc] == 0);
+    }"#;
+    let path = write_source("argv", src);
+    let out = run_driver(&[path.to_str().unwrap(), "one", "two words", "--", "--time"]);
+    // `--` ends the driver's flags; everything after it is the program's.
+    assert_eq!(out.status.code(), Some(41));
+    let expected = format!("[{}][one][two words][--time]", path.display());
+    assert!(String::from_utf8_lossy(&out.stdout).starts_with(&expected), "{}", String::from_utf8_lossy(&out.stdout));
+}
+
+#[test]
+fn bench_subcommand_reports_statistics() {
+    let path = write_source("bench", "int main() { return 4; }");
+    let out = run_driver(&["bench", path.to_str().unwrap(), "--iterations", "3", "--warmup", "1"]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    for label in ["3 iterations", "min", "median", "mean", "max", "instructions:"] {
+        assert!(stdout.contains(label), "missing {} in:\n{}", label, stdout);
+    }
+    assert_eq!(run_driver(&["bench", "--iterations", "x"]).status.code(), Some(2));
+}
+
+#[test]
+fn check_mode_compiles_without_running() {
+    let path = write_source("check_ok", "int main() { return 7; }");
+    let out = run_driver(&["--check", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(0));
+    assert!(out.stdout.is_empty(), "nothing should run under --check");
+}
+
+#[test]
+fn check_mode_reports_compile_errors() {
+    // Parses fine, but codegen rejects the call to an unknown function.
+    let path = write_source("check_err", "int main() { return helper(); }");
+    let out = run_driver(&["--check", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(out.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("helper"));
+}
+
+#[test]
+fn fmt_rewrites_file_and_fmt_check_verifies_it() {
+    let path = write_source("fmt", "int main(){return 6*7;}");
+    let p = path.to_str().unwrap();
+
+    let out = run_driver(&["--fmt-check", p]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("is not formatted"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "int main(){return 6*7;}", "--fmt-check must not write");
+
+    assert_eq!(run_driver(&["--fmt", p]).status.code(), Some(0));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "int main() {\n    return 6 * 7;\n}\n");
+    assert_eq!(run_driver(&["--fmt-check", p]).status.code(), Some(0));
+}
+
+#[test]
+fn fmt_leaves_unparsable_file_alone() {
+    let path = write_source("fmt_bad", "int main( {");
+    let out = run_driver(&["--fmt", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("parse error"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "int main( {");
+}
+
+#[test]
+fn emit_c_writes_translation_without_running() {
+    let path = write_source("emit_c", "int main() { return 6 * 7; }");
+    let out_file = std::env::temp_dir().join(format!("c4_cli_{}_emitted.c", std::process::id()));
+    let out = run_driver(&["--emit-c", out_file.to_str().unwrap(), path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(0));
+    assert!(out.stdout.is_empty(), "nothing should run under --emit-c");
+    assert!(fs::read_to_string(&out_file).unwrap().contains("long long c4_main(void)"));
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn ast_json_output_runs_again_with_from_json() {
+    let path = write_source("ast_json", "int main() { return 6 * 7; }");
+    let out = run_driver(&["--ast-json", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(0));
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("Program exited"), "--ast-json must not run");
+
+    let json_path = write_source("ast_json_out", &String::from_utf8_lossy(&out.stdout));
+    let out = run_driver(&["--from-json", json_path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(42));
+
+    let bad = write_source("from_json_bad", r#"{"items": [{"Function": {"ret": "Int", "name": "main",
+        "params": [], "locals": [], "body": {"stmts": [{"Return": {"Var": "y"}}]}}}]}"#);
+    let out = run_driver(&["--from-json", bad.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("undefined variable: y"));
+}
+
+#[test]
+fn asm_flag_prints_the_listing_without_running() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let source = dir.join("listing.c");
+    let golden = fs::read_to_string(dir.join("listing.lst")).unwrap();
+    // Either side of the file, as c4 takes it.
+    for args in [["-s", source.to_str().unwrap()], [source.to_str().unwrap(), "-s"]] {
+        let out = run_driver(&args);
+        assert_eq!(out.status.code(), Some(0), "{}", String::from_utf8_lossy(&out.stderr));
+        assert_eq!(String::from_utf8_lossy(&out.stdout), golden);
+    }
+
+    let bad = write_source("asm_bad", "int main() { return x; }");
+    let out = run_driver(&["--asm", bad.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("undefined variable: x"));
+}
+
+#[test]
+fn disasm_flag_prints_only_the_named_functions() {
+    let path = write_source(
+        "disasm",
+        "int neg(int x) { return -x; }
+         int twice(int x) { return x * 2; }
+         int main() { return neg(1) + twice(2); }",
+    );
+    let p = path.to_str().unwrap();
+    let out = run_driver(&["--disasm=twice", p]);
+    assert_eq!(out.status.code(), Some(0), "{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.starts_with("\ntwice:\n"), "{}", stdout);
+    assert!(stdout.contains("MUL") && stdout.contains("LEV"));
+    assert!(!stdout.contains("SUB") && !stdout.contains("JSR") && !stdout.contains("neg:") && !stdout.contains("main:"));
+
+    let out = run_driver(&["--disasm", "main", "--disasm=neg", p]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let (main, neg) = stdout.split_once("\nneg:\n").expect("neg after main");
+    assert!(main.contains("JSR twice") && !main.contains("MUL"));
+    assert!(neg.contains("SUB") && !neg.contains("JSR"));
+
+    let out = run_driver(&["--disasm=thrice", p]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("no function named 'thrice' (available: neg, twice, main)"));
+}
+
+#[test]
+fn dump_symbols_lists_functions_globals_and_enums() {
+    let path = write_source(
+        "symbols",
+        "enum { RED, GREEN = 5, BLUE }; int count; char *name;
+         int add(int a, int b) { return a + b; }
+         int main() { count = add(GREEN, BLUE); return count; }",
+    );
+    let out = run_driver(&["--dump-symbols", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(0), "{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    for line in [
+        "functions:",
+        "0002: add(a, b), 2 params",
+        "main(), 0 params",
+        "0000: int count = 0",
+        "0008: char * name = 0",
+        "RED = 0",
+        "GREEN = 5",
+        "BLUE = 6",
+    ] {
+        assert!(stdout.contains(line), "missing {:?} in:\n{}", line, stdout);
+    }
+    assert!(!stdout.contains("Program exited"));
+
+    // With -s the listing follows the symbols.
+    let out = run_driver(&["--dump-symbols", "-s", path.to_str().unwrap()]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let (symbols, listing) = stdout.split_once("\nadd:\n").expect("a listing after the symbols");
+    assert!(symbols.contains("BLUE = 6"));
+    assert!(listing.contains("ENT"));
+}
+
+#[test]
+fn emit_bytecode_saves_a_chunk_that_runs_later() {
+    let path = write_source("bytecode", "int main() { printf(\"%d\\n\", 6 * 7); return 7; }");
+    let saved = std::env::temp_dir().join(format!("c4_cli_{}_saved.c4b", std::process::id()));
+    let out = run_driver(&["-o", saved.to_str().unwrap(), path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(0), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(out.stdout.is_empty(), "nothing should run under -o");
+    assert_eq!(&fs::read(&saved).unwrap()[..4], b"C4RS");
+
+    // The saved chunk runs like the source does.
+    let from_source = run_driver(&[path.to_str().unwrap()]);
+    let from_bytecode = run_driver(&[saved.to_str().unwrap()]);
+    assert_eq!(from_bytecode.status.code(), Some(7));
+    assert_eq!(from_bytecode.stdout, from_source.stdout);
+
+    // A damaged one is reported, not run.
+    let bytes = fs::read(&saved).unwrap();
+    fs::write(&saved, &bytes[..bytes.len() - 3]).unwrap();
+    let out = run_driver(&[saved.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("truncated"), "{}", String::from_utf8_lossy(&out.stderr));
+}
+
+#[test]
+fn stack_size_flag_limits_recursion() {
+    let path = write_source("deep", "int f(int n) { if (n == 0) return 0; return 1 + f(n - 1); } int main() { return f(10000) - 9958; }");
+    let p = path.to_str().unwrap();
+    let out = run_driver(&[p]);
+    assert_eq!(out.status.code(), Some(42));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Program exited with code 42"));
+    let out = run_driver(&["--stack-size", "1K", p]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("stack overflow"));
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("Program exited"));
+    // Suffixes scale by 1024; a big enough stack runs it again.
+    let out = run_driver(&["--stack-size=64k", "--heap-size", "1M", p]);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Program exited with code 42"));
+}
+
+#[test]
+fn fuel_flag_stops_infinite_loops() {
+    let path = write_source("spin", "int main() { while (1) ; return 0; }");
+    let out = run_driver(&["--fuel", "10000", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("out of fuel after 10000 instructions"));
+}
+
+#[test]
+fn bad_sizes_and_fuel_are_usage_errors() {
+    for args in [
+        ["--stack-size", "0"],
+        ["--stack-size", "12Q"],
+        ["--heap-size", "-1"],
+        ["--heap-size", "99999999999999999999M"],
+        ["--fuel", "0"],
+        ["--fuel", "lots"],
+    ] {
+        let out = run_driver(&[args[0], args[1], "prog.c"]);
+        assert_eq!(out.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&out.stderr).contains(args[0]), "{:?}", args);
+    }
+}
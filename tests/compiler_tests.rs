@@ -0,0 +1,104 @@
+// tests/compiler_tests.rs
+
+use std::fs;
+use std::path::PathBuf;
+use c4_rust_AlRafaah::analysis::Severity;
+use c4_rust_AlRafaah::compiler::Compiler;
+use c4_rust_AlRafaah::vm::VM;
+use c4_rust_AlRafaah::{compile_source, Error};
+
+/// Helper: write `src` to a uniquely named temp file and return its path.
+fn write_source(name: &str, src: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("c4_compiler_{}_{}.c", std::process::id(), name));
+    fs::write(&path, src).expect("failed to write temp source");
+    path
+}
+
+const SLOPPY: &str = "int main() { int unused; return 1; return 2; }";
+
+#[test]
+fn compile_str_matches_compile_source() {
+    let compiled = Compiler::new().compile_str(SLOPPY).unwrap();
+    assert_eq!(compiled.chunk, compile_source(SLOPPY).unwrap());
+    assert_eq!(VM::new().run(&compiled.chunk).unwrap(), 1);
+}
+
+#[test]
+fn warnings_are_collected_only_when_asked_for() {
+    assert!(Compiler::new().compile_str(SLOPPY).unwrap().diagnostics.is_empty());
+
+    let compiled = Compiler::new().warnings(true).compile_str(SLOPPY).unwrap();
+    let codes: Vec<&str> = compiled.diagnostics.iter().map(|d| d.code).collect();
+    assert_eq!(codes, ["W0001", "W0002"]);
+    assert!(compiled.diagnostics.iter().all(|d| d.severity == Severity::Warning));
+    // They don't change the code.
+    assert_eq!(compiled.chunk, compile_source(SLOPPY).unwrap());
+}
+
+#[test]
+fn errors_stop_compilation() {
+    let err = Compiler::new().warnings(true).compile_str("int main() { return x; }").unwrap_err();
+    assert!(matches!(err, Error::Compile(_)), "{:?}", err);
+}
+
+#[test]
+fn compile_files_links_files_into_one_program() {
+    let lib = write_source("lib", "int unused_global; int square(int x) { int y; return x * x; }");
+    let main = write_source("main", "int main() { return square(7); }");
+    let compiled = Compiler::new().warnings(true).compile_files(&[&lib, &main]).unwrap();
+    assert_eq!(VM::new().run(&compiled.chunk).unwrap(), 49);
+    assert_eq!(compiled.diagnostics.len(), 1);
+    assert!(compiled.diagnostics[0].message.starts_with(&format!("{}: unused local variable `y`", lib.display())));
+
+    // Without the library, `square` is undefined...
+    assert!(Compiler::new().compile_file(&main).is_err());
+    // ...and a missing file is an I/O error.
+    let missing = Compiler::new().compile_files(&[lib, PathBuf::from("/nonexistent/c4.c")]).unwrap_err();
+    assert!(matches!(missing, Error::Io(_)), "{:?}", missing);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_input_compiles_the_same_program() {
+    let src = "int main() { return 6 * 7; }";
+    let ast = Compiler::new().parse(src).unwrap();
+    let json = c4_rust_AlRafaah::json::to_json(&ast);
+    let compiled = Compiler::new().json(true).compile_str(&json).unwrap();
+    assert_eq!(compiled.chunk, compile_source(src).unwrap());
+    // C source isn't JSON.
+    assert!(Compiler::new().json(true).compile_str(src).is_err());
+}
+
+#[test]
+fn optimize_folds_constant_expressions() {
+    let src = "int main() { int x; x = 4; return 2 * 3 + sizeof(int) - (1 << 2) + !0 + x; }";
+    let plain = Compiler::new().compile_str(src).unwrap().chunk;
+    let folded = Compiler::new().optimize(1).compile_str(src).unwrap().chunk;
+    assert!(folded.code.len() < plain.code.len(), "{} vs {}", folded.code.len(), plain.code.len());
+    assert_eq!(VM::new().run(&plain).unwrap(), 15);
+    assert_eq!(VM::new().run(&folded).unwrap(), 15);
+
+    // Division by a constant zero still fails when it runs.
+    let chunk = Compiler::new().optimize(2).compile_str("int main() { return 1 / 0; }").unwrap().chunk;
+    assert!(VM::new().run(&chunk).is_err());
+}
+
+#[test]
+fn defines_are_constants_the_program_sees() {
+    let src = "int main() { return DEBUG * 10 + LEVEL; }";
+    assert!(Compiler::new().compile_str(src).is_err());
+    let chunk = Compiler::new().define("DEBUG", 1).define("LEVEL", 3).compile_str(src).unwrap().chunk;
+    assert_eq!(VM::new().run(&chunk).unwrap(), 13);
+
+    let err = Compiler::new().define("N", 1).compile_str("int N; int main() { return 0; }").unwrap_err();
+    assert!(matches!(err, Error::Compile(_)), "{:?}", err);
+}
+
+#[test]
+fn debug_info_keeps_the_source_map() {
+    let with = Compiler::new().compile_str(SLOPPY).unwrap().chunk;
+    let without = Compiler::new().debug_info(false).compile_str(SLOPPY).unwrap().chunk;
+    assert!(!with.source_map.0.is_empty());
+    assert!(without.source_map.0.is_empty());
+    assert_eq!(with.code, without.code);
+}
@@ -0,0 +1,72 @@
+// tests/coredump_tests.rs
+
+use c4_rust_AlRafaah::bytecode::{Chunk, OpCode};
+use c4_rust_AlRafaah::compiler::Compiler;
+use c4_rust_AlRafaah::coredump::{CoreDump, HeapBlock};
+use c4_rust_AlRafaah::vm::{VmConfig, VmError, VM};
+
+/// main calls a function that pushes 1234 and then loads from address -1.
+fn bad_load() -> Chunk {
+    let mut c = Chunk::default();
+    c.push_call(OpCode::JSR, 2);
+    c.push(OpCode::EXIT);
+    c.push_int(OpCode::ENT, 1);
+    c.push_int(OpCode::IMM, 1234);
+    c.push(OpCode::PSH);
+    c.push_int(OpCode::IMM, -1);
+    c.push(OpCode::LI);
+    c.push(OpCode::LEV);
+    c
+}
+
+#[test]
+fn dump_round_trips_through_text() {
+    let chunk = bad_load();
+    let mut vm = VM::new();
+    let err = vm.run(&chunk).unwrap_err();
+    assert_eq!(err, VmError::InvalidAddress { pc: 6, addr: -1 });
+
+    let dump = err.dump(&vm, &chunk);
+    let path = std::env::temp_dir().join(format!("c4_core_{}", std::process::id()));
+    std::fs::write(&path, dump.to_text()).unwrap();
+    let loaded = CoreDump::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(loaded, dump);
+
+    assert_eq!(loaded.fault_pc, 6);
+    assert_eq!(loaded.frames.len(), 1); // the JSR's
+    assert_eq!(loaded.stack, [0, 1234]); // the local, then the pushed value
+    assert_eq!(loaded.fingerprint, chunk.fingerprint());
+
+    let report = loaded.render(&chunk);
+    assert!(report.starts_with("core dump: invalid address -1 at pc 6\n"), "{}", report);
+    assert!(report.contains("#0   0006  LI"), "{}", report);
+    assert!(report.contains("0001  1234"), "{}", report);
+    assert!(!report.contains("different chunk"), "{}", report);
+}
+
+#[test]
+fn malformed_dumps_are_rejected() {
+    assert!(CoreDump::parse("hello\n").is_err());
+    assert!(CoreDump::parse("c4-core 2\nframe 1 2\n").is_err());
+    assert!(CoreDump::parse("c4-core 2\nsp many\n").is_err());
+    assert!(CoreDump::parse("c4-core 2\nregisters 3\n").is_err());
+    assert!(CoreDump::parse("c4-core 2\nheap 128\n").is_err());
+    // A dump from before heap blocks were recorded.
+    assert!(CoreDump::parse("c4-core 1\nsp 0\n").is_err());
+}
+
+#[test]
+fn dump_lists_live_heap_blocks() {
+    let src = "int main() { int *p; p = malloc(2 * sizeof(int)); free(malloc(8)); malloc(0); return *p / 0; }";
+    let chunk = Compiler::new().compile_str(src).unwrap().chunk;
+    let mut vm = VM::with_config(VmConfig { stack_size: 16, ..VmConfig::default() });
+    let err = vm.run(&chunk).unwrap_err();
+    assert!(matches!(err, VmError::DivisionByZero { .. }), "{}", err);
+
+    // The heap starts at word 16, byte 128; the freed block is gone.
+    let dump = err.dump(&vm, &chunk);
+    assert_eq!(dump.heap, [HeapBlock { addr: 128, words: 2 }, HeapBlock { addr: 152, words: 1 }]);
+    assert_eq!(CoreDump::parse(&dump.to_text()).unwrap(), dump);
+    let report = dump.render(&chunk);
+    assert!(report.contains("heap (2 blocks):\n  128  2 words\n  152  1 words\n"), "{}", report);
+}
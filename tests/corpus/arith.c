@@ -0,0 +1,4 @@
+// Arithmetic with mixed precedence.
+int main() {
+  return 2 + 3 * 4 - 10 / 2 + 17 % 5;
+}
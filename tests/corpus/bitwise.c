@@ -0,0 +1,4 @@
+// Shifts and bitwise operators.
+int main() {
+  return (1 << 5 | 3) ^ (240 >> 4 & 6);
+}
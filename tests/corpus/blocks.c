@@ -0,0 +1,9 @@
+// Nested blocks and expression statements before the return.
+int main() {
+  1 + 1;
+  {
+    2 * 3;
+    { ; }
+  }
+  return 017 + 16;
+}
@@ -0,0 +1,29 @@
+// Recursion and calls with several arguments.
+int fib(int n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+
+int gcd(int a, int b) {
+  while (b) {
+    a = a % b;
+    if (a == 0) return b;
+    b = b % a;
+  }
+  return a;
+}
+
+int clamp(int x, int lo, int hi) {
+  return x < lo ? lo : x > hi ? hi : x;
+}
+
+int main() {
+  int n;
+  n = 0;
+  while (n <= 10) {
+    printf("fib(%d) = %d\n", n, fib(n));
+    n++;
+  }
+  printf("gcd = %d, clamp = %d %d %d\n", gcd(1071, 462), clamp(-5, 0, 9), clamp(4, 0, 9), clamp(99, 0, 9));
+  return fib(12) - gcd(48, 18);
+}
@@ -0,0 +1,4 @@
+// Character literals are plain integers.
+int main() {
+  return 'z' - 'a' + '\n';
+}
@@ -0,0 +1,4 @@
+// Comparisons produce 0 or 1.
+int main() {
+  return (3 < 4) + (4 <= 4) * 2 + (5 > 6) * 4 + (7 >= 7) * 8 + (1 == 1) * 16 + (1 != 1) * 32;
+}
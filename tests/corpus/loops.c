@@ -0,0 +1,17 @@
+// Nested while loops: sum the multiplication table below 10.
+int main() {
+  int i, j, sum;
+  sum = 0;
+  i = 1;
+  while (i < 10) {
+    j = 1;
+    while (j <= i) {
+      if ((i + j) % 3 == 0) sum = sum + i * j;
+      else sum = sum - 1;
+      j++;
+    }
+    i++;
+  }
+  printf("sum=%d\n", sum);
+  return sum % 256;
+}
@@ -0,0 +1,10 @@
+// printf conversions, widths and flags; c4 passes at most five arguments
+// after the format.
+int main() {
+  int n;
+  n = printf("[%d] [%5d] [%-5d] [%05d] [%05d]\n", 42, 42, 42, 42, -42);
+  n = n + printf("[%c%c] [%s] [%8s]\n", 'o', 'k', "str", "right");
+  n = n + printf("[%-8s] [%.3s]\n", "left", "truncated");
+  n = n + printf("[%x] [%o] [%i] [%%]\n", 48879, 8, -7);
+  return n;
+}
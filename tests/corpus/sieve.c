@@ -0,0 +1,33 @@
+// Sieve of Eratosthenes over a malloc'd array, with globals and an enum.
+enum { LIMIT = 100 };
+
+int count;
+int *flags;
+
+void sieve() {
+  int i, j;
+  i = 2;
+  while (i < LIMIT) {
+    if (!flags[i]) {
+      count++;
+      printf("%d ", i);
+      j = i * i;
+      while (j < LIMIT) {
+        flags[j] = 1;
+        j = j + i;
+      }
+    }
+    i++;
+  }
+  printf("\n");
+}
+
+int main() {
+  int i;
+  flags = malloc(LIMIT * sizeof(int));
+  i = 0;
+  while (i < LIMIT) flags[i++] = 0;
+  sieve();
+  printf("%d primes below %d\n", count, LIMIT);
+  return count;
+}
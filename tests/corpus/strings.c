@@ -0,0 +1,36 @@
+// Walk and build strings through char pointers.
+int length(char *s) {
+  char *p;
+  p = s;
+  while (*p) p++;
+  return p - s;
+}
+
+void reverse(char *s) {
+  char *end;
+  char c;
+  end = s + length(s) - 1;
+  while (s < end) {
+    c = *s;
+    *s = *end;
+    *end = c;
+    s++;
+    end--;
+  }
+}
+
+int main() {
+  char *buf;
+  int i;
+  buf = malloc(16);
+  i = 0;
+  while (i < 10) {
+    buf[i] = 'a' + i;
+    i++;
+  }
+  buf[i] = 0;
+  reverse(buf);
+  printf("%s (%d)\n", buf, length(buf));
+  free(buf);
+  return length("hello, world");
+}
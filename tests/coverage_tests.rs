@@ -0,0 +1,149 @@
+// tests/coverage_tests.rs
+
+use c4_rust_AlRafaah::bytecode::*;
+use c4_rust_AlRafaah::compiler::Compiler;
+use c4_rust_AlRafaah::vm::VM;
+
+/// `if (0) return 1; return 2;` behind a call from the startup code.
+fn untaken_branch() -> Chunk {
+    let mut c = Chunk::default();
+    c.push_call(OpCode::JSR, 2); // 0
+    c.push(OpCode::EXIT); // 1
+    c.push_int(OpCode::ENT, 0); // 2
+    c.push_int(OpCode::IMM, 0); // 3
+    c.push_jump(OpCode::BZ, 7); // 4
+    c.push_int(OpCode::IMM, 1); // 5: never runs
+    c.push(OpCode::EXIT); // 6: never runs
+    c.push_int(OpCode::IMM, 2); // 7
+    c.push(OpCode::EXIT); // 8
+    c
+}
+
+/// Counts `a` down from 3; the body runs three times.
+fn countdown() -> Chunk {
+    let mut c = Chunk::default();
+    c.push_int(OpCode::IMM, 3); // 0
+    c.push_jump(OpCode::BZ, 6); // 1: loop head
+    c.push(OpCode::PSH); // 2: body
+    c.push_int(OpCode::IMM, 1); // 3
+    c.push(OpCode::SUB); // 4
+    c.push_jump(OpCode::JMP, 1); // 5
+    c.push(OpCode::EXIT); // 6
+    c
+}
+
+#[test]
+fn untaken_branch_reports_zero() {
+    let chunk = untaken_branch();
+    let mut vm = VM::new();
+    vm.enable_coverage();
+    assert_eq!(vm.run(&chunk).unwrap(), 2);
+    let report = vm.take_coverage().unwrap();
+    assert_eq!(report.hits(4), 1);
+    assert_eq!(report.hits(5), 0);
+    assert_eq!(report.hits(6), 0);
+    assert_eq!(report.hits(7), 1);
+
+    let rendered = report.render_instructions(&chunk);
+    assert!(rendered.contains("   ##### | 0005  IMM 1"), "{}", rendered);
+    assert!(rendered.contains("       1 | 0007  IMM 2"), "{}", rendered);
+}
+
+#[test]
+fn loop_bodies_count_iterations() {
+    let chunk = countdown();
+    let mut vm = VM::new();
+    vm.enable_coverage();
+    assert_eq!(vm.run(&chunk).unwrap(), 0);
+    let report = vm.take_coverage().unwrap();
+    assert_eq!(report.hits(1), 4);
+    assert_eq!((2..6).map(|pc| report.hits(pc)).collect::<Vec<_>>(), [3, 3, 3, 3]);
+}
+
+#[test]
+fn summary_matches_a_hand_count() {
+    let chunk = untaken_branch();
+    let mut vm = VM::new();
+    vm.enable_coverage();
+    vm.run(&chunk).unwrap();
+    let report = vm.take_coverage().unwrap();
+
+    let functions = report.functions(&chunk);
+    assert_eq!(functions.len(), 2);
+    // Startup: JSR ran, EXIT at 1 never did (main exits itself).
+    assert_eq!((functions[0].start, functions[0].end, functions[0].executed), (0, 2, 1));
+    // main: 5 of its 7 instructions.
+    assert_eq!((functions[1].start, functions[1].end, functions[1].executed), (2, 9, 5));
+    assert!((functions[1].percent() - 500.0 / 7.0).abs() < 1e-9);
+    assert!(report.render_instructions(&chunk).contains("total                 6/9     66.7%"));
+}
+
+#[test]
+fn counts_accumulate_across_runs_until_taken() {
+    let chunk = countdown();
+    let mut vm = VM::new();
+    vm.enable_coverage();
+    vm.run(&chunk).unwrap();
+    vm.reset();
+    vm.run(&chunk).unwrap();
+    let report = vm.take_coverage().unwrap();
+    assert_eq!(report.hits(2), 6);
+
+    // Taking the counts starts afresh.
+    vm.reset();
+    vm.run(&chunk).unwrap();
+    assert_eq!(vm.take_coverage().unwrap().hits(2), 3);
+}
+
+#[test]
+fn coverage_is_off_by_default() {
+    let mut vm = VM::new();
+    vm.run(&countdown()).unwrap();
+    assert!(vm.take_coverage().is_none());
+}
+
+/// Sums 0..3 in a loop, then skips a branch.
+const SUM: &str = "int main() {
+  int i, n;
+  n = 0;
+  for (i = 0; i < 3; i++)
+    n = n + i;
+  if (n > 10)
+    return 1;
+  return abs(n);
+}
+";
+
+#[test]
+fn lines_count_the_statements_on_them() {
+    let chunk = Compiler::new().compile_str(SUM).unwrap().chunk;
+    let mut vm = VM::new();
+    vm.enable_coverage();
+    assert_eq!(vm.run(&chunk).unwrap(), 3);
+    let report = vm.take_coverage().unwrap();
+    // The header's test runs once more than the body; the untaken branch
+    // and the lines without code differ.
+    assert_eq!(report.lines(&chunk, SUM), [None, None, Some(1), Some(4), Some(3), Some(1), Some(0), Some(1), None]);
+
+    let rendered = report.render(&chunk, SUM);
+    assert!(rendered.contains("       - |    2    int i, n;"), "{}", rendered);
+    assert!(rendered.contains("       3 |    5      n = n + i;"), "{}", rendered);
+    assert!(rendered.contains("   ##### |    7      return 1;"), "{}", rendered);
+    // Five of main's six lines with code ran; the linked `abs` has no
+    // lines here.
+    assert!(rendered.contains("main                  5/6     83.3%"), "{}", rendered);
+    assert!(!rendered.lines().any(|l| l.starts_with("abs")), "{}", rendered);
+    assert!(rendered.ends_with("total                 5/6     83.3%\n"), "{}", rendered);
+}
+
+#[test]
+fn line_counts_accumulate_across_runs() {
+    let chunk = Compiler::new().compile_str(SUM).unwrap().chunk;
+    let mut vm = VM::new();
+    vm.enable_coverage();
+    vm.run(&chunk).unwrap();
+    vm.reset();
+    vm.run(&chunk).unwrap();
+    let lines = vm.take_coverage().unwrap().lines(&chunk, SUM);
+    assert_eq!((lines[3], lines[4], lines[6]), (Some(8), Some(6), Some(0)));
+}
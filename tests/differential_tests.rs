@@ -0,0 +1,94 @@
+// tests/differential_tests.rs
+
+//! Random programs from `testgen`, run through both the AST interpreter and
+//! the compile+VM pipeline. A divergence is shrunk to a minimal program.
+//! `sizeof` is c4's size in bytes in both; see `reference_tests` for where
+//! the interpreter's cells and the VM's bytes can be told apart.
+
+mod testgen;
+
+use std::fs;
+use c4_rust_AlRafaah::interp::interpret;
+use c4_rust_AlRafaah::parser::Parser;
+use c4_rust_AlRafaah::run_source;
+use testgen::{generate, shrink, Features, GenProgram};
+
+/// Features the compiler currently handles: all of them, `for` included.
+const BASIC: Features = Features::all();
+
+/// Interpreter result for `src`, as (exit code, output).
+fn oracle(src: &str) -> Result<(i64, Vec<u8>), String> {
+    let ast = Parser::new(src).and_then(|mut p| p.parse_program()).map_err(|e| e.to_string())?;
+    let out = interpret(&ast, &[]).map_err(|e| e.to_string())?;
+    Ok((out.exit_code, out.output))
+}
+
+/// Compile+VM result for `src`, as (exit code, output).
+fn pipeline(src: &str) -> Result<(i64, Vec<u8>), String> {
+    let out = run_source(src).map_err(|e| e.to_string())?;
+    Ok((out.exit_code, out.stdout))
+}
+
+fn diverges(program: &GenProgram) -> bool {
+    let src = program.render();
+    oracle(&src) != pipeline(&src)
+}
+
+/// Check one seed, panicking with a shrunk reproducer on divergence.
+fn check_seed(seed: u64, features: Features) {
+    let program = generate(seed, features);
+    if diverges(&program) {
+        let small = shrink(program, diverges).render();
+        panic!(
+            "seed {} diverges; minimal program:\n{}\ninterpreter: {:?}\npipeline:    {:?}",
+            seed,
+            small,
+            oracle(&small),
+            pipeline(&small)
+        );
+    }
+}
+
+#[test]
+fn generated_programs_agree() {
+    for seed in 0..300 {
+        check_seed(seed, BASIC);
+    }
+}
+
+#[test]
+fn full_grammar_programs_run_cleanly_in_interpreter() {
+    // The generator promises termination and no UB for every feature.
+    for seed in 0..300 {
+        let src = generate(seed, Features::all()).render();
+        if let Err(e) = oracle(&src) {
+            panic!("seed {}: {}\n{}", seed, e, src);
+        }
+    }
+}
+
+#[test]
+fn regression_seeds_agree() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/testgen/regressions.txt");
+    for line in fs::read_to_string(path).unwrap().lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (seed, features) = line.split_once(' ').unwrap_or((line, "basic"));
+        let features = if features.trim() == "all" { Features::all() } else { BASIC };
+        check_seed(seed.parse().unwrap(), features);
+    }
+}
+
+#[test]
+fn shrinking_finds_a_smaller_program() {
+    // Pretend any program mentioning a shift fails; shrinking must keep one.
+    let program = (0..100)
+        .map(|s| generate(s, BASIC))
+        .find(|p| p.render().contains("<<"))
+        .expect("some seed generates a shift");
+    let small = shrink(program.clone(), |p| p.render().contains("<<"));
+    assert!(small.render().contains("<<"));
+    assert!(small.render().len() < program.render().len());
+}
@@ -0,0 +1,144 @@
+// tests/display_tests.rs
+
+use std::fs;
+use std::path::Path;
+use c4_rust_AlRafaah::ast::*;
+use c4_rust_AlRafaah::parser::Parser;
+
+fn parse(src: &str) -> Program {
+    Parser::new(src)
+        .and_then(|mut p| p.parse_program())
+        .unwrap_or_else(|e| panic!("{}\n{}", e, src))
+}
+
+/// Parse `src`, print it, and check the printed program parses back to the
+/// same tree and prints the same way again.
+fn round_trip(src: &str) -> String {
+    let ast = parse(src);
+    let printed = ast.to_string();
+    let reparsed = parse(&printed);
+    assert_eq!(reparsed, ast, "{}", printed);
+    assert_eq!(reparsed.to_string(), printed);
+    printed
+}
+
+#[test]
+fn corpus_round_trips_through_display() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut checked = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "c") {
+            continue;
+        }
+        round_trip(&fs::read_to_string(&path).unwrap());
+        checked += 1;
+    }
+    assert!(checked > 0, "corpus is empty");
+}
+
+#[test]
+fn statements_round_trip() {
+    round_trip(
+        r#"
+        int g, h;
+        int *gp;
+        char **argv;
+        enum { A = 1, B, C = 7 };
+        enum { };
+        void *f(int n, char *s) {
+            int i;
+            for (;;) break;
+            for (i = 0; i < n; i++) { if (i == 3) continue; s[i] = 'x'; }
+            while (n) n--;
+            do n++; while (n < 10);
+            do { n = n * 2; } while (n < 100);
+            switch (n) { case -1: case 2: n = 0; break; default: ; }
+            if (n) if (i) n = 1; else n = 2;
+            if (n) { n = 3; } else if (i) n = 4; else { char c; c = 5; }
+            { int j; j = 1; }
+            ;
+            return (void *)s;
+        }
+        int main() { printf("a \"quoted\" \\ line\n"); return 0; }
+        "#,
+    );
+}
+
+#[test]
+fn expressions_round_trip_with_minimal_parentheses() {
+    let cases = [
+        ("a + b * c", "a + b * c"),
+        ("(a + b) * c", "(a + b) * c"),
+        ("a - (b - c)", "a - (b - c)"),
+        ("(a - b) - c", "a - b - c"),
+        ("a = b = c", "a = b = c"),
+        ("(a = b) + 1", "(a = b) + 1"),
+        ("a += b -= 1", "a += b -= 1"),
+        ("- -a", "- -a"),
+        ("-(-a)", "- -a"),
+        ("!!a", "! !a"),
+        ("-a++", "-a++"),
+        ("*p++", "*p++"),
+        ("&a[1]", "&a[1]"),
+        ("(char *)p + sizeof(int *)", "(char *)p + sizeof(int *)"),
+        ("(int)(a + b)", "(int)(a + b)"),
+        ("a ? b : c", "a ? b : c"),
+        ("(a ? b : c) + 1", "(a ? b : c) + 1"),
+        ("a ? b : c ? d : e", "a ? b : c ? d : e"),
+        ("(a ? b : c) ? d : e", "(a ? b : c) ? d : e"),
+        // `?:` binds tighter than `&&` here, and is kept in parentheses.
+        ("a && b ? c : d", "a && (b ? c : d)"),
+        ("(a || b) && c", "(a || b) && c"),
+        ("(a && b ? c : d) || e", "a && (b ? c : d) || e"),
+        ("x = a ? b : c", "x = a ? b : c"),
+        ("f(a, b = 1, g())[2]", "f(a, b = 1, g())[2]"),
+        ("a << 1 >> 2 & b | c ^ d", "a << 1 >> 2 & b | c ^ d"),
+        ("a == (b < c)", "a == b < c"),
+        ("(a == b) < c", "(a == b) < c"),
+    ];
+    for (src, expected) in cases {
+        let printed = round_trip(&format!("int f() {{ return {}; }}", src));
+        assert!(printed.contains(&format!("return {};", expected)), "{}: {}", src, printed);
+    }
+}
+
+#[test]
+fn programs_print_in_the_repo_layout() {
+    let printed = round_trip("int x; char *p; int main(int argc) { int i; if (argc) { i = 1; } else i = 2; return i; }");
+    assert_eq!(
+        printed,
+        "int x;\nchar *p;\n\nint main(int argc) {\n    int i;\n    if (argc) {\n        i = 1;\n    } else\n        i = 2;\n    return i;\n}\n"
+    );
+}
+
+#[test]
+fn dangling_else_keeps_its_if() {
+    // Built by hand: the `else` belongs to the outer `if`, which the
+    // parser could never produce without braces.
+    let var = |n: &str| Expr::from(ExprKind::Var(n.into()));
+    let assign = |n: &str| Stmt::from(StmtKind::Expr(ExprKind::Binary {
+        op: BinOp::Assign,
+        left: Box::new(var(n)),
+        right: Box::new(ExprKind::Num(1).into()),
+    }.into()));
+    let inner = StmtKind::If { cond: var("b"), then_branch: Box::new(assign("x")), else_branch: None };
+    let outer: Stmt = StmtKind::If {
+        cond: var("a"),
+        then_branch: Box::new(inner.into()),
+        else_branch: Some(Box::new(assign("y"))),
+    }
+    .into();
+    assert_eq!(outer.to_string(), "if (a) {\n    if (b)\n        x = 1;\n} else\n    y = 1;");
+}
+
+#[test]
+fn string_escapes_round_trip() {
+    let printed = round_trip(r#"int main() { printf("\t|\r|\0011\1772|\x00|\a\b\?\'"); return 0; }"#);
+    assert!(printed.contains(r#""\t|\r|\0011\1772|\000|\007\010?'""#), "{}", printed);
+
+    // Bytes that aren't UTF-8 come back as octal escapes; text that is
+    // stays as it was written.
+    let printed = round_trip(r#"int main() { printf("\xff|\377|é"); return 0; }"#);
+    assert!(printed.contains(r#""\377|\377|é""#), "{}", printed);
+}
@@ -0,0 +1,290 @@
+// tests/driver_tests.rs
+
+use std::time::Duration;
+use c4_rust_AlRafaah::driver::{watch, ChangeSource, Clock, PhaseTimer, WatchConfig};
+
+/// Clock that only moves when the watch loop sleeps.
+struct FakeClock {
+    now: Duration,
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Duration {
+        self.now
+    }
+
+    fn sleep(&mut self, d: Duration) {
+        self.now += d;
+    }
+}
+
+/// Replays a fixed script of per-poll change notifications.
+struct ScriptedChanges {
+    script: Vec<bool>,
+    polls: usize,
+}
+
+impl ChangeSource for ScriptedChanges {
+    fn poll_changed(&mut self) -> bool {
+        let changed = self.script.get(self.polls).copied().unwrap_or(false);
+        self.polls += 1;
+        changed
+    }
+}
+
+/// Helper: run the watch loop over `script`, with some quiet polls at the
+/// end, and return how many times it rebuilt.
+fn rebuilds_for(script: Vec<bool>) -> usize {
+    let ticks = script.len() + 10;
+    let mut changes = ScriptedChanges { script, polls: 0 };
+    let mut clock = FakeClock { now: Duration::ZERO };
+    let config = WatchConfig {
+        poll_interval: Duration::from_millis(100),
+        debounce: Duration::from_millis(250),
+    };
+
+    let mut built = 0;
+    let returned = watch(&mut changes, &mut clock, &config, || built += 1, stop_after(ticks));
+    assert_eq!(returned, built);
+    built
+}
+
+/// Helper: a stop condition that ends the loop after `ticks` iterations.
+fn stop_after(ticks: usize) -> impl FnMut() -> bool {
+    let mut seen = 0;
+    move || {
+        seen += 1;
+        seen > ticks
+    }
+}
+
+#[test]
+fn watch_builds_once_up_front() {
+    assert_eq!(rebuilds_for(vec![]), 1);
+}
+
+#[test]
+fn watch_rebuilds_once_per_change() {
+    let mut script = vec![false; 30];
+    script[2] = true;
+    script[15] = true;
+    assert_eq!(rebuilds_for(script), 3);
+}
+
+#[test]
+fn watch_debounces_rapid_writes() {
+    // Three writes 100ms apart are within the 250ms debounce window.
+    let mut script = vec![false; 20];
+    script[3] = true;
+    script[4] = true;
+    script[5] = true;
+    assert_eq!(rebuilds_for(script), 2);
+}
+
+#[test]
+fn phase_timer_records_in_order() {
+    let mut timer = PhaseTimer::new();
+    let v = timer.time("first", || 1);
+    timer.time("second", || ());
+    assert_eq!(v, 1);
+    let names: Vec<_> = timer.phases().iter().map(|(n, _)| *n).collect();
+    assert_eq!(names, vec!["first", "second"]);
+    assert!(timer.get("second").is_some());
+    assert!(timer.get("third").is_none());
+    assert!(timer.report(None).contains("total"));
+}
+
+#[test]
+fn bench_collects_sensible_statistics() {
+    use c4_rust_AlRafaah::{bytecode::Chunk, driver::bench, parser::Parser};
+
+    let ast = Parser::new("int main() { return 1 + 2 * 3; }")
+        .and_then(|mut p| p.parse_program())
+        .unwrap();
+    let mut chunk = Chunk::default();
+    ast.compile(&mut chunk).unwrap();
+
+    let stats = bench(&chunk, 3, 1).unwrap();
+    assert_eq!(stats.samples.len(), 3);
+    assert!(stats.instructions > 0);
+    assert!(stats.min() <= stats.median());
+    assert!(stats.median() <= stats.max());
+    assert!(stats.min() <= stats.mean() && stats.mean() <= stats.max());
+    assert!(stats.report().contains("median"));
+}
+
+#[test]
+fn run_source_returns_exit_code_and_output() {
+    let outcome = c4_rust_AlRafaah::run_source("int main(){return 6*7;}").unwrap();
+    assert_eq!(outcome.exit_code, 42);
+    assert!(outcome.stdout.is_empty());
+    assert!(outcome.instructions > 0);
+    assert!(outcome.max_stack_words > 0);
+    assert!(outcome.max_call_depth >= 1);
+    assert_eq!(outcome.heap_peak_words, 0);
+}
+
+#[test]
+fn run_source_reports_parse_errors_with_offset() {
+    use c4_rust_AlRafaah::Error;
+    match c4_rust_AlRafaah::run_source("int main() { return 1 + ; }") {
+        Err(Error::Parse(e)) => assert_eq!(e.offset, 24),
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn run_source_with_small_stack_overflows() {
+    use c4_rust_AlRafaah::{vm::{VmConfig, VmError}, Error};
+    let config = VmConfig { stack_size: 2, ..VmConfig::default() };
+    let err = c4_rust_AlRafaah::run_source_with("int main() { return 1 + (2 + (3 + 4)); }", config).unwrap_err();
+    assert!(matches!(err, Error::Runtime(VmError::StackOverflow { .. })));
+}
+
+#[test]
+fn run_collecting_counts_a_tiny_chunk_exactly() {
+    use c4_rust_AlRafaah::bytecode::{Chunk, OpCode};
+    use c4_rust_AlRafaah::vm::VM;
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 5);
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 2);
+    chunk.push(OpCode::MUL);
+    chunk.push(OpCode::EXIT);
+    let outcome = VM::new().run_collecting(&chunk).unwrap();
+    assert_eq!(outcome.exit_code, 10);
+    assert_eq!(outcome.instructions, 5);
+    assert_eq!(outcome.max_stack_words, 1);
+    assert_eq!(outcome.max_call_depth, 0);
+    assert!(outcome.report().contains("instructions   5"));
+}
+
+#[test]
+fn spawned_infinite_loop_can_be_cancelled() {
+    use std::time::Instant;
+    use c4_rust_AlRafaah::bytecode::{Chunk, OpCode};
+    use c4_rust_AlRafaah::driver::spawn_run;
+    use c4_rust_AlRafaah::vm::{VmConfig, VmError};
+    use c4_rust_AlRafaah::Error;
+    let mut chunk = Chunk::default();
+    chunk.push_jump(OpCode::JMP, 0);
+
+    let handle = spawn_run(chunk, VmConfig::default());
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(!handle.is_finished());
+    let start = Instant::now();
+    handle.cancel();
+    let result = handle.join();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(matches!(result, Err(Error::Runtime(VmError::Cancelled { pc: 0 }))), "{:?}", result);
+}
+
+#[test]
+fn spawned_run_with_token_completes_uncancelled() {
+    use c4_rust_AlRafaah::driver::{compile_source, spawn_run};
+    use c4_rust_AlRafaah::vm::{CancelToken, VmConfig};
+    let chunk = compile_source("int main() { return 6 * 7; }").unwrap();
+    let config = VmConfig { cancel_token: Some(CancelToken::new()), ..VmConfig::default() };
+    let outcome = spawn_run(chunk, config).join().unwrap();
+    assert_eq!(outcome.exit_code, 42);
+}
+
+/// Program clock that moves 250 ms forward every time it is read.
+struct SteppingClock {
+    reads: std::sync::atomic::AtomicU64,
+}
+
+impl c4_rust_AlRafaah::vm::ProgramClock for SteppingClock {
+    fn now(&self) -> Duration {
+        let n = self.reads.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Duration::from_millis(250 * n)
+    }
+
+    fn unix_time(&self) -> i64 {
+        1_700_000_000
+    }
+}
+
+#[test]
+fn clock_and_time_use_the_configured_clock() {
+    use c4_rust_AlRafaah::run_source_with;
+    use c4_rust_AlRafaah::vm::{FrozenClock, SharedClock, VmConfig};
+    let stepping = SharedClock::new(SteppingClock { reads: Default::default() });
+    let config = VmConfig { clock: Some(stepping), ..VmConfig::default() };
+    let interval = "int main() { return 0 - clock() + clock(); }";
+    assert_eq!(run_source_with(interval, config.clone()).unwrap().exit_code, 250);
+    assert_eq!(run_source_with("int main() { return time(0); }", config).unwrap().exit_code, 1_700_000_000);
+
+    let frozen = VmConfig { clock: Some(SharedClock::new(FrozenClock)), ..VmConfig::default() };
+    assert_eq!(run_source_with("int main() { return clock() + time(0); }", frozen).unwrap().exit_code, 0);
+}
+
+#[test]
+fn host_clock_does_not_go_backwards() {
+    let interval = c4_rust_AlRafaah::run_source("int main() { return 0 - clock() + clock(); }").unwrap();
+    assert!(interval.exit_code >= 0);
+    let now = c4_rust_AlRafaah::run_source("int main() { return time(0); }").unwrap();
+    assert!(now.exit_code > 1_600_000_000, "{}", now.exit_code);
+}
+
+#[test]
+fn loaded_programs_run_in_isolation_after_faults() {
+    use c4_rust_AlRafaah::bytecode::{Chunk, OpCode};
+    use c4_rust_AlRafaah::driver::compile_source;
+    use c4_rust_AlRafaah::vm::{CancelToken, VmConfig, VmError, VM};
+    let mut vm = VM::with_config(VmConfig { stack_size: 64, ..VmConfig::default() });
+
+    // Leaves 99 in stack word 5, then divides by zero.
+    let mut trap = Chunk::default();
+    trap.push_int(OpCode::LEA, 5);
+    trap.push(OpCode::PSH);
+    trap.push_int(OpCode::IMM, 99);
+    trap.push(OpCode::SI);
+    trap.push(OpCode::PSH);
+    trap.push_int(OpCode::IMM, 0);
+    trap.push(OpCode::DIV);
+    // Returns stack word 5 plus its first argument.
+    let mut peek = Chunk::default();
+    peek.push_int(OpCode::LEA, 5);
+    peek.push(OpCode::LI);
+    peek.push(OpCode::PSH);
+    peek.push_int(OpCode::LEA, 0);
+    peek.push(OpCode::LI);
+    peek.push(OpCode::ADD);
+    peek.push(OpCode::EXIT);
+    let mut spin = Chunk::default();
+    spin.push_jump(OpCode::JMP, 0);
+
+    let trap = vm.load(trap).unwrap();
+    let peek = vm.load(peek).unwrap();
+    let spin = vm.load(spin).unwrap();
+    let chunk = compile_source("int main() { return 6 * 7; }").unwrap();
+    let fresh = VM::new().run_collecting(&chunk).unwrap();
+    let clean = vm.load(chunk).unwrap();
+
+    assert_eq!(vm.run_program(trap, &[]), Err(VmError::DivisionByZero { pc: 6 }));
+    assert_eq!(vm.run_program(peek, &[3]).unwrap().exit_code, 3);
+
+    let token = CancelToken::new();
+    token.cancel();
+    vm.set_cancel_token(Some(token));
+    assert_eq!(vm.run_program(spin, &[]), Err(VmError::Cancelled { pc: 0 }));
+    vm.set_cancel_token(None);
+    let outcome = vm.run_program(clean, &[]).unwrap();
+    assert_eq!((outcome.exit_code, outcome.instructions), (42, fresh.instructions));
+
+    assert!(vm.unload(trap).is_some());
+    assert!(vm.unload(trap).is_none());
+    assert_ne!(vm.load(Chunk::default()).unwrap(), trap, "ids are not reused");
+    assert_eq!(vm.run_program(peek, &[4]).unwrap().exit_code, 4);
+    assert_eq!(vm.run_program(peek, &[0; 65]), Err(VmError::StackOverflow { pc: 0 }));
+
+    // Data has to fit in the 64 words the stack has.
+    let big = Chunk { data: vec![0; 65], ..Chunk::default() };
+    assert_eq!(vm.load(big), Err(VmError::DataTooLarge { words: 65, limit: 64 }));
+    let mut fits = Chunk { data: vec![0; 64], ..Chunk::default() };
+    fits.push_int(OpCode::IMM, 1);
+    fits.push(OpCode::EXIT);
+    let fits = vm.load(fits).unwrap();
+    assert_eq!(vm.run_program(fits, &[]).unwrap().exit_code, 1);
+}
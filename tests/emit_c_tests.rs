@@ -0,0 +1,105 @@
+// tests/emit_c_tests.rs
+//
+// Programs translated by `emit_c` are built with the system C compiler and
+// must behave like our own pipeline. Skipped with a message when `cc` is
+// unavailable.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use c4_rust_AlRafaah::emit_c::emit_c;
+use c4_rust_AlRafaah::interp::interpret;
+use c4_rust_AlRafaah::parser::Parser;
+use c4_rust_AlRafaah::run_source;
+
+/// Exit status and stdout of a finished program.
+type Run = (i32, String);
+
+fn emit(src: &str) -> String {
+    let ast = Parser::new(src).and_then(|mut p| p.parse_program()).unwrap();
+    emit_c(&ast).unwrap()
+}
+
+/// Build the C translation of `src` and run it, or None without `cc`.
+fn run_native(name: &str, src: &str) -> Option<Run> {
+    let dir = std::env::temp_dir().join(format!("c4_emit_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let c_file = dir.join(format!("{}.c", name));
+    let exe: PathBuf = dir.join(name);
+    fs::write(&c_file, emit(src)).unwrap();
+    let build = Command::new("cc").args(["-std=c99", "-fwrapv", "-w", "-o"]).arg(&exe).arg(&c_file).output().ok()?;
+    assert!(build.status.success(), "cc rejected {}:\n{}", name, String::from_utf8_lossy(&build.stderr));
+    let out = Command::new(&exe).output().unwrap();
+    Some((out.status.code().unwrap_or(-1), String::from_utf8_lossy(&out.stdout).into_owned()))
+}
+
+#[test]
+fn corpus_matches_vm() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut programs: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+    programs.sort();
+    for path in programs.iter().filter(|p| p.extension().is_some_and(|e| e == "c")) {
+        let src = fs::read_to_string(path).unwrap();
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        let Some(native) = run_native(name, &src) else {
+            eprintln!("cc not found; skipping emitted C tests");
+            return;
+        };
+        let vm = run_source(&src).unwrap();
+        let expected = ((vm.exit_code & 0xFF) as i32, String::from_utf8_lossy(&vm.stdout).into_owned());
+        assert_eq!(native, expected, "{}", path.display());
+    }
+}
+
+#[test]
+fn loops_calls_and_printf_match_interpreter() {
+    // Beyond what codegen handles today, so the interpreter is the oracle.
+    let src = "\
+enum { BASE = 3, STEP };
+int total;
+int sq(int x) { return x * x; }
+void add(int n) { total = total + n; }
+int main() {
+    int i;
+    i = 0;
+    while (i < 5) {
+        add(sq(i) + STEP);
+        if (i % 2) printf(\"odd %d\\n\", i); else printf(\"even %x\\n\", i + 250);
+        i++;
+    }
+    printf(\"%s %c %5d|%-3d|%%\\n\", \"done\", 'A' + 1, total, -BASE);
+    return total > 40 && !(total & 1) ? total : -1;
+}
+";
+    let Some(native) = run_native("mixed", src) else {
+        eprintln!("cc not found; skipping emitted C tests");
+        return;
+    };
+    let ast = Parser::new(src).and_then(|mut p| p.parse_program()).unwrap();
+    let oracle = interpret(&ast, &[]).unwrap();
+    assert_eq!(native, ((oracle.exit_code & 0xFF) as i32, String::from_utf8_lossy(&oracle.output).into_owned()));
+}
+
+#[test]
+fn names_are_prefixed_and_printf_formats_widened() {
+    let c = emit("int int_; int main() { printf(\"%d %c\", int_, 65); return int_; }");
+    assert!(c.contains("long long c4_int_;"), "{}", c);
+    assert!(c.contains("long long c4_main(void)"), "{}", c);
+    assert!(c.contains("printf(\"%lld %c\", (long long)c4_int_, (int)65LL)"), "{}", c);
+    assert!(c.contains("return (int)c4_main();"), "{}", c);
+}
+
+#[test]
+fn void_libc_calls_are_zero() {
+    let c = emit("int main() { char *p; p = malloc(4); free(p); srand(1); return 0; }");
+    assert!(c.contains("((long long)malloc(4LL))"), "{}", c);
+    assert!(c.contains("(free(c4_p), 0LL)"), "{}", c);
+    assert!(c.contains("(srand(1LL), 0LL)"), "{}", c);
+}
+
+#[test]
+fn program_without_main_is_an_error() {
+    let ast = Parser::new("int f() { return 1; }").and_then(|mut p| p.parse_program()).unwrap();
+    assert_eq!(emit_c(&ast).unwrap_err().to_string(), "program has no main function");
+}
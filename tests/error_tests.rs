@@ -0,0 +1,108 @@
+// tests/error_tests.rs
+
+use std::error::Error as _;
+use c4_rust_AlRafaah::bytecode::{Chunk, OpCode};
+use c4_rust_AlRafaah::error::{Error, LexError, ParseError};
+use c4_rust_AlRafaah::parser::Parser;
+use c4_rust_AlRafaah::vm::{VmError, VM};
+
+/// Helper: parse and compile `src`, returning the first error.
+fn build(src: &str) -> Result<Chunk, Error> {
+    let ast = Parser::new(src)?.parse_program()?;
+    let mut chunk = Chunk::default();
+    ast.compile(&mut chunk)?;
+    Ok(chunk)
+}
+
+#[test]
+fn lex_error_is_reported_as_lex_variant() {
+    match build("int main() { return 1 @ 2; }") {
+        Err(Error::Lex(LexError { msg, line, col })) => {
+            assert!(msg.contains('@'));
+            assert_eq!((line, col), (1, 23));
+        }
+        other => panic!("expected a lex error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn parse_error_carries_offset_of_bad_token() {
+    let src = "int main() { return 1 }";
+    match build(src) {
+        Err(Error::Parse(ParseError { message, offset, line, col })) => {
+            assert!(message.contains("Semicolon"), "{}", message);
+            assert_eq!(offset, src.find('}').unwrap());
+            assert_eq!((line, col), (1, offset + 1));
+        }
+        other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn parse_error_reports_its_line_and_column() {
+    let src = "int main() {\n    int x;\n    x = 1\n    return x;\n}\n";
+    let err = build(src).unwrap_err();
+    assert_eq!(err.to_string(), "parse error: expected Semicolon, got KwReturn at line 4, column 5");
+}
+
+#[test]
+fn errors_on_line_five_report_line_five_with_either_line_ending() {
+    let lines = ["int f(int a) {", "    return a;", "}", "int main() {", "    return f(1) +;", "}"];
+    for newline in ["\n", "\r\n"] {
+        let src = lines.join(newline);
+        match build(&src) {
+            Err(Error::Parse(ParseError { line, col, offset, .. })) => {
+                assert_eq!((line, col), (5, 18), "{:?}", newline);
+                assert_eq!(&src[offset..offset + 1], ";");
+            }
+            other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+        }
+
+        let src = src.replace("+;", "+ $;");
+        match build(&src) {
+            Err(Error::Lex(LexError { line, col, .. })) => assert_eq!((line, col), (5, 19), "{:?}", newline),
+            other => panic!("expected a lex error, got {:?}", other.map(|_| ())),
+        }
+    }
+}
+
+#[test]
+fn compile_error_names_the_construct() {
+    match build("int main() { return helper(); }") {
+        Err(Error::Compile(e)) => assert!(e.message.contains("helper")),
+        other => panic!("expected a compile error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn runtime_errors_carry_the_faulting_pc() {
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 1);
+    chunk.push(OpCode::ADD); // nothing to pop
+    assert_eq!(VM::new().run(&chunk), Err(VmError::StackUnderflow { pc: 1 }));
+
+    let mut chunk = Chunk::default();
+    chunk.push(OpCode::LEV); // return with no caller
+    assert_eq!(VM::new().run(&chunk), Err(VmError::CallStackUnderflow { pc: 0 }));
+
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, -8);
+    chunk.push(OpCode::LI);
+    assert_eq!(VM::new().run(&chunk), Err(VmError::InvalidAddress { pc: 1, addr: -8 }));
+}
+
+#[test]
+fn runtime_error_converts_into_crate_error() {
+    let mut chunk = Chunk::default();
+    chunk.push(OpCode::SUB);
+    let err: Error = VM::new().run(&chunk).unwrap_err().into();
+    assert!(matches!(err, Error::Runtime(VmError::StackUnderflow { pc: 0 })));
+    assert_eq!(err.to_string(), "runtime error: stack underflow at pc 0");
+    assert!(err.source().is_some());
+}
+
+#[test]
+fn io_error_converts_into_crate_error() {
+    let err: Error = std::fs::read_to_string("/nonexistent/prog.c").unwrap_err().into();
+    assert!(matches!(err, Error::Io(_)));
+}
@@ -0,0 +1,2 @@
+int sq(int x) { return x * x; }
+int main() { int i; i = 0; while (i < 3) i = i + 1; return sq(i); }
@@ -0,0 +1,175 @@
+// tests/format_tests.rs
+
+use std::fs;
+use std::path::Path;
+
+use c4_rust_AlRafaah::format::{format_source, BraceStyle, FormatOptions};
+use c4_rust_AlRafaah::lexer::{Lexer, Token};
+use c4_rust_AlRafaah::Error;
+
+const MESSY: &str = "\
+#include <stdio.h>
+// Leading comment.
+enum {RED,GREEN=5};
+int   count ,total;
+
+int add(int a,int b){return a+b;} // trailing
+
+int main(){
+  int x;char *p;
+  x=-1; // set
+  if(x<0)x=(int)p;else{x=x*2+sizeof(int);}
+
+
+  while(x>0)
+    // countdown
+    x--;
+  return add(x,1)?!x:~x;
+}
+";
+
+const TIDY: &str = "\
+#include <stdio.h>
+// Leading comment.
+enum { RED, GREEN = 5 };
+int count, total;
+
+int add(int a, int b) {
+    return a + b;
+} // trailing
+
+int main() {
+    int x;
+    char *p;
+    x = -1; // set
+    if (x < 0)
+        x = (int)p;
+    else {
+        x = x * 2 + sizeof(int);
+    }
+
+    while (x > 0)
+        // countdown
+        x--;
+    return add(x, 1) ? !x : ~x;
+}
+";
+
+fn fmt(src: &str) -> String {
+    format_source(src, &FormatOptions::default()).unwrap()
+}
+
+/// Every token, comments included, in order.
+fn tokens(src: &str) -> Vec<Token> {
+    let mut lex = Lexer::new(src).with_trivia();
+    let mut out = Vec::new();
+    loop {
+        match lex.next_token().unwrap() {
+            Token::Eof => return out,
+            t => out.push(t),
+        }
+    }
+}
+
+#[test]
+fn normalizes_spacing_indentation_and_braces() {
+    assert_eq!(fmt(MESSY), TIDY);
+}
+
+#[test]
+fn formatting_is_idempotent_over_corpus() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut sources = vec![MESSY.to_string()];
+    for dir in ["tests/corpus", "tests/goldens"] {
+        for entry in fs::read_dir(root.join(dir)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|e| e == "c") {
+                sources.push(fs::read_to_string(path).unwrap());
+            }
+        }
+    }
+    for src in &sources {
+        let once = fmt(src);
+        assert_eq!(fmt(&once), once, "not idempotent for:\n{}", src);
+        assert_eq!(tokens(&once), tokens(src), "tokens or comments changed for:\n{}", src);
+    }
+}
+
+#[test]
+fn dangling_else_stays_with_inner_if() {
+    let src = "int main() { if (1) if (2) return 1; else return 2; return 0; }";
+    assert_eq!(
+        fmt(src),
+        "int main() {\n    if (1)\n        if (2)\n            return 1;\n        else\n            return 2;\n    return 0;\n}\n"
+    );
+}
+
+#[test]
+fn for_headers_stay_on_one_line() {
+    let src = "int main() { int i; for(;;) return 1; for(i=0;i<3;i++){ i; } }";
+    assert_eq!(
+        fmt(src),
+        "int main() {\n    int i;\n    for (;;)\n        return 1;\n    for (i = 0; i < 3; i++) {\n        i;\n    }\n}\n"
+    );
+}
+
+#[test]
+fn do_while_keeps_its_condition_after_the_brace() {
+    let src = "int main() { int i; do { i++; } while(i<3); do i--; while(i); while(i);}";
+    assert_eq!(
+        fmt(src),
+        "int main() {\n    int i;\n    do {\n        i++;\n    } while (i < 3);\n    do\n        i--;\n    while (i);\n    while (i);\n}\n"
+    );
+}
+
+#[test]
+fn case_labels_line_up_with_their_switch() {
+    let src = "int main() { int n; switch(n){case 1: case -2: n++; break; default:{ n = 0; }} return n; }";
+    assert_eq!(
+        fmt(src),
+        "int main() {\n    int n;\n    switch (n) {\n    case 1:\n    case -2:\n        n++;\n        break;\n    default:\n        {\n            n = 0;\n        }\n    }\n    return n;\n}\n"
+    );
+}
+
+#[test]
+fn compound_assignments_are_spaced_like_assignment() {
+    assert_eq!(fmt("int main() { int x; x+=1; x<<=2; return x; }"), "int main() {\n    int x;\n    x += 1;\n    x <<= 2;\n    return x;\n}\n");
+}
+
+#[test]
+fn block_comments_stay_where_they_were() {
+    let src = "/* header\n   two lines */\nint main() { return /* zero */ 0; /* done */ }\n";
+    assert_eq!(fmt(src), "/* header\n   two lines */\nint main() {\n    return /* zero */ 0; /* done */\n}\n");
+}
+
+#[test]
+fn next_line_braces_and_indent_width() {
+    let opts = FormatOptions { indent_width: 2, brace_style: BraceStyle::NextLine, ..FormatOptions::default() };
+    let out = format_source("int main() { while (1) { return 0; } }", &opts).unwrap();
+    assert_eq!(out, "int main()\n{\n  while (1)\n  {\n    return 0;\n  }\n}\n");
+    assert_eq!(format_source(&out, &opts).unwrap(), out);
+}
+
+#[test]
+fn long_lines_wrap_at_outermost_commas_then_weakest_operators() {
+    let opts = FormatOptions { max_width: 40, ..FormatOptions::default() };
+    let call = "int main() { return f(alpha + 1, beta * 2, gamma - 3, delta); }";
+    assert_eq!(
+        format_source(call, &opts).unwrap(),
+        "int main() {\n    return f(alpha + 1,\n            beta * 2,\n            gamma - 3,\n            delta);\n}\n"
+    );
+
+    let chain = "int main() { return alpha * 2 + beta * 3 + gamma * 4 + delta; }";
+    let out = format_source(chain, &opts).unwrap();
+    assert_eq!(
+        out,
+        "int main() {\n    return alpha * 2 +\n            beta * 3 +\n            gamma * 4 +\n            delta;\n}\n"
+    );
+    assert_eq!(format_source(&out, &opts).unwrap(), out);
+}
+
+#[test]
+fn unparsable_input_is_an_error() {
+    assert!(matches!(format_source("int main( { return 1; }", &FormatOptions::default()), Err(Error::Parse(_))));
+    assert!(matches!(format_source("int main() { @ }", &FormatOptions::default()), Err(Error::Lex(_))));
+}
@@ -0,0 +1,111 @@
+// tests/fuzz_regressions.rs
+
+//! Replays inputs that once crashed (or would have crashed) the fuzz targets
+//! in `fuzz/`. Every phase must return an error instead of panicking.
+
+use std::fs;
+use std::path::Path;
+use c4_rust_AlRafaah::bytecode::{Chunk, Instruction, OpCode};
+use c4_rust_AlRafaah::lexer::Lexer;
+use c4_rust_AlRafaah::parser::Parser;
+use c4_rust_AlRafaah::vm::{Discard, VmConfig, VmError, VM};
+use c4_rust_AlRafaah::{compile_source, run_source_with, Error};
+
+/// The VM settings the `run` and `chunk` fuzz targets use.
+fn fuzz_config() -> VmConfig {
+    VmConfig { stack_size: 4096, max_steps: Some(10_000), ..VmConfig::default() }
+}
+
+/// Helper: run a loaded chunk the way the `chunk` fuzz target does, but
+/// without printing.
+fn run_chunk(chunk: &Chunk) {
+    let mut vm = VM::with_config(fuzz_config());
+    vm.set_output(Box::new(Discard));
+    let _ = vm.run(chunk);
+}
+
+/// Helper: run `src` the way the `run` fuzz target does.
+fn run(src: &str) -> Result<i64, Error> {
+    run_source_with(src, fuzz_config()).map(|o| o.exit_code)
+}
+
+#[test]
+fn regression_inputs_do_not_panic() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/regressions");
+    let mut replayed = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let bytes = fs::read(entry.unwrap().path()).unwrap();
+        let src = String::from_utf8_lossy(&bytes);
+        let _ = Lexer::new(&src).tokenize_all();
+        let _ = Parser::new(&src).and_then(|mut p| p.parse_program());
+        let _ = run(&src);
+        replayed += 1;
+    }
+    assert!(replayed > 0, "no regression inputs found");
+}
+
+#[test]
+fn deep_nesting_is_a_parse_error() {
+    for src in [
+        format!("int main() {{ return {}1{}; }}", "(".repeat(500), ")".repeat(500)),
+        format!("int main() {{ return {}1; }}", "!".repeat(500)),
+        format!("int main() {{ {} }}", "{".repeat(500)),
+        format!("int main() {{ return 1{}; }}", "+1".repeat(500)),
+    ] {
+        assert!(matches!(run(&src), Err(Error::Parse(_))), "{}", &src[..40]);
+    }
+}
+
+#[test]
+fn runtime_traps_are_errors() {
+    assert!(matches!(run("int main() { return main(); }"), Err(Error::Runtime(VmError::StackOverflow { .. }))));
+    assert!(matches!(run("int main() { return 7 / 0; }"), Err(Error::Runtime(VmError::DivisionByZero { .. }))));
+    assert!(matches!(run("int main() { return 7 % 0; }"), Err(Error::Runtime(VmError::DivisionByZero { .. }))));
+    assert_eq!(run("int main() { return 1 << 65; }").unwrap(), 2); // shift count wraps
+}
+
+#[test]
+fn printf_widths_are_capped() {
+    // Widths that overflow usize, or that would pad out gigabytes.
+    for spec in ["%99999999999999999999d", "%999999999999d", "%.99999999999999999999s", "%4097c"] {
+        let src = format!("int main() {{ printf(\"{}\", 65); return 0; }}", spec);
+        assert!(matches!(run(&src), Err(Error::Runtime(VmError::FormatTooWide { .. }))), "{}", spec);
+    }
+    assert_eq!(run("int main() { return printf(\"%4096c\", 65); }").unwrap(), 4096);
+}
+
+#[test]
+fn tokenize_all_stops_before_eof() {
+    let tokens = Lexer::new("int x;").tokenize_all().unwrap();
+    assert_eq!(tokens.len(), 3);
+}
+
+#[test]
+fn runs_are_bounded_by_fuel() {
+    assert!(matches!(run("int main() { while (1); }"), Err(Error::Runtime(VmError::OutOfFuel { .. }))));
+}
+
+#[test]
+fn malformed_chunks_are_errors() {
+    let bytes = compile_source("int g; int main() { g = 2; printf(\"%d\", g); return g; }").unwrap().serialize();
+    // Every truncation, and every single-byte corruption, fails cleanly or
+    // loads a chunk that runs without panicking.
+    for len in 0..bytes.len() {
+        assert!(Chunk::deserialize(&bytes[..len]).is_err(), "truncated to {}", len);
+    }
+    for i in 0..bytes.len() {
+        for byte in [0x00, 0x7f, 0xff] {
+            let mut corrupt = bytes.clone();
+            corrupt[i] = byte;
+            if let Ok(chunk) = Chunk::deserialize(&corrupt) {
+                run_chunk(&chunk);
+            }
+        }
+    }
+
+    // Jumps and calls past the end of the code.
+    let mut chunk = Chunk::default();
+    chunk.code.push(Instruction::Jump(OpCode::JMP, usize::MAX));
+    chunk.code.push(Instruction::Call(OpCode::JSR, 1 << 40));
+    run_chunk(&chunk);
+}
@@ -0,0 +1,56 @@
+// tests/golden_tests.rs
+//
+// Compares `compile_to_asm` for every `tests/goldens/*.c` against the
+// `.asm` file next to it. Run with `UPDATE_GOLDENS=1` to rewrite the
+// expectations after an intended codegen change, then review the diff.
+
+use std::fs;
+use std::path::Path;
+
+use c4_rust_AlRafaah::compile_to_asm;
+
+#[test]
+fn listings_match_goldens() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/goldens");
+    let update = std::env::var_os("UPDATE_GOLDENS").is_some();
+    let mut sources: Vec<_> = fs::read_dir(&dir)
+        .expect("goldens directory missing")
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty(), "no goldens");
+
+    let mut stale = Vec::new();
+    for src in &sources {
+        let asm = compile_to_asm(&fs::read_to_string(src).unwrap())
+            .unwrap_or_else(|e| panic!("{}: {}", src.display(), e));
+        let golden = src.with_extension("asm");
+        if update {
+            fs::write(&golden, &asm).unwrap();
+        } else if fs::read_to_string(&golden).ok().as_deref() != Some(asm.as_str()) {
+            stale.push(format!("{}:\n{}", golden.display(), asm));
+        }
+    }
+    assert!(stale.is_empty(), "listings differ from goldens (UPDATE_GOLDENS=1 to accept):\n{}", stale.join("\n"));
+}
+
+#[test]
+fn listing_is_deterministic() {
+    let src = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/goldens/two_functions.c")).unwrap();
+    let first = compile_to_asm(&src).unwrap();
+    for _ in 0..10 {
+        assert_eq!(compile_to_asm(&src).unwrap(), first);
+    }
+}
+
+#[test]
+fn labels_are_numbered_in_address_order() {
+    let asm = compile_to_asm("int main() { return 0; }").unwrap();
+    assert_eq!(asm, "    JSR L0\n    EXIT\nL0:\n    ENT 0\n    IMM 0\n    LEV\n    LEV\n");
+}
+
+#[test]
+fn errors_pass_through() {
+    assert!(compile_to_asm("int main() { return 1 }").is_err());
+}
@@ -0,0 +1,25 @@
+    JSR L0
+    EXIT
+L0:
+    ENT 0
+    IMM 2
+    PSH
+    IMM 3
+    PSH
+    IMM 4
+    MUL
+    ADD
+    PSH
+    IMM 10
+    PSH
+    IMM 2
+    DIV
+    SUB
+    PSH
+    IMM 17
+    PSH
+    IMM 5
+    MOD
+    ADD
+    LEV
+    LEV
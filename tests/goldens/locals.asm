@@ -0,0 +1,16 @@
+    JSR L0
+    EXIT
+L0:
+    ENT 3
+    IMM 10
+    PSH
+    IMM 3
+    DIV
+    PSH
+    IMM 10
+    PSH
+    IMM 3
+    MOD
+    SUB
+    LEV
+    LEV
@@ -0,0 +1,5 @@
+int main() {
+    int a, b;
+    int c;
+    return 10 / 3 - 10 % 3;
+}
@@ -0,0 +1,20 @@
+    JSR L0
+    EXIT
+L0:
+    ENT 0
+    IMM 2
+    PSH
+    IMM 3
+    PSH
+    IMM 4
+    MUL
+    ADD
+    LEV
+    LEV
+    ENT 0
+    IMM 1
+    PSH
+    IMM 4
+    SHL
+    LEV
+    LEV
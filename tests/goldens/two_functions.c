@@ -0,0 +1,7 @@
+int main() {
+    return 2 + 3 * 4;
+}
+
+int unused() {
+    return 1 << 4;
+}
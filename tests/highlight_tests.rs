@@ -0,0 +1,92 @@
+// tests/highlight_tests.rs
+
+use c4_rust_AlRafaah::classify;
+use c4_rust_AlRafaah::highlight::TokenClass::{self, *};
+use c4_rust_AlRafaah::lexer::{Lexer, Token};
+
+const SAMPLE: &str = "\
+#include <stdio.h>
+enum { RED, GREEN };
+int count;
+
+int add(int a, int b) { return a + b; }
+
+int main() {
+    int add;
+    // add = add(1, 2);
+    add = 'x' + GREEN;
+    return add + count + sizeof(int) + \"s\"[0];
+}
+";
+
+/// The class of each occurrence of `text`, in order.
+fn classes_of(src: &str, text: &str) -> Vec<TokenClass> {
+    classify(src)
+        .into_iter()
+        .filter(|(span, _)| &src[span.start..span.end] == text)
+        .map(|(_, class)| class)
+        .collect()
+}
+
+#[test]
+fn classifies_sample_program() {
+    assert_eq!(classes_of(SAMPLE, "RED"), [EnumConstant]);
+    assert_eq!(classes_of(SAMPLE, "GREEN"), [EnumConstant, EnumConstant]);
+    assert_eq!(classes_of(SAMPLE, "count"), [Variable, Variable]);
+    assert_eq!(classes_of(SAMPLE, "a"), [Parameter, Parameter]);
+    assert_eq!(classes_of(SAMPLE, "main"), [Function]);
+    assert_eq!(classes_of(SAMPLE, "int"), [Type; 7]);
+    assert_eq!(classes_of(SAMPLE, "return"), [Keyword, Keyword]);
+    assert_eq!(classes_of(SAMPLE, "'x'"), [Char]);
+    assert_eq!(classes_of(SAMPLE, "\"s\""), [String]);
+    assert_eq!(classes_of(SAMPLE, "0"), [Number]);
+    assert_eq!(classes_of(SAMPLE, "+"), [Operator; 5]);
+}
+
+#[test]
+fn shadowing_local_is_a_variable() {
+    // The definition of `add` is a function; inside `main` the local
+    // `add` shadows it.
+    assert_eq!(classes_of(SAMPLE, "add"), [Function, Variable, Variable, Variable]);
+}
+
+#[test]
+fn commented_out_code_is_one_comment() {
+    assert_eq!(classes_of(SAMPLE, "// add = add(1, 2);"), [Comment]);
+    assert_eq!(classes_of(SAMPLE, "#include <stdio.h>"), [Comment]);
+}
+
+#[test]
+fn spans_cover_the_file_without_overlapping() {
+    for src in [SAMPLE, "int main() { return @ 1; }", "int main( { \"unterminated"] {
+        let spans = classify(src);
+        let mut covered = vec![false; src.len()];
+        let mut last_end = 0;
+        for (span, _) in &spans {
+            assert!(span.start >= last_end && span.start < span.end, "{:?} in {:?}", span, src);
+            last_end = span.end;
+            covered[span.start..span.end].iter_mut().for_each(|c| *c = true);
+        }
+        for (i, b) in src.bytes().enumerate() {
+            assert!(covered[i] || b.is_ascii_whitespace(), "byte {} of {:?} not covered", i, src);
+        }
+    }
+}
+
+#[test]
+fn unparsable_input_falls_back_to_lexical_classes() {
+    let src = "int main( { return x; }";
+    assert_eq!(classes_of(src, "main"), [Identifier]);
+    assert_eq!(classes_of(src, "x"), [Identifier]);
+    assert_eq!(classes_of(src, "return"), [Keyword]);
+    assert_eq!(classes_of("int main() { return 1 @ 2; }", "@"), [Unknown]);
+}
+
+#[test]
+fn trivia_mode_yields_comment_tokens() {
+    let mut lex = Lexer::new("1 // one\n2").with_trivia();
+    assert_eq!(lex.next_token().unwrap(), Token::Num(1));
+    assert_eq!(lex.next_token().unwrap(), Token::Comment("// one".into()));
+    assert_eq!(lex.next_token().unwrap(), Token::Num(2));
+    assert_eq!(Lexer::new("1 // one\n2").tokenize_all().unwrap(), [Token::Num(1), Token::Num(2)]);
+}
@@ -0,0 +1,235 @@
+// tests/interp_tests.rs
+
+use std::fs;
+use std::path::Path;
+use c4_rust_AlRafaah::interp::{interpret, InterpError};
+use c4_rust_AlRafaah::bytecode::Chunk;
+use c4_rust_AlRafaah::parser::Parser;
+use c4_rust_AlRafaah::run_source;
+use c4_rust_AlRafaah::vm::VM;
+
+/// Helper: interpret `src` with no arguments.
+fn interp(src: &str) -> Result<(i64, String), InterpError> {
+    let ast = Parser::new(src)
+        .and_then(|mut p| p.parse_program())
+        .expect("parsing failed");
+    interpret(&ast, &[]).map(|o| (o.exit_code, String::from_utf8_lossy(&o.output).into_owned()))
+}
+
+/// Run `src` through both the interpreter and compile+VM, and require the
+/// same exit code and output.
+fn assert_same_result(src: &str) {
+    let vm = run_source(src).unwrap_or_else(|e| panic!("pipeline failed: {}\n{}", e, src));
+    let ast = Parser::new(src).and_then(|mut p| p.parse_program()).unwrap();
+    let oracle = interpret(&ast, &[]).unwrap_or_else(|e| panic!("interpreter failed: {}\n{}", e, src));
+    assert_eq!(
+        (vm.exit_code, String::from_utf8_lossy(&vm.stdout)),
+        (oracle.exit_code, String::from_utf8_lossy(&oracle.output)),
+        "VM (left) and interpreter (right) disagree on:\n{}",
+        src
+    );
+}
+
+#[test]
+fn corpus_agrees_with_interpreter() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut checked = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|e| e == "c") {
+            assert_same_result(&fs::read_to_string(&path).unwrap());
+            checked += 1;
+        }
+    }
+    assert!(checked > 0, "corpus is empty");
+}
+
+#[test]
+fn expressions_agree_with_interpreter() {
+    for e in ["1 + 2 * 3", "(7 - 10) / 2", "(0 - 7) % 3", "1 << 4 >> 2", "5 & 3 | 8 ^ 2", "3 <= 3 == 1"] {
+        assert_same_result(&format!("int main() {{ return {}; }}", e));
+    }
+}
+
+#[test]
+fn casts_agree_with_interpreter() {
+    for e in ["(char)256", "(char)65", "(char)(0 - 1)", "(int)300", "(char)(int)321"] {
+        assert_same_result(&format!("int main() {{ return {}; }}", e));
+    }
+}
+
+#[test]
+fn locals_loops_and_calls() {
+    let src = "
+        int sq(int x) { return x * x; }
+        int main() {
+            int i, sum;
+            i = 0; sum = 0;
+            while (i < 4) { sum = sum + sq(i); i++; }
+            return sum;
+        }";
+    assert_eq!(interp(src).unwrap().0, 14);
+}
+
+#[test]
+fn for_loops_nest_and_break_out_by_returning() {
+    let src = "
+        int main() {
+            int i, j, n;
+            n = 0;
+            for (i = 0; i < 10; i++)
+                for (j = i; j < 10; j++) n++;
+            for (;;) { if (n > 60) return n; n = n + 1; }
+        }";
+    assert_eq!(interp(src).unwrap().0, 61);
+}
+
+#[test]
+fn do_while_tests_after_the_body() {
+    let src = "int main() { int n; n = 0; do n = n + 5; while (0); do { n++; } while (n < 9); return n; }";
+    assert_eq!(interp(src).unwrap().0, 9);
+}
+
+#[test]
+fn break_and_continue_act_on_the_innermost_loop() {
+    let src = "
+        int main() {
+            int i, j, n;
+            i = 0;
+            while (1) { if (i == 5) break; i++; }
+            for (j = 0; j < 10; j++) { if (j % 2) continue; n = n + j; }
+            for (i = 0; i < 3; i++)
+                while (1) { n = n + 100; break; }
+            do { n++; continue; n = 0; } while (n < 325);
+            return n + i;
+        }";
+    // (0 + 2 + 4 + 6 + 8) + 300, counted up to 325, plus i = 3.
+    assert_eq!(interp(src).unwrap().0, 328);
+    let err = interp("int main() { break; return 0; }").unwrap_err();
+    assert_eq!(err.to_string(), "break outside a loop or switch");
+}
+
+#[test]
+fn switch_falls_through_until_break() {
+    let src = "
+        int classify(int x) {
+            int n;
+            switch (x) {
+                case 1: n = n + 1;
+                case 2: n = n + 10; break;
+                case -3: return 7;
+                default: n = 100;
+                case 4: n = n + 1000;
+            }
+            return n;
+        }
+        int main() { return classify(1) + classify(2) + classify(-3) + classify(9) + classify(4); }";
+    assert_eq!(interp(src).unwrap().0, 11 + 10 + 7 + 1100 + 1000);
+}
+
+#[test]
+fn compound_assignment_through_a_pointer_evaluates_it_once() {
+    let src = "
+        int g, calls;
+        int *slot() { calls++; return &g; }
+        int main() {
+            int n;
+            g = 3;
+            *slot() += 4;
+            *slot() <<= 2;
+            n = (*slot() -= 8);
+            return calls * 100 + n;
+        }";
+    assert_eq!(interp(src).unwrap().0, 3 * 100 + 20);
+}
+
+#[test]
+fn globals_enums_pointers_and_short_circuit() {
+    let src = "
+        enum { A, B = 5, C };
+        int g;
+        int bump() { g = g + 1; return 1; }
+        int main() {
+            int *p;
+            p = &g;
+            *p = C;
+            if (0 && bump()) return 99;
+            if (1 || bump()) g = g * 2;
+            return g ? g : -1;
+        }";
+    assert_eq!(interp(src).unwrap().0, 12);
+}
+
+#[test]
+fn rand_agrees_with_interpreter() {
+    let src = "int main() { srand(42); rand(); rand(); return rand(); }";
+    assert_same_result(src);
+    assert_eq!(interp(src).unwrap().0, 886563538);
+    assert_same_result("int main() { srand(7); return rand() % 6 + 1; }");
+}
+
+#[test]
+fn printf_writes_formatted_output() {
+    let src = r#"int main() { printf("%d-%c-%s|%3d|%-3d|%x%%", 42, 65, "hi", 7, 7, 255); return 0; }"#;
+    assert_eq!(interp(src).unwrap().1, "42-A-hi|  7|7  |ff%");
+    assert_eq!(interp(r#"int main() { printf("[%05d]", -42); return 0; }"#).unwrap().1, "[-0042]");
+}
+
+#[test]
+fn printf_widths_agree_with_interpreter() {
+    for call in [
+        r#""%3d|%-3d|%03d", 7, 7, 7"#,
+        r#""%5s|%-5s|%.1s", "ab", "ab", "abcdef""#,
+        r#""%8.4s|%o|%i", "abcdef", 8, -3"#,
+        r#""%x%%%", 255"#,
+        r#""[%05d]|[%05i]|[%-5d]|[%5d]", -42, -7, -42, -42"#,
+    ] {
+        assert_same_result(&format!("int main() {{ return printf({}); }}", call));
+    }
+}
+
+#[test]
+fn errors_are_reported() {
+    assert_eq!(interp("int main() { return 1 / 0; }"), Err(InterpError::DivisionByZero));
+    assert_eq!(
+        interp("int main() { return nope(); }"),
+        Err(InterpError::UnknownFunction("nope".into()))
+    );
+    assert_eq!(interp("int main() { return exit(3); }").unwrap().0, 3);
+    assert_eq!(interp(r#"int main() { return printf("%99999999999999999999d", 1); }"#), Err(InterpError::FormatTooWide));
+}
+
+#[test]
+fn malloc_gives_fresh_zeroed_cells() {
+    let src = "int main() { int *a, *b; a = malloc(3); b = malloc(2); a[2] = 5; free(a); return b - a + a[2] + b[0]; }";
+    assert_eq!(interp(src).unwrap().0, 8);
+    assert_same_result("int main() { char *s; s = malloc(3); s[0] = 'h'; s[1] = 'i'; return printf(\"%s\", s); }");
+    assert_eq!(interp("int main() { return malloc(-1); }").unwrap().0, 0);
+}
+
+#[test]
+fn printf_writes_to_the_programs_output() {
+    let print = |src: &str| {
+        let outcome = run_source(src).unwrap();
+        (String::from_utf8(outcome.stdout).unwrap(), outcome.exit_code)
+    };
+    assert_eq!(print(r#"int main(){ printf("%d %s\n", 42, "hi"); return 0; }"#), ("42 hi\n".to_string(), 0));
+    // It returns the number of bytes written.
+    assert_eq!(print(r#"int main(){ return printf("%c%x|%%|%d", 65, 255, -7); }"#), ("Aff|%|-7".to_string(), 8));
+    // Unknown conversions are printed as written.
+    assert_eq!(print(r#"int main(){ return printf("%q"); }"#), ("%q".to_string(), 2));
+    assert!(run_source(r#"int main(){ return printf(); }"#).is_err());
+
+    assert_same_result(r#"int main(){ int n; n = printf("%d-%c-%s\n", 7, 122, "str"); return n; }"#);
+}
+
+#[test]
+fn string_literals_survive_a_serialized_chunk() {
+    let src = r#"int main(){ printf("%s, %s!\n", "hello", "world"); printf("%s\n", "hello"); return 0; }"#;
+    let chunk = c4_rust_AlRafaah::compile_source(src).unwrap();
+    assert_eq!(chunk.strings.len(), 4);
+    let chunk = Chunk::deserialize(&chunk.serialize()).unwrap();
+    let outcome = VM::new().run_collecting(&chunk).unwrap();
+    assert_eq!(String::from_utf8(outcome.stdout).unwrap(), "hello, world!\nhello\n");
+    assert_same_result(src);
+}
@@ -0,0 +1,94 @@
+// tests/json_tests.rs
+
+use std::fs;
+use std::path::Path;
+use c4_rust_AlRafaah::bytecode::Chunk;
+use c4_rust_AlRafaah::error::Error;
+use c4_rust_AlRafaah::json::{program_from_json, to_json};
+use c4_rust_AlRafaah::parser::Parser;
+use c4_rust_AlRafaah::vm::VM;
+
+#[test]
+fn corpus_round_trips_through_json() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut checked = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "c") {
+            continue;
+        }
+        let src = fs::read_to_string(&path).unwrap();
+        let parsed = Parser::new(&src).and_then(|mut p| p.parse_program()).unwrap();
+        let rebuilt = program_from_json(&to_json(&parsed))
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert_eq!(rebuilt, parsed, "{}", path.display());
+
+        let run = |program: &c4_rust_AlRafaah::ast::Program| {
+            let mut chunk = Chunk::default();
+            program.compile(&mut chunk).unwrap();
+            let outcome = VM::new().run_collecting(&chunk).unwrap();
+            (outcome.exit_code, outcome.stdout)
+        };
+        assert_eq!(run(&rebuilt), run(&parsed), "{}", path.display());
+        checked += 1;
+    }
+    assert!(checked > 0, "corpus is empty");
+}
+
+#[test]
+fn format_is_externally_tagged() {
+    let ast = Parser::new("int main() { return 5; }").unwrap().parse_program().unwrap();
+    let json = to_json(&ast);
+    assert!(json.contains("\"Function\""), "{}", json);
+    assert!(json.contains("\"Num\": 5"), "{}", json);
+    assert!(json.contains("\"ret\": \"Int\""), "{}", json);
+}
+
+#[test]
+fn well_typed_json_with_a_semantic_error_is_rejected() {
+    // Indexing a number: fine for serde, nonsense for the compiler.
+    let json = r#"{"items": [{"Function": {
+        "ret": "Int", "name": "main", "params": [], "locals": [],
+        "body": {"stmts": [{"Return": {"Index": {"array": {"Num": 1}, "index": {"Num": 0}}}}]}
+    }}]}"#;
+    match program_from_json(json) {
+        Err(Error::Compile(e)) => assert!(e.message.contains("pointer type expected"), "{}", e),
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+
+    let undefined = json.replace(r#"{"Num": 1}"#, r#"{"Var": "nowhere"}"#);
+    match program_from_json(&undefined) {
+        Err(Error::Compile(e)) => assert!(e.message.contains("undefined variable: nowhere"), "{}", e),
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+}
+
+#[test]
+fn malformed_json_is_a_parse_error_at_its_offset() {
+    let json = "{\"items\": [\n  {\"Function\": 7}\n]}";
+    match program_from_json(json) {
+        Err(Error::Parse(e)) => {
+            assert_eq!(e.line, 2, "{}", e);
+            assert!(!e.message.contains("line"), "{}", e);
+            assert!(e.offset > json.find('\n').unwrap(), "offset {} is on line 1", e.offset);
+        }
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+    assert!(matches!(program_from_json("{\"items\": ["), Err(Error::Parse(_))));
+}
+
+#[test]
+fn checker_rejects_what_parsed_code_cannot_do_either() {
+    // The same checks run on parsed code, inside `Program::compile`.
+    for (src, message) in [
+        ("int main() { return x; }", "undefined variable: x"),
+        ("enum { A }; int main() { A = 1; return 0; }", "bad lvalue in assignment"),
+        ("int main() { int x; return *x; }", "bad dereference"),
+        ("int f(int a) { return a; } int main() { return f(); }", "f expects 1 arguments, got 0"),
+        ("int x; int x; int main() { return 0; }", "duplicate global definition: x"),
+    ] {
+        let ast = Parser::new(src).unwrap().parse_program().unwrap();
+        let err = ast.compile(&mut Chunk::default()).unwrap_err();
+        assert!(err.to_string().contains(message), "{}: {}", src, err);
+    }
+}
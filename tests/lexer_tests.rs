@@ -26,8 +26,9 @@ fn lex_identifiers_and_keywords() {
     expect_tokens!("foo _bar2", Token::Ident("foo".into()), Token::Ident("_bar2".into()));
     // Test lexing of keywords.
     expect_tokens!(
-        "if else while return sizeof char enum int",
-        Token::KwIf, Token::KwElse, Token::KwWhile,
+        "if else while do for break continue switch case default return sizeof char enum int",
+        Token::KwIf, Token::KwElse, Token::KwWhile, Token::KwDo, Token::KwFor,
+        Token::KwBreak, Token::KwContinue, Token::KwSwitch, Token::KwCase, Token::KwDefault,
         Token::KwReturn, Token::KwSizeof,
         Token::KwChar, Token::KwEnum, Token::KwInt
     );
@@ -59,6 +60,18 @@ fn lex_assign_and_bitwise() {
     expect_tokens!("= & | ^", Token::Assign, Token::And, Token::Or, Token::Xor);
 }
 
+#[test]
+fn lex_compound_assignments() {
+    expect_tokens!(
+        "+= -= *= /= %= &= |= ^= <<= >>=",
+        Token::PlusAssign, Token::MinusAssign, Token::StarAssign, Token::SlashAssign, Token::PercentAssign,
+        Token::AndAssign, Token::OrAssign, Token::XorAssign, Token::ShlAssign, Token::ShrAssign
+    );
+    // Longest match: `<<=` is one token, `< <=` two.
+    expect_tokens!("a<<=b", Token::Ident("a".into()), Token::ShlAssign, Token::Ident("b".into()));
+    expect_tokens!("< <=", Token::Lt, Token::Le);
+}
+
 #[test]
 fn lex_comparisons() {
     // Test lexing of comparison operators.
@@ -86,6 +99,45 @@ fn lex_string_literal() {
     assert_eq!(lx.next_token().unwrap(), Token::Eof);
 }
 
+#[test]
+fn lex_escape_sequences() {
+    expect_tokens!(
+        r#""\t\r\0|\\\"\'\?\a\b\f\v|\x41\x7e\101\7""#,
+        Token::Str("\t\r\0|\\\"'?\x07\x08\x0C\x0B|A~A\x07".into())
+    );
+    expect_tokens!(
+        r"'\t' '\0' '\\' '\'' '\x41' '\xff' '\377' '\12'",
+        Token::Num(9), Token::Num(0), Token::Num('\\' as i64), Token::Num('\'' as i64),
+        Token::Num(0x41), Token::Num(0xFF), Token::Num(0o377), Token::Num(10)
+    );
+    // An escape ends where its digits do.
+    expect_tokens!(r#""\x41g\1018""#, Token::Str("AgA8".into()));
+}
+
+#[test]
+fn error_bad_escape_sequences() {
+    for (src, msg) in [
+        (r#""\q""#, "unknown escape sequence \\q"),
+        (r"'\q'", "unknown escape sequence \\q"),
+        (r#""\x""#, "\\x used with no following hex digits"),
+        (r"'\xg'", "\\x used with no following hex digits"),
+        (r"'\x100'", "hex escape \\x100 is out of range"),
+        (r#""\777""#, "octal escape \\777 is out of range"),
+        ("'\\", "unterminated escape sequence"),
+    ] {
+        let err = Lexer::new(src).next_token().unwrap_err();
+        assert_eq!(err.msg, msg, "{}", src);
+    }
+}
+
+#[test]
+fn lex_strings_hold_any_byte() {
+    // Escapes reach every byte value, and other characters are UTF-8.
+    expect_tokens!(r#""\xff\377\x80\200""#, Token::Str(vec![0xFF, 0xFF, 0x80, 0x80]));
+    expect_tokens!("\"é\"", Token::Str("é".into()));
+    expect_tokens!(r"'\xff' '\377'", Token::Num(0xFF), Token::Num(0xFF));
+}
+
 #[test]
 fn lex_char_literal() {
     // Character literals are folded into Num(i64).
@@ -105,12 +157,37 @@ fn skip_comments_and_whitespace() {
     assert_eq!(lx.next_token().unwrap(), Token::Eof);
 }
 
+#[test]
+fn skip_block_comments() {
+    // On one line, between tokens and inside an expression.
+    expect_tokens!("/* lead */ 1 /**/+/* x * y / z */2", Token::Num(1), Token::Plus, Token::Num(2));
+    // Spanning lines, with a `*` just before the close.
+    expect_tokens!("a /* one\n * two\n **/ b", Token::Ident("a".into()), Token::Ident("b".into()));
+    // The first `*/` ends it; a `/` after that is division again.
+    expect_tokens!("/* /* */ 4 / 2", Token::Num(4), Token::Slash, Token::Num(2));
+    // Last in the file, with or without a newline after it.
+    expect_tokens!("x /* trailing */", Token::Ident("x".into()));
+    expect_tokens!("x/**/\n/* a\n b */\n", Token::Ident("x".into()));
+    // Nothing but a comment.
+    expect_tokens!("/* only */", Token::Eof);
+}
+
+#[test]
+fn error_unterminated_block_comment() {
+    let mut lx = Lexer::new("1 /* never\n closed *");
+    assert_eq!(lx.next_token().unwrap(), Token::Num(1));
+    let err = lx.next_token().unwrap_err();
+    assert_eq!(err.msg, "unterminated block comment");
+    assert_eq!((err.line, err.col), (1, 3));
+    assert_eq!(lx.token_start(), 2);
+}
+
 #[test]
 fn error_unexpected_character() {
     // Test error handling for unexpected characters.
     let mut lx = Lexer::new("@");
     match lx.next_token() {
-        Err(LexError(msg)) => assert!(msg.contains("@")), // Ensure error mentions the character.
+        Err(LexError { msg, .. }) => assert!(msg.contains("@")), // Ensure error mentions the character.
         Ok(tok) => panic!("Expected error, got {:?}", tok), // Fail if no error.
     }
 }
@@ -123,13 +200,40 @@ fn lex_octal_numbers() {
     expect_tokens!("0 0755", Token::Num(0), Token::Num(0o755));
 }
 
-// Current lexer doesn’t strip “0x”/“0X”, so hex should error.
 #[test]
-fn error_hex_numbers() {
-    let mut lx1 = Lexer::new("0x1A3F");
-    assert!(lx1.next_token().is_err());
-    let mut lx2 = Lexer::new("0XdeadBEEF");
-    assert!(lx2.next_token().is_err());
+fn errors_report_line_and_column() {
+    // Comments and blank lines count; columns are in characters.
+    let err = Lexer::new("int a; // é\n/* two\nthree */\n  é = 1;").tokenize_all().unwrap_err();
+    assert_eq!((err.line, err.col), (4, 3));
+    assert_eq!(err.to_string(), "Unexpected character 'é' at line 4, column 3");
+
+    let mut lx = Lexer::new("a\n  b");
+    lx.next_token().unwrap();
+    assert_eq!(lx.position(), (1, 1));
+    lx.next_token().unwrap();
+    assert_eq!(lx.position(), (2, 3));
+}
+
+// Test lexing of hex integer literals, either case.
+#[test]
+fn lex_hex_numbers() {
+    expect_tokens!("0x1A3F 0XdeadBEEF 0x0+0xff", Token::Num(0x1A3F), Token::Num(0xDEADBEEF), Token::Num(0), Token::Plus, Token::Num(255));
+}
+
+#[test]
+fn error_malformed_numbers() {
+    for (src, msg) in [
+        ("0x", "hex constant 0x has no digits"),
+        ("0X;", "hex constant 0X has no digits"),
+        ("09", "invalid digit '9' in octal constant 09"),
+        ("0758", "invalid digit '8' in octal constant 0758"),
+        ("0x8000000000000000", "bad number 0x8000000000000000"),
+    ] {
+        let err = Lexer::new(src).next_token().unwrap_err();
+        assert!(err.msg.starts_with(msg), "{}: {}", src, err.msg);
+        assert_eq!((err.line, err.col), (1, 1));
+    }
+    expect_tokens!("0 10 90", Token::Num(0), Token::Num(10), Token::Num(90));
 }
 
 // Test skipping of preprocessor lines starting with '#'.
@@ -154,7 +258,7 @@ fn lex_adjacent_tokens() {
 // Test string literals containing escaped quotes and backslashes.
 #[test]
 fn lex_string_with_quotes_and_backslashes() {
-    let s = r#""She said: \"Hi!\" and \\OK\\\""#;
+    let s = r#""She said: \"Hi!\" and \\OK\\\"""#;
     let mut lx = Lexer::new(s);
     assert_eq!(
         lx.next_token().unwrap(),
@@ -163,10 +267,21 @@ fn lex_string_with_quotes_and_backslashes() {
     assert_eq!(lx.next_token().unwrap(), Token::Eof);
 }
 
-// Test how unterminated string literal is currently handled (returns Str and EOF).
+// A string or char literal must be closed, and a char literal must hold
+// exactly one character.
 #[test]
-fn lex_unterminated_string() {
-    let mut lx = Lexer::new("\"no end");
-    assert_eq!(lx.next_token().unwrap(), Token::Str("no end".into()));
-    assert_eq!(lx.next_token().unwrap(), Token::Eof);
+fn lex_unterminated_literals() {
+    for (src, msg) in [
+        ("\"no end", "unterminated string literal"),
+        (r#""ends in an escape\""#, "unterminated string literal"),
+        ("''", "empty char literal"),
+        ("'ab'", "char literal has more than one character"),
+        ("'\\nb'", "char literal has more than one character"),
+        ("'a", "unterminated char literal"),
+        ("'a;\n'", "unterminated char literal"),
+        ("'", "unterminated char literal"),
+    ] {
+        let err = Lexer::new(src).next_token().unwrap_err();
+        assert_eq!(err.msg, msg, "{}", src);
+    }
 }
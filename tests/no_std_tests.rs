@@ -0,0 +1,34 @@
+// tests/no_std_tests.rs
+
+//! Uses only the parts of the library available without the `std` feature;
+//! run with `cargo test --no-default-features --test no_std_tests`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use c4_rust_AlRafaah::{bytecode::Chunk, parser::Parser, vm::{Output, VM}};
+
+/// Output sink built on the `Output` trait alone, counting bytes written.
+struct CountingOutput(Arc<AtomicUsize>);
+
+impl Output for CountingOutput {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.0.fetch_add(bytes.len(), Ordering::SeqCst);
+    }
+
+    fn flush_output(&mut self) {}
+}
+
+#[test]
+fn compiles_and_runs_with_alloc_only() {
+    let ast = Parser::new("int main() { return (1 + 2) * 14; }")
+        .and_then(|mut p| p.parse_program())
+        .unwrap();
+    let mut chunk = Chunk::default();
+    ast.compile(&mut chunk).unwrap();
+
+    let written = Arc::new(AtomicUsize::new(0));
+    let mut vm = VM::new();
+    vm.set_output(Box::new(CountingOutput(written.clone())));
+    assert_eq!(vm.run(&chunk), Ok(42));
+    assert_eq!(written.load(Ordering::SeqCst), 0);
+}
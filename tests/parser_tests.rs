@@ -60,33 +60,31 @@ fn parse_function_and_statements() {
     assert_eq!(func.body.stmts.len(), 6);
 
     // 0: Empty
-    assert!(matches!(func.body.stmts[0], Stmt::Empty));
+    assert!(matches!(func.body.stmts[0].kind, StmtKind::Empty));
 
     // 1: Nested block with local and assignment
-    if let Stmt::Block(Block { stmts }) = &func.body.stmts[1] {
-        assert!(matches!(stmts[0], Stmt::Expr(_)));
+    if let StmtKind::Block(Block { stmts, .. }) = &func.body.stmts[1].kind {
+        assert!(matches!(stmts[0].kind, StmtKind::Expr(_)));
     } else {
         panic!("expected nested block");
     }
 
     // 2: If / else
-    if let Stmt::If { cond, then_branch, else_branch } = &func.body.stmts[2] {
-        // cond is &Expr, so *cond is Expr
-        assert!(matches!(*cond, Expr::Binary { op: BinOp::Gt, .. }));
-        // then_branch is &Box<Stmt>, so **then_branch is Stmt
-        assert!(matches!(**then_branch, Stmt::Expr(_)));
+    if let StmtKind::If { cond, then_branch, else_branch } = &func.body.stmts[2].kind {
+        assert!(matches!(cond.kind, ExprKind::Binary { op: BinOp::Gt, .. }));
+        assert!(matches!(then_branch.kind, StmtKind::Expr(_)));
         // else_branch is &Option<Box<Stmt>>
         let eb = else_branch.as_ref().unwrap(); // &Box<Stmt>
-        assert!(matches!(**eb, Stmt::Expr(_)));
+        assert!(matches!(eb.kind, StmtKind::Expr(_)));
     } else {
         panic!("expected if stmt");
     }
 
     // 3: While
-    assert!(matches!(func.body.stmts[3], Stmt::While { .. }));
+    assert!(matches!(func.body.stmts[3].kind, StmtKind::While { .. }));
 
     // 4: Return without expr
-    assert!(matches!(func.body.stmts[4], Stmt::Return(None)));
+    assert!(matches!(func.body.stmts[4].kind, StmtKind::Return(None)));
 }
 
 #[test]
@@ -99,9 +97,9 @@ fn parse_unary_and_postfix_ops() {
     let Program { items } = parse_to_ast(src);
     let body = match &items[0] { Item::Function(f) => &f.body, _ => panic!() };
     // Expr: Binary chain ((+x) - (!y)) * (~z) & (*p) | (&q)
-    if let Stmt::Return(Some(expr)) = &body.stmts[0] {
+    if let StmtKind::Return(Some(expr)) = &body.stmts[0].kind {
         // Top-level op is BitOr
-        assert!(matches!(expr, Expr::Binary { op: BinOp::BitOr, .. }));
+        assert!(matches!(expr.kind, ExprKind::Binary { op: BinOp::BitOr, .. }));
     } else {
         panic!("expected return expr");
     }
@@ -109,11 +107,11 @@ fn parse_unary_and_postfix_ops() {
     // Postfix ++/--:
     let src2 = "int p() { return a++ + --b; }";
     let Program { items } = parse_to_ast(src2);
-    let expr = if let Stmt::Return(Some(e)) = &match &items[0] { Item::Function(f) => &f.body.stmts[0], _ => panic!() } { e } else { panic!() };
+    let expr = if let StmtKind::Return(Some(e)) = &match &items[0] { Item::Function(f) => &f.body.stmts[0], _ => panic!() }.kind { e } else { panic!() };
     // Check PostInc and PreDec appear
-    if let Expr::Binary { left, right, .. } = expr {
-        assert!(matches!(**left, Expr::Unary { op: UnOp::PostInc, .. }));
-        assert!(matches!(**right, Expr::Unary { op: UnOp::PreDec, .. }));
+    if let ExprKind::Binary { left, right, .. } = &expr.kind {
+        assert!(matches!(left.kind, ExprKind::Unary { op: UnOp::PostInc, .. }));
+        assert!(matches!(right.kind, ExprKind::Unary { op: UnOp::PreDec, .. }));
     } else {
         panic!("expected binary");
     }
@@ -123,7 +121,7 @@ fn parse_unary_and_postfix_ops() {
 fn parse_sizeof_and_cast() {
     let src = "int c() { return sizeof(int) + sizeof(char*) + (char*)p; }";
     let Program { items } = parse_to_ast(src);
-    let expr = if let Stmt::Return(Some(e)) = &match &items[0] { Item::Function(f) => &f.body.stmts[0], _ => panic!() } { e } else { panic!() };
+    let expr = if let StmtKind::Return(Some(e)) = &match &items[0] { Item::Function(f) => &f.body.stmts[0], _ => panic!() }.kind { e } else { panic!() };
     // should be a Binary chain; check one SizeOf and one Cast
     let found_sizeof = format!("{:?}", expr).contains("SizeOf");
     let found_cast = format!("{:?}", expr).contains("Cast");
@@ -134,7 +132,7 @@ fn parse_sizeof_and_cast() {
 fn parse_shifts_and_bitwise() {
     let src = "int s() { return a << 2 >> 1 & b | c ^ d; }";
     let Program { items } = parse_to_ast(src);
-    let expr = if let Stmt::Return(Some(e)) = &match &items[0] { Item::Function(f) => &f.body.stmts[0], _ => panic!() } { e } else { panic!() };
+    let expr = if let StmtKind::Return(Some(e)) = &match &items[0] { Item::Function(f) => &f.body.stmts[0], _ => panic!() }.kind { e } else { panic!() };
     let repr = format!("{:?}", expr);
     assert!(repr.contains("Shl") && repr.contains("Shr") && repr.contains("BitAnd"));
     assert!(repr.contains("BitOr") && repr.contains("Xor"));
@@ -151,14 +149,170 @@ fn parse_indexing_and_calls_and_strings() {
     "#;
     let Program { items } = parse_to_ast(src);
     let stmt = &match &items[0] { Item::Function(f) => &f.body.stmts[0], _ => panic!() };
-    if let Stmt::Return(Some(Expr::Call { callee, args })) = stmt {
-        // callee is &Box<Expr>, so **callee is Expr
-        assert!(matches!(**callee, Expr::Var(ref s) if s == "foo"));
+    let call = match &stmt.kind { StmtKind::Return(Some(e)) => Some(&e.kind), _ => None };
+    if let Some(ExprKind::Call { callee, args }) = call {
+        assert!(matches!(callee.kind, ExprKind::Var(ref s) if s == "foo"));
         // args: Str, Index, Binary
-        assert!(matches!(args[0], Expr::Str(_)));
-        assert!(matches!(args[1], Expr::Index { .. }));
-        assert!(matches!(args[2], Expr::Binary { op: BinOp::Mul, .. }));
+        assert!(matches!(args[0].kind, ExprKind::Str(_)));
+        assert!(matches!(args[1].kind, ExprKind::Index { .. }));
+        assert!(matches!(args[2].kind, ExprKind::Binary { op: BinOp::Mul, .. }));
     } else {
         panic!("expected call in return");
     }
 }
+
+#[test]
+fn parse_for_loops() {
+    let src = r#"
+        int f() {
+          int i, j, n;
+          for (;;) n++;
+          for (i = 0; i < 10; i++) n = n + i;
+          for (i = 0; i < 3; i++)
+            for (j = 0; j < 3; j++) { n++; }
+        }
+    "#;
+    let Program { items } = parse_to_ast(src);
+    let body = match &items[0] { Item::Function(f) => &f.body, _ => panic!() };
+
+    // for (;;): every clause empty, so the loop only ends by returning.
+    assert_eq!(
+        body.stmts[0],
+        StmtKind::For {
+            init: None,
+            cond: None,
+            step: None,
+            body: Box::new(
+                StmtKind::Expr(
+                    ExprKind::Unary { op: UnOp::PostInc, expr: Box::new(ExprKind::Var("n".into()).into()) }.into()
+                )
+                .into()
+            ),
+        }
+        .into()
+    );
+
+    let StmtKind::For { init, cond, step, body: inner } = &body.stmts[1].kind else { panic!("expected for") };
+    assert!(matches!(init.as_ref().map(|e| &e.kind), Some(ExprKind::Binary { op: BinOp::Assign, .. })));
+    assert!(matches!(cond.as_ref().map(|e| &e.kind), Some(ExprKind::Binary { op: BinOp::Lt, .. })));
+    assert!(matches!(step.as_ref().map(|e| &e.kind), Some(ExprKind::Unary { op: UnOp::PostInc, .. })));
+    assert!(matches!(inner.kind, StmtKind::Expr(_)));
+
+    let StmtKind::For { body: outer_body, .. } = &body.stmts[2].kind else { panic!("expected for") };
+    let StmtKind::For { body: inner_body, .. } = &outer_body.kind else { panic!("expected nested for") };
+    assert!(matches!(inner_body.kind, StmtKind::Block(_)));
+}
+
+#[test]
+fn parse_for_requires_both_semicolons() {
+    for src in ["int f() { for (i = 0) ; }", "int f() { for (;) ; }", "int f() { for (;; ; }"] {
+        assert!(Parser::new(src).and_then(|mut p| p.parse_program()).is_err(), "{}", src);
+    }
+}
+
+#[test]
+fn parse_declarations_anywhere_in_a_block() {
+    let ast = parse_to_ast("int f() { int a; a = 1; char b; { a = 2; int c, *d; } return a; }");
+    let Item::Function(func) = &ast.items[0] else { panic!("expected a function") };
+    assert_eq!(func.locals, vec![("a".to_string(), Type::Int), ("b".to_string(), Type::Char)]);
+    let StmtKind::Block(inner) = &func.body.stmts[1].kind else { panic!("expected a block") };
+    assert_eq!(
+        inner.locals,
+        vec![("c".to_string(), Type::Int), ("d".to_string(), Type::Ptr(Box::new(Type::Int)))]
+    );
+    assert_eq!(inner.stmts.len(), 1);
+}
+
+#[test]
+fn parse_do_while() {
+    let ast = parse_to_ast("int f() { do { f(); } while (1); do ; while (0); }");
+    let Item::Function(func) = &ast.items[0] else { panic!("expected a function") };
+    match &func.body.stmts[0].kind {
+        StmtKind::DoWhile { body, cond } if cond.kind == ExprKind::Num(1) => {
+            assert!(matches!(body.kind, StmtKind::Block(_)))
+        }
+        other => panic!("expected do-while, got {:?}", other),
+    }
+    assert!(matches!(&func.body.stmts[1].kind, StmtKind::DoWhile { cond, .. } if cond.kind == ExprKind::Num(0)));
+
+    // The trailing `while (...)` and its `;` are required.
+    for src in ["int f() { do ; }", "int f() { do ; while (1) }", "int f() { do ; while 1; }"] {
+        assert!(Parser::new(src).and_then(|mut p| p.parse_program()).is_err(), "{}", src);
+    }
+}
+
+#[test]
+fn parse_break_and_continue() {
+    let ast = parse_to_ast("int f() { while (1) { break; continue; } }");
+    let Item::Function(func) = &ast.items[0] else { panic!("expected a function") };
+    let StmtKind::While { body, .. } = &func.body.stmts[0].kind else { panic!("expected a while loop") };
+    let StmtKind::Block(block) = &body.kind else { panic!("expected a block") };
+    assert_eq!(block.stmts, vec![StmtKind::Break.into(), StmtKind::Continue.into()]);
+    assert!(Parser::new("int f() { while (1) break }").and_then(|mut p| p.parse_program()).is_err());
+}
+
+#[test]
+fn parse_switch_cases() {
+    let ast = parse_to_ast("int f(int x) { switch (x) { case 1: case -2: f(0); break; default: ; } }");
+    let Item::Function(func) = &ast.items[0] else { panic!("expected a function") };
+    let StmtKind::Switch { scrutinee, cases } = &func.body.stmts[0].kind else { panic!("expected a switch") };
+    assert_eq!(scrutinee.kind, ExprKind::Var("x".into()));
+    let labels: Vec<_> = cases.iter().map(|(label, stmts)| (*label, stmts.len())).collect();
+    assert_eq!(labels, [(Some(1), 0), (Some(-2), 2), (None, 1)]);
+
+    for src in [
+        "int f(int x) { switch (x) { case x: ; } }",
+        "int f(int x) { switch (x) { f(0); } }",
+        "int f(int x) { switch (x) { case 1 ; } }",
+    ] {
+        assert!(Parser::new(src).and_then(|mut p| p.parse_program()).is_err(), "{}", src);
+    }
+}
+
+#[test]
+fn parse_compound_assignments() {
+    let ast = parse_to_ast(
+        "int f(int x) { x += 1; x -= 1; x *= 1; x /= 1; x %= 1; x &= 1; x |= 1; x ^= 1; x <<= 1; x >>= 1; }",
+    );
+    let Item::Function(func) = &ast.items[0] else { panic!("expected a function") };
+    let ops: Vec<_> = func.body.stmts[..10]
+        .iter()
+        .map(|s| match &s.kind {
+            StmtKind::Expr(Expr { kind: ExprKind::CompoundAssign { op, target, value }, .. }) => {
+                assert_eq!(target.kind, ExprKind::Var("x".into()));
+                assert_eq!(value.kind, ExprKind::Num(1));
+                op
+            }
+            other => panic!("expected a compound assignment, got {:?}", other),
+        })
+        .collect();
+    use BinOp::*;
+    assert_eq!(ops, [&Add, &Sub, &Mul, &Div, &Mod, &BitAnd, &BitOr, &Xor, &Shl, &Shr]);
+
+    // Right-associative, like `=`: `a += b -= 1` updates b first.
+    let ast = parse_to_ast("int f(int a, int b) { a += b -= 1; }");
+    let Item::Function(func) = &ast.items[0] else { panic!("expected a function") };
+    let StmtKind::Expr(Expr { kind: ExprKind::CompoundAssign { op: Add, value, .. }, .. }) = &func.body.stmts[0].kind else {
+        panic!("expected a compound assignment")
+    };
+    assert!(matches!(value.kind, ExprKind::CompoundAssign { op: Sub, .. }));
+}
+
+#[test]
+fn nodes_carry_their_source_spans() {
+    let src = "int f(int a, int b, int c) { return a + b * c; }";
+    let ast = parse_to_ast(src);
+    let Item::Function(func) = &ast.items[0] else { panic!("expected a function") };
+    let text = |span: Option<Span>| span.map(|s| &src[s.start..s.end]);
+
+    let ret = &func.body.stmts[0];
+    assert_eq!(text(ret.span), Some("return a + b * c;"));
+    let StmtKind::Return(Some(sum)) = &ret.kind else { panic!("expected a return") };
+    assert_eq!(text(sum.span), Some("a + b * c"));
+    let ExprKind::Binary { left, right, .. } = &sum.kind else { panic!("expected a sum") };
+    assert_eq!(text(left.span), Some("a"));
+    assert_eq!(text(right.span), Some("b * c"));
+
+    // Spans don't take part in equality.
+    assert_eq!(**left, Expr::from(ExprKind::Var("a".into())));
+}
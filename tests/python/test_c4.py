@@ -0,0 +1,69 @@
+# tests/python/test_c4.py
+#
+# Exercises the `c4` module the way an autograder would. Run by
+# tests/python_tests.rs with the freshly built module on PYTHONPATH.
+
+import threading
+
+import c4
+
+
+def test_run():
+    result = c4.run("int main() { return 3; }")
+    assert result.exit_code == 3, result
+    assert result.stdout == ""
+    assert result.instructions > 0
+
+
+def test_compile_and_rerun():
+    chunk = c4.compile("int main() { return 6 * 7; }")
+    assert chunk.run().exit_code == 42
+    assert chunk.run().exit_code == 42
+    assert len(chunk) > 0
+
+
+def test_disassemble():
+    asm = c4.disassemble("int main() { return 0; }")
+    assert asm.startswith("    JSR L0\n"), asm
+    assert c4.compile("int main() { return 0; }").disassemble() == asm
+
+
+def test_parse_error_has_offset():
+    try:
+        c4.run("int main() { return 1 }")
+    except c4.ParseError as e:
+        assert isinstance(e, c4.C4Error)
+        assert e.offset == 22, e.offset
+        assert "parse error" in str(e)
+    else:
+        raise AssertionError("expected ParseError")
+
+
+def test_runtime_error_has_pc():
+    try:
+        c4.run("int main() { return 1 / 0; }")
+    except c4.RuntimeError as e:
+        assert "division by zero" in str(e)
+        assert e.pc >= 0
+    else:
+        raise AssertionError("expected RuntimeError")
+
+
+def test_runs_on_threads():
+    results = []
+    threads = [
+        threading.Thread(target=lambda n=n: results.append(c4.run(f"int main() {{ return {n}; }}").exit_code))
+        for n in range(4)
+    ]
+    for t in threads:
+        t.start()
+    for t in threads:
+        t.join()
+    assert sorted(results) == [0, 1, 2, 3]
+
+
+if __name__ == "__main__":
+    for name, test in list(globals().items()):
+        if name.startswith("test_"):
+            test()
+    print("ok")
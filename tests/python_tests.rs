@@ -0,0 +1,45 @@
+// tests/python_tests.rs
+//
+// Builds the `c4` Python extension module and runs tests/python/test_c4.py
+// against it. Skipped with a message when python3 is unavailable.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn python_module_passes_its_tests() {
+    if Command::new("python3").arg("--version").output().is_err() {
+        eprintln!("python3 not found; skipping Python binding tests");
+        return;
+    }
+
+    let project_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // A separate target directory keeps this build from waiting on the lock
+    // held by the `cargo test` that is running us.
+    let target_dir = project_dir.join("target/python");
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+        .current_dir(project_dir)
+        .env("CARGO_TARGET_DIR", &target_dir)
+        .args(["rustc", "--lib", "--crate-type", "cdylib", "--features", "python,pyo3/extension-module"])
+        .status()
+        .expect("failed to run cargo");
+    assert!(status.success(), "building the extension module failed");
+
+    let module_dir = target_dir.join("module");
+    fs::create_dir_all(&module_dir).unwrap();
+    fs::copy(target_dir.join("debug/libc4_rust_AlRafaah.so"), module_dir.join("c4.so")).unwrap();
+
+    let output = Command::new("python3")
+        .arg(project_dir.join("tests/python/test_c4.py"))
+        .env("PYTHONPATH", &module_dir)
+        .output()
+        .expect("failed to run python3");
+    assert!(
+        output.status.success(),
+        "python tests failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,81 @@
+// tests/refc4/mod.rs
+
+//! The original `c4.c`, built with the host C compiler, as a reference
+//! implementation. [`ReferenceC4::get`] builds it once per test binary and
+//! returns None when there is no `c4.c` or no working C compiler, so
+//! callers can skip instead of failing.
+//!
+//! c4 prints `exit(N) cycle = M` after the program's own output; [`run`]
+//! strips that line and returns `N` as the exit code. c4 formats `N` with
+//! `%d`, so only its low 32 bits survive: compare exit codes as `i32`.
+//!
+//! [`run`]: ReferenceC4::run
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+pub struct ReferenceC4 {
+    binary: PathBuf,
+}
+
+impl ReferenceC4 {
+    /// The reference binary, built on first use from the repository's
+    /// `c4.c` with `$CC` (default `cc`).
+    pub fn get() -> Option<&'static ReferenceC4> {
+        static REFERENCE: OnceLock<Option<ReferenceC4>> = OnceLock::new();
+        REFERENCE.get_or_init(Self::build).as_ref()
+    }
+
+    fn build() -> Option<ReferenceC4> {
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("c4.c");
+        if !source.exists() {
+            return None;
+        }
+        let cc = std::env::var("CC").unwrap_or_else(|_| "cc".into());
+        let binary = std::env::temp_dir().join(format!("c4_reference_{}", std::process::id()));
+        // c4.c predates modern warnings; it still builds and runs fine.
+        let status = Command::new(cc).arg("-w").arg("-o").arg(&binary).arg(&source).status().ok()?;
+        status.success().then_some(ReferenceC4 { binary })
+    }
+
+    /// Run `src` under c4, as (exit code, output).
+    pub fn run(&self, src: &str) -> Result<(i64, Vec<u8>), String> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("c4_reference_{}_{}.c", std::process::id(), n));
+        fs::write(&path, src).map_err(|e| e.to_string())?;
+        let out = Command::new(&self.binary).arg(&path).output().map_err(|e| e.to_string());
+        let _ = fs::remove_file(&path);
+        let out = out?;
+
+        let stdout = out.stdout;
+        let body = stdout.strip_suffix(b"\n").unwrap_or(&stdout);
+        let last_line = body.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        let trailer = String::from_utf8_lossy(&body[last_line..]);
+        let code = trailer
+            .strip_prefix("exit(")
+            .and_then(|rest| rest.split_once(')'))
+            .and_then(|(code, _)| code.parse().ok())
+            .ok_or_else(|| format!("c4 did not finish: {}{}", trailer, String::from_utf8_lossy(&out.stderr)))?;
+        Ok((code, stdout[..last_line].to_vec()))
+    }
+}
+
+/// Save `src` to `dir/name.c` with `// expect` lines recording what the
+/// reference made of it, for `test_runner::run_dir` to replay.
+pub fn archive(dir: &Path, name: &str, src: &str, expected: &(i64, Vec<u8>)) -> PathBuf {
+    let mut text = String::from("// Diverged from the reference c4; archived by tests/reference_tests.rs.\n");
+    writeln!(text, "// expect: exit {}", expected.0).unwrap();
+    for line in String::from_utf8_lossy(&expected.1).lines() {
+        writeln!(text, "// expect-output: {}", line).unwrap();
+    }
+    text.push_str(src);
+    let path = dir.join(format!("{}.c", name));
+    fs::create_dir_all(dir).unwrap();
+    fs::write(&path, text).unwrap();
+    path
+}
@@ -0,0 +1,109 @@
+// tests/replay_tests.rs
+
+use c4_rust_AlRafaah::bytecode::Chunk;
+use c4_rust_AlRafaah::compile_source;
+use c4_rust_AlRafaah::replay::{Syscall, SyscallLog};
+use c4_rust_AlRafaah::vfs::{MemFs, SharedFs};
+use c4_rust_AlRafaah::vm::{OutputBuffer, VmConfig, VmError, VM};
+
+/// Prints the first line of `in.txt`, then whether `missing.txt` exists.
+const CAT: &str = r#"
+int main() {
+    char *buf;
+    int fd, n, i;
+    buf = malloc(16);
+    fd = open("in.txt", 0);
+    n = read(fd, buf, 16);
+    i = 0;
+    while (i < n && buf[i] != 10) { printf("%c", buf[i]); i++; }
+    printf("|%d|%d\n", n, open("missing.txt", 0));
+    close(fd);
+    return n;
+}
+"#;
+
+/// A VM reading from `fs`, printing to the returned buffer.
+fn vm_with(fs: MemFs) -> (VM, OutputBuffer) {
+    let mut vm = VM::with_config(VmConfig { filesystem: Some(SharedFs::new(fs)), ..VmConfig::default() });
+    let out = OutputBuffer::new();
+    vm.set_output(Box::new(out.clone()));
+    (vm, out)
+}
+
+fn record(chunk: &Chunk) -> (i64, Vec<u8>, SyscallLog) {
+    let (mut vm, out) = vm_with(MemFs::new([("in.txt", "first line\nsecond\n")]));
+    vm.record();
+    let code = vm.run(chunk).unwrap();
+    (code, out.contents(), vm.take_syscall_log().unwrap())
+}
+
+#[test]
+fn a_replay_reproduces_the_recorded_run_without_the_files() {
+    let chunk = compile_source(CAT).unwrap();
+    let (code, output, log) = record(&chunk);
+    assert_eq!(output, b"first line|16|-1\n");
+    assert_eq!(
+        log.calls,
+        [
+            Syscall::Open { path: b"in.txt".to_vec(), fd: 3 },
+            Syscall::Read { fd: 3, len: 16, data: Some(b"first line\nsecon".to_vec()) },
+            Syscall::Open { path: b"missing.txt".to_vec(), fd: -1 },
+        ]
+    );
+
+    // No files at all: everything comes from the log, through its text form.
+    let log = SyscallLog::parse(&log.to_text()).unwrap();
+    let (mut vm, out) = vm_with(MemFs::default());
+    vm.replay(log);
+    assert_eq!(vm.run(&chunk), Ok(code));
+    assert_eq!(out.contents(), output);
+}
+
+#[test]
+fn a_different_call_diverges() {
+    let (_, _, log) = record(&compile_source(CAT).unwrap());
+    let changed = compile_source(&CAT.replace("read(fd, buf, 16)", "read(fd, buf, 8)")).unwrap();
+    let (mut vm, _) = vm_with(MemFs::default());
+    vm.replay(log.clone());
+    let err = vm.run(&changed).unwrap_err();
+    assert!(matches!(err, VmError::ReplayDiverged { index: 1, .. }), "{:?}", err);
+    let message = err.to_string();
+    assert!(
+        message.starts_with("replay diverged at syscall 1 (pc ")
+            && message.ends_with("the program called read(3, buf, 8) but the recording has read(3, buf, 16)"),
+        "{}",
+        message
+    );
+
+    // Calls past the end of the log diverge too...
+    let (mut vm, _) = vm_with(MemFs::default());
+    vm.replay(SyscallLog { calls: log.calls[..1].to_vec() });
+    let message = vm.run(&compile_source(CAT).unwrap()).unwrap_err().to_string();
+    assert!(message.ends_with("the program called read(3, buf, 16) after the recording ended"), "{}", message);
+
+    // ...and so does finishing with calls left over.
+    let (mut vm, _) = vm_with(MemFs::default());
+    vm.replay(log);
+    let message = vm.run(&compile_source("int main() { return 0; }").unwrap()).unwrap_err().to_string();
+    assert!(message.ends_with(r#"the program finished before the recorded open("in.txt")"#), "{}", message);
+}
+
+#[test]
+fn logs_round_trip_through_text() {
+    let log = SyscallLog {
+        calls: vec![
+            Syscall::Open { path: b"a b\n".to_vec(), fd: 3 },
+            Syscall::Read { fd: 3, len: 4, data: Some(vec![0, 255]) },
+            Syscall::Read { fd: 3, len: 4, data: Some(Vec::new()) },
+            Syscall::Read { fd: 9, len: 4, data: None },
+            Syscall::Open { path: Vec::new(), fd: -1 },
+        ],
+    };
+    let text = log.to_text();
+    assert_eq!(text, "c4-syscalls 1\nopen 3 6120620a\nread 3 4 2 00ff\nread 3 4 0\nread 9 4 -1\nopen -1\n");
+    assert_eq!(SyscallLog::parse(&text), Ok(log));
+
+    assert!(SyscallLog::parse("c4-core 1\n").unwrap_err().starts_with("not a syscall log"));
+    assert_eq!(SyscallLog::parse("c4-syscalls 1\nwrite 1 2\n"), Err("line 2: unknown syscall 'write'".into()));
+    assert_eq!(SyscallLog::parse("c4-syscalls 1\nread 3 4 3 00ff\n"), Err("line 2: malformed 'read 3 4 3 00ff'".into()));
+}
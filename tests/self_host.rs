@@ -1,5 +1,7 @@
+use std::fs;
 use std::process::{Command};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use c4_rust_AlRafaah::{bytecode::Chunk, error::Error, parser::Parser, vm::{OutputBuffer, VM}};
 
 #[test]
 fn test_self_host_output_matches_c4() {
@@ -29,7 +31,7 @@ fn test_self_host_output_matches_c4() {
     let output = Command::new(&c4_exe)
         .arg("-s")
         .arg("c4.c")
-        .current_dir(&project_dir)
+        .current_dir(project_dir)
         .output()
         .expect("Failed to run c4.exe");
 
@@ -39,3 +41,97 @@ fn test_self_host_output_matches_c4() {
 
     assert!(output.status.success(), "C4 did not exit successfully");
 }
+
+// ─── Comparison Harness Against gcc-built c4 ───────────────────
+
+/// Exit status and stdout of one program run.
+#[derive(Debug, PartialEq)]
+struct RunResult {
+    exit: i32,
+    stdout: String,
+}
+
+/// Build the reference c4 interpreter with gcc, or None if gcc is unavailable.
+fn build_reference_c4() -> Option<PathBuf> {
+    let project_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = Path::new(env!("CARGO_TARGET_TMPDIR")).join("c4_reference");
+    let status = Command::new("gcc")
+        .arg(project_dir.join("c4.c"))
+        .arg("-o")
+        .arg(&exe)
+        .output()
+        .ok()?
+        .status;
+    status.success().then_some(exe)
+}
+
+/// Run `prog` under the reference c4, dropping its trailing `exit(N) cycle = M` line.
+fn run_reference(c4: &Path, prog: &Path) -> RunResult {
+    let out = Command::new(c4).arg(prog).output().expect("failed to run reference c4");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let stdout = match stdout.rfind("exit(") {
+        Some(i) => &stdout[..i],
+        None => &stdout[..],
+    };
+    RunResult {
+        exit: out.status.code().unwrap_or(-1),
+        stdout: stdout.to_string(),
+    }
+}
+
+/// Run `prog` through our parser, compiler and VM with output captured.
+fn run_ours(prog: &Path) -> Result<RunResult, Error> {
+    let src = fs::read_to_string(prog)?;
+    let ast = Parser::new(&src).and_then(|mut p| p.parse_program())?;
+    let mut chunk = Chunk::default();
+    ast.compile(&mut chunk)?;
+
+    let buffer = OutputBuffer::new();
+    let mut vm = VM::new();
+    vm.set_output(Box::new(buffer.clone()));
+    let code = vm.run(&chunk)?;
+    Ok(RunResult {
+        exit: (code & 0xFF) as i32, // what the OS would report as the exit status
+        stdout: String::from_utf8_lossy(&buffer.contents()).into_owned(),
+    })
+}
+
+/// Compare every `*.c` program in `dir` and describe each divergence.
+fn compare_corpus(c4: &Path, dir: &Path) -> Vec<String> {
+    let mut programs: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("corpus directory missing")
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .collect();
+    programs.sort();
+    assert!(!programs.is_empty(), "corpus is empty");
+
+    let mut divergences = Vec::new();
+    for prog in programs {
+        let expected = run_reference(c4, &prog);
+        match run_ours(&prog) {
+            Err(e) => divergences.push(format!("{}: failed to compile: {}", prog.display(), e)),
+            Ok(actual) if actual != expected => divergences.push(format!(
+                "{}:\n  c4:   exit {} stdout {:?}\n  ours: exit {} stdout {:?}",
+                prog.display(),
+                expected.exit,
+                expected.stdout,
+                actual.exit,
+                actual.stdout
+            )),
+            Ok(_) => {}
+        }
+    }
+    divergences
+}
+
+#[test]
+fn corpus_matches_reference_c4() {
+    let Some(c4) = build_reference_c4() else {
+        eprintln!("skipping: gcc is unavailable, cannot build the reference c4");
+        return;
+    };
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let divergences = compare_corpus(&c4, &corpus);
+    assert!(divergences.is_empty(), "divergences from c4:\n{}", divergences.join("\n"));
+}
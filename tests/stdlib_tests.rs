@@ -0,0 +1,107 @@
+// tests/stdlib_tests.rs
+
+use c4_rust_AlRafaah::compiler::Compiler;
+use c4_rust_AlRafaah::ast::Item;
+use c4_rust_AlRafaah::error::Error;
+use c4_rust_AlRafaah::parser::Parser;
+use c4_rust_AlRafaah::stdlib::{FUNCTIONS, PRELUDE};
+use c4_rust_AlRafaah::vm::VM;
+
+/// Helper: compile `src` with the prelude linked and return `main`'s result.
+fn run(src: &str) -> i64 {
+    let compiled = Compiler::new().compile_str(src).unwrap();
+    VM::new().run(&compiled.chunk).unwrap()
+}
+
+/// Helper: `main` returning `expr`.
+fn eval(expr: &str) -> i64 {
+    run(&format!("int main() {{ return {}; }}", expr))
+}
+
+#[test]
+fn functions_lists_what_the_prelude_defines() {
+    let program = Parser::new(PRELUDE).unwrap().parse_program().unwrap();
+    let defined: Vec<&str> = program
+        .items
+        .iter()
+        .map(|item| match item {
+            Item::Function(f) => f.name.as_str(),
+            other => panic!("the prelude should only define functions: {:?}", other),
+        })
+        .collect();
+    assert_eq!(defined, FUNCTIONS);
+}
+
+#[test]
+fn string_functions() {
+    assert_eq!(eval(r#"strlen("hello")"#), 5);
+    assert_eq!(eval(r#"strlen("")"#), 0);
+
+    assert_eq!(eval(r#"strcmp("abc", "abc")"#), 0);
+    assert!(eval(r#"strcmp("abc", "abd")"#) < 0);
+    assert!(eval(r#"strcmp("abd", "abc")"#) > 0);
+    assert!(eval(r#"strcmp("ab", "abc")"#) < 0);
+    assert!(eval(r#"strcmp("abc", "")"#) > 0);
+
+    let src = r#"int main() {
+        char *buf, *ret;
+        buf = malloc(8);
+        memset(buf, 'x', 8);
+        ret = strcpy(buf, "copy");
+        if (ret != buf) return -1;
+        return strlen(buf) * 100 + strcmp(buf, "copy") * 10 + (buf[5] == 'x');
+    }"#;
+    assert_eq!(run(src), 401);
+}
+
+#[test]
+fn atoi_reads_a_leading_number() {
+    assert_eq!(eval(r#"atoi("42")"#), 42);
+    assert_eq!(eval(r#"atoi("  -17 apples")"#), -17);
+    assert_eq!(eval(r#"atoi("+8")"#), 8);
+    assert_eq!(eval(r#"atoi("\t\n9x9")"#), 9);
+    assert_eq!(eval(r#"atoi("none")"#), 0);
+    assert_eq!(eval(r#"atoi("")"#), 0);
+}
+
+#[test]
+fn integer_functions() {
+    assert_eq!(eval("abs(-5)"), 5);
+    assert_eq!(eval("abs(5)"), 5);
+    assert_eq!(eval("abs(0)"), 0);
+    assert_eq!(eval("min(3, -4)"), -4);
+    assert_eq!(eval("min(2, 2)"), 2);
+    assert_eq!(eval("max(3, -4)"), 3);
+    assert_eq!(eval("max(-1, -2)"), -1);
+}
+
+#[test]
+fn only_called_functions_are_linked() {
+    let chunk = Compiler::new().compile_str(r#"int main() { return abs(strlen("ab")); }"#).unwrap().chunk;
+    assert!(chunk.function("abs").is_some() && chunk.function("strlen").is_some());
+    assert!(chunk.function("atoi").is_none() && chunk.function("max").is_none());
+
+    // A program that calls none of them compiles exactly as without the prelude.
+    let src = "int main() { return 7; }";
+    assert_eq!(Compiler::new().compile_str(src).unwrap(), Compiler::new().stdlib(false).compile_str(src).unwrap());
+}
+
+#[test]
+fn the_programs_own_definitions_win() {
+    assert_eq!(run("int abs(int n) { return 99; } int main() { return abs(-1); }"), 99);
+    // Defined after its first use, too.
+    assert_eq!(run("int main() { return max(1, 2); } int max(int a, int b) { return a; }"), 1);
+    // The program's strlen doesn't change the prelude's strcpy.
+    let src = r#"int strlen(char *s) { return 0; }
+        int main() { char *b; b = malloc(4); strcpy(b, "abc"); return b[2] == 'c'; }"#;
+    assert_eq!(run(src), 1);
+    // A global of the same name shadows the prelude function too.
+    let err = Compiler::new().compile_str("int min; int main() { return min(1, 2); }").unwrap_err();
+    assert!(matches!(err, Error::Compile(_)), "{:?}", err);
+}
+
+#[test]
+fn without_the_stdlib_prelude_calls_are_undefined() {
+    let err = Compiler::new().stdlib(false).compile_str(r#"int main() { return strlen("x"); }"#).unwrap_err();
+    assert_eq!(err.to_string(), "compile error: bad function call: strlen (in main)");
+}
@@ -0,0 +1,95 @@
+// tests/test_runner_tests.rs
+
+use std::fs;
+use std::path::PathBuf;
+use c4_rust_AlRafaah::driver::compile_source;
+use c4_rust_AlRafaah::test_runner::{run_dir, run_tests, Expectations};
+use c4_rust_AlRafaah::vm::VmError;
+
+/// Helper: create a fresh, empty temp directory for one test.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("c4_runner_{}_{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn parse_expectations_from_leading_comment() {
+    let src = "// expect: exit 7\n// expect-output: hello\n// expect-output: world\nint main() { return 7; }\n// expect: exit 9\n";
+    let exp = Expectations::parse(src).unwrap();
+    assert_eq!(exp.exit, Some(7)); // the trailing comment is ignored
+    assert_eq!(exp.output, vec!["hello", "world"]);
+    assert!(!exp.compile_error);
+
+    let exp = Expectations::parse("// expect: compile-error\nint main( {").unwrap();
+    assert!(exp.compile_error);
+
+    assert!(Expectations::parse("// expect: exit seven\n").is_err());
+}
+
+#[test]
+fn runner_reports_pass_fail_and_expected_errors() {
+    let dir = temp_dir("mixed");
+    fs::write(dir.join("a_pass.c"), "// expect: exit 42\nint main() { return 40 + 2; }\n").unwrap();
+    fs::write(
+        dir.join("b_fail.c"),
+        "// expect: exit 1\n// expect-output: hi\nint main() { return 2; }\n",
+    )
+    .unwrap();
+    fs::create_dir(dir.join("nested")).unwrap();
+    fs::write(dir.join("nested/c_error.c"), "// expect: compile-error\nint main( { return 0; }\n").unwrap();
+    fs::write(dir.join("notes.txt"), "not a test").unwrap();
+
+    let summary = run_dir(&dir).unwrap();
+    assert_eq!(summary.results.len(), 3);
+    assert_eq!(summary.passed(), 2);
+    assert_eq!(summary.failed(), 1);
+
+    let failed = summary.results.iter().find(|r| !r.passed()).unwrap();
+    assert!(failed.path.ends_with("b_fail.c"));
+    let msg = failed.failure.as_ref().unwrap();
+    assert!(msg.contains("expected 1, got 2"));
+    assert!(msg.contains("- hi"));
+
+    let report = summary.render();
+    assert!(report.contains("FAIL"));
+    assert!(report.contains("2 passed, 1 failed, 3 total"));
+}
+
+#[test]
+fn unexpected_compile_error_fails() {
+    let dir = temp_dir("broken");
+    fs::write(dir.join("broken.c"), "// expect: exit 0\nint main( {\n").unwrap();
+    let summary = run_dir(&dir).unwrap();
+    assert_eq!(summary.failed(), 1);
+    assert!(summary.results[0].failure.as_ref().unwrap().contains("parse error"));
+}
+
+const UNIT_TESTS: &str = "\
+int test_sum() { assert(2 + 2 == 4); return 0; }
+int helper(int x) { return 1; }
+int test_product() { return 6 * 7 - 42; }
+int test_broken() { assert(1 < 2); assert(3 < 2); return 0; }
+int test_wrong_value() { return 5; }
+int main() { return 0; }
+";
+
+#[test]
+fn unit_tests_report_passes_failures_and_faults() {
+    let chunk = compile_source(UNIT_TESTS).unwrap();
+    let report = run_tests(&chunk);
+
+    let names: Vec<&str> = report.tests.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, ["test_sum", "test_product", "test_broken", "test_wrong_value"]);
+    assert_eq!((report.passed(), report.failed()), (2, 2));
+    assert!(matches!(report.tests[2].outcome, Err(VmError::AssertionFailed { .. })));
+    assert_eq!(report.tests[3].outcome, Ok(5));
+
+    let text = report.render();
+    assert!(text.contains("PASS test_sum ("), "{}", text);
+    assert!(text.contains("FAIL test_broken ("), "{}", text);
+    assert!(text.contains("    assertion failed at pc"), "{}", text);
+    assert!(text.contains("    returned 5"), "{}", text);
+    assert!(text.ends_with("2 passed, 2 failed, 4 total\n"), "{}", text);
+}
@@ -0,0 +1,375 @@
+// tests/testgen/mod.rs
+
+//! Random generator of well-typed, terminating, UB-free C4 programs for
+//! differential testing, with greedy shrinking of failing cases.
+//!
+//! Programs avoid undefined behaviour by construction: divisors are forced
+//! non-zero, shift counts and multiplication operands are masked small, and
+//! every assignment is masked, so no intermediate value can overflow. Loops
+//! run a fixed number of times on a dedicated counter, and helpers only call
+//! helpers defined before them.
+//!
+//! Loops are `while`s unless `for_loops` is set: the original c4 has no
+//! `for`, so programs compared against it leave that off.
+
+use std::fmt::Write as _;
+
+/// Which constructs the generator may use.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Features {
+    pub unary: bool,
+    pub locals: bool,
+    pub loops: bool,
+    pub calls: bool,
+    /// Write some loops as `for` rather than `while`.
+    pub for_loops: bool,
+}
+
+impl Features {
+    pub const fn all() -> Self {
+        Features { unary: true, locals: true, loops: true, calls: true, for_loops: true }
+    }
+}
+
+/// xorshift64* — small, deterministic, and good enough for test input.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Add, Sub, Mul, Div, Mod, Lt, Le, Gt, Ge, Eq, Ne, And, Or, Xor, Shl, Shr,
+}
+
+const OPS: [Op; 16] = [
+    Op::Add, Op::Sub, Op::Mul, Op::Div, Op::Mod, Op::Lt, Op::Le, Op::Gt,
+    Op::Ge, Op::Eq, Op::Ne, Op::And, Op::Or, Op::Xor, Op::Shl, Op::Shr,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenExpr {
+    Num(i64),
+    /// Local or parameter, by name.
+    Var(String),
+    /// One of `-`, `!`, `~`.
+    Unary(char, Box<GenExpr>),
+    Binary(Op, Box<GenExpr>, Box<GenExpr>),
+    Call(usize, Vec<GenExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenStmt {
+    Assign(String, GenExpr),
+    /// Run `body` `times` times using counter local `counter`, as a `for`
+    /// if `is_for`.
+    Loop { counter: String, times: i64, body: Vec<GenStmt>, is_for: bool },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenFunc {
+    pub arity: usize,
+    pub body: GenExpr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenProgram {
+    pub helpers: Vec<GenFunc>,
+    /// Locals of `main`, with initial values.
+    pub locals: Vec<(String, i64)>,
+    /// Loop counters of `main`.
+    pub counters: Vec<String>,
+    pub stmts: Vec<GenStmt>,
+    pub ret: GenExpr,
+}
+
+const MAX_DEPTH: usize = 4;
+
+struct Generator<'r> {
+    rng: &'r mut Rng,
+    features: Features,
+}
+
+impl Generator<'_> {
+    /// Expression over `vars`, calling only helpers `0..callable`.
+    fn expr(&mut self, depth: usize, vars: &[String], callable: &[usize]) -> GenExpr {
+        let leaf = depth >= MAX_DEPTH || self.rng.below(4) == 0;
+        if leaf {
+            if !vars.is_empty() && self.rng.below(2) == 0 {
+                return GenExpr::Var(vars[self.rng.below(vars.len())].clone());
+            }
+            return GenExpr::Num(self.rng.below(100) as i64);
+        }
+        match self.rng.below(10) {
+            0 if self.features.unary => {
+                let op = ['-', '!', '~'][self.rng.below(3)];
+                GenExpr::Unary(op, Box::new(self.expr(depth + 1, vars, callable)))
+            }
+            1 if !callable.is_empty() => {
+                let f = callable[self.rng.below(callable.len())];
+                let arity = f % 4; // helpers' arities are fixed by index
+                let args = (0..arity).map(|_| self.expr(depth + 1, vars, callable)).collect();
+                GenExpr::Call(f, args)
+            }
+            _ => {
+                let op = OPS[self.rng.below(OPS.len())];
+                let l = self.expr(depth + 1, vars, callable);
+                let r = self.expr(depth + 1, vars, callable);
+                GenExpr::Binary(op, Box::new(l), Box::new(r))
+            }
+        }
+    }
+
+    fn stmts(&mut self, depth: usize, locals: &[String], counters: &mut Vec<String>, callable: &[usize]) -> Vec<GenStmt> {
+        let mut out = Vec::new();
+        for _ in 0..1 + self.rng.below(3) {
+            if self.features.loops && depth < 2 && self.rng.below(3) == 0 {
+                let counter = format!("i{}", counters.len());
+                counters.push(counter.clone());
+                let body = self.stmts(depth + 1, locals, counters, callable);
+                let times = 1 + self.rng.below(5) as i64;
+                let is_for = self.features.for_loops && self.rng.below(2) == 0;
+                out.push(GenStmt::Loop { counter, times, body, is_for });
+            } else {
+                let target = locals[self.rng.below(locals.len())].clone();
+                out.push(GenStmt::Assign(target, self.expr(1, locals, callable)));
+            }
+        }
+        out
+    }
+}
+
+/// Generate a program from `seed`.
+pub fn generate(seed: u64, features: Features) -> GenProgram {
+    let mut rng = Rng::new(seed);
+    let mut g = Generator { rng: &mut rng, features };
+
+    let mut helpers = Vec::new();
+    if features.calls {
+        for f in 0..g.rng.below(4) {
+            let params: Vec<String> = (0..f % 4).map(|i| format!("p{}", i)).collect();
+            let callable: Vec<usize> = (0..f).collect();
+            helpers.push(GenFunc { arity: params.len(), body: g.expr(1, &params, &callable) });
+        }
+    }
+    let callable: Vec<usize> = (0..helpers.len()).collect();
+
+    let mut locals = Vec::new();
+    let mut counters = Vec::new();
+    let mut stmts = Vec::new();
+    if features.locals {
+        for i in 0..1 + g.rng.below(3) {
+            locals.push((format!("v{}", i), g.rng.below(50) as i64));
+        }
+        let names: Vec<String> = locals.iter().map(|(n, _)| n.clone()).collect();
+        stmts = g.stmts(0, &names, &mut counters, &callable);
+    }
+    let names: Vec<String> = locals.iter().map(|(n, _)| n.clone()).collect();
+    let ret = g.expr(0, &names, &callable);
+    GenProgram { helpers, locals, counters, stmts, ret }
+}
+
+impl GenExpr {
+    fn render(&self, out: &mut String) {
+        let sub = |e: &GenExpr| {
+            let mut s = String::new();
+            e.render(&mut s);
+            s
+        };
+        match self {
+            GenExpr::Num(n) => write!(out, "{}", n).unwrap(),
+            GenExpr::Var(v) => out.push_str(v),
+            GenExpr::Unary(op, e) => write!(out, "({}{})", op, sub(e)).unwrap(),
+            GenExpr::Call(f, args) => {
+                let args: Vec<String> = args.iter().map(sub).collect();
+                write!(out, "f{}({})", f, args.join(", ")).unwrap();
+            }
+            GenExpr::Binary(op, l, r) => {
+                let (l, r) = (sub(l), sub(r));
+                let text = match op {
+                    Op::Add => format!("({} + {})", l, r),
+                    Op::Sub => format!("({} - {})", l, r),
+                    Op::Mul => format!("(({} & 1023) * ({} & 1023))", l, r),
+                    Op::Div => format!("({} / (({} & 15) + 1))", l, r),
+                    Op::Mod => format!("({} % (({} & 15) + 1))", l, r),
+                    Op::Lt => format!("({} < {})", l, r),
+                    Op::Le => format!("({} <= {})", l, r),
+                    Op::Gt => format!("({} > {})", l, r),
+                    Op::Ge => format!("({} >= {})", l, r),
+                    Op::Eq => format!("({} == {})", l, r),
+                    Op::Ne => format!("({} != {})", l, r),
+                    Op::And => format!("({} & {})", l, r),
+                    Op::Or => format!("({} | {})", l, r),
+                    Op::Xor => format!("({} ^ {})", l, r),
+                    Op::Shl => format!("(({} & 1023) << ({} & 7))", l, r),
+                    Op::Shr => format!("({} >> ({} & 7))", l, r),
+                };
+                out.push_str(&text);
+            }
+        }
+    }
+
+    /// Smaller expressions that might still trigger the same failure.
+    fn shrink(&self) -> Vec<GenExpr> {
+        let mut out = Vec::new();
+        if *self != GenExpr::Num(0) {
+            out.push(GenExpr::Num(0));
+        }
+        match self {
+            GenExpr::Num(n) if *n > 1 => out.push(GenExpr::Num(1)),
+            GenExpr::Unary(op, e) => {
+                out.push((**e).clone());
+                out.extend(e.shrink().into_iter().map(|s| GenExpr::Unary(*op, Box::new(s))));
+            }
+            GenExpr::Binary(op, l, r) => {
+                out.push((**l).clone());
+                out.push((**r).clone());
+                out.extend(l.shrink().into_iter().map(|s| GenExpr::Binary(*op, Box::new(s), r.clone())));
+                out.extend(r.shrink().into_iter().map(|s| GenExpr::Binary(*op, l.clone(), Box::new(s))));
+            }
+            GenExpr::Call(f, args) => {
+                for (i, a) in args.iter().enumerate() {
+                    out.extend(a.shrink().into_iter().map(|s| {
+                        let mut args = args.clone();
+                        args[i] = s;
+                        GenExpr::Call(*f, args)
+                    }));
+                }
+            }
+            _ => {}
+        }
+        out
+    }
+}
+
+fn render_stmts(stmts: &[GenStmt], indent: usize, out: &mut String) {
+    let pad = "    ".repeat(indent);
+    for s in stmts {
+        match s {
+            GenStmt::Assign(v, e) => {
+                let mut text = String::new();
+                e.render(&mut text);
+                writeln!(out, "{}{} = ({}) & 4095;", pad, v, text).unwrap();
+            }
+            GenStmt::Loop { counter, times, body, is_for: true } => {
+                writeln!(out, "{}for ({} = 0; {} < {}; {}++) {{", pad, counter, counter, times, counter).unwrap();
+                render_stmts(body, indent + 1, out);
+                writeln!(out, "{}}}", pad).unwrap();
+            }
+            GenStmt::Loop { counter, times, body, is_for: false } => {
+                writeln!(out, "{}{} = 0;", pad, counter).unwrap();
+                writeln!(out, "{}while ({} < {}) {{", pad, counter, times).unwrap();
+                render_stmts(body, indent + 1, out);
+                writeln!(out, "{}    {} = {} + 1;", pad, counter, counter).unwrap();
+                writeln!(out, "{}}}", pad).unwrap();
+            }
+        }
+    }
+}
+
+fn shrink_stmts(stmts: &[GenStmt]) -> Vec<Vec<GenStmt>> {
+    let mut out = Vec::new();
+    for i in 0..stmts.len() {
+        let mut fewer = stmts.to_vec();
+        fewer.remove(i);
+        out.push(fewer);
+        match &stmts[i] {
+            GenStmt::Assign(v, e) => {
+                for s in e.shrink() {
+                    let mut next = stmts.to_vec();
+                    next[i] = GenStmt::Assign(v.clone(), s);
+                    out.push(next);
+                }
+            }
+            GenStmt::Loop { counter, times, body, is_for } => {
+                for b in shrink_stmts(body) {
+                    let mut next = stmts.to_vec();
+                    next[i] = GenStmt::Loop { counter: counter.clone(), times: *times, body: b, is_for: *is_for };
+                    out.push(next);
+                }
+                if *times > 1 {
+                    let mut next = stmts.to_vec();
+                    next[i] = GenStmt::Loop { counter: counter.clone(), times: 1, body: body.clone(), is_for: *is_for };
+                    out.push(next);
+                }
+            }
+        }
+    }
+    out
+}
+
+impl GenProgram {
+    /// The program as C4 source.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (f, func) in self.helpers.iter().enumerate() {
+            let params: Vec<String> = (0..func.arity).map(|i| format!("int p{}", i)).collect();
+            let mut body = String::new();
+            func.body.render(&mut body);
+            writeln!(out, "int f{}({}) {{ return {}; }}", f, params.join(", "), body).unwrap();
+        }
+        writeln!(out, "int main() {{").unwrap();
+        let decls: Vec<&str> = self.locals.iter().map(|(n, _)| n.as_str())
+            .chain(self.counters.iter().map(String::as_str))
+            .collect();
+        if !decls.is_empty() {
+            writeln!(out, "    int {};", decls.join(", ")).unwrap();
+        }
+        for (n, v) in &self.locals {
+            writeln!(out, "    {} = {};", n, v).unwrap();
+        }
+        render_stmts(&self.stmts, 1, &mut out);
+        let mut ret = String::new();
+        self.ret.render(&mut ret);
+        writeln!(out, "    return {};", ret).unwrap();
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    /// Candidate simplifications, smallest changes last.
+    fn shrink(&self) -> Vec<GenProgram> {
+        let mut out = Vec::new();
+        for s in self.ret.shrink() {
+            out.push(GenProgram { ret: s, ..self.clone() });
+        }
+        for stmts in shrink_stmts(&self.stmts) {
+            out.push(GenProgram { stmts, ..self.clone() });
+        }
+        for (i, h) in self.helpers.iter().enumerate() {
+            for body in h.body.shrink() {
+                let mut helpers = self.helpers.clone();
+                helpers[i] = GenFunc { arity: h.arity, body };
+                out.push(GenProgram { helpers, ..self.clone() });
+            }
+        }
+        out
+    }
+}
+
+/// Greedily simplify `program` while `fails` keeps returning true.
+pub fn shrink(mut program: GenProgram, fails: impl Fn(&GenProgram) -> bool) -> GenProgram {
+    'outer: loop {
+        for candidate in program.shrink() {
+            if fails(&candidate) {
+                program = candidate;
+                continue 'outer;
+            }
+        }
+        return program;
+    }
+}
@@ -0,0 +1,138 @@
+// tests/tracing_tests.rs
+//
+// Checks the spans and events emitted with the `tracing` feature, using a
+// small subscriber that records everything it sees.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use c4_rust_AlRafaah::lexer::Lexer;
+use c4_rust_AlRafaah::run_source;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// One recorded event: the span it happened in and its fields.
+#[derive(Debug)]
+struct Recorded {
+    span: Option<&'static str>,
+    fields: Vec<(String, String)>,
+}
+
+impl Recorded {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Default)]
+struct Log {
+    spans: Vec<&'static str>,
+    stack: Vec<&'static str>,
+    events: Vec<Recorded>,
+}
+
+#[derive(Clone, Default)]
+struct Collector {
+    next_id: Arc<AtomicU64>,
+    log: Arc<Mutex<Log>>,
+}
+
+struct Fields<'a>(&'a mut Vec<(String, String)>);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.push((field.name().to_string(), format!("{:?}", value)));
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        self.log.lock().unwrap().spans.push(span.metadata().name());
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Vec::new();
+        event.record(&mut Fields(&mut fields));
+        let mut log = self.log.lock().unwrap();
+        let span = log.stack.last().copied();
+        log.events.push(Recorded { span, fields });
+    }
+
+    fn enter(&self, id: &Id) {
+        let mut log = self.log.lock().unwrap();
+        let name = log.spans[id.into_u64() as usize - 1];
+        log.stack.push(name);
+    }
+
+    fn exit(&self, _: &Id) {
+        self.log.lock().unwrap().stack.pop();
+    }
+}
+
+fn collect(f: impl FnOnce()) -> Log {
+    let collector = Collector::default();
+    tracing::subscriber::with_default(collector.clone(), f);
+    Arc::try_unwrap(collector.log).ok().unwrap().into_inner().unwrap()
+}
+
+fn event_in<'a>(log: &'a Log, span: &str) -> &'a Recorded {
+    log.events
+        .iter()
+        .find(|e| e.span == Some(span))
+        .unwrap_or_else(|| panic!("no event in span {span}: {:?}", log.events))
+}
+
+#[test]
+fn pipeline_emits_a_span_per_phase() {
+    let log = collect(|| {
+        let outcome = run_source("int main() { return 6 * 7; } int f() { return 1; }").unwrap();
+        assert_eq!(outcome.exit_code, 42);
+    });
+    assert_eq!(log.spans, ["parse", "compile", "vm::run"]);
+
+    assert_eq!(event_in(&log, "parse").field("items"), Some("2"));
+
+    let functions: Vec<_> = log
+        .events
+        .iter()
+        .filter(|e| e.span == Some("compile") && e.field("function").is_some())
+        .collect();
+    assert_eq!(functions.len(), 2);
+    assert_eq!(functions[0].field("function"), Some("main"));
+    assert_eq!(functions[1].field("function"), Some("f"));
+    assert_eq!(functions[1].field("instructions"), Some("4"));
+
+    let run = event_in(&log, "vm::run");
+    assert_eq!(run.field("exit_code"), Some("42"));
+    assert!(run.field("instructions").is_some());
+}
+
+#[test]
+fn runtime_errors_are_reported_on_the_run_span() {
+    let log = collect(|| {
+        assert!(run_source("int main() { return 1 / 0; }").is_err());
+    });
+    let run = event_in(&log, "vm::run");
+    assert_eq!(run.field("error"), Some("division by zero at pc 6"));
+    assert_eq!(run.field("exit_code"), None);
+}
+
+#[test]
+fn tokenize_all_reports_token_count() {
+    let log = collect(|| {
+        Lexer::new("int main() { return 0; }").tokenize_all().unwrap();
+    });
+    assert_eq!(log.spans, ["lex"]);
+    assert_eq!(event_in(&log, "lex").field("tokens"), Some("9"));
+}
@@ -0,0 +1,66 @@
+// tests/visitor_tests.rs
+
+use c4_rust_AlRafaah::ast::*;
+use c4_rust_AlRafaah::parser::Parser;
+use c4_rust_AlRafaah::visitor::{walk_expr, walk_expr_mut, AstCounter, Visitor, VisitorMut};
+
+fn parse(src: &str) -> Program {
+    Parser::new(src).and_then(|mut p| p.parse_program()).expect("parsing failed")
+}
+
+#[test]
+fn counter_counts_every_expression() {
+    // `return a + b * c;` is 5: the sum, the product, and three variables.
+    assert_eq!(AstCounter::count(&parse("int f(int a, int b, int c) { return a + b * c; }")), 5);
+    // The call, its callee and its two arguments; then `x = -1` is 4.
+    assert_eq!(AstCounter::count(&parse("int f(int x) { f(x, 2); x = -1; return; }")), 8);
+    // Every clause of a `for`, plus conditions, casts and sizeof.
+    let src = "int f(int i) { for (i = 0; i < 3; i++) if (i) return (int)sizeof(char); }";
+    assert_eq!(AstCounter::count(&parse(src)), 11);
+    assert_eq!(AstCounter::count(&parse("int x; enum { A }; int main() { ; }")), 0);
+}
+
+#[test]
+fn visitor_sees_types_and_operators_in_source_order() {
+    #[derive(Default)]
+    struct Log(Vec<String>);
+    impl<'ast> Visitor<'ast> for Log {
+        fn visit_type(&mut self, ty: &'ast Type) {
+            self.0.push(format!("{:?}", ty));
+        }
+        fn visit_binop(&mut self, op: &'ast BinOp) {
+            self.0.push(format!("{:?}", op));
+        }
+        fn visit_unop(&mut self, op: &'ast UnOp) {
+            self.0.push(format!("{:?}", op));
+        }
+        fn visit_expr(&mut self, expr: &'ast Expr) {
+            if let ExprKind::Var(name) = &expr.kind {
+                self.0.push(name.clone());
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    let mut log = Log::default();
+    log.visit_program(&parse("char g; int f(int a) { return -a * g; }"));
+    assert_eq!(log.0, ["Char", "Int", "Int", "Neg", "a", "Mul", "g"]);
+}
+
+#[test]
+fn visitor_mut_rewrites_in_place() {
+    // Doubles every literal.
+    struct Double;
+    impl VisitorMut for Double {
+        fn visit_expr(&mut self, expr: &mut Expr) {
+            if let ExprKind::Num(n) = &mut expr.kind {
+                *n *= 2;
+            }
+            walk_expr_mut(self, expr);
+        }
+    }
+
+    let mut program = parse("int f(int x) { while (x < 5) x = x + 1; return x * 3; }");
+    Double.visit_program(&mut program);
+    assert_eq!(program, parse("int f(int x) { while (x < 10) x = x + 2; return x * 6; }"));
+}
@@ -1,230 +1,1544 @@
-use c4_rust_AlRafaah::bytecode::*;
-use c4_rust_AlRafaah::vm::VM;
-use c4_rust_AlRafaah::ast::*;
-
-// Manual Bytecode Tests 
-
-fn run_chunk(chunk: Chunk) -> i64 {
-    let mut vm = VM::new();
-    vm.run(&chunk)
-}
-
-#[test]
-fn test_addition() {
-    let mut chunk = Chunk::default();
-    chunk.push_int(OpCode::IMM, 40);
-    chunk.push(OpCode::PSH);
-    chunk.push_int(OpCode::IMM, 2);
-    chunk.push(OpCode::ADD);
-    chunk.push(OpCode::EXIT);
-
-    assert_eq!(run_chunk(chunk), 42);
-}
-
-#[test]
-fn test_comparisons() {
-    let mut chunk = Chunk::default();
-    chunk.push_int(OpCode::IMM, 10);
-    chunk.push(OpCode::PSH);
-    chunk.push_int(OpCode::IMM, 20);
-    chunk.push(OpCode::LT);
-    chunk.push(OpCode::EXIT);
-    assert_eq!(run_chunk(chunk), 1);
-}
-
-#[test]
-fn test_conditional_jump_false() {
-    let mut chunk = Chunk::default();
-    chunk.push_int(OpCode::IMM, 0);
-    chunk.push_jump(OpCode::BZ, 4);
-    chunk.push_int(OpCode::IMM, 100);
-    chunk.push(OpCode::JMP);
-    chunk.push_int(OpCode::IMM, 42);
-    chunk.push(OpCode::EXIT);
-
-    assert_eq!(run_chunk(chunk), 42);
-}
-
-#[test]
-fn test_conditional_jump_true() {
-    let mut chunk = Chunk::default();
-    chunk.push_int(OpCode::IMM, 1);
-    chunk.push_jump(OpCode::BZ, 4);
-    chunk.push_int(OpCode::IMM, 42);
-    chunk.push(OpCode::EXIT);
-    chunk.push_int(OpCode::IMM, 999);
-
-    assert_eq!(run_chunk(chunk), 42);
-}
-
-#[test]
-fn test_stack_and_load_store() {
-    let mut body = Chunk::default();
-    body.push_int(OpCode::ENT, 1);
-    body.push_int(OpCode::IMM, 123);
-    body.push(OpCode::PSH);
-    body.push_int(OpCode::LEA, 0);
-    body.push(OpCode::SI);
-    body.push_int(OpCode::LEA, 0);
-    body.push(OpCode::LI);
-    body.push(OpCode::LEV);
-
-    let mut wrapper = Chunk::default();
-    let entry_point = wrapper.code.len() + 2;
-    wrapper.push_call(OpCode::JSR, entry_point);
-    wrapper.push(OpCode::EXIT);
-    wrapper.code.extend(body.code);
-
-    assert_eq!(run_chunk(wrapper), 123);
-}
-
-#[test]
-fn test_nested_arithmetic() {
-    let mut chunk = Chunk::default();
-    chunk.push_int(OpCode::IMM, 5);
-    chunk.push(OpCode::PSH);
-    chunk.push_int(OpCode::IMM, 3);
-    chunk.push(OpCode::ADD);
-    chunk.push(OpCode::PSH);
-    chunk.push_int(OpCode::IMM, 2);
-    chunk.push(OpCode::MUL);
-    chunk.push(OpCode::EXIT);
-
-    assert_eq!(run_chunk(chunk), 16);
-}
-
-#[test]
-fn test_equality_logic() {
-    let mut chunk = Chunk::default();
-    chunk.push_int(OpCode::IMM, 10);
-    chunk.push(OpCode::PSH);
-    chunk.push_int(OpCode::IMM, 10);
-    chunk.push(OpCode::EQ);
-    chunk.push(OpCode::EXIT);
-
-    assert_eq!(run_chunk(chunk), 1);
-}
-
-#[test]
-fn test_call_and_return() {
-    let mut chunk = Chunk::default();
-    chunk.push_call(OpCode::JSR, 2);
-    chunk.push(OpCode::EXIT);
-    chunk.push_int(OpCode::IMM, 42);
-    chunk.push(OpCode::LEV);
-
-    assert_eq!(run_chunk(chunk), 42);
-}
-
-#[test]
-fn test_ent_adj_lev_function_frame() {
-    let mut body = Chunk::default();
-    body.push_int(OpCode::ENT, 1);
-    body.push_int(OpCode::IMM, 99);
-    body.push(OpCode::PSH);
-    body.push_int(OpCode::LEA, 0);
-    body.push(OpCode::SI);
-    body.push_int(OpCode::LEA, 0);
-    body.push(OpCode::LI);
-    body.push(OpCode::LEV);
-
-    let mut chunk = Chunk::default();
-    let func_start = chunk.code.len() + 2;
-    chunk.push_call(OpCode::JSR, func_start);
-    chunk.push(OpCode::EXIT);
-    chunk.code.extend(body.code);
-
-    assert_eq!(run_chunk(chunk), 99);
-}
-
-// AST → Bytecode → VM Tests 
-
-fn run_ast(program: Program) -> i64 {
-    let mut chunk = Chunk::default();
-    program.compile(&mut chunk).unwrap();
-    let mut vm = VM::new();
-    vm.run(&chunk)
-}
-
-#[test]
-fn test_ast_simple_return() {
-    let program = Program {
-        items: vec![Item::Function(FuncDef {
-            name: "main".into(),
-            params: vec![],
-            locals: vec![],
-            ret: Type::Int,
-            body: Block {
-                stmts: vec![Stmt::Return(Some(Expr::Num(42)))],
-            },
-        })],
-    };
-    assert_eq!(run_ast(program), 42);
-}
-
-#[test]
-fn test_ast_addition() {
-    let program = Program {
-        items: vec![Item::Function(FuncDef {
-            name: "main".into(),
-            params: vec![],
-            locals: vec![],
-            ret: Type::Int,
-            body: Block {
-                stmts: vec![Stmt::Return(Some(Expr::Binary {
-                    op: BinOp::Add,
-                    left: Box::new(Expr::Num(20)),
-                    right: Box::new(Expr::Num(22)),
-                }))],
-            },
-        })],
-    };
-    assert_eq!(run_ast(program), 42);
-}
-
-#[test]
-fn test_ast_nested_binary_expression() {
-    let program = Program {
-        items: vec![Item::Function(FuncDef {
-            name: "main".into(),
-            params: vec![],
-            locals: vec![],
-            ret: Type::Int,
-            body: Block {
-                stmts: vec![Stmt::Return(Some(Expr::Binary {
-                    op: BinOp::Mul,
-                    left: Box::new(Expr::Binary {
-                        op: BinOp::Add,
-                        left: Box::new(Expr::Num(2)),
-                        right: Box::new(Expr::Num(3)),
-                    }),
-                    right: Box::new(Expr::Num(8)),
-                }))],
-            },
-        })],
-    };
-    assert_eq!(run_ast(program), 40);
-}
-
-#[test]
-fn test_ast_expression_stmt_discarded() {
-    let program = Program {
-        items: vec![Item::Function(FuncDef {
-            name: "main".into(),
-            params: vec![],
-            locals: vec![],
-            ret: Type::Int,
-            body: Block {
-                stmts: vec![
-                    Stmt::Expr(Expr::Binary {
-                        op: BinOp::Add,
-                        left: Box::new(Expr::Num(1)),
-                        right: Box::new(Expr::Num(2)),
-                    }),
-                    Stmt::Return(Some(Expr::Num(5))),
-                ],
-            },
-        })],
-    };
-    assert_eq!(run_ast(program), 5);
-}
+use c4_rust_AlRafaah::bytecode::*;
+use c4_rust_AlRafaah::vfs::{MemFs, SharedFs};
+use c4_rust_AlRafaah::vm::{Rng, VmConfig, VmError, VM, MAX_OPEN_FILES, RAND_MAX};
+use c4_rust_AlRafaah::ast::*;
+use c4_rust_AlRafaah::error::Error;
+use c4_rust_AlRafaah::parser::Parser;
+use c4_rust_AlRafaah::symtable::{Symbol, SymbolTable};
+
+// Manual Bytecode Tests 
+
+fn run_chunk(chunk: Chunk) -> i64 {
+    let mut vm = VM::new();
+    vm.run(&chunk).unwrap()
+}
+
+#[test]
+fn test_addition() {
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 40);
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 2);
+    chunk.push(OpCode::ADD);
+    chunk.push(OpCode::EXIT);
+
+    assert_eq!(run_chunk(chunk), 42);
+}
+
+#[test]
+fn test_comparisons() {
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 10);
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 20);
+    chunk.push(OpCode::LT);
+    chunk.push(OpCode::EXIT);
+    assert_eq!(run_chunk(chunk), 1);
+}
+
+#[test]
+fn test_conditional_jump_false() {
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 0);
+    chunk.push_jump(OpCode::BZ, 4);
+    chunk.push_int(OpCode::IMM, 100);
+    chunk.push(OpCode::JMP);
+    chunk.push_int(OpCode::IMM, 42);
+    chunk.push(OpCode::EXIT);
+
+    assert_eq!(run_chunk(chunk), 42);
+}
+
+#[test]
+fn test_conditional_jump_true() {
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 1);
+    chunk.push_jump(OpCode::BZ, 4);
+    chunk.push_int(OpCode::IMM, 42);
+    chunk.push(OpCode::EXIT);
+    chunk.push_int(OpCode::IMM, 999);
+
+    assert_eq!(run_chunk(chunk), 42);
+}
+
+#[test]
+fn test_stack_and_load_store() {
+    let mut body = Chunk::default();
+    body.push_int(OpCode::ENT, 1);
+    body.push_int(OpCode::LEA, 0);
+    body.push(OpCode::PSH);
+    body.push_int(OpCode::IMM, 123);
+    body.push(OpCode::SI);
+    body.push_int(OpCode::LEA, 0);
+    body.push(OpCode::LI);
+    body.push(OpCode::LEV);
+
+    let mut wrapper = Chunk::default();
+    let entry_point = wrapper.code.len() + 2;
+    wrapper.push_call(OpCode::JSR, entry_point);
+    wrapper.push(OpCode::EXIT);
+    wrapper.code.extend(body.code);
+
+    assert_eq!(run_chunk(wrapper), 123);
+}
+
+#[test]
+fn test_nested_arithmetic() {
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 5);
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 3);
+    chunk.push(OpCode::ADD);
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 2);
+    chunk.push(OpCode::MUL);
+    chunk.push(OpCode::EXIT);
+
+    assert_eq!(run_chunk(chunk), 16);
+}
+
+#[test]
+fn test_equality_logic() {
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 10);
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 10);
+    chunk.push(OpCode::EQ);
+    chunk.push(OpCode::EXIT);
+
+    assert_eq!(run_chunk(chunk), 1);
+}
+
+#[test]
+fn test_call_and_return() {
+    let mut chunk = Chunk::default();
+    chunk.push_call(OpCode::JSR, 2);
+    chunk.push(OpCode::EXIT);
+    chunk.push_int(OpCode::IMM, 42);
+    chunk.push(OpCode::LEV);
+
+    assert_eq!(run_chunk(chunk), 42);
+}
+
+#[test]
+fn test_ent_adj_lev_function_frame() {
+    let mut body = Chunk::default();
+    body.push_int(OpCode::ENT, 1);
+    body.push_int(OpCode::LEA, 0);
+    body.push(OpCode::PSH);
+    body.push_int(OpCode::IMM, 99);
+    body.push(OpCode::SI);
+    body.push_int(OpCode::LEA, 0);
+    body.push(OpCode::LI);
+    body.push(OpCode::LEV);
+
+    let mut chunk = Chunk::default();
+    let func_start = chunk.code.len() + 2;
+    chunk.push_call(OpCode::JSR, func_start);
+    chunk.push(OpCode::EXIT);
+    chunk.code.extend(body.code);
+
+    assert_eq!(run_chunk(chunk), 99);
+}
+
+// AST → Bytecode → VM Tests 
+
+fn run_ast(program: Program) -> i64 {
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    let mut vm = VM::new();
+    vm.run(&chunk).unwrap()
+}
+
+#[test]
+fn test_ast_simple_return() {
+    let program = Program {
+        items: vec![Item::Function(FuncDef {
+            name: "main".into(),
+            params: vec![],
+            locals: vec![],
+            ret: Type::Int,
+            body: Block {
+                locals: vec![],
+                stmts: vec![StmtKind::Return(Some(ExprKind::Num(42).into())).into()],
+            },
+        })],
+    };
+    assert_eq!(run_ast(program), 42);
+}
+
+#[test]
+fn test_ast_addition() {
+    let program = Program {
+        items: vec![Item::Function(FuncDef {
+            name: "main".into(),
+            params: vec![],
+            locals: vec![],
+            ret: Type::Int,
+            body: Block {
+                locals: vec![],
+                stmts: vec![StmtKind::Return(Some(ExprKind::Binary {
+                    op: BinOp::Add,
+                    left: Box::new(ExprKind::Num(20).into()),
+                    right: Box::new(ExprKind::Num(22).into()),
+                }.into())).into()],
+            },
+        })],
+    };
+    assert_eq!(run_ast(program), 42);
+}
+
+#[test]
+fn test_ast_nested_binary_expression() {
+    let program = Program {
+        items: vec![Item::Function(FuncDef {
+            name: "main".into(),
+            params: vec![],
+            locals: vec![],
+            ret: Type::Int,
+            body: Block {
+                locals: vec![],
+                stmts: vec![StmtKind::Return(Some(ExprKind::Binary {
+                    op: BinOp::Mul,
+                    left: Box::new(ExprKind::Binary {
+                        op: BinOp::Add,
+                        left: Box::new(ExprKind::Num(2).into()),
+                        right: Box::new(ExprKind::Num(3).into()),
+                    }.into()),
+                    right: Box::new(ExprKind::Num(8).into()),
+                }.into())).into()],
+            },
+        })],
+    };
+    assert_eq!(run_ast(program), 40);
+}
+
+#[test]
+fn test_ast_expression_stmt_discarded() {
+    let program = Program {
+        items: vec![Item::Function(FuncDef {
+            name: "main".into(),
+            params: vec![],
+            locals: vec![],
+            ret: Type::Int,
+            body: Block {
+                locals: vec![],
+                stmts: vec![
+                    StmtKind::Expr(ExprKind::Binary {
+                        op: BinOp::Add,
+                        left: Box::new(ExprKind::Num(1).into()),
+                        right: Box::new(ExprKind::Num(2).into()),
+                    }.into()).into(),
+                    StmtKind::Return(Some(ExprKind::Num(5).into())).into(),
+                ],
+            },
+        })],
+    };
+    assert_eq!(run_ast(program), 5);
+}
+
+#[test]
+fn test_rand_sequence_is_pinned() {
+    let mut rng = Rng::new(42);
+    let first: Vec<i64> = (0..5).map(|_| rng.rand()).collect();
+    assert_eq!(first, [1220265334, 484179026, 886563538, 1353769503, 1460606294]);
+    assert!(first.iter().all(|v| (0..=RAND_MAX).contains(v)));
+
+    // Reseeding starts the sequence over; no seed means seed 1.
+    let mut again = Rng::new(42);
+    assert_eq!((0..5).map(|_| again.rand()).collect::<Vec<_>>(), first);
+    assert_eq!(Rng::default(), Rng::new(1));
+    assert_eq!(Rng::default().rand(), 908834774);
+}
+
+#[test]
+fn test_rand_and_srand_instructions() {
+    let mut chunk = Chunk::default();
+    chunk.push(OpCode::RAND); // unseeded
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 42);
+    chunk.push(OpCode::SRND);
+    chunk.push(OpCode::RAND);
+    chunk.push(OpCode::SUB);
+    assert_eq!(run_chunk(chunk), 908834774 - 1220265334);
+}
+
+#[test]
+fn test_heap_words_and_leak_report() {
+    // p = malloc(3 * sizeof(int)); *p = 7; return *p; with p kept in
+    // stack slot 0.
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 3 * 8);
+    chunk.push(OpCode::MALC);
+    chunk.push(OpCode::PSH);
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 7);
+    chunk.push(OpCode::SI);
+    chunk.push_int(OpCode::LEA, 0);
+    chunk.push(OpCode::LI);
+    chunk.push(OpCode::LI);
+    chunk.push(OpCode::EXIT);
+
+    let config = VmConfig { stack_size: 16, track_allocations: true, ..VmConfig::default() };
+    let mut vm = VM::with_config(config);
+    assert_eq!(vm.run(&chunk).unwrap(), 7);
+    let report = vm.leak_report(&chunk).unwrap();
+    assert_eq!((report.leaked_blocks(), report.leaked_words(), report.peak_words), (1, 3, 3));
+    assert_eq!(report.sites[0].pc, 1);
+    assert_eq!(report.timeline, [(2, 3)]);
+
+    // Untracked VMs have nothing to report; a reset frees everything.
+    assert!(VM::new().leak_report(&chunk).is_none());
+    vm.reset();
+    assert_eq!(vm.leak_report(&chunk).unwrap().leaked_blocks(), 0);
+}
+
+#[test]
+fn test_heap_exhaustion_and_double_free() {
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 16 * 8 + 1);
+    chunk.push(OpCode::MALC); // more than the 16-word heap
+    chunk.push(OpCode::EXIT);
+    let mut vm = VM::with_config(VmConfig { stack_size: 16, ..VmConfig::default() });
+    assert_eq!(vm.run(&chunk).unwrap(), 0);
+
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 16);
+    chunk.push(OpCode::MALC);
+    chunk.push(OpCode::PSH);
+    chunk.push(OpCode::FREE);
+    chunk.push_int(OpCode::LEA, 0);
+    chunk.push(OpCode::LI);
+    chunk.push(OpCode::FREE);
+    chunk.push(OpCode::EXIT);
+    let mut vm = VM::with_config(VmConfig { stack_size: 16, ..VmConfig::default() });
+    assert_eq!(vm.run(&chunk), Err(VmError::InvalidAddress { pc: 6, addr: 16 * 8 }));
+}
+
+#[test]
+fn test_zero_size_blocks_get_their_own_address() {
+    let src = "int main() { int *p, *q; p = malloc(0); q = malloc(sizeof(int)); *q = 5; \
+               if (p == 0 || p == q) return -1; free(p); free(q); return 5; }";
+    assert_eq!(run_source(src).unwrap(), 5);
+}
+
+#[test]
+fn test_heap_size_is_separate_from_the_stack() {
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 17 * 8);
+    chunk.push(OpCode::MALC);
+    chunk.push(OpCode::EXIT);
+    // A 32-word heap fits 17 words beside a 16-word stack...
+    let mut vm = VM::with_config(VmConfig { stack_size: 16, heap_size: Some(32), ..VmConfig::default() });
+    assert_eq!(vm.run(&chunk).unwrap(), 16 * 8);
+    // ...and a 4-word heap doesn't beside a large one.
+    let mut vm = VM::with_config(VmConfig { heap_size: Some(4), ..VmConfig::default() });
+    assert_eq!(vm.run(&chunk).unwrap(), 0);
+}
+
+// Source → Bytecode → VM Tests
+
+fn run_source(src: &str) -> Result<i64, Error> {
+    let program = Parser::new(src)?.parse_program()?;
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk)?;
+    Ok(VM::new().run(&chunk)?)
+}
+
+#[test]
+fn test_locals_are_stored_and_loaded() {
+    assert_eq!(run_source("int main() { int x; x = 5; return x; }").unwrap(), 5);
+    assert_eq!(run_source("int main() { int a, b; a = 40; b = a + 2; return b; }").unwrap(), 42);
+    // Locals start out zero, and assignment is an expression.
+    assert_eq!(run_source("int main() { int a, b; a = b = 7; return a * 10 + b; }").unwrap(), 77);
+    assert_eq!(run_source("int main() { int x; return x; }").unwrap(), 0);
+    // `char` locals keep only the low byte.
+    assert_eq!(run_source("int main() { char c; c = 321; return c; }").unwrap(), 65);
+}
+
+#[test]
+fn test_block_locals_shadow_and_share_slots() {
+    let src = "int main() {
+        int x;
+        x = 1;
+        { int x; x = 2; { int x; x = 3; } }
+        { int y; y = 10; x = x + y; }
+        return x;
+    }";
+    assert_eq!(run_source(src).unwrap(), 11);
+
+    // The outer x and the deepest inner one are live at once; the two
+    // sibling blocks reuse a slot.
+    let program = Parser::new(src).unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    let entry = chunk.function("main").unwrap().entry;
+    assert_eq!(chunk.code[entry], Instruction::InstrInt(OpCode::ENT, 3));
+}
+
+#[test]
+fn test_undeclared_variables_are_compile_errors() {
+    match run_source("int main() { { int x; x = 1; } return x; }") {
+        Err(Error::Compile(e)) => assert!(e.message.contains("undefined variable: x"), "{}", e),
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+    match run_source("int main() { y = 1; return 0; }") {
+        Err(Error::Compile(e)) => assert!(e.message.contains("undefined variable: y"), "{}", e),
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_do_while_runs_its_body_at_least_once() {
+    assert_eq!(run_source("int main() { int n; n = 1; do n = n * 10; while (0); return n; }").unwrap(), 10);
+    let src = "int main() { int i, sum; i = 1; do { sum = sum + i; i = i + 1; } while (i <= 4); return sum; }";
+    assert_eq!(run_source(src).unwrap(), 10);
+}
+
+#[test]
+fn test_assignment_stores_and_yields_the_value() {
+    assert_eq!(run_source("int main() { int x; x = 4; x = x * x; return x; }").unwrap(), 16);
+    assert_eq!(run_source("int main() { int x; return x = 3; }").unwrap(), 3);
+    // Chained assignment stores the innermost value everywhere.
+    let src = "int main() { int a, b, c; a = b = c = 6; return a + b + c; }";
+    assert_eq!(run_source(src).unwrap(), 18);
+    assert_eq!(run_source("int main() { char c; int i; i = c = 300; return i; }").unwrap(), 44);
+}
+
+#[test]
+fn test_assignment_compiles_to_lea_psh_si() {
+    let program = Parser::new("int main() { int x; x = 7; return 0; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    let entry = chunk.function("main").unwrap().entry;
+    assert_eq!(
+        chunk.code[entry + 1..entry + 5],
+        [
+            Instruction::InstrInt(OpCode::LEA, 0),
+            Instruction::Instr(OpCode::PSH),
+            Instruction::InstrInt(OpCode::IMM, 7),
+            Instruction::Instr(OpCode::SI),
+        ]
+    );
+}
+
+#[test]
+fn test_break_and_continue_jump_out_of_the_innermost_loop() {
+    // `continue` goes to the condition, `break` past it.
+    let src = "int main() { int n; do { n = n + 1; continue; n = 100; } while (n < 4); return n; }";
+    assert_eq!(run_source(src).unwrap(), 4);
+    let src = "int main() { int n; do { n = 7; break; n = 100; } while (1); return n; }";
+    assert_eq!(run_source(src).unwrap(), 7);
+
+    let src = "int main() {
+        int outer, inner;
+        do {
+            outer = outer + 1;
+            do { inner = inner + 10; break; } while (1);
+        } while (outer < 3);
+        return outer + inner;
+    }";
+    assert_eq!(run_source(src).unwrap(), 33);
+    // In a `for`, `continue` still runs the step.
+    let src = "int main() { int i, n; for (i = 0; i < 10; i++) { if (i % 2) continue; n = n + i; } return n; }";
+    assert_eq!(run_source(src).unwrap(), 20);
+    let src = "int main() { int i; for (i = 0; i < 10; i++) if (i == 4) break; return i; }";
+    assert_eq!(run_source(src).unwrap(), 4);
+    let src = "int main() {
+        int i, j, n;
+        for (i = 0; i < 3; i++)
+            for (j = 0; ; j++) { if (j == 2) break; if (i == 1) continue; n = n + 1; }
+        return n * 100 + i * 10 + j;
+    }";
+    assert_eq!(run_source(src).unwrap(), 432);
+}
+
+#[test]
+fn test_break_outside_a_loop_is_a_compile_error() {
+    match run_source("int main() { continue; return 0; }") {
+        Err(Error::Compile(e)) => assert!(e.message.contains("continue outside a loop"), "{}", e),
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+    // Code generation catches it too, without the checker in front.
+    let program = Parser::new("int main() { break; }").unwrap().parse_program().unwrap();
+    let err = program.items[0].compile(&mut Chunk::default()).unwrap_err();
+    assert_eq!(err.message, "break outside a loop or switch");
+}
+
+#[test]
+fn test_switch_dispatches_and_falls_through() {
+    let run = |x: i64| {
+        let src = format!(
+            "int main() {{
+                int n;
+                switch ({}) {{
+                    case 1: n = n + 1;
+                    case 2: n = n + 10; break;
+                    case 3: return 3;
+                    default: n = 100;
+                    case 4: n = n + 1000;
+                }}
+                return n;
+            }}",
+            x
+        );
+        run_source(&src).unwrap()
+    };
+    assert_eq!(run(1), 11); // falls into case 2, which breaks
+    assert_eq!(run(2), 10);
+    assert_eq!(run(3), 3);
+    assert_eq!(run(4), 1000);
+    assert_eq!(run(9), 1100); // default falls through too
+
+    // Without a match or a default the whole switch is skipped.
+    assert_eq!(run_source("int main() { switch (5) { case 1: return 1; } return 2; }").unwrap(), 2);
+    assert_eq!(run_source("int main() { switch (5) { default: return 1; } return 2; }").unwrap(), 1);
+}
+
+#[test]
+fn test_break_in_a_switch_leaves_only_the_switch() {
+    // `continue` passes through the switch to the loop around it.
+    let src = "int main() {
+        int i, n;
+        do {
+            i = i + 1;
+            switch (i) { case 2: continue; case 3: n = n + 100; break; default: n = n + 1; }
+            n = n + 10;
+        } while (i < 4);
+        return n;
+    }";
+    assert_eq!(run_source(src).unwrap(), 1 + 10 + 100 + 10 + 1 + 10);
+
+    match run_source("int main() { switch (1) { case 1: case 1: ; } return 0; }") {
+        Err(Error::Compile(e)) => assert!(e.message.contains("duplicate case value: 1"), "{}", e),
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+    match run_source("int main() { switch (1) { case 1: continue; } return 0; }") {
+        Err(Error::Compile(e)) => assert!(e.message.contains("continue outside a loop"), "{}", e),
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_while_loops_test_before_each_pass() {
+    assert_eq!(run_source("int main(){int i; i=0; while(i<10) i = i + 1; return i;}").unwrap(), 10);
+    // False on entry: the body never runs.
+    assert_eq!(run_source("int main() { int n; n = 3; while (n > 5) n = 0; return n; }").unwrap(), 3);
+
+    let src = "int main() {
+        int i, sum;
+        while (1) {
+            i = i + 1;
+            while (sum >= 0) { sum = sum + i; break; }
+            switch (i) { case 5: break; default: continue; }
+            break;
+        }
+        return sum * 100 + i;
+    }";
+    assert_eq!(run_source(src).unwrap(), 1505);
+}
+
+#[test]
+fn test_while_compiles_to_a_test_and_a_back_edge() {
+    let program = Parser::new("int main() { int i; while (i) i = 0; return 0; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    let head = chunk.function("main").unwrap().entry + 1;
+    assert_eq!(chunk.code[head + 2], Instruction::Jump(OpCode::BZ, head + 8));
+    assert_eq!(chunk.code[head + 7], Instruction::Jump(OpCode::JMP, head));
+}
+
+#[test]
+fn test_for_loops_run_init_test_body_and_step() {
+    let src = "int main(){int i;int s;s=0;for(i=0;i<5;i++) s=s+i;return s;}";
+    assert_eq!(run_source(src).unwrap(), 10);
+    // False on entry: only the init runs.
+    assert_eq!(run_source("int main() { int i, n; for (i = 7; i < 5; i++) n = 1; return i * 10 + n; }").unwrap(), 70);
+    // Every clause is optional; without a condition the loop runs until a break.
+    let src = "int main() { int i; for (;;) { if (++i == 6) break; } for (; i < 9;) i = i + 1; return i; }";
+    assert_eq!(run_source(src).unwrap(), 9);
+
+    let src = "int main() {
+        int i, j, n;
+        for (i = 0; i < 3; i++)
+            for (j = 0; j < 4; j++)
+                n = n + 1;
+        return n;
+    }";
+    assert_eq!(run_source(src).unwrap(), 12);
+}
+
+#[test]
+fn test_compound_assignment_updates_and_yields_the_new_value() {
+    let src = "int main() {
+        int x, y;
+        x = 6;
+        x += 4; x -= 1; x *= 3; x /= 2; x %= 7;
+        y = 12;
+        y &= 10; y |= 5; y ^= 3; y <<= 2; y >>= 1;
+        return (x += 100) * 1000 + y;
+    }";
+    assert_eq!(run_source(src).unwrap(), 106 * 1000 + 28);
+
+    // A char target stores a byte.
+    assert_eq!(run_source("int main() { char c; c = 250; c += 10; return c; }").unwrap(), 4);
+}
+
+#[test]
+fn test_compound_assignment_computes_the_address_once() {
+    let program = Parser::new("int main() { int x; x += 2; return x; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    let body = chunk.function("main").unwrap().entry + 1;
+    use Instruction::*;
+    assert_eq!(
+        chunk.code[body..body + 7],
+        [
+            InstrInt(OpCode::LEA, 0),
+            Instr(OpCode::PSH),
+            Instr(OpCode::LI),
+            Instr(OpCode::PSH),
+            InstrInt(OpCode::IMM, 2),
+            Instr(OpCode::ADD),
+            Instr(OpCode::SI),
+        ]
+    );
+}
+
+#[test]
+fn test_calls_reach_functions_defined_before_and_after_the_caller() {
+    let src = "
+        int seven() { return 7; }
+        int main() { return seven() * 10 + later(); }
+        int later() { return seven() - 5; }";
+    assert_eq!(run_source(src).unwrap(), 72);
+
+    // `main` need not come first: startup code always calls it.
+    assert_eq!(run_source("int one() { return 1; } int main() { return one() + 1; }").unwrap(), 2);
+}
+
+#[test]
+fn test_calls_pop_their_arguments() {
+    // With the arguments left on the stack, the caller's next
+    // pushed operand would land in the wrong place; check it all adds up.
+    let src = "
+        int ignore(int a, int b) { return 3; }
+        int main() { int x; x = 5; return ignore(1, 2) + ignore(x, x) * 10 + x * 100; }";
+    assert_eq!(run_source(src).unwrap(), 533);
+}
+
+#[test]
+fn test_forward_calls_are_backpatched() {
+    let program = Parser::new("int main() { return ping(); } int ping() { return pong(); } int pong() { return ping(); }")
+        .unwrap()
+        .parse_program()
+        .unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    let entry = |name| chunk.function(name).unwrap().entry;
+    assert_eq!(chunk.code[0], Instruction::Call(OpCode::JSR, entry("main")));
+    assert!(chunk.code.contains(&Instruction::Call(OpCode::JSR, entry("ping"))));
+    // Mutual recursion: each calls the other.
+    assert_eq!(chunk.code[entry("ping") + 1], Instruction::Call(OpCode::JSR, entry("pong")));
+    assert_eq!(chunk.code[entry("pong") + 1], Instruction::Call(OpCode::JSR, entry("ping")));
+    assert!(chunk.symbols.is_resolved());
+}
+
+#[test]
+fn test_parameters_are_read_from_the_callers_arguments() {
+    assert_eq!(run_source("int add(int a, int b) { return a + b; } int main() { return add(40, 2); }").unwrap(), 42);
+    // Order matters: the first argument is the first parameter.
+    assert_eq!(run_source("int sub(int a, int b) { return a - b; } int main() { return sub(50, 8); }").unwrap(), 42);
+    // Parameters are assignable, and a block's local may shadow one.
+    let src = "int f(int x) { int y; x += 1; y = x; { int x; x = 100; y += x; } return x * 1000 + y; }
+               int main() { return f(4); }";
+    assert_eq!(run_source(src).unwrap(), 5105);
+}
+
+#[test]
+fn test_recursive_factorial() {
+    let src = "
+        int fact(int n) {
+            switch (n) { case 0: return 1; }
+            return n * fact(n - 1);
+        }
+        int main() { return fact(10); }";
+    assert_eq!(run_source(src).unwrap(), 3628800);
+}
+
+#[test]
+fn test_mutual_recursion() {
+    let src = "
+        int is_even(int n) { if (n == 0) return 1; else return is_odd(n - 1); }
+        int is_odd(int n) { if (n == 0) return 0; else return is_even(n - 1); }
+        int main() { return is_even(10) * 10 + is_odd(7); }";
+    assert_eq!(run_source(src).unwrap(), 11);
+
+    // Calls nest as arguments, and each pops its own arguments: a
+    // thousand calls leave the stack where it started.
+    let src = "int add(int a, int b) { return a + b; }
+        int main() { int i, t; i = 0; t = 0; while (i < 1000) { t = add(t, add(i, 1)); i++; } return t; }";
+    assert_eq!(run_source(src).unwrap(), 500500);
+    let mut chunk = Chunk::default();
+    Parser::new(src).unwrap().parse_program().unwrap().compile(&mut chunk).unwrap();
+    let mut vm = VM::new();
+    vm.run(&chunk).unwrap();
+    assert!(vm.max_stack_depth() < 16, "{}", vm.max_stack_depth());
+
+    // A name that is never defined is an error, not a jump to 0.
+    let err = run_source("int main() { return nowhere(1); }").unwrap_err();
+    assert!(err.to_string().contains("bad function call: nowhere"), "{}", err);
+}
+
+#[test]
+fn test_unary_operators() {
+    assert_eq!(run_source("int main() { return -5; }").unwrap(), -5);
+    assert_eq!(run_source("int main() { return !0; }").unwrap(), 1);
+    assert_eq!(run_source("int main() { return !7; }").unwrap(), 0);
+    assert_eq!(run_source("int main() { return ~0; }").unwrap(), -1);
+    assert_eq!(run_source("int main() { int x; x = 6; return +x - -x + ~x; }").unwrap(), 5);
+}
+
+#[test]
+fn test_globals_live_in_the_data_segment() {
+    assert_eq!(run_source("int g; int main() { g = 7; return g; }").unwrap(), 7);
+    // Zeroed at startup, shared by every function, and usable by functions
+    // defined ahead of them.
+    let src = "
+        int get() { return total; }
+        int total, calls;
+        void add(int n) { total = total + n; calls += 1; }
+        int main() { add(30); add(12); return get() * 10 + calls; }";
+    assert_eq!(run_source(src).unwrap(), 422);
+    // A local or parameter shadows a global of the same name.
+    let src = "int x; int f(int x) { x = 5; return x; } int main() { x = 1; return f(2) * 10 + x; }";
+    assert_eq!(run_source(src).unwrap(), 51);
+
+    let program = Parser::new("int a; char *p; int main() { a = 1; return 0; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    assert_eq!(chunk.data, [0, 0]);
+    assert_eq!(chunk.global("p").map(|g| g.offset), Some(8));
+    assert!(chunk.code.contains(&Instruction::InstrInt(OpCode::GLO, 0)));
+}
+
+#[test]
+fn test_enum_constants_compile_to_immediates() {
+    assert_eq!(run_source("enum { A = 1, B, C = 10 }; int main() { return A + B + C; }").unwrap(), 13);
+    // Numbering starts at 0, and functions may use constants declared
+    // after them.
+    let src = "int last() { return Z; } enum { X, Y, Z }; int main() { return last() * 10 + Y; }";
+    assert_eq!(run_source(src).unwrap(), 21);
+
+    let program = Parser::new("enum { K = 4 }; int main() { return K; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    assert_eq!(chunk.constant("K"), Some(4));
+    assert!(chunk.code.contains(&Instruction::InstrInt(OpCode::IMM, 4)));
+    assert!(chunk.data.is_empty());
+
+    // A constant's name can't be reused by a global or a local.
+    for (src, message) in [
+        ("enum { A }; int A; int main() { return 0; }", "duplicate global definition: A"),
+        ("enum { A }; int main() { int A; return 0; }", "A is already an enum constant"),
+        ("enum { A }; int f(int A) { return A; } int main() { return 0; }", "A is already an enum constant"),
+    ] {
+        let err = run_source(src).unwrap_err();
+        assert!(err.to_string().contains(message), "{}: {}", src, err);
+    }
+}
+
+#[test]
+fn test_symbol_table_resolves_calls_by_name() {
+    let src = "int helper(int n) { return n * 3; } int main() { return helper(14); }";
+    assert_eq!(run_source(src).unwrap(), 42);
+
+    // Compiled item by item, a caller waits for a callee that comes later.
+    let program = Parser::new("int main() { return later(); } int later() { return 9; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.items[0].compile(&mut chunk).unwrap();
+    assert_eq!(chunk.symbols.unresolved().collect::<Vec<_>>(), ["later"]);
+    program.items[1].compile(&mut chunk).unwrap();
+    SymbolTable::patch(&mut chunk).unwrap();
+    assert!(chunk.symbols.is_resolved());
+    let entry = SymbolTable::entry(&chunk, "later").unwrap();
+    assert_eq!(chunk.code[1], Instruction::Call(OpCode::JSR, entry));
+
+    // A callee that never turns up is an error.
+    let mut chunk = Chunk::default();
+    program.items[0].compile(&mut chunk).unwrap();
+    let err = SymbolTable::patch(&mut chunk).unwrap_err();
+    assert!(err.to_string().contains("bad function call: later"), "{}", err);
+}
+
+#[test]
+fn test_string_literals_live_in_the_data_segment() {
+    // The literal's address reads back its bytes, then the NUL.
+    for (i, byte) in [104, 105, 0].into_iter().enumerate() {
+        let mut chunk = Chunk::default();
+        let offset = chunk.intern(b"hi");
+        chunk.push_int(OpCode::GLO, (offset + i) as i64);
+        chunk.push(OpCode::LC);
+        chunk.push(OpCode::EXIT);
+        assert_eq!(run_chunk(chunk), byte);
+    }
+    assert_eq!(run_source(r#"int main() { return "hi" == "hi"; }"#).unwrap(), 1);
+    // Escapes reach every byte value.
+    assert_eq!(run_source(r#"int main() { char *s; s = "\xff\377\x01"; return s[0] + s[1] + s[2]; }"#).unwrap(), 511);
+
+    // Identical literals share one copy; different ones don't.
+    let src = r#"int main() { char *a; char *b; char *c; a = "ab"; b = "ab"; c = "b"; return (a == b) * 10 + (a == c); }"#;
+    assert_eq!(run_source(src).unwrap(), 10);
+    let program = Parser::new(src).unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    // Each is packed into words of its own, little-endian.
+    assert_eq!(chunk.data, [0x6261, 98]);
+    assert_eq!(chunk.strings, [(b"ab".to_vec(), 0), (b"b".to_vec(), 8)]);
+    assert_eq!(chunk.code.iter().filter(|i| **i == Instruction::InstrInt(OpCode::GLO, 0)).count(), 2);
+
+    // Strings follow the globals declared ahead of them.
+    let program = Parser::new(r#"int g; int main() { char *s; s = "z"; return g; }"#).unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    assert_eq!(chunk.data, [0, 122]);
+    assert!(chunk.code.contains(&Instruction::InstrInt(OpCode::GLO, 8)));
+}
+
+#[test]
+fn test_global_written_by_one_function_is_read_by_another() {
+    let src = "int counter; void set() { counter = 41; } int get() { return counter + 1; } int main() { set(); return get(); }";
+    assert_eq!(run_source(src).unwrap(), 42);
+
+    let mut chunk = Chunk::default();
+    assert_eq!(chunk.alloc_global("a", Type::Int), 0);
+    assert_eq!(chunk.intern(b"s"), 8);
+    assert_eq!(chunk.alloc_global("b", Type::Ptr(Box::new(Type::Char))), 16);
+    assert_eq!(chunk.data, [0, 115, 0]);
+    assert_eq!(chunk.global("b").map(|g| &g.ty), Some(&Type::Ptr(Box::new(Type::Char))));
+}
+
+#[test]
+fn test_enum_variant_as_return_value() {
+    assert_eq!(run_source("enum { RED, GREEN = 5, BLUE }; int main() { return BLUE; }").unwrap(), 6);
+
+    let program = Parser::new("enum { ONE = 1 }; int two; int main() { return ONE; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    assert_eq!(SymbolTable::resolve(&chunk, "ONE"), Some(Symbol::Constant(1)));
+    assert_eq!(SymbolTable::resolve(&chunk, "two"), Some(Symbol::Global { offset: 0, ty: Type::Int }));
+    assert_eq!(SymbolTable::resolve(&chunk, "main"), None);
+    assert_eq!(chunk.code[chunk.function("main").unwrap().entry + 1], Instruction::InstrInt(OpCode::IMM, 1));
+}
+
+#[test]
+fn test_malloc_and_free_from_source() {
+    // Blocks are handed out in order, and a freed block's words stay put.
+    let src = "int main() { int p, q; p = malloc(4 * sizeof(int)); q = malloc(1); free(p); free(q); return q - p; }";
+    assert_eq!(run_source(src).unwrap(), 32);
+    // Sizes round up to whole words.
+    let src = "int main() { int p, q; p = malloc(9); q = malloc(1); return q - p; }";
+    assert_eq!(run_source(src).unwrap(), 16);
+    assert_eq!(run_source("int main() { free(0); return 1; }").unwrap(), 1);
+
+    // A double free or a bogus address is a runtime error, not a panic.
+    for src in [
+        "int main() { int p; p = malloc(2); free(p); free(p); return 0; }",
+        "int main() { int p; p = malloc(2); free(p + 1); return 0; }",
+        "int main() { free(5); return 0; }",
+    ] {
+        match run_source(src) {
+            Err(Error::Runtime(VmError::InvalidAddress { .. })) => {}
+            other => panic!("{}: expected an invalid address, got {:?}", src, other),
+        }
+    }
+
+    // `SC` keeps the low byte of what it stores in a block.
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 1);
+    chunk.push(OpCode::MALC);
+    chunk.push(OpCode::PSH);
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 0x1FF);
+    chunk.push(OpCode::SC);
+    chunk.push_int(OpCode::LEA, 0);
+    chunk.push(OpCode::LI);
+    chunk.push(OpCode::LI);
+    chunk.push(OpCode::EXIT);
+    assert_eq!(run_chunk(chunk), 0xFF);
+}
+
+#[test]
+fn test_memset_and_memcmp_opcodes() {
+    // memset(p, 0x141, 3) on a fresh block, then read back the byte p[i].
+    let memset_then_load = |i: i64| {
+        let mut chunk = Chunk::default();
+        chunk.push_int(OpCode::IMM, 4);
+        chunk.push(OpCode::MALC);
+        chunk.push(OpCode::PSH); // dest, kept in slot 0
+        chunk.push_int(OpCode::IMM, 0x141);
+        chunk.push(OpCode::PSH);
+        chunk.push_int(OpCode::IMM, 3);
+        chunk.push(OpCode::PSH);
+        chunk.push(OpCode::MSET);
+        chunk.push_int(OpCode::ADJ, 2);
+        chunk.push_int(OpCode::LEA, 0);
+        chunk.push(OpCode::LI);
+        chunk.push(OpCode::PSH);
+        chunk.push_int(OpCode::IMM, i);
+        chunk.push(OpCode::ADD);
+        chunk.push(OpCode::LC);
+        chunk.push(OpCode::EXIT);
+        run_chunk(chunk)
+    };
+    assert_eq!(memset_then_load(2), 0x41);
+    assert_eq!(memset_then_load(3), 0);
+
+    // memcmp of two stack words, byte by byte: the first difference.
+    let memcmp = |x: i64, y: i64, n: i64| {
+        let mut chunk = Chunk::default();
+        for v in [x, y, 0, 8, n] {
+            chunk.push_int(OpCode::IMM, v);
+            chunk.push(OpCode::PSH);
+        }
+        chunk.push(OpCode::MCMP);
+        chunk.push(OpCode::EXIT);
+        run_chunk(chunk)
+    };
+    assert_eq!(memcmp(5, 5, 1), 0);
+    assert_eq!(memcmp(5, 9, 1), -4);
+    assert_eq!(memcmp(0x109, 0x205, 1), 4);
+    assert_eq!(memcmp(0x109, 0x209, 2), -1);
+    assert_eq!(memcmp(5, 9, 0), 0);
+
+    let mut chunk = Chunk::default();
+    chunk.push(OpCode::MSET);
+    assert_eq!(VM::new().run(&chunk), Err(VmError::StackUnderflow { pc: 0 }));
+}
+
+#[test]
+fn test_memset_and_memcmp_from_source() {
+    let src = "
+        int main() {
+            int a, b, before;
+            a = malloc(8); b = malloc(8);
+            memset(a, 'x', 8); memset(b, 'x', 8);
+            before = memcmp(a, b, 8);
+            memset(b + 5, 'y', 1);
+            return before * 100 + (memcmp(a, b, 8) < 0) * 10 + memcmp(b, a, 5);
+        }";
+    assert_eq!(run_source(src).unwrap(), 10);
+    // memset returns its destination.
+    assert_eq!(run_source("int main() { int p; p = malloc(2); return memset(p, 0, 2) == p; }").unwrap(), 1);
+    assert!(run_source("int main() { return memcmp(0, 0); }").is_err());
+}
+
+#[test]
+fn test_memset_by_sizeof_keeps_the_neighbours() {
+    // sizeof counts bytes, as memset does: clearing two ints of a block
+    // leaves the third and fourth alone.
+    let src = "
+        int main() {
+            int *a;
+            a = malloc(4 * sizeof(int));
+            a[0] = 1; a[1] = 2; a[2] = 3; a[3] = 4;
+            memset(a, 0, 2 * sizeof(int));
+            return a[0] * 1000 + a[1] * 100 + a[2] * 10 + a[3];
+        }";
+    assert_eq!(run_source(src).unwrap(), 34);
+    let src = "
+        int main() {
+            int *a, *b;
+            a = malloc(3 * sizeof(int)); b = malloc(3 * sizeof(int));
+            a[0] = b[0] = 7; a[1] = b[1] = 8; a[2] = 1; b[2] = 2;
+            return memcmp(a, b, 2 * sizeof(int)) == 0 && memcmp(a, b, 3 * sizeof(int)) < 0;
+        }";
+    assert_eq!(run_source(src).unwrap(), 1);
+}
+
+#[test]
+fn test_pointer_steps_are_scaled_by_the_pointee() {
+    // Indexes and pointer differences count elements; through a `char *`
+    // the same distance is in bytes, sizeof's unit.
+    let src = "
+        int main() {
+            int *a; char *c;
+            a = malloc(4 * sizeof(int)); c = (char *)a;
+            a[3] = 9;
+            return (&a[1] - &a[0] == 1) + ((char *)(a + 2) - c == 2 * sizeof(int)) * 10 + *(a + 3) * 100;
+        }";
+    assert_eq!(run_source(src).unwrap(), 911);
+
+    // So do `++`, `--`, `+=` and `-=` on a pointer.
+    let src = "
+        int main() {
+            int *a, *p; char *s;
+            a = malloc(4 * sizeof(int)); a[1] = 1; a[2] = 2; a[3] = 3;
+            p = a; p++; p += 2; s = \"xyz\"; s++; s += 1;
+            return *p * 100 + *--p * 10 + (*s == 'z');
+        }";
+    assert_eq!(run_source(src).unwrap(), 321);
+}
+
+#[test]
+fn test_if_takes_the_right_branch() {
+    assert_eq!(run_source("int main() { if (1) return 1; else return 2; }").unwrap(), 1);
+    assert_eq!(run_source("int main() { if (0) return 1; else return 2; }").unwrap(), 2);
+    // Without an else, a false condition skips the then-branch.
+    assert_eq!(run_source("int main() { int x; x = 3; if (x > 5) x = 0; return x; }").unwrap(), 3);
+    // An else binds to the nearest if.
+    let src = "int main() { int a, b; a = 1; if (a) if (b) return 1; else return 2; return 3; }";
+    assert_eq!(run_source(src).unwrap(), 2);
+    let src = "
+        int sign(int n) { if (n < 0) return -1; else if (n == 0) return 0; return 1; }
+        int main() { int i, s; i = -2; while (i <= 2) { s = s * 10 + sign(i) + 1; i = i + 1; } return s; }";
+    assert_eq!(run_source(src).unwrap(), 122);
+
+    let program = Parser::new("int main() { int x; if (x) x = 1; else x = 2; return x; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    let start = chunk.function("main").unwrap().entry + 1;
+    // LEA, LI, BZ else; then: LEA, PSH, IMM, SI, JMP end; else: 4 more.
+    assert_eq!(chunk.code[start + 2], Instruction::Jump(OpCode::BZ, start + 8));
+    assert_eq!(chunk.code[start + 7], Instruction::Jump(OpCode::JMP, start + 12));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_open_read_and_close_a_file() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/read_me.txt");
+    let src = format!(
+        r#"int main() {{
+            int fd, buf, n, more;
+            buf = malloc(64);
+            fd = open("{}", 0);
+            if (fd < 0) return -1;
+            n = read(fd, buf, 64);
+            more = read(fd, buf, 64);
+            if (close(fd) != 0) return -2;
+            if (close(fd) != -1) return -3;
+            return n * 1000 + more * 100 + memcmp(buf, "hello", 5);
+        }}"#,
+        path
+    );
+    assert_eq!(run_source(&src).unwrap(), 12_000);
+
+    // A short read, then a missing file and a descriptor never opened.
+    let src = format!(r#"int main() {{ int b; b = malloc(4); return read(open("{}", 0), b, 4); }}"#, path);
+    assert_eq!(run_source(&src).unwrap(), 4);
+    assert_eq!(run_source(r#"int main() { return open("/no/such/file", 0); }"#).unwrap(), -1);
+    assert_eq!(run_source("int main() { int b; b = malloc(4); return read(7, b, 4); }").unwrap(), -1);
+    assert!(run_source("int main() { return open(0); }").is_err());
+}
+
+fn run_with_files(src: &str, fs: SharedFs) -> i64 {
+    let program = Parser::new(src).unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    VM::with_config(VmConfig { filesystem: Some(fs), ..VmConfig::default() }).run(&chunk).unwrap()
+}
+
+#[test]
+fn test_files_come_from_the_configured_filesystem() {
+    let fs = SharedFs::new(MemFs::new([("data/in.txt", "hello")]));
+    let src = r#"int main() {
+        int fd, buf, n;
+        buf = malloc(8);
+        fd = open("data/in.txt", 0);
+        if (fd < 0) return -1;
+        n = read(fd, buf, 8);
+        close(fd);
+        return n * 100 + memcmp(buf, "hello", 5);
+    }"#;
+    assert_eq!(run_with_files(src, fs.clone()), 500);
+    // Only what the MemFs holds exists.
+    assert_eq!(run_with_files(r#"int main() { return open("data/other.txt", 0); }"#, fs), -1);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_a_sandboxed_host_fs_stays_under_its_root() {
+    use c4_rust_AlRafaah::vfs::HostFs;
+    let fs = SharedFs::new(HostFs::sandboxed(concat!(env!("CARGO_MANIFEST_DIR"), "/tests")));
+    let open = |path: &str| run_with_files(&format!(r#"int main() {{ return open("{}", 0); }}"#, path), fs.clone());
+    assert_eq!(open("fixtures/read_me.txt"), 3);
+    assert_eq!(open("./fixtures/read_me.txt"), 3);
+    assert_eq!(open("../Cargo.toml"), -1);
+    assert_eq!(open("fixtures/../../Cargo.toml"), -1);
+    assert_eq!(open("../../etc/passwd"), -1);
+    assert_eq!(open("/etc/passwd"), -1);
+    assert_eq!(open(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/read_me.txt")), -1);
+}
+
+#[test]
+fn test_set_args_passes_argc_and_argv_to_main() {
+    let compile = |src: &str| {
+        let program = Parser::new(src).unwrap().parse_program().unwrap();
+        let mut chunk = Chunk::default();
+        program.compile(&mut chunk).unwrap();
+        chunk
+    };
+    let chunk = compile(r#"int main(int argc, char **argv) { return argc * 100 + argv[1][0] * (argv[2][1] == 0) + (argv[3] == 0); }"#);
+    let mut vm = VM::new();
+    vm.set_args(&["prog", "a", "b"]).unwrap();
+    assert_eq!(vm.run(&chunk).unwrap(), 300 + 'a' as i64 + 1);
+
+    // A main without parameters just doesn't look at them.
+    let mut vm = VM::new();
+    vm.set_args(&["prog"]).unwrap();
+    assert_eq!(vm.run(&compile("int main() { return 7; }")).unwrap(), 7);
+
+    // Arguments that don't fit on the stack are an error, not a panic.
+    let mut vm = VM::with_config(VmConfig { stack_size: 4, ..VmConfig::default() });
+    assert!(matches!(vm.set_args(&["a long argument"]), Err(VmError::StackOverflow { .. })));
+}
+
+#[test]
+fn test_read_lengths_are_capped_by_memory() {
+    let fs = SharedFs::new(MemFs::new([("f", "abc")]));
+    // A length far past the end of memory reads what there is, without
+    // the host trying to allocate the whole length.
+    let src = r#"int main() { char *b; b = malloc(4); return read(open("f", 0), b, 1 << 40); }"#;
+    assert_eq!(run_with_files(src, fs.clone()), 3);
+
+    let program = Parser::new(r#"int main() { return read(open("f", 0), -5, 1 << 40); }"#).unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    let err = VM::with_config(VmConfig { filesystem: Some(fs), ..VmConfig::default() }).run(&chunk).unwrap_err();
+    assert!(matches!(err, VmError::InvalidAddress { addr: -5, .. }), "{:?}", err);
+}
+
+#[test]
+fn test_open_fails_once_every_descriptor_is_taken() {
+    let fs = SharedFs::new(MemFs::new([("f", "")]));
+    let src = format!(
+        r#"int main() {{
+            int i, fd;
+            i = 0;
+            while (i < {}) {{ if (open("f", 0) < 0) return -1; i++; }}
+            if (open("f", 0) != -1) return -2;
+            // Closing one frees its descriptor for the next open.
+            if (close(5) != 0) return -3;
+            fd = open("f", 0);
+            return fd;
+        }}"#,
+        MAX_OPEN_FILES
+    );
+    assert_eq!(run_with_files(&src, fs), 5);
+}
+
+#[test]
+fn test_assignment_stores_through_the_lvalues_address() {
+    assert_eq!(run_source("int main() { int x, y; y = (x = 6) * 7; return y; }").unwrap(), 42);
+
+    let program = Parser::new("int main() { int x; x = 9; return x; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    let start = chunk.function("main").unwrap().entry + 1;
+    assert_eq!(
+        chunk.code[start..start + 6],
+        [
+            Instruction::InstrInt(OpCode::LEA, 0),
+            Instruction::Instr(OpCode::PSH),
+            Instruction::InstrInt(OpCode::IMM, 9),
+            Instruction::Instr(OpCode::SI),
+            Instruction::InstrInt(OpCode::LEA, 0),
+            Instruction::Instr(OpCode::LI),
+        ]
+    );
+
+    // Only storage can be assigned to. The checker says so first, but the
+    // code generator refuses too when an item is compiled on its own.
+    let program = Parser::new("int main() { 5 = 1; return 0; }").unwrap().parse_program().unwrap();
+    let err = program.items[0].compile(&mut Chunk::default()).unwrap_err();
+    assert!(err.to_string().contains("bad lvalue in assignment"), "{}", err);
+}
+
+#[test]
+fn test_every_fault_is_an_error_not_a_panic() {
+    let run = |build: &dyn Fn(&mut Chunk), stack_size| {
+        let mut chunk = Chunk::default();
+        build(&mut chunk);
+        VM::with_config(VmConfig { stack_size, ..VmConfig::default() }).run(&chunk)
+    };
+
+    let overflow = |c: &mut Chunk| {
+        c.push(OpCode::PSH);
+        c.push_jump(OpCode::JMP, 0);
+    };
+    assert_eq!(run(&overflow, 4), Err(VmError::StackOverflow { pc: 0 }));
+    let underflow = |c: &mut Chunk| c.push(OpCode::ADD);
+    assert_eq!(run(&underflow, 4), Err(VmError::StackUnderflow { pc: 0 }));
+    let divide = |c: &mut Chunk| {
+        c.push(OpCode::PSH);
+        c.push(OpCode::MOD);
+    };
+    assert_eq!(run(&divide, 4), Err(VmError::DivisionByZero { pc: 1 }));
+    let bad_load = |c: &mut Chunk| {
+        c.push_int(OpCode::IMM, -1);
+        c.push(OpCode::LI);
+    };
+    assert_eq!(run(&bad_load, 4), Err(VmError::InvalidAddress { pc: 1, addr: -1 }));
+    let unknown = |c: &mut Chunk| c.push_int(OpCode::ADD, 1);
+    assert_eq!(
+        run(&unknown, 4),
+        Err(VmError::UnknownInstruction { pc: 0, instr: Instruction::InstrInt(OpCode::ADD, 1) })
+    );
+    let stray_return = |c: &mut Chunk| c.push(OpCode::LEV);
+    assert_eq!(run(&stray_return, 4), Err(VmError::CallStackUnderflow { pc: 0 }));
+
+    // Each error names the instruction that faulted.
+    assert_eq!(run(&divide, 4).unwrap_err().pc(), 1);
+}
+
+#[test]
+fn test_names_resolve_innermost_first() {
+    // A parameter sits below the frame: a negative offset from fp.
+    let src = "enum { K = 5 }; int g; int f(int a, char c) { int l; return a + c + l + g + K; } int main() { return f(1, 2); }";
+    let program = Parser::new(src).unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    let start = chunk.function("f").unwrap().entry + 1;
+    let loads: Vec<_> = chunk.code[start..]
+        .iter()
+        .take_while(|i| **i != Instruction::Instr(OpCode::LEV))
+        .filter(|i| !matches!(i, Instruction::Instr(OpCode::PSH | OpCode::ADD)))
+        .cloned()
+        .collect();
+    assert_eq!(
+        loads,
+        [
+            Instruction::InstrInt(OpCode::LEA, -2),
+            Instruction::Instr(OpCode::LI),
+            Instruction::InstrInt(OpCode::LEA, -1),
+            Instruction::Instr(OpCode::LC),
+            Instruction::InstrInt(OpCode::LEA, 0),
+            Instruction::Instr(OpCode::LI),
+            Instruction::InstrInt(OpCode::GLO, 0),
+            Instruction::Instr(OpCode::LI),
+            Instruction::InstrInt(OpCode::IMM, 5),
+        ]
+    );
+    assert_eq!(run_source(src).unwrap(), 8);
+
+    // A local hides a global of the same name.
+    assert_eq!(run_source("int v; int main() { v = 3; { int v; v = 4; } return v; }").unwrap(), 3);
+}
+
+#[test]
+fn test_division_faults_on_zero_and_wraps_on_overflow() {
+    let divide = |left: i64, right: i64, op: OpCode| {
+        let mut chunk = Chunk::default();
+        chunk.push_int(OpCode::IMM, left);
+        chunk.push(OpCode::PSH);
+        chunk.push_int(OpCode::IMM, right);
+        chunk.push(op);
+        chunk.push(OpCode::EXIT);
+        VM::new().run(&chunk)
+    };
+    assert_eq!(divide(1, 0, OpCode::DIV), Err(VmError::DivisionByZero { pc: 3 }));
+    assert_eq!(divide(1, 0, OpCode::MOD), Err(VmError::DivisionByZero { pc: 3 }));
+    // The one quotient that doesn't fit wraps, as on two's complement hardware.
+    assert_eq!(divide(i64::MIN, -1, OpCode::DIV), Ok(i64::MIN));
+    assert_eq!(divide(i64::MIN, -1, OpCode::MOD), Ok(0));
+    // C truncates toward zero.
+    assert_eq!(divide(-7, 2, OpCode::DIV), Ok(-3));
+    assert_eq!(divide(-7, 2, OpCode::MOD), Ok(-1));
+}
+
+#[test]
+fn test_increment_and_decrement() {
+    // Postfix yields the old value, prefix the new one; both store.
+    assert_eq!(run_source("int main() { int a, b; a = 5; b = a++; return b * 10 + a; }").unwrap(), 56);
+    assert_eq!(run_source("int main() { int a, b; a = 5; b = ++a; return b * 10 + a; }").unwrap(), 66);
+    assert_eq!(run_source("int main() { int a, b; a = 5; b = a--; return b * 10 + a; }").unwrap(), 54);
+    assert_eq!(run_source("int main() { int a, b; a = 5; b = --a; return b * 10 + a; }").unwrap(), 44);
+    let src = "int n; int main() { int i; while (i < 4) { n++; ++i; } return n * 10 + i--; }";
+    assert_eq!(run_source(src).unwrap(), 44);
+
+    let program = Parser::new("int main() { int a; return a++; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    let start = chunk.function("main").unwrap().entry + 1;
+    assert_eq!(
+        chunk.code[start..start + 10],
+        [
+            Instruction::InstrInt(OpCode::LEA, 0),
+            Instruction::Instr(OpCode::PSH),
+            Instruction::Instr(OpCode::LI),
+            Instruction::Instr(OpCode::PSH),
+            Instruction::InstrInt(OpCode::IMM, 1),
+            Instruction::Instr(OpCode::ADD),
+            Instruction::Instr(OpCode::SI),
+            Instruction::Instr(OpCode::PSH),
+            Instruction::InstrInt(OpCode::IMM, 1),
+            Instruction::Instr(OpCode::SUB),
+        ]
+    );
+
+    // Only storage can be stepped.
+    let program = Parser::new("int main() { return 3++; }").unwrap().parse_program().unwrap();
+    let err = program.items[0].compile(&mut Chunk::default()).unwrap_err();
+    assert!(err.to_string().contains("bad lvalue in increment or decrement"), "{}", err);
+    let program = Parser::new("enum { K }; int main() { return K++; }").unwrap().parse_program().unwrap();
+    assert!(program.compile(&mut Chunk::default()).is_err());
+}
+
+#[test]
+fn test_deep_recursion_needs_a_big_enough_stack() {
+    let src = "int down(int n) { if (n == 0) return 7; return down(n - 1); } int main() { return down(100000); }";
+    let program = Parser::new(src).unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+
+    // Too small: a clean error, and the depth it got to.
+    let mut vm = VM::with_stack_size(10_000);
+    assert!(matches!(vm.run(&chunk), Err(VmError::StackOverflow { .. })));
+    assert!(vm.call_depth() > 1000 && vm.call_depth() <= 10_000, "{}", vm.call_depth());
+    vm.reset();
+    assert_eq!(vm.call_depth(), 0);
+
+    // Enlarged: every frame fits.
+    let mut vm = VM::with_stack_size(1 << 18);
+    assert_eq!(vm.run(&chunk), Ok(7));
+    assert_eq!(vm.call_depth(), 0);
+}
+
+#[test]
+fn test_address_of_and_dereference() {
+    // Reading back through `&x`, and storing through a pointer to it.
+    assert_eq!(run_source("int main() { int x; x = 42; return *(&x); }").unwrap(), 42);
+    assert_eq!(run_source("int main() { int x; int *p; x = 1; p = &x; *p = 5; return x; }").unwrap(), 5);
+    assert_eq!(run_source("int g; int main() { int *p; p = &g; *p = 9; return g; }").unwrap(), 9);
+    assert_eq!(run_source("int main() { int *p; p = malloc(2 * sizeof(int)); *(p + 1) = 3; return *(p + 1) + *p * 0; }").unwrap(), 3);
+    assert_eq!(run_source("int main() { int x; int *p; int **pp; p = &x; pp = &p; **pp = 6; return x; }").unwrap(), 6);
+
+    // A `char *` points at a byte.
+    assert_eq!(run_source("int main() { char *s; s = \"hi\"; return *(s + 1); }").unwrap(), 'i' as i64);
+    let program = Parser::new("int main() { char *s; return *s; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.items[0].compile(&mut chunk).unwrap();
+    let load = [Instruction::Instr(OpCode::LI), Instruction::Instr(OpCode::LC)];
+    assert!(chunk.code.windows(2).any(|w| w == load), "{:?}", chunk.code);
+
+    // Only pointers can be dereferenced.
+    let program = Parser::new("int main() { int x; return *x; }").unwrap().parse_program().unwrap();
+    let err = program.items[0].compile(&mut Chunk::default()).unwrap_err();
+    assert!(err.to_string().contains("bad dereference"), "{}", err);
+}
+
+#[test]
+fn test_instruction_budget_stops_an_infinite_loop() {
+    let program = Parser::new("int main() { while (1); return 0; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+
+    let mut vm = VM::new();
+    vm.set_max_steps(Some(10_000));
+    match vm.run(&chunk) {
+        Err(e @ VmError::OutOfFuel { steps, .. }) => {
+            assert_eq!(steps, 10_000);
+            assert!(e.to_string().contains("out of fuel after 10000 instructions"), "{}", e);
+        }
+        other => panic!("expected to run out of fuel, got {:?}", other),
+    }
+    assert_eq!(vm.instruction_count(), 10_000);
+
+    // The budget is per run, and unlimited unless set.
+    let program = Parser::new("int main() { return 3; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.compile(&mut chunk).unwrap();
+    vm.reset();
+    assert_eq!(vm.run(&chunk), Ok(3));
+    vm.set_max_steps(None);
+    vm.reset();
+    assert_eq!(vm.run(&chunk), Ok(3));
+    let config = VmConfig { max_steps: Some(2), ..VmConfig::default() };
+    assert!(matches!(VM::with_config(config).run(&chunk), Err(VmError::OutOfFuel { steps: 2, .. })));
+}
+
+#[test]
+fn test_casts() {
+    // A char keeps only the low byte, like C's truncation.
+    assert_eq!(run_source("int main() { return (char)256; }").unwrap(), 0);
+    assert_eq!(run_source("int main() { return (char)65; }").unwrap(), 65);
+    assert_eq!(run_source("int main() { return (char)(0 - 1); }").unwrap(), 255);
+
+    // Other casts only change the type: no instructions, and a pointer
+    // keeps its address.
+    assert_eq!(run_source("int main() { int x; x = 300; return (int)x; }").unwrap(), 300);
+    assert_eq!(run_source("int main() { int *p; p = malloc(4); *p = 7; return *(int *)(char *)p; }").unwrap(), 7);
+    let program = Parser::new("int main() { return (int)5; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.items[0].compile(&mut chunk).unwrap();
+    assert!(!chunk.code.contains(&Instruction::Instr(OpCode::AND)), "{:?}", chunk.code);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_trace_goes_to_its_writer() {
+    use c4_rust_AlRafaah::vm::OutputBuffer;
+
+    let mut chunk = Chunk::default();
+    chunk.push_int(OpCode::IMM, 40);
+    chunk.push(OpCode::PSH);
+    chunk.push_int(OpCode::IMM, 2);
+    chunk.push(OpCode::ADD);
+    chunk.push(OpCode::EXIT);
+
+    let trace = OutputBuffer::new();
+    let mut vm = VM::new();
+    vm.set_trace_writer(Some(Box::new(trace.clone())));
+    assert_eq!(vm.run(&chunk), Ok(42));
+    let lines: Vec<String> = String::from_utf8(trace.contents()).unwrap().lines().map(String::from).collect();
+    assert_eq!(
+        lines,
+        [
+            "0000 InstrInt(IMM, 40) a=0 sp=0 fp=0",
+            "0001 Instr(PSH) a=40 sp=0 fp=0",
+            "0002 InstrInt(IMM, 2) a=40 sp=1 fp=0",
+            "0003 Instr(ADD) a=2 sp=1 fp=0",
+            "0004 Instr(EXIT) a=42 sp=0 fp=0",
+        ]
+    );
+
+    // Switched off, nothing more is written.
+    vm.set_trace_writer(None);
+    vm.reset();
+    assert_eq!(vm.run(&chunk), Ok(42));
+    assert_eq!(trace.contents().iter().filter(|&&b| b == b'\n').count(), 5);
+}
+
+#[test]
+fn test_sizeof_is_a_constant() {
+    for (ty, size) in [("int", 8), ("char", 1), ("char*", 8), ("void*", 8), ("int**", 8)] {
+        let src = format!("int main() {{ return sizeof({}); }}", ty);
+        assert_eq!(run_source(&src).unwrap(), size, "sizeof({})", ty);
+    }
+    assert_eq!(Type::Void.size_of(), 8);
+    assert_eq!(Type::Ptr(Box::new(Type::Char)).size_of(), 8);
+
+    let program = Parser::new("int main() { return sizeof(char); }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.items[0].compile(&mut chunk).unwrap();
+    assert_eq!(chunk.code[1], Instruction::InstrInt(OpCode::IMM, 1));
+}
+
+#[test]
+fn test_index_loads_and_stores() {
+    // Locals sit in consecutive words, so three of them make an array.
+    let src = "int main() { int a, b, c; int *p; p = &a; p[0] = 1; p[1] = 20; p[2] = 300; return a + b + c + p[2]; }";
+    assert_eq!(run_source(src).unwrap(), 621);
+
+    let src = "int main() { int *v; int i; v = malloc(10 * sizeof(int)); i = 0; \
+               while (i < 10) { v[i] = i * i; i++; } return v[3] + v[9]; }";
+    assert_eq!(run_source(src).unwrap(), 90);
+    assert_eq!(run_source("int main() { char *s; s = \"abc\"; return s[2]; }").unwrap(), 'c' as i64);
+    assert_eq!(run_source("int main() { int *v; v = malloc(2 * sizeof(int)); v[1] = 4; v[1] += 3; v[1]++; return v[1]; }").unwrap(), 8);
+
+    // `a[i]` is `*(a + i)`, with the pointee's load.
+    let program = Parser::new("int main() { char *s; return s[1]; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.items[0].compile(&mut chunk).unwrap();
+    assert_eq!(
+        chunk.code[1..8],
+        [
+            Instruction::InstrInt(OpCode::LEA, 0),
+            Instruction::Instr(OpCode::LI),
+            Instruction::Instr(OpCode::PSH),
+            Instruction::InstrInt(OpCode::IMM, 1),
+            Instruction::Instr(OpCode::ADD),
+            Instruction::Instr(OpCode::LC),
+            Instruction::Instr(OpCode::LEV),
+        ]
+    );
+
+    // An `int *` index is scaled by the size of an int.
+    let program = Parser::new("int main() { int *v; return v[1]; }").unwrap().parse_program().unwrap();
+    let mut chunk = Chunk::default();
+    program.items[0].compile(&mut chunk).unwrap();
+    assert_eq!(
+        chunk.code[4..10],
+        [
+            Instruction::InstrInt(OpCode::IMM, 1),
+            Instruction::Instr(OpCode::PSH),
+            Instruction::InstrInt(OpCode::IMM, 8),
+            Instruction::Instr(OpCode::MUL),
+            Instruction::Instr(OpCode::ADD),
+            Instruction::Instr(OpCode::LI),
+        ]
+    );
+
+    let program = Parser::new("int main() { int x; return x[0]; }").unwrap().parse_program().unwrap();
+    let err = program.items[0].compile(&mut Chunk::default()).unwrap_err();
+    assert!(err.to_string().contains("pointer type expected"), "{}", err);
+}
+
+#[test]
+fn test_conditional_evaluates_one_branch() {
+    assert_eq!(run_source("int main() { return 1 ? 42 : 0; }").unwrap(), 42);
+    assert_eq!(run_source("int main() { return 0 ? 99 : 7; }").unwrap(), 7);
+    assert_eq!(run_source("int main() { int x; x = 5; return x > 3 ? x < 5 ? 1 : 2 : 3; }").unwrap(), 2);
+
+    // Only the chosen branch runs.
+    let src = "int n; int bump() { n = n + 1; return n; } int main() { int r; r = 0 ? bump() : 10; r = 1 ? r + bump() : bump(); return r * 10 + n; }";
+    assert_eq!(run_source(src).unwrap(), 111);
+    assert_eq!(run_source("int main() { int *p; p = 0; return p ? *p : 4; }").unwrap(), 4);
+}
+
+#[test]
+fn test_logical_operators_short_circuit() {
+    for (e, expected) in [("0 && 1", 0), ("2 && 3", 1), ("0 || 0", 0), ("0 || 5", 1), ("4 || 0", 1), ("1 && 0 || 7", 1)] {
+        assert_eq!(run_source(&format!("int main() {{ return {}; }}", e)).unwrap(), expected, "{}", e);
+    }
+
+    // The right side doesn't run once the left decides.
+    assert_eq!(run_source("int main() { int z; z = 0; return 0 && 1 / z; }").unwrap(), 0);
+    assert_eq!(run_source("int main() { int z; z = 0; return 1 || 1 / z; }").unwrap(), 1);
+    assert!(matches!(
+        run_source("int main() { int z; z = 0; return 1 && 1 / z; }"),
+        Err(Error::Runtime(VmError::DivisionByZero { .. }))
+    ));
+    let src = "int n; int bump() { n = n + 1; return 1; } int main() { 0 && bump(); 1 || bump(); 1 && bump(); 0 || bump(); return n; }";
+    assert_eq!(run_source(src).unwrap(), 2);
+    assert_eq!(run_source("int main() { int *p; p = 0; return p && *p; }").unwrap(), 0);
+}
+
+#[test]
+fn test_block_comments_compile_away() {
+    let src = "/* Adds two numbers.\n * Returns their sum. */\nint add(int a, /* first */ int b) { return a /* + 100 */ + b; }\n\
+               int main() { return add(40, 2); } /* done */";
+    assert_eq!(run_source(src).unwrap(), 42);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_printf_writes_to_the_vms_output() {
+    use c4_rust_AlRafaah::vm::OutputBuffer;
+
+    // printf("%s=%4d%c%%\n", "x", 42, 33)
+    let mut chunk = Chunk::default();
+    let format = chunk.intern(b"%s=%4d%c%%\n");
+    let name = chunk.intern(b"x");
+    for instr in [
+        Instruction::InstrInt(OpCode::GLO, format as i64),
+        Instruction::Instr(OpCode::PSH),
+        Instruction::InstrInt(OpCode::GLO, name as i64),
+        Instruction::Instr(OpCode::PSH),
+        Instruction::InstrInt(OpCode::IMM, 42),
+        Instruction::Instr(OpCode::PSH),
+        Instruction::InstrInt(OpCode::IMM, 33),
+        Instruction::Instr(OpCode::PSH),
+        Instruction::InstrInt(OpCode::PRTF, 4),
+        Instruction::InstrInt(OpCode::ADJ, 4),
+        Instruction::Instr(OpCode::EXIT),
+    ] {
+        chunk.code.push(instr);
+    }
+
+    let out = OutputBuffer::new();
+    let mut vm = VM::new();
+    vm.set_output(Box::new(out.clone()));
+    assert_eq!(vm.run(&chunk), Ok(9)); // printf's count of bytes written
+    assert_eq!(out.contents(), b"x=  42!%\n");
+}
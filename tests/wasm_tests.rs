@@ -0,0 +1,52 @@
+// tests/wasm_tests.rs
+
+//! The JavaScript bindings. `wasm-pack test --node -- --features wasm`
+//! runs these under wasm-bindgen-test in Node; a plain
+//! `cargo test --features wasm` runs them natively as ordinary tests, all
+//! but the one that needs a real `JsValue`.
+
+use c4_rust_AlRafaah::wasm::{compile, run};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test(unsupported = test)]
+fn run_captures_output_and_exit_code() {
+    let result = run("int main() { printf(\"%d apples\\n\", 6 * 7); return 3; }", 10_000);
+    assert_eq!(result.error, None);
+    assert_eq!(result.exit_code, 3.0);
+    assert_eq!(result.stdout, "42 apples\n");
+    assert!(result.disassembly.contains("main:"));
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn fuel_is_mandatory_and_stops_loops() {
+    let result = run("int main() { printf(\"spin\"); while (1) ; return 0; }", 1_000);
+    assert!(result.error.unwrap().contains("out of fuel after 1000 instructions"));
+    // What was printed before the error is kept.
+    assert_eq!(result.stdout, "spin");
+
+    let result = run("int main() { return 0; }", 0);
+    assert_eq!(result.error.as_deref(), Some("fuel must be at least 1"));
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn run_reports_compile_errors_without_throwing() {
+    let result = run("int main() { return x; }", 1_000);
+    assert!(result.error.unwrap().contains("undefined variable: x"));
+    assert_eq!(result.disassembly, "");
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn compiled_chunks_run_repeatedly() {
+    let chunk = compile("int n; int main() { n = n + 1; return n; }").unwrap_or_else(|_| panic!("compile failed"));
+    // Each run starts from a fresh VM.
+    assert_eq!(chunk.run(100).exit_code, 1.0);
+    assert_eq!(chunk.run(100).exit_code, 1.0);
+    assert_eq!(chunk.run(100).disassembly, chunk.disassembly());
+}
+
+#[cfg(target_family = "wasm")]
+#[wasm_bindgen_test]
+fn compile_throws_the_error_message() {
+    let err = compile("int main() {").err().unwrap();
+    assert!(err.as_string().unwrap().contains("parse error"));
+}
@@ -0,0 +1,4 @@
+//! The `wasm` feature's bindings as a cdylib for wasm-pack; see
+//! `c4_rust_AlRafaah::wasm`.
+
+pub use c4_rust_AlRafaah::wasm::*;